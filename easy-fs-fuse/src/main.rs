@@ -1,5 +1,12 @@
 use clap::{App, Arg};
-use easy_fs::{BlockDevice, EasyFileSystem};
+use easy_fs::{block_cache_sync_all, BlockDevice, EasyFileSystem, ImageDevice, Inode};
+#[cfg(test)]
+use easy_fs::{Bitmap, CreateError, GrowError, MountError, RenameError, RenameMode, ZEROED_FREE_MAX};
+#[cfg(test)]
+use easy_fs::{
+    block_cache_drop_device, block_cache_sync_dirty_limit, block_cache_try_sync_all, peek_block_cache, try_get_block_cache, BlockError,
+    BLOCK_CACHE_MANAGER,
+};
 use std::fs::{read_dir, File, OpenOptions};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::sync::Arc;
@@ -8,6 +15,8 @@ use std::sync::Mutex;
 /// Use a block size of 512 bytes
 const BLOCK_SZ: usize = 512;
 const BLOCK_NUM: usize = 131072; //64*2048
+/// Number of blocks of mnt.img, one inode bitmap block alone needs an inode area of 1024 blocks
+const MOUNT_IMAGE_BLOCKS: usize = 1100;
 
 /// Wrapper for turning a File into a BlockDevice
 struct BlockFile(Mutex<File>);
@@ -28,6 +37,10 @@ impl BlockDevice for BlockFile {
             .expect("Error when seeking!");
         assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
     }
+    /// Ask the host to write the file back to its disk
+    fn flush(&self) {
+        self.0.lock().unwrap().sync_data().expect("Error when flushing!");
+    }
 }
 
 fn main() {
@@ -86,6 +99,9 @@ fn easy_fs_pack() -> std::io::Result<()> {
         // write data to easy-fs
         inode.write_at(0, all_data.as_slice());
     }
+    pack_mount_image(&root_inode);
+    // write_at does not flush, write back all dirty blocks before the image is used
+    block_cache_sync_all();
     // list apps
     for app in root_inode.ls() {
         println!("{}", app);
@@ -93,8 +109,44 @@ fn easy_fs_pack() -> std::io::Result<()> {
     Ok(())
 }
 
+/// Create mnt.img, itself an easy-fs image holding a single file "hello"
+/// 供用户测试 ch6_mount 挂载到 /mnt
+fn pack_mount_image(root_inode: &Inode) {
+    let image = root_inode.create("mnt.img").unwrap();
+    image.write_at(0, &vec![0u8; MOUNT_IMAGE_BLOCKS * BLOCK_SZ]);
+    let efs = EasyFileSystem::create(Arc::new(ImageDevice::new(image)), MOUNT_IMAGE_BLOCKS as u32, 1);
+    let hello = EasyFileSystem::root_inode(&efs).create("hello").unwrap();
+    hello.write_at(0, b"hello from mnt.img\n");
+}
+
+/// All tests share target/fs.img and the global block cache, so they must run one at a time
+#[cfg(test)]
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Open (and truncate) target/fs.img as a block device for tests
+#[cfg(test)]
+fn open_test_image() -> std::io::Result<Arc<BlockFile>> {
+    open_test_image_at("target/fs.img")
+}
+
+/// Open (and truncate) the image at `path` as a block device for tests
+/// 块缓存按设备区分，先把之前的测试留在缓存中的脏块写回旧的设备，避免它们之后被换出时覆盖新的镜像
+#[cfg(test)]
+fn open_test_image_at(path: &str) -> std::io::Result<Arc<BlockFile>> {
+    block_cache_sync_all();
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    f.set_len((BLOCK_NUM * BLOCK_SZ) as u64)?;
+    Ok(Arc::new(BlockFile(Mutex::new(f))))
+}
+
 #[test]
 fn efs_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
             .read(true)
@@ -107,7 +159,7 @@ fn efs_test() -> std::io::Result<()> {
     // 为block_file创建一个efs，创建的过程中会将efs相关的数据存到block_file这个设备的超级块中，所以没必要保存，要的时候直接从block_file中读
     EasyFileSystem::create(block_file.clone(), 4096, 1);
     // 从block_file中读取刚刚创建的efs
-    let efs = EasyFileSystem::open(block_file.clone());
+    let efs = EasyFileSystem::open(block_file.clone()).unwrap();
 
     let root_inode = EasyFileSystem::root_inode(&efs);
     root_inode.create("filea");
@@ -158,3 +210,2214 @@ fn efs_test() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn efs_dealloc_inode_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let mut fs = efs.lock();
+    let inode_id = fs.alloc_inode().unwrap();
+    assert_ne!(inode_id, 0);
+    fs.dealloc_inode(inode_id);
+    // 释放后再次分配应当得到同一个inode_id
+    assert_eq!(fs.alloc_inode(), Some(inode_id));
+    Ok(())
+}
+
+#[test]
+fn efs_unlinkat_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("filea");
+    root_inode.create("fileb");
+    root_inode.create("filec");
+    // 先删除中间的，再删除最后的
+    assert_eq!(root_inode.unlinkat("fileb"), 0);
+    assert_eq!(root_inode.unlinkat("filec"), 0);
+    assert_eq!(root_inode.unlinkat("fileb"), -1);
+    assert_eq!(root_inode.ls(), vec![String::from("filea")]);
+    // 非空目录不能删除，空目录删除后它的inode和数据块都被释放
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let free = || {
+        let fs = efs.lock();
+        (fs.free_inodes(), fs.data_bitmap.count_free(&device))
+    };
+    let before = free();
+    let dir = root_inode.create_dir("dir").unwrap();
+    dir.create("inner").unwrap().write_at(0, &[1u8; BLOCK_SZ]);
+    assert_eq!(root_inode.unlinkat("dir"), -1);
+    assert!(root_inode.find("dir").is_some());
+    assert_eq!(dir.unlinkat("inner"), 0);
+    drop(dir);
+    assert_eq!(root_inode.unlinkat("dir"), 0);
+    assert!(root_inode.find("dir").is_none());
+    assert_eq!(free(), before);
+    Ok(())
+}
+
+#[test]
+fn efs_unlink_open_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let allocated = || {
+        let fs = efs.lock();
+        (fs.inode_bitmap.allocated(&device), fs.data_bitmap.allocated(&device))
+    };
+    let before = allocated();
+    let file = root_inode.create("open").unwrap();
+    file.write_at(0, &[0x5au8; 3 * BLOCK_SZ]);
+    // 打开期间删除：目录项消失，inode和数据块仍然可以读写
+    file.hold_open();
+    file.hold_open();
+    assert_eq!(root_inode.unlinkat("open"), 0);
+    assert!(root_inode.find("open").is_none());
+    assert_eq!(file.write_at(3 * BLOCK_SZ, &[0xa5u8; BLOCK_SZ]), BLOCK_SZ);
+    let mut buffer = [0u8; 4 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buffer), 4 * BLOCK_SZ);
+    assert!(buffer[..3 * BLOCK_SZ].iter().all(|&byte| byte == 0x5a));
+    assert!(buffer[3 * BLOCK_SZ..].iter().all(|&byte| byte == 0xa5));
+    assert_eq!(allocated(), (before.0 + 1, before.1 + 4));
+    // 最后一次关闭时才释放
+    file.release_open();
+    assert_eq!(allocated(), (before.0 + 1, before.1 + 4));
+    file.release_open();
+    assert_eq!(allocated(), before);
+    // 没有打开的文件删除最后一个链接时立即释放，还有别的链接时不释放
+    let file = root_inode.create("closed").unwrap();
+    file.write_at(0, &[1u8; BLOCK_SZ]);
+    let dir = root_inode.create_dir("d").unwrap();
+    let with_dir = allocated();
+    assert_eq!(dir.link("other", &file), 0);
+    assert_eq!(root_inode.unlinkat("closed"), 0);
+    assert_eq!(allocated(), with_dir);
+    assert_eq!(dir.find("other").unwrap().read_at(0, &mut buffer[..1]), 1);
+    assert_eq!(dir.unlinkat("other"), 0);
+    assert_eq!(allocated(), (with_dir.0 - 1, with_dir.1 - 1));
+    Ok(())
+}
+
+/// A block device kept entirely in memory, for tests that do not need an image file
+#[cfg(test)]
+struct MemBlockDevice {
+    blocks: Mutex<Vec<[u8; BLOCK_SZ]>>,
+    /// how many times `flush` was called
+    flushes: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl MemBlockDevice {
+    /// Create a zero-filled device of `block_count` blocks
+    fn new(block_count: usize) -> Self {
+        Self {
+            blocks: Mutex::new(vec![[0u8; BLOCK_SZ]; block_count]),
+            flushes: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+}
+
+#[cfg(test)]
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let blocks = self.blocks.lock().unwrap();
+        assert!(block_id < blocks.len(), "Block {} out of range!", block_id);
+        buf.copy_from_slice(&blocks[block_id]);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut blocks = self.blocks.lock().unwrap();
+        assert!(block_id < blocks.len(), "Block {} out of range!", block_id);
+        blocks[block_id].copy_from_slice(buf);
+    }
+    fn flush(&self) {
+        self.flushes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// A block device counting how many blocks have been written to it and logging their ids
+#[cfg(test)]
+struct CountingBlockFile {
+    inner: BlockFile,
+    writes: std::sync::atomic::AtomicUsize,
+    log: Mutex<Vec<usize>>,
+}
+
+#[cfg(test)]
+impl BlockDevice for CountingBlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.inner.read_block(block_id, buf);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.writes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.log.lock().unwrap().push(block_id);
+        self.inner.write_block(block_id, buf);
+    }
+}
+
+/// A block device losing writes on purpose, to simulate crashes, lost writes and I/O errors
+/// 可以丢弃或推迟写往指定块的写操作，也可以在再写入N个块之后"崩溃"，忽略之后所有的写操作；
+/// 还可以让读写指定的块时报告I/O错误
+#[cfg(test)]
+struct FaultyBlockDevice {
+    inner: BlockFile,
+    faults: Mutex<Faults>,
+}
+
+/// The faults injected by a FaultyBlockDevice
+#[cfg(test)]
+#[derive(Default)]
+struct Faults {
+    /// writes to these blocks are silently lost
+    dropped: Vec<usize>,
+    /// writes to these blocks are held back until `release_delayed`
+    delayed: Vec<usize>,
+    /// the held back writes, oldest first
+    pending: Vec<(usize, Vec<u8>)>,
+    /// how many more writes reach the disk before the device crashes, None means never
+    writes_left: Option<usize>,
+    /// reads of these blocks fail with an I/O error
+    failed_reads: Vec<usize>,
+    /// writes to these blocks fail with an I/O error
+    failed_writes: Vec<usize>,
+}
+
+#[cfg(test)]
+impl FaultyBlockDevice {
+    fn new(inner: BlockFile) -> Self {
+        Self {
+            inner,
+            faults: Default::default(),
+        }
+    }
+    /// Lose every later write to `block_id`
+    fn drop_writes_to(&self, block_id: usize) {
+        self.faults.lock().unwrap().dropped.push(block_id);
+    }
+    /// Hold back every later write to `block_id` until `release_delayed`
+    fn delay_writes_to(&self, block_id: usize) {
+        self.faults.lock().unwrap().delayed.push(block_id);
+    }
+    /// Fail every later read of `block_id`
+    fn fail_reads_of(&self, block_id: usize) {
+        self.faults.lock().unwrap().failed_reads.push(block_id);
+    }
+    /// Fail every later write to `block_id`
+    fn fail_writes_to(&self, block_id: usize) {
+        self.faults.lock().unwrap().failed_writes.push(block_id);
+    }
+    /// Let `writes` more writes reach the disk, then ignore all the others
+    fn crash_after(&self, writes: usize) {
+        self.faults.lock().unwrap().writes_left = Some(writes);
+    }
+    /// Stop delaying writes and perform the held back ones, unless the device has crashed meanwhile
+    fn release_delayed(&self) {
+        let pending = {
+            let mut faults = self.faults.lock().unwrap();
+            faults.delayed.clear();
+            core::mem::take(&mut faults.pending)
+        };
+        for (block_id, buf) in pending {
+            self.write_block(block_id, &buf);
+        }
+    }
+}
+
+#[cfg(test)]
+impl BlockDevice for FaultyBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.try_read_block(block_id, buf).expect("Error when reading a block!");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf).expect("Error when writing a block!");
+    }
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        if self.faults.lock().unwrap().failed_reads.contains(&block_id) {
+            return Err(BlockError::Io { block_id });
+        }
+        self.inner.read_block(block_id, buf);
+        Ok(())
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        let mut faults = self.faults.lock().unwrap();
+        if faults.failed_writes.contains(&block_id) {
+            return Err(BlockError::Io { block_id });
+        }
+        if faults.writes_left == Some(0) || faults.dropped.contains(&block_id) {
+            return Ok(());
+        }
+        if faults.delayed.contains(&block_id) {
+            faults.pending.push((block_id, buf.to_vec()));
+            return Ok(());
+        }
+        if let Some(writes_left) = faults.writes_left.as_mut() {
+            *writes_left -= 1;
+        }
+        drop(faults);
+        self.inner.write_block(block_id, buf);
+        Ok(())
+    }
+}
+
+/// Overwrite `bytes` at `offset` of block `block_id` on the disk, bypassing the block cache
+/// 调用前需要先同步块缓存，之后要用 reopen_test_image 重新打开镜像才能看到修改
+#[cfg(test)]
+fn patch_block(block_file: &BlockFile, block_id: usize, offset: usize, bytes: &[u8]) {
+    let mut block = [0u8; BLOCK_SZ];
+    block_file.read_block(block_id, &mut block);
+    block[offset..offset + bytes.len()].copy_from_slice(bytes);
+    block_file.write_block(block_id, &block);
+}
+
+/// Open target/fs.img again as a new device, so that nothing is read from the blocks cached for the old one
+#[cfg(test)]
+fn reopen_test_image() -> std::io::Result<Arc<BlockFile>> {
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("target/fs.img")?;
+    Ok(Arc::new(BlockFile(Mutex::new(f))))
+}
+
+#[test]
+fn efs_small_writes_test() -> std::io::Result<()> {
+    use std::sync::atomic::Ordering;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(CountingBlockFile {
+        inner: Arc::try_unwrap(block_file).ok().unwrap(),
+        writes: Default::default(),
+        log: Default::default(),
+    });
+    let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("small").unwrap();
+    block_cache_sync_all();
+    device.writes.store(0, Ordering::SeqCst);
+    let chunk = [0x5au8; 8];
+    for i in 0..1000 {
+        assert_eq!(file.write_at(i * chunk.len(), &chunk), chunk.len());
+    }
+    block_cache_sync_all();
+    // 每次写入都同步时至少要写1000次块，现在只在换出和显式同步时写回
+    let writes = device.writes.load(Ordering::SeqCst);
+    assert!(writes < 100, "{} block writes for 1000 small writes", writes);
+    Ok(())
+}
+
+#[test]
+fn efs_read_before_sync_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("unsynced").unwrap();
+    let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    // 还没有同步到磁盘，但应当可以从块缓存中读到刚写入的数据
+    let mut buffer = vec![0u8; data.len()];
+    let inode = root_inode.find("unsynced").unwrap();
+    assert_eq!(inode.read_at(0, &mut buffer), data.len());
+    assert_eq!(buffer, data);
+    Ok(())
+}
+
+#[test]
+fn efs_symlink_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let dir = root_inode.create_dir("dir").unwrap();
+    let file = dir.create("file").unwrap();
+    file.write_at(0, b"hello");
+    let mut buffer = [0u8; 16];
+    // 指向文件的绝对路径链接
+    root_inode.create_symlink("abs", "/dir/file").unwrap();
+    let inode = root_inode.find_path("abs").unwrap();
+    assert_eq!(inode.read_at(0, &mut buffer), 5);
+    assert_eq!(&buffer[..5], b"hello");
+    // 相对路径从链接所在的目录开始解析，链接到目录后还可以继续向下查找
+    dir.create_symlink("rel", "file").unwrap();
+    root_inode.create_symlink("dirlink", "dir").unwrap();
+    let inode = root_inode.find_path("dirlink/rel").unwrap();
+    assert_eq!(inode.read_at(0, &mut buffer), 5);
+    // 指向自身的链接在超过跟随次数上限后返回None
+    root_inode.create_symlink("loop", "loop").unwrap();
+    assert!(root_inode.find_path("loop").is_none());
+    assert!(root_inode.find("loop").unwrap().is_symlink());
+    // readlink不跟随链接，普通文件返回None
+    assert_eq!(root_inode.find("abs").unwrap().readlink(), Some(String::from("/dir/file")));
+    assert_eq!(root_inode.find("loop").unwrap().readlink(), Some(String::from("loop")));
+    assert_eq!(file.readlink(), None);
+    Ok(())
+}
+
+#[test]
+fn efs_remove_dir_all_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let allocated = || {
+        let fs = efs.lock();
+        (fs.inode_bitmap.allocated(&device), fs.data_bitmap.allocated(&device))
+    };
+    root_inode.create("keep");
+    let before = allocated();
+    // top/{file1, sub/{file2, file3}}，file3足够大以用到一级间接块
+    let top = root_inode.create_dir("top").unwrap();
+    top.create("file1").unwrap().write_at(0, &[1u8; 1000]);
+    let sub = top.create_dir("sub").unwrap();
+    sub.create("file2").unwrap().write_at(0, &[2u8; 100]);
+    sub.create("file3").unwrap().write_at(0, &[3u8; 40 * BLOCK_SZ]);
+    assert_ne!(allocated(), before);
+    assert_eq!(root_inode.remove_dir_all("top"), 0);
+    assert_eq!(allocated(), before);
+    assert_eq!(root_inode.ls(), vec![String::from("keep")]);
+    assert_eq!(root_inode.remove_dir_all("top"), -1);
+    Ok(())
+}
+
+#[test]
+fn efs_linkat_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("filea");
+    root_inode.create("fileb");
+    assert_eq!(root_inode.linkat("filea", "linka"), 0);
+    // 新名字已经存在，或者新旧名字相同时都不能创建链接
+    assert_eq!(root_inode.linkat("filea", "fileb"), -1);
+    assert_eq!(root_inode.linkat("filea", "linka"), -1);
+    assert_eq!(root_inode.linkat("filea", "filea"), -1);
+    assert_eq!(
+        root_inode.ls(),
+        vec![String::from("filea"), String::from("fileb"), String::from("linka")]
+    );
+    // 链接数统计整个文件系统中的目录项，不只是根目录
+    let filea = root_inode.find("filea").unwrap();
+    assert_eq!(filea.nlink(), 2);
+    let dir = root_inode.create_dir("dir").unwrap();
+    assert_eq!(dir.link("linkb", &filea), 0);
+    assert_eq!(filea.nlink(), 3);
+    assert_eq!(root_inode.find("fileb").unwrap().nlink(), 1);
+    // 目录：父目录中的名字、自己的"."以及子目录的".."
+    dir.create_dir("sub").unwrap();
+    assert_eq!(dir.nlink(), 3);
+    Ok(())
+}
+
+#[test]
+fn efs_dot_entries_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let a = root_inode.create_dir("a").unwrap();
+    let b = a.create_dir("b").unwrap();
+    let ino = |inode: &easy_fs::Inode| inode.get_diskinodetype().0;
+    assert_eq!(ino(&root_inode.find_path("/a/b/..").unwrap()), ino(&a));
+    assert_eq!(ino(&root_inode.find_path("a/b/.").unwrap()), ino(&b));
+    assert_eq!(ino(&root_inode.find_path("a/b/../..").unwrap()), ino(&root_inode));
+    // 根目录的".."指向它自己
+    assert_eq!(ino(&root_inode.find_path("..").unwrap()), ino(&root_inode));
+    // ls不列出"."和".."，read_dir则会列出
+    assert!(b.ls().is_empty());
+    let names: Vec<String> = b.read_dir().into_iter().map(|entry| entry.name).collect();
+    assert_eq!(names, vec![String::from("."), String::from("..")]);
+    assert_eq!(a.unlinkat(".."), -1);
+    Ok(())
+}
+
+#[test]
+fn efs_rename_over_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let inodes_allocated = || efs.lock().inode_bitmap.allocated(&device);
+    root_inode.create("filea").unwrap().write_at(0, b"content of a");
+    let fileb = root_inode.create("fileb").unwrap();
+    fileb.write_at(0, b"b");
+    let b_ino = fileb.get_diskinodetype().0 as u32;
+    let before = inodes_allocated();
+    assert_eq!(root_inode.rename("filea", &root_inode, "fileb"), 0);
+    // fileb原来的inode已被释放，再次分配时会被复用
+    assert_eq!(inodes_allocated(), before - 1);
+    assert_eq!(root_inode.ls(), vec![String::from("fileb")]);
+    let mut buffer = [0u8; 32];
+    let len = root_inode.find("fileb").unwrap().read_at(0, &mut buffer);
+    assert_eq!(&buffer[..len], b"content of a");
+    assert_eq!(efs.lock().alloc_inode(), Some(b_ino));
+    assert_eq!(root_inode.rename("filea", &root_inode, "filec"), -1);
+    // 被覆盖的b在另一个目录中还有硬链接，不能被释放
+    root_inode.create("a").unwrap().write_at(0, b"a");
+    let b = root_inode.create("b").unwrap();
+    b.write_at(0, b"still here");
+    let d = root_inode.create_dir("d").unwrap();
+    assert_eq!(d.link("keep", &b), 0);
+    let before = inodes_allocated();
+    assert_eq!(root_inode.rename("a", &root_inode, "b"), 0);
+    assert_eq!(inodes_allocated(), before);
+    let keep = d.find("keep").unwrap();
+    assert_eq!(keep.size(), 10);
+    let len = keep.read_at(0, &mut buffer);
+    assert_eq!(&buffer[..len], b"still here");
+    Ok(())
+}
+
+#[test]
+fn efs_rename2_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let inodes_allocated = || efs.lock().inode_bitmap.allocated(&device);
+    let read_all = |dir: &Inode, name: &str| {
+        let mut buffer = [0u8; 32];
+        let len = dir.find(name).unwrap().read_at(0, &mut buffer);
+        buffer[..len].to_vec()
+    };
+    root_inode.create("filea").unwrap().write_at(0, b"content of a");
+    root_inode.create("fileb").unwrap().write_at(0, b"b");
+    let before = inodes_allocated();
+    // 不覆盖已存在的目标
+    assert_eq!(
+        root_inode.rename2("filea", &root_inode, "fileb", RenameMode::NoReplace),
+        Err(RenameError::Exists)
+    );
+    assert_eq!(read_all(&root_inode, "fileb"), b"b");
+    assert_eq!(root_inode.rename2("filea", &root_inode, "filec", RenameMode::NoReplace), Ok(()));
+    // 交换两个名字，inode既不分配也不释放
+    assert_eq!(root_inode.rename2("filec", &root_inode, "fileb", RenameMode::Exchange), Ok(()));
+    assert_eq!(inodes_allocated(), before);
+    assert_eq!(read_all(&root_inode, "filec"), b"b");
+    assert_eq!(read_all(&root_inode, "fileb"), b"content of a");
+    assert_eq!(
+        root_inode.rename2("filec", &root_inode, "filed", RenameMode::Exchange),
+        Err(RenameError::NotFound)
+    );
+    // 跨目录交换文件和目录，目录的".."随之更新
+    let dir = root_inode.create_dir("d").unwrap();
+    dir.create_dir("sub").unwrap().create("inner").unwrap();
+    assert_eq!(dir.rename2("sub", &root_inode, "fileb", RenameMode::Exchange), Ok(()));
+    assert_eq!(read_all(&dir, "sub"), b"content of a");
+    let moved = root_inode.find("fileb").unwrap();
+    assert!(moved.is_dir());
+    assert!(moved.find("inner").is_some());
+    assert_eq!(moved.abs_path(), Some(String::from("/fileb")));
+    assert_eq!(inodes_allocated(), before + 3);
+    // 目录不能被移动或交换到它自己的子树中
+    let sub = moved.create_dir("deeper").unwrap();
+    assert_eq!(root_inode.rename2("fileb", &sub, "loop", RenameMode::Replace), Err(RenameError::Invalid));
+    assert_eq!(root_inode.rename2("fileb", &moved, "loop", RenameMode::Replace), Err(RenameError::Invalid));
+    sub.create("leaf").unwrap();
+    assert_eq!(sub.rename2("leaf", &root_inode, "fileb", RenameMode::Exchange), Err(RenameError::Invalid));
+    assert_eq!(moved.abs_path(), Some(String::from("/fileb")));
+    // 目录和非目录不能互相覆盖，但可以交换
+    assert_eq!(root_inode.rename2("fileb", &dir, "sub", RenameMode::Replace), Err(RenameError::NotDir));
+    assert_eq!(dir.rename2("sub", &root_inode, "fileb", RenameMode::Replace), Err(RenameError::IsDir));
+    assert_eq!(read_all(&dir, "sub"), b"content of a");
+    assert!(root_inode.find("fileb").unwrap().is_dir());
+    Ok(())
+}
+
+#[test]
+fn efs_disk_full_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    // 1个超级块 + 1个inode位图块 + 1024个inode块 + 1个日志块，剩下173块中只有172块可以存数据
+    let efs = EasyFileSystem::create(block_file.clone(), 1200, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("big").unwrap();
+    let data = vec![0x5au8; 200 * BLOCK_SZ];
+    let written = file.write_at(0, &data);
+    assert!(written > 0 && written < data.len());
+    assert_eq!(written % BLOCK_SZ, 0);
+    assert_eq!(file.write_at(written, &data[..BLOCK_SZ]), 0);
+    // 已写入的部分仍然可以完整读出
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buffer), written);
+    assert!(buffer[..written].iter().all(|byte| *byte == 0x5a));
+    // 没有空间存放新的目录项时创建失败
+    assert!(root_inode.create_dir("dir").is_none());
+    Ok(())
+}
+
+#[test]
+fn efs_grow_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    // 数据区从第1027块开始，只有1个数据位图块，最多管理4096个数据块
+    let efs = EasyFileSystem::create(block_file.clone(), 1200, 1);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("big").unwrap();
+    let data = vec![0x5au8; 400 * BLOCK_SZ];
+    let written = file.write_at(0, &data);
+    assert!(written > 0 && written < data.len());
+    assert_eq!(efs.lock().data_bitmap.count_free(&device), 0);
+    // 数据位图装不下，或者要截掉已用的块时都不做任何修改
+    assert_eq!(efs.lock().grow(1027 + 4097 + 1), Err(GrowError::BitmapFull));
+    assert_eq!(efs.lock().grow(1100), Err(GrowError::InUse));
+    assert_eq!(efs.lock().grow(1029), Err(GrowError::InUse));
+    assert_eq!(efs.lock().grow(1028), Err(GrowError::TooSmall));
+    assert_eq!(efs.lock().data_bitmap.limit(), 172);
+    // 增长之后原来的日志块和新的块都可以分配
+    assert_eq!(efs.lock().grow(1600), Ok(()));
+    assert_eq!(efs.lock().data_bitmap.count_free(&device), 400);
+    assert_eq!(file.write_at(written, &data[written..]), data.len() - written);
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buffer), data.len());
+    assert!(buffer == data);
+    // 重新打开镜像看到的是增长后的布局，日志也在新的位置
+    block_cache_sync_all();
+    let reopened: Arc<dyn BlockDevice> = reopen_test_image()?;
+    let efs = EasyFileSystem::open(reopened.clone()).unwrap();
+    assert_eq!(efs.lock().data_bitmap.limit(), 572);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.find("big").unwrap().read_at(0, &mut buffer), data.len());
+    assert!(buffer == data);
+    assert!(root_inode.create_dir("dir").is_some());
+    // 空闲的尾部可以再缩掉，但不能截掉最后一个已用的块
+    let last = *efs.lock().data_bitmap.allocated_bits(&reopened).last().unwrap() as u32;
+    assert!(last < 571);
+    assert_eq!(efs.lock().grow(1028 + last), Err(GrowError::InUse));
+    assert_eq!(efs.lock().grow(1028 + last + 1), Ok(()));
+    assert_eq!(efs.lock().data_bitmap.limit(), last as usize + 1);
+    // 增长之后仍在数据区内的已清零空闲块还记得，再次分配时不必清零
+    let mut fs = efs.lock();
+    assert_eq!(fs.grow(1600), Ok(()));
+    let block_id = fs.alloc_data().unwrap();
+    let fills = fs.zero_fills();
+    fs.dealloc_data(block_id);
+    assert_eq!(fs.grow(1700), Ok(()));
+    assert_eq!(fs.alloc_data(), Some(block_id));
+    assert_eq!(fs.zero_fills(), fills);
+    Ok(())
+}
+
+#[test]
+fn efs_try_create_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.try_create("excl").unwrap();
+    file.write_at(0, b"data");
+    // 已存在的名字（包括目录和符号链接）都返回Exists，且不影响原来的文件
+    assert_eq!(root_inode.try_create("excl").err(), Some(CreateError::Exists));
+    root_inode.create_dir("dir").unwrap();
+    assert_eq!(root_inode.try_create("dir").err(), Some(CreateError::Exists));
+    root_inode.create_symlink("link", "missing").unwrap();
+    assert_eq!(root_inode.try_create("link").err(), Some(CreateError::Exists));
+    assert_eq!(root_inode.find("excl").unwrap().size(), 4);
+    // 索引位图用完时返回NoSpace而不是panic，也不会占用数据块
+    while efs.lock().alloc_inode().is_some() {}
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = efs.lock().data_bitmap.allocated(&device);
+    assert_eq!(root_inode.try_create("full").err(), Some(CreateError::NoSpace));
+    assert!(root_inode.create("full").is_none());
+    assert!(root_inode.create_dir("fulldir").is_none());
+    assert_eq!(efs.lock().data_bitmap.allocated(&device), data_allocated);
+    assert!(root_inode.find("full").is_none());
+    Ok(())
+}
+
+#[test]
+fn efs_block_map_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("mapped").unwrap();
+    // 第200个数据块已经在二级间接索引的范围内
+    let data: Vec<u8> = (0..201 * BLOCK_SZ).map(|i| (i % 253) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    let offset = 200 * BLOCK_SZ;
+    let mut buffer = [0u8; BLOCK_SZ];
+    let misses = file.block_map_misses();
+    for _ in 0..10 {
+        assert_eq!(file.read_at(offset, &mut buffer), BLOCK_SZ);
+        assert_eq!(&buffer[..], &data[offset..]);
+    }
+    assert_eq!(file.block_map_misses(), misses + 1);
+    // 同一文件的其他Inode共享映射
+    let other = root_inode.find("mapped").unwrap();
+    assert_eq!(other.read_at(offset, &mut buffer), BLOCK_SZ);
+    assert_eq!(other.block_map_misses(), misses + 1);
+    // 改变大小后映射失效，且读到的是新的数据块
+    other.clear();
+    let data = vec![0x6bu8; 201 * BLOCK_SZ];
+    assert_eq!(other.write_at(0, &data), data.len());
+    assert_eq!(file.read_at(offset, &mut buffer), BLOCK_SZ);
+    assert!(buffer.iter().all(|byte| *byte == 0x6b));
+    assert_eq!(file.block_map_misses(), misses + 2);
+    Ok(())
+}
+
+/// Overwrite the u32 at `offset` of the on-disk super block, bypassing the block cache
+/// 先写入足够多的块把块0挤出缓存，这样之后open时才会从磁盘重新读取被修改过的超级块
+#[cfg(test)]
+fn patch_super_block(root_inode: &easy_fs::Inode, block_file: &BlockFile, offset: usize, value: u32) {
+    let filler = root_inode.create("filler").unwrap();
+    filler.write_at(0, &vec![0u8; 32 * BLOCK_SZ]);
+    block_cache_sync_all();
+    let mut block = [0u8; BLOCK_SZ];
+    block_file.read_block(0, &mut block);
+    block[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    block_file.write_block(0, &block);
+}
+
+#[test]
+fn efs_block_size_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    assert!(EasyFileSystem::open(block_file.clone()).is_ok());
+    // 超级块中block_size字段位于偏移24处
+    patch_super_block(&EasyFileSystem::root_inode(&efs), &block_file, 24, 1024);
+    assert_eq!(
+        EasyFileSystem::open(block_file.clone()).err(),
+        Some(easy_fs::OpenError::BlockSizeMismatch { image: 1024, expected: BLOCK_SZ as u32 })
+    );
+    Ok(())
+}
+
+#[test]
+fn efs_perm_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.perm(), 0o755);
+    let file = root_inode.create("file").unwrap();
+    assert_eq!(file.perm(), 0o644);
+    assert_eq!(root_inode.create_dir("dir").unwrap().perm(), 0o755);
+    assert_eq!(root_inode.create_symlink("link", "file").unwrap().perm(), 0o777);
+    // 超出rwxrwxrwx的位被忽略，权限位和inode的其他字段互不影响
+    file.write_at(0, b"perm");
+    file.set_perm(0o7444);
+    assert_eq!(root_inode.find("file").unwrap().perm(), 0o444);
+    assert_eq!(file.size(), 4);
+    assert!(!file.is_dir());
+    // 权限位保存在磁盘上
+    drop(file);
+    drop(root_inode);
+    block_cache_sync_all();
+    let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+    let file = EasyFileSystem::root_inode(&efs).find("file").unwrap();
+    assert_eq!(file.perm(), 0o444);
+    assert_eq!(file.size(), 4);
+    Ok(())
+}
+
+#[test]
+fn efs_iter_inodes_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(efs.lock().iter_allocated_inodes(), vec![0]);
+    let dir = root_inode.create_dir("dir").unwrap();
+    let inodes = [
+        root_inode.create("a").unwrap(),
+        root_inode.create_symlink("link", "a").unwrap(),
+        dir.create("b").unwrap(),
+        dir.create_dir("sub").unwrap(),
+        dir,
+    ];
+    let mut expected: Vec<u32> = inodes.iter().map(|inode| inode.get_diskinodetype().0 as u32).collect();
+    expected.push(0);
+    expected.sort_unstable();
+    assert_eq!(efs.lock().iter_allocated_inodes(), expected);
+    // inode_from_id得到的Inode与按名字找到的是同一个文件
+    let b = EasyFileSystem::inode_from_id(&efs, inodes[2].get_diskinodetype().0 as u32);
+    inodes[2].write_at(0, b"by id");
+    let mut buffer = [0u8; 5];
+    assert_eq!(b.read_at(0, &mut buffer), 5);
+    assert_eq!(&buffer, b"by id");
+    assert!(EasyFileSystem::inode_from_id(&efs, 0).is_dir());
+    // 被释放的inode不再出现
+    let removed: Vec<u32> = inodes[2..].iter().map(|inode| inode.get_diskinodetype().0 as u32).collect();
+    assert_eq!(root_inode.remove_dir_all("dir"), 0);
+    expected.retain(|id| !removed.contains(id));
+    assert_eq!(efs.lock().iter_allocated_inodes(), expected);
+    Ok(())
+}
+
+#[test]
+fn efs_version_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    assert!(EasyFileSystem::open(block_file.clone()).is_ok());
+    // 超级块中version字段位于偏移28处
+    patch_super_block(&EasyFileSystem::root_inode(&efs), &block_file, 28, easy_fs::EFS_VERSION + 1);
+    assert_eq!(
+        EasyFileSystem::open(block_file.clone()).err(),
+        Some(easy_fs::OpenError::VersionMismatch {
+            image: easy_fs::EFS_VERSION + 1,
+            expected: easy_fs::EFS_VERSION,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn efs_inode_size_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // DiskInode是128字节
+    let inode_size = 128usize;
+    assert_eq!(easy_fs::inodes_per_block(inode_size), Some(BLOCK_SZ / inode_size));
+    // 不能整除块大小的inode在创建文件系统时就被拒绝
+    assert_eq!(easy_fs::inodes_per_block(inode_size - 8), None);
+    assert_eq!(easy_fs::inodes_per_block(inode_size + 8), None);
+    assert_eq!(easy_fs::inodes_per_block(0), None);
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    assert!(EasyFileSystem::open(block_file.clone()).is_ok());
+    // 超级块中inode_size字段位于偏移32处
+    patch_super_block(&EasyFileSystem::root_inode(&efs), &block_file, 32, inode_size as u32 + 8);
+    assert_eq!(
+        EasyFileSystem::open(block_file.clone()).err(),
+        Some(easy_fs::OpenError::InodeSizeMismatch {
+            image: inode_size as u32 + 8,
+            expected: inode_size as u32,
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn efs_dentry_cache_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("cached").unwrap().write_at(0, b"dentry");
+    let hits = efs.lock().dentry_cache_hits();
+    let first = root_inode.find("cached").unwrap();
+    let second = root_inode.find("cached").unwrap();
+    assert_eq!(efs.lock().dentry_cache_hits(), hits + 1);
+    let mut buffer = [0u8; 6];
+    assert_eq!(second.read_at(0, &mut buffer), 6);
+    assert_eq!(&buffer, b"dentry");
+    assert_eq!(first.get_diskinodetype().0, second.get_diskinodetype().0);
+    // 通过另一个指向根目录的Inode删除，缓存项同样需要失效
+    let other_root = EasyFileSystem::root_inode(&efs);
+    assert_eq!(other_root.unlinkat("cached"), 0);
+    assert!(root_inode.find("cached").is_none());
+    assert_eq!(efs.lock().dentry_cache_hits(), hits + 1);
+    // 重新创建后可以再次找到新的文件
+    root_inode.create("cached").unwrap();
+    assert_eq!(root_inode.find("cached").unwrap().size(), 0);
+    Ok(())
+}
+
+#[test]
+fn efs_root_inode_arc_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file, 4096, 1);
+    let first = EasyFileSystem::root_inode_arc(&efs);
+    let second = EasyFileSystem::root_inode_arc(&efs);
+    assert!(Arc::ptr_eq(&first, &second));
+    // 按值返回的根Inode与共享的根指向同一个目录
+    first.create("a").unwrap();
+    assert_eq!(EasyFileSystem::root_inode(&efs).ls(), second.ls());
+    // 缓存不持有根Inode，丢弃之后文件系统只剩efs一个引用
+    drop(first);
+    drop(second);
+    assert_eq!(Arc::strong_count(&efs), 1);
+    assert_eq!(EasyFileSystem::root_inode_arc(&efs).ls(), vec![String::from("a")]);
+    Ok(())
+}
+
+#[test]
+fn efs_path_cache_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let d = root_inode.create_dir("a").unwrap().create_dir("b").unwrap().create_dir("c").unwrap().create_dir("d").unwrap();
+    d.create("f").unwrap().write_at(0, b"deep");
+    let hits = || {
+        let fs = efs.lock();
+        (fs.path_cache_hits(), fs.dentry_cache_hits())
+    };
+    // 第一次逐级查找，之后直接命中，不再查找任何一个分量
+    let file = root_inode.find_path("/a/b/c/d/f").unwrap();
+    let (path_hits, dentry_hits) = hits();
+    for _ in 0..3 {
+        let again = root_inode.find_path("a//b/c/d/f/").unwrap();
+        assert_eq!(again.get_diskinodetype().0, file.get_diskinodetype().0);
+    }
+    assert_eq!(hits(), (path_hits + 3, dentry_hits));
+    let mut buffer = [0u8; 4];
+    assert_eq!(root_inode.find_path("/a/b/c/d/f").unwrap().read_at(0, &mut buffer), 4);
+    assert_eq!(&buffer, b"deep");
+    // 同一目录下别的名字的修改不影响缓存
+    d.create("g").unwrap();
+    root_inode.find_path("/a/b/c/d/f").unwrap();
+    assert_eq!(hits().0, path_hits + 5);
+    // 重命名路径中间的目录，以它为前缀的路径都失效
+    let b = root_inode.find_path("/a/b").unwrap();
+    assert_eq!(b.rename("c", &b, "c2"), 0);
+    assert!(root_inode.find_path("/a/b/c/d/f").is_none());
+    assert!(root_inode.find_path("/a/b/c2/d/f").is_some());
+    // 删除之后不再能找到
+    assert_eq!(d.unlinkat("f"), 0);
+    assert!(root_inode.find_path("/a/b/c2/d/f").is_none());
+    // 含有".."的路径不缓存
+    let path_hits = hits().0;
+    assert!(root_inode.find_path("/a/../a/b").is_some());
+    assert!(root_inode.find_path("/a/../a/b").is_some());
+    assert_eq!(hits().0, path_hits);
+    Ok(())
+}
+
+#[test]
+fn efs_read_at_range_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("large").unwrap();
+    // 26个直接块 + 128个一级间接块之后的部分要经过二级间接块
+    let data: Vec<u8> = (0..300 * BLOCK_SZ + 123).map(|i| (i * 7 % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    let mut expected = vec![0u8; data.len()];
+    let mut offset = 0;
+    while offset < expected.len() {
+        let end = (offset + 100).min(expected.len());
+        offset += file.read_at(offset, &mut expected[offset..end]);
+    }
+    assert_eq!(expected, data);
+    // 整个文件、跨越各级索引边界的中间片段以及文件末尾
+    for (start, len) in [(0, data.len()), (25 * BLOCK_SZ + 5, 200 * BLOCK_SZ), (153 * BLOCK_SZ, 3 * BLOCK_SZ), (data.len() - 10, 100)] {
+        let mut buffer = vec![0u8; len];
+        let read = file.read_at_range(start, &mut buffer);
+        assert_eq!(read, len.min(data.len() - start));
+        assert_eq!(&buffer[..read], &expected[start..start + read]);
+    }
+    Ok(())
+}
+
+#[test]
+fn efs_read_at_eof_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file, 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("file").unwrap();
+    let data = [7u8; 100];
+    assert_eq!(file.write_at(0, &data), 100);
+    // 短读：只写入buf的前100个字节，其余部分保持不变
+    let mut buffer = [0xAAu8; 200];
+    assert_eq!(file.read_at(0, &mut buffer), 100);
+    assert_eq!(buffer[..100], data[..]);
+    assert!(buffer[100..].iter().all(|&b| b == 0xAA));
+    assert_eq!(file.read_at(60, &mut buffer), 40);
+    // 到达或超过末尾时返回0，buf完全不被修改
+    let mut buffer = [0xAAu8; 200];
+    for offset in [100usize, 101, 4096, usize::MAX].iter() {
+        assert_eq!(file.read_at(*offset, &mut buffer), 0);
+        assert_eq!(file.read_at_range(*offset, &mut buffer), 0);
+    }
+    assert!(buffer.iter().all(|&b| b == 0xAA));
+    // 写入的末尾超过文件大小的上限时什么都不写，不会因为截断成u32而写到文件开头
+    for offset in [u32::MAX as usize, usize::MAX - 1].iter() {
+        assert_eq!(file.write_at(*offset, &data[..2]), 0);
+    }
+    assert_eq!(file.size(), 100);
+    assert_eq!(file.read_at(0, &mut buffer), 100);
+    assert_eq!(buffer[..100], data[..]);
+    Ok(())
+}
+
+#[test]
+fn efs_truncate_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = || efs.lock().data_bitmap.allocated(&device);
+    let file = root_inode.create("shrink").unwrap();
+    let before = data_allocated();
+    file.write_at(0, &[7u8; 5 * BLOCK_SZ]);
+    assert_eq!(data_allocated(), before + 5);
+    assert!(file.truncate(2 * BLOCK_SZ));
+    assert_eq!(file.size(), 2 * BLOCK_SZ);
+    assert_eq!(data_allocated(), before + 2);
+    // 截断到块中间后再增长，新增部分读出来是0
+    assert!(file.truncate(BLOCK_SZ + 10));
+    assert!(file.truncate(3 * BLOCK_SZ));
+    assert_eq!(data_allocated(), before + 3);
+    let mut buffer = vec![0xffu8; 3 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buffer), 3 * BLOCK_SZ);
+    assert!(buffer[..BLOCK_SZ + 10].iter().all(|byte| *byte == 7));
+    assert!(buffer[BLOCK_SZ + 10..].iter().all(|byte| *byte == 0));
+    assert!(file.truncate(0));
+    assert_eq!(data_allocated(), before);
+    Ok(())
+}
+
+#[test]
+fn efs_abs_path_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.abs_path(), Some(String::from("/")));
+    root_inode.create("x");
+    let b = root_inode.create_dir("a").unwrap().create_dir("b").unwrap();
+    assert_eq!(b.abs_path(), Some(String::from("/a/b")));
+    assert_eq!(root_inode.find_path("a/b/..").unwrap().abs_path(), Some(String::from("/a")));
+    // 移动目录之后路径随之改变
+    let a = root_inode.find("a").unwrap();
+    assert_eq!(a.rename("b", &root_inode, "c"), 0);
+    assert_eq!(b.abs_path(), Some(String::from("/c")));
+    assert_eq!(root_inode.find("x").unwrap().abs_path(), None);
+    Ok(())
+}
+
+#[test]
+fn efs_fsync_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(CountingBlockFile {
+        inner: Arc::try_unwrap(block_file).ok().unwrap(),
+        writes: Default::default(),
+        log: Default::default(),
+    });
+    let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let a = root_inode.create("a").unwrap();
+    let b = root_inode.create("b").unwrap();
+    // 分配新数据块的写入会立即按顺序写回，先把数据块分配好，之后的写入只覆盖已有的块
+    assert_eq!(a.write_at(0, &[0u8; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    assert_eq!(b.write_at(0, &[0u8; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    block_cache_sync_all();
+    device.log.lock().unwrap().clear();
+    assert_eq!(a.write_at(0, &[0xa1u8; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    assert_eq!(b.write_at(0, &[0xb2u8; 3 * BLOCK_SZ]), 3 * BLOCK_SZ);
+    assert!(device.log.lock().unwrap().is_empty());
+    a.fsync();
+    // 只写回了a的3个数据块和存放a的DiskInode的块
+    let log = device.log.lock().unwrap().clone();
+    assert_eq!(log.len(), 4);
+    let blocks_filled_with = |byte: u8| {
+        let mut buf = [0u8; BLOCK_SZ];
+        (0..4096)
+            .filter(|&block_id| {
+                device.inner.read_block(block_id, &mut buf);
+                buf.iter().all(|&x| x == byte)
+            })
+            .collect::<Vec<usize>>()
+    };
+    let a_blocks = blocks_filled_with(0xa1);
+    assert_eq!(a_blocks.len(), 3);
+    assert!(a_blocks.iter().all(|block_id| log.contains(block_id)));
+    assert!(blocks_filled_with(0xb2).is_empty());
+    // b的数据仍然只在缓存区中，全部同步之后才写回
+    block_cache_sync_all();
+    assert_eq!(blocks_filled_with(0xb2).len(), 3);
+    Ok(())
+}
+
+#[test]
+fn efs_concurrent_append_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    root_inode.create("log").unwrap();
+    const THREADS: usize = 4;
+    const RECORDS: usize = 200;
+    const RECORD_SZ: usize = 24;
+    // 每个线程各自打开一个Inode，共享同一把inode锁
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let root_inode = Arc::clone(&root_inode);
+            std::thread::spawn(move || {
+                let log = root_inode.find("log").unwrap();
+                for _ in 0..RECORDS {
+                    log.append(&[t as u8 + 1; RECORD_SZ]);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let log = root_inode.find("log").unwrap();
+    assert_eq!(log.size(), THREADS * RECORDS * RECORD_SZ);
+    let mut data = vec![0u8; log.size()];
+    assert_eq!(log.read_at(0, &mut data), data.len());
+    // 没有丢失的记录，也没有相互交错的记录
+    let mut counts = [0usize; THREADS];
+    for record in data.chunks(RECORD_SZ) {
+        assert!(record.iter().all(|&byte| byte == record[0]));
+        counts[record[0] as usize - 1] += 1;
+    }
+    assert_eq!(counts, [RECORDS; THREADS]);
+    Ok(())
+}
+
+#[test]
+fn efs_two_devices_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file1 = open_test_image()?;
+    let block_file2 = open_test_image_at("target/fs2.img")?;
+    let efs1 = EasyFileSystem::create(block_file1.clone(), 4096, 1);
+    let efs2 = EasyFileSystem::create(block_file2.clone(), 4096, 1);
+    let root_inode1 = EasyFileSystem::root_inode(&efs1);
+    let root_inode2 = EasyFileSystem::root_inode(&efs2);
+    // 两个镜像的布局完全相同，同名文件的DiskInode和数据块在两个设备上的块编号也相同
+    let file1 = root_inode1.create("f").unwrap();
+    let file2 = root_inode2.create("f").unwrap();
+    assert_eq!(file1.write_at(0, &[0x11u8; BLOCK_SZ]), BLOCK_SZ);
+    assert_eq!(file2.write_at(0, &[0x22u8; BLOCK_SZ]), BLOCK_SZ);
+    let mut buf = [0u8; BLOCK_SZ];
+    assert_eq!(file1.read_at(0, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|&byte| byte == 0x11));
+    assert_eq!(file2.read_at(0, &mut buf), BLOCK_SZ);
+    assert!(buf.iter().all(|&byte| byte == 0x22));
+    // 写回之后每个镜像上各自只有自己的数据
+    block_cache_sync_all();
+    let count_blocks_filled_with = |block_file: &BlockFile, byte: u8| {
+        let mut buf = [0u8; BLOCK_SZ];
+        (0..4096)
+            .filter(|&block_id| {
+                block_file.read_block(block_id, &mut buf);
+                buf.iter().all(|&x| x == byte)
+            })
+            .count()
+    };
+    assert_eq!(count_blocks_filled_with(&block_file1, 0x11), 1);
+    assert_eq!(count_blocks_filled_with(&block_file1, 0x22), 0);
+    assert_eq!(count_blocks_filled_with(&block_file2, 0x22), 1);
+    assert_eq!(count_blocks_filled_with(&block_file2, 0x11), 0);
+    Ok(())
+}
+
+#[test]
+fn efs_zero_fill_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("sparse").unwrap();
+    assert_eq!(file.write_at(0, &[7u8; 2 * BLOCK_SZ]), 2 * BLOCK_SZ);
+    // 截断后第一个块中超出size的部分仍残留着旧数据
+    assert!(file.truncate(10));
+    assert_eq!(file.write_at(3 * BLOCK_SZ, &[1u8]), 1);
+    assert_eq!(file.size(), 3 * BLOCK_SZ + 1);
+    let mut buffer = vec![0xffu8; 3 * BLOCK_SZ + 1];
+    assert_eq!(file.read_at(0, &mut buffer), buffer.len());
+    assert!(buffer[..10].iter().all(|byte| *byte == 7));
+    assert!(buffer[10..3 * BLOCK_SZ].iter().all(|byte| *byte == 0));
+    assert_eq!(buffer[3 * BLOCK_SZ], 1);
+    Ok(())
+}
+
+#[test]
+fn efs_compact_test() -> std::io::Result<()> {
+    const DIRENT_SZ: usize = 32;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // "."、".."加上40个文件共42个目录项，占数据区最前面的3个块，compact会释放翻倍扩容留下的第4个块
+    let names: Vec<String> = (0..40).map(|i| format!("f{}", i)).collect();
+    for name in names.iter() {
+        root_inode.create(name).unwrap();
+    }
+    assert_eq!(root_inode.size(), 64 * DIRENT_SZ);
+    root_inode.compact();
+    assert_eq!(root_inode.size(), 42 * DIRENT_SZ);
+    // 已经紧凑的目录上什么都不做
+    root_inode.compact();
+    assert_eq!(root_inode.size(), 42 * DIRENT_SZ);
+    assert_eq!(root_inode.ls(), names);
+    // 直接在磁盘上把第2~21个文件的目录项清零来制造空洞，再重新打开镜像
+    block_cache_sync_all();
+    let first_block = efs.lock().get_data_block_id(0) as usize;
+    for i in 2..22 {
+        patch_block(&block_file, first_block + i / 16, (i % 16) * DIRENT_SZ, &[0u8; DIRENT_SZ]);
+    }
+    let reopened: Arc<dyn BlockDevice> = reopen_test_image()?;
+    let efs = EasyFileSystem::open(reopened.clone()).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let data_allocated = || efs.lock().data_bitmap.allocated(&reopened);
+    let before = data_allocated();
+    assert_eq!(root_inode.size(), 42 * DIRENT_SZ);
+    root_inode.compact();
+    assert_eq!(root_inode.size(), 22 * DIRENT_SZ);
+    assert_eq!(data_allocated(), before - 1);
+    assert_eq!(root_inode.ls(), names[20..].to_vec());
+    assert!(root_inode.find("f39").is_some());
+    assert!(root_inode.find("f0").is_none());
+    Ok(())
+}
+
+#[test]
+fn efs_read_all_dirents_test() -> std::io::Result<()> {
+    const DIRENT_SZ: usize = 32;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // "."、".."加上60个文件共62个目录项，根目录扩容到数据区最前面的4个块
+    for i in 0..60 {
+        root_inode.create(&format!("f{}", i)).unwrap();
+    }
+    for i in (0..60).step_by(7) {
+        assert_eq!(root_inode.unlinkat(&format!("f{}", i)), 0);
+    }
+    assert_eq!(root_inode.size(), 64 * DIRENT_SZ);
+    block_cache_sync_all();
+    // 逐个目录项直接从镜像中读出，与按块批量读出的结果比较
+    let first_block = efs.lock().get_data_block_id(0) as usize;
+    let mut expected: Vec<(String, u32, usize)> = Vec::new();
+    for slot in 0..64 {
+        let mut block = [0u8; BLOCK_SZ];
+        block_file.read_block(first_block + slot / 16, &mut block);
+        let raw = &block[(slot % 16) * DIRENT_SZ..(slot % 16 + 1) * DIRENT_SZ];
+        let len = raw[..28].iter().position(|&b| b == 0).unwrap_or(28);
+        let name = String::from_utf8(raw[..len].to_vec()).unwrap();
+        let inode_id = u32::from_le_bytes([raw[28], raw[29], raw[30], raw[31]]);
+        if inode_id != 0 || name == "." || name == ".." {
+            expected.push((name, inode_id, slot));
+        }
+    }
+    assert_eq!(expected.len(), 2 + 60 - 9);
+    let entries: Vec<(String, u32, usize)> = root_inode
+        .read_dir()
+        .into_iter()
+        .map(|entry| (entry.name, entry.inode_id, entry.slot))
+        .collect();
+    assert_eq!(entries, expected);
+    let names: Vec<String> = expected[2..].iter().map(|(name, _, _)| name.clone()).collect();
+    assert_eq!(root_inode.ls(), names);
+    Ok(())
+}
+
+#[test]
+fn efs_preallocate_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = || efs.lock().data_bitmap.allocated(&device);
+    let file = root_inode.create("prealloc").unwrap();
+    assert_eq!(file.write_at(0, &[9u8; 100]), 100);
+    let before = data_allocated();
+    assert_eq!(file.preallocate(20 * BLOCK_SZ as u32), 0);
+    assert_eq!(file.size(), 20 * BLOCK_SZ);
+    // 原来已有1个块，又新分配了19个
+    assert_eq!(data_allocated(), before + 19);
+    // 已写入的部分不变，其余部分读出来都是0
+    let mut buffer = vec![0xffu8; 20 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buffer), buffer.len());
+    assert!(buffer[..100].iter().all(|byte| *byte == 9));
+    assert!(buffer[100..].iter().all(|byte| *byte == 0));
+    // 在预分配的范围内写入不再分配数据块
+    assert_eq!(file.write_at(5 * BLOCK_SZ, &[1u8; 10 * BLOCK_SZ]), 10 * BLOCK_SZ);
+    assert_eq!(data_allocated(), before + 19);
+    assert_eq!(file.preallocate(BLOCK_SZ as u32), 0);
+    assert_eq!(file.size(), 20 * BLOCK_SZ);
+    // 空间不足时不分配任何数据块
+    assert_eq!(file.preallocate((4096 * BLOCK_SZ) as u32), -1);
+    assert_eq!(data_allocated(), before + 19);
+    assert_eq!(file.size(), 20 * BLOCK_SZ);
+    Ok(())
+}
+
+#[test]
+fn efs_punch_hole_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = || efs.lock().data_bitmap.allocated(&device);
+    let file = root_inode.create("sparse").unwrap();
+    let data: Vec<u8> = (0..5 * BLOCK_SZ).map(|i| (i % 251 + 1) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    let before = data_allocated();
+    // 打掉中间的两个块，大小不变，这两个块读出来都是0
+    assert_eq!(file.punch_hole(BLOCK_SZ as u32, 2 * BLOCK_SZ as u32), 0);
+    assert_eq!(data_allocated(), before - 2);
+    assert_eq!(file.size(), data.len());
+    assert_eq!(file.allocated_blocks(), 3);
+    let mut expected = data.clone();
+    expected[BLOCK_SZ..3 * BLOCK_SZ].fill(0);
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buffer), data.len());
+    assert_eq!(buffer, expected);
+    assert_eq!(file.read_at_range(0, &mut buffer), data.len());
+    assert_eq!(buffer, expected);
+    // 只覆盖块的一部分时不释放块，只把这一段清零
+    assert_eq!(file.punch_hole(3 * BLOCK_SZ as u32 + 100, 50), 0);
+    assert_eq!(data_allocated(), before - 2);
+    expected[3 * BLOCK_SZ + 100..3 * BLOCK_SZ + 150].fill(0);
+    file.read_at(0, &mut buffer);
+    assert_eq!(buffer, expected);
+    // 写入空洞时重新分配数据块，块中其余部分仍然是0
+    assert_eq!(file.write_at(BLOCK_SZ + 10, b"refill"), 6);
+    assert_eq!(data_allocated(), before - 1);
+    assert_eq!(file.allocated_blocks(), 4);
+    expected[BLOCK_SZ + 10..BLOCK_SZ + 16].copy_from_slice(b"refill");
+    file.read_at(0, &mut buffer);
+    assert_eq!(buffer, expected);
+    // 释放文件时跳过空洞
+    assert!(file.truncate(0));
+    assert_eq!(data_allocated(), before - 5);
+    assert_eq!(root_inode.punch_hole(0, BLOCK_SZ as u32), -1);
+    Ok(())
+}
+
+#[test]
+fn efs_append_only_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("log").unwrap();
+    assert_eq!(file.write_at(0, b"hello "), 6);
+    assert!(file.set_append_only(true));
+    assert!(file.append_only());
+    // 只能从文件末尾（或更后面）开始写
+    assert_eq!(file.write_at(0, b"HELLO"), 0);
+    assert_eq!(file.write_at(5, b"!"), 0);
+    assert_eq!(file.write_at(6, b"world"), 5);
+    assert_eq!(file.append(b"!"), 12);
+    assert!(!file.truncate(6));
+    assert_eq!(file.punch_hole(0, BLOCK_SZ as u32), -1);
+    assert!(file.truncate(20));
+    let mut buffer = [0u8; 12];
+    assert_eq!(file.read_at(0, &mut buffer), 12);
+    assert_eq!(&buffer, b"hello world!");
+    // 标志保存在DiskInode中，重新打开后仍然有效
+    block_cache_sync_all();
+    let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+    let file = EasyFileSystem::root_inode(&efs).find("log").unwrap();
+    assert!(file.append_only());
+    assert!(file.set_append_only(false));
+    assert_eq!(file.write_at(0, b"HELLO"), 5);
+    assert!(!EasyFileSystem::root_inode(&efs).set_append_only(true));
+    Ok(())
+}
+
+#[test]
+fn efs_dropped_bitmap_write_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(FaultyBlockDevice::new(Arc::try_unwrap(block_file).ok().unwrap()));
+    let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // 索引位图位于块1，创建文件时对它的写入丢失了
+    device.drop_writes_to(1);
+    root_inode.create("f").unwrap();
+    block_cache_sync_all();
+    let reopened = reopen_test_image()?;
+    let efs = EasyFileSystem::open(reopened.clone()).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // 目录项和DiskInode都已落盘，但索引位图中只有根目录：没有日志时这样的撕裂状态是可见的
+    assert!(root_inode.find("f").is_some());
+    let reopened: Arc<dyn BlockDevice> = reopened;
+    assert_eq!(efs.lock().inode_bitmap.allocated(&reopened), 1);
+    Ok(())
+}
+
+#[test]
+fn efs_crash_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(FaultyBlockDevice::new(Arc::try_unwrap(block_file).ok().unwrap()));
+    let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    // 重新打开磁盘上的镜像，返回其中已分配的inode个数以及根目录
+    let reopen = || -> std::io::Result<(usize, easy_fs::Inode)> {
+        let reopened: Arc<dyn BlockDevice> = reopen_test_image()?;
+        let efs = EasyFileSystem::open(reopened.clone()).unwrap();
+        let allocated = efs.lock().inode_bitmap.allocated(&reopened);
+        Ok((allocated, EasyFileSystem::root_inode(&efs)))
+    };
+    root_inode.create("old").unwrap();
+    block_cache_sync_all();
+    // 推迟的写入在释放之前不会落盘
+    device.delay_writes_to(1);
+    root_inode.create("delayed").unwrap();
+    block_cache_sync_all();
+    assert_eq!(reopen()?.0, 2);
+    device.release_delayed();
+    assert_eq!(reopen()?.0, 3);
+    // 在create写回之前崩溃，重新打开后看到的是原来的状态
+    device.crash_after(0);
+    root_inode.create("new").unwrap();
+    block_cache_sync_all();
+    let (allocated, reopened_root) = reopen()?;
+    assert_eq!(allocated, 3);
+    assert!(reopened_root.find("old").is_some());
+    assert!(reopened_root.find("new").is_none());
+    Ok(())
+}
+
+#[test]
+fn efs_quota_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let limited = root_inode.create_dir("limited").unwrap();
+    assert!(limited.set_block_quota(Some(10)));
+    // 目录自己存放目录项的块已经计入用量
+    assert_eq!(limited.block_quota(), Some((10, 1)));
+    assert!(!limited.create("f").unwrap().set_block_quota(Some(1)));
+    // 经由路径找到的文件同样受配额限制：只能再写入9个块，第11个块写不进去
+    let file = root_inode.find_path("/limited/f").unwrap();
+    assert_eq!(file.write_at(0, &[1u8; 9 * BLOCK_SZ]), 9 * BLOCK_SZ);
+    assert_eq!(limited.block_quota(), Some((10, 10)));
+    assert_eq!(file.write_at(9 * BLOCK_SZ, &[1u8; BLOCK_SZ]), 0);
+    assert!(!file.truncate(10 * BLOCK_SZ));
+    assert!(limited.create_dir("sub").is_none());
+    // 文件系统的其余部分仍有空间
+    let other = root_inode.create("other").unwrap();
+    assert_eq!(other.write_at(0, &[2u8; 20 * BLOCK_SZ]), 20 * BLOCK_SZ);
+    // 释放的块还给配额
+    assert!(file.truncate(4 * BLOCK_SZ));
+    assert_eq!(limited.block_quota(), Some((10, 5)));
+    let sub = limited.create_dir("sub").unwrap();
+    assert_eq!(limited.block_quota(), Some((10, 6)));
+    assert_eq!(sub.create("g").unwrap().write_at(0, &[3u8; 8 * BLOCK_SZ]), 4 * BLOCK_SZ);
+    assert_eq!(limited.remove_dir_all("sub"), 0);
+    assert_eq!(limited.block_quota(), Some((10, 5)));
+    // 取消配额之后不再限制
+    assert!(limited.set_block_quota(None));
+    assert_eq!(limited.block_quota(), None);
+    let file = root_inode.find_path("/limited/f").unwrap();
+    assert_eq!(file.write_at(0, &[1u8; 12 * BLOCK_SZ]), 12 * BLOCK_SZ);
+    Ok(())
+}
+
+#[test]
+fn efs_block_error_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(FaultyBlockDevice::new(Arc::try_unwrap(block_file).ok().unwrap()));
+    let dyn_device: Arc<dyn BlockDevice> = device.clone();
+    // 读块7出错时得到错误而不是panic，出错的块不会留在缓存中
+    device.fail_reads_of(7);
+    assert_eq!(
+        try_get_block_cache(7, dyn_device.clone()).err(),
+        Some(BlockError::Io { block_id: 7 })
+    );
+    assert!(peek_block_cache(7, &dyn_device).is_none());
+    assert!(try_get_block_cache(6, dyn_device.clone()).is_ok());
+    // 写回块6出错时块仍然是脏的，设备恢复之后还能写回
+    device.fail_writes_to(6);
+    try_get_block_cache(6, dyn_device.clone())
+        .unwrap()
+        .lock()
+        .modify(0, |value: &mut u32| *value = 0xdead);
+    assert_eq!(block_cache_try_sync_all(), Err(BlockError::Io { block_id: 6 }));
+    assert_eq!(block_cache_sync_dirty_limit(8), (0, 1));
+    device.faults.lock().unwrap().failed_writes.clear();
+    assert_eq!(block_cache_try_sync_all(), Ok(()));
+    assert_eq!(block_cache_sync_dirty_limit(8), (0, 0));
+    Ok(())
+}
+
+/// Blocks kept borrowed by efs_evict_retry_test, so that none of them can be evicted
+#[cfg(test)]
+static HELD_BLOCKS: Mutex<Vec<Arc<dyn std::any::Any + Send + Sync>>> = Mutex::new(Vec::new());
+#[cfg(test)]
+static BACKOFFS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// 模拟另一个核上的借用者：第三次重试之前归还一个块
+#[cfg(test)]
+fn release_on_third_retry(attempt: usize) {
+    BACKOFFS.store(attempt, std::sync::atomic::Ordering::SeqCst);
+    if attempt == 3 {
+        HELD_BLOCKS.lock().unwrap().pop();
+    }
+}
+
+#[test]
+fn efs_evict_retry_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device: Arc<dyn BlockDevice> = block_file;
+    // 借用16个块（缓存的容量），之后缓存中的每个块都不能被换出
+    for block_id in 100..116 {
+        HELD_BLOCKS.lock().unwrap().push(try_get_block_cache(block_id, device.clone()).unwrap());
+    }
+    BLOCK_CACHE_MANAGER.lock().set_backoff(Some(release_on_third_retry));
+    let block = try_get_block_cache(200, device.clone());
+    assert!(block.is_ok());
+    assert_eq!(BACKOFFS.load(std::sync::atomic::Ordering::SeqCst), 3);
+    HELD_BLOCKS.lock().unwrap().push(block.unwrap());
+    // 一直没有人归还时，重试次数用完后返回错误而不是panic
+    BLOCK_CACHE_MANAGER.lock().set_evict_retries(Some(2));
+    BLOCK_CACHE_MANAGER.lock().set_backoff(Some(|_| {}));
+    assert_eq!(
+        try_get_block_cache(201, device.clone()).err(),
+        Some(BlockError::CacheFull { block_id: 201 })
+    );
+    assert!(peek_block_cache(201, &device).is_none());
+    BLOCK_CACHE_MANAGER.lock().set_evict_retries(None);
+    BLOCK_CACHE_MANAGER.lock().set_backoff(None);
+    HELD_BLOCKS.lock().unwrap().clear();
+    assert!(try_get_block_cache(201, device.clone()).is_ok());
+    Ok(())
+}
+
+/// A block device taking a while to read or write one chosen block, so that another thread can race with it
+#[cfg(test)]
+struct SlowBlockDevice {
+    inner: MemBlockDevice,
+    slow_block: usize,
+    /// how many times the slow block was read
+    reads: std::sync::atomic::AtomicUsize,
+    /// set once a write of the slow block has started
+    writing: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(test)]
+impl BlockDevice for SlowBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        if block_id == self.slow_block {
+            self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        self.inner.read_block(block_id, buf);
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if block_id == self.slow_block {
+            self.writing.store(true, std::sync::atomic::Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        self.inner.write_block(block_id, buf);
+    }
+}
+
+#[test]
+fn efs_cache_in_flight_test() -> std::io::Result<()> {
+    use std::sync::atomic::Ordering;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let slow = Arc::new(SlowBlockDevice {
+        inner: MemBlockDevice::new(64),
+        slow_block: 1,
+        reads: std::sync::atomic::AtomicUsize::new(0),
+        writing: std::sync::atomic::AtomicBool::new(false),
+    });
+    let device: Arc<dyn BlockDevice> = slow.clone();
+    // 两个线程同时读入同一个块：设备只被读一次，两者拿到同一个缓存项
+    let loaders: Vec<_> = (0..2)
+        .map(|_| {
+            let device = device.clone();
+            std::thread::spawn(move || try_get_block_cache(1, device).unwrap())
+        })
+        .collect();
+    let caches: Vec<_> = loaders.into_iter().map(|loader| loader.join().unwrap()).collect();
+    assert!(Arc::ptr_eq(&caches[0], &caches[1]));
+    assert_eq!(slow.reads.load(Ordering::SeqCst), 1);
+    caches[0].lock().modify(0, |data: &mut [u8; BLOCK_SZ]| data[0] = 0xaa);
+    drop(caches);
+    // 被换出的脏块正在写回时读入它，要等写回完成，不能读到磁盘上的旧数据
+    let evictor = {
+        let device = device.clone();
+        std::thread::spawn(move || {
+            for block_id in 2..18 {
+                try_get_block_cache(block_id, device.clone()).unwrap();
+            }
+        })
+    };
+    while !slow.writing.load(Ordering::SeqCst) {
+        std::thread::yield_now();
+    }
+    let reloaded = try_get_block_cache(1, device.clone()).unwrap();
+    assert_eq!(reloaded.lock().read(0, |data: &[u8; BLOCK_SZ]| data[0]), 0xaa);
+    evictor.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn efs_validate_dir_test() -> std::io::Result<()> {
+    const DIRENT_SZ: usize = 32;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("a").unwrap();
+    root_inode.create_dir("d").unwrap();
+    assert!(root_inode.validate_dir());
+    assert!(root_inode.find("d").unwrap().validate_dir());
+    assert!(!root_inode.find("a").unwrap().validate_dir());
+    block_cache_sync_all();
+    let validate_reopened = || -> std::io::Result<bool> {
+        let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+        let valid = EasyFileSystem::root_inode(&efs).validate_dir();
+        Ok(valid)
+    };
+    // 目录项"a"（根目录第一个数据块中的第3项）的inode编号位于偏移28处，让它指向一个未分配的inode
+    let first_block = efs.lock().get_data_block_id(0) as usize;
+    patch_block(&block_file, first_block, 2 * DIRENT_SZ + 28, &100u32.to_le_bytes());
+    assert!(!validate_reopened()?);
+    patch_block(&block_file, first_block, 2 * DIRENT_SZ + 28, &1u32.to_le_bytes());
+    assert!(validate_reopened()?);
+    // 把根目录的size改成不是DIRENT_SZ的整数倍，size是DiskInode的第一个字段
+    let (block_id, offset) = efs.lock().get_disk_inode_pos(0);
+    patch_block(&block_file, block_id as usize, offset, &(4 * DIRENT_SZ as u32 + 1).to_le_bytes());
+    assert!(!validate_reopened()?);
+    Ok(())
+}
+
+#[test]
+fn efs_sync_dirty_limit_test() -> std::io::Result<()> {
+    use std::sync::atomic::Ordering;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let device = Arc::new(CountingBlockFile {
+        inner: Arc::try_unwrap(block_file).ok().unwrap(),
+        writes: Default::default(),
+        log: Default::default(),
+    });
+    let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("batched").unwrap();
+    assert_eq!(file.preallocate(9 * BLOCK_SZ as u32), 0);
+    block_cache_sync_all();
+    device.writes.store(0, Ordering::SeqCst);
+    // 9个数据块加上存放DiskInode的块，共10个脏块
+    assert_eq!(file.write_at(0, &[3u8; 9 * BLOCK_SZ]), 9 * BLOCK_SZ);
+    assert_eq!(device.writes.load(Ordering::SeqCst), 0);
+    assert_eq!(block_cache_sync_dirty_limit(4), (4, 6));
+    assert_eq!(device.writes.load(Ordering::SeqCst), 4);
+    assert_eq!(block_cache_sync_dirty_limit(4), (4, 2));
+    assert_eq!(block_cache_sync_dirty_limit(4), (2, 0));
+    assert_eq!(device.writes.load(Ordering::SeqCst), 10);
+    assert_eq!(block_cache_sync_dirty_limit(4), (0, 0));
+    Ok(())
+}
+
+#[test]
+fn efs_dir_type_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("a").unwrap();
+    let size = root_inode.size();
+    // 目录不能通过文件接口读写
+    assert_eq!(root_inode.write_at(0, &[0xffu8; 64]), 0);
+    assert_eq!(root_inode.write_at(size, b"junk"), 0);
+    assert_eq!(root_inode.append(b"junk"), 0);
+    assert!(!root_inode.truncate(0));
+    assert_eq!(root_inode.preallocate(4096), -1);
+    let mut buffer = [0u8; 64];
+    assert_eq!(root_inode.read_at(0, &mut buffer), 0);
+    assert_eq!(root_inode.read_at_range(0, &mut buffer), 0);
+    // 目录保持原样
+    assert_eq!(root_inode.size(), size);
+    assert_eq!(root_inode.ls(), vec![String::from("a")]);
+    assert!(root_inode.validate_dir());
+    assert!(root_inode.find("..").unwrap().find("a").is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_dir_growth_test() -> std::io::Result<()> {
+    const DIRENT_SZ: usize = 32;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = || efs.lock().data_bitmap.allocated(&device);
+    // 每次size变化就是一次扩容：容量从一个块开始翻倍，200个文件只需要5次
+    let names: Vec<String> = (0..200).map(|i| format!("f{}", i)).collect();
+    let mut size = root_inode.size();
+    let mut grows = 0;
+    for name in names.iter() {
+        root_inode.create(name).unwrap();
+        if root_inode.size() != size {
+            size = root_inode.size();
+            grows += 1;
+        }
+    }
+    assert_eq!(grows, 5);
+    assert_eq!(root_inode.size(), 256 * DIRENT_SZ);
+    assert_eq!(root_inode.ls(), names);
+    assert!(root_inode.validate_dir());
+    // 删除不会缩小容量，之后的创建直接复用空位而不分配新的块
+    let before = data_allocated();
+    for name in names[..50].iter() {
+        assert_eq!(root_inode.unlinkat(name), 0);
+    }
+    assert_eq!(root_inode.size(), 256 * DIRENT_SZ);
+    assert_eq!(root_inode.ls().len(), 150);
+    for name in names[..50].iter() {
+        root_inode.create(name).unwrap();
+    }
+    assert_eq!(data_allocated(), before);
+    assert_eq!(root_inode.size(), 256 * DIRENT_SZ);
+    assert_eq!(root_inode.ls().len(), 200);
+    for name in names.iter() {
+        assert!(root_inode.find(name).is_some());
+    }
+    // compact释放未使用的容量，之后再增长时截断留下的旧目录项已被清零
+    for name in names[100..].iter() {
+        assert_eq!(root_inode.unlinkat(name), 0);
+    }
+    root_inode.compact();
+    assert_eq!(root_inode.size(), 102 * DIRENT_SZ);
+    root_inode.create("new").unwrap();
+    assert_eq!(root_inode.size(), 204 * DIRENT_SZ);
+    assert_eq!(root_inode.ls().len(), 101);
+    assert!(root_inode.validate_dir());
+    Ok(())
+}
+
+#[test]
+fn efs_peek_block_cache_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let (block_id, _) = efs.lock().get_disk_inode_pos(0);
+    // 新打开的设备还没有任何块被缓存，peek不会把块读进来
+    let device: Arc<dyn BlockDevice> = reopen_test_image()?;
+    assert!(peek_block_cache(0, &device).is_none());
+    assert!(peek_block_cache(0, &device).is_none());
+    // 打开文件系统时会读入超级块，其他块仍然不在缓存中
+    let efs = EasyFileSystem::open(device.clone()).unwrap();
+    assert!(peek_block_cache(0, &device).is_some());
+    assert!(peek_block_cache(block_id as usize, &device).is_none());
+    EasyFileSystem::root_inode(&efs).ls();
+    assert!(peek_block_cache(block_id as usize, &device).is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_mem_block_device_test() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // 一个块的索引位图对应的索引节点区就要占1024个块，所以设备至少要比这大
+    let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(2048));
+    let efs = EasyFileSystem::create(device.clone(), 2048, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("mem").unwrap();
+    let data: Vec<u8> = (0..3 * BLOCK_SZ + 7).map(|i| (i % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buffer), data.len());
+    assert_eq!(buffer, data);
+    // 写回之后重新打开同一个设备，数据仍然在
+    drop(file);
+    drop(root_inode);
+    block_cache_sync_all();
+    let efs = EasyFileSystem::open(device.clone()).unwrap();
+    let file = EasyFileSystem::root_inode(&efs).find("mem").unwrap();
+    let mut buffer = vec![0u8; data.len()];
+    assert_eq!(file.read_at(0, &mut buffer), data.len());
+    assert_eq!(buffer, data);
+}
+
+#[test]
+fn efs_device_flush_test() {
+    use std::sync::atomic::Ordering;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let device = Arc::new(MemBlockDevice::new(2048));
+    let efs = EasyFileSystem::create(device.clone(), 2048, 1);
+    // create结束时调用了一次sync_all
+    assert_eq!(device.flushes.load(Ordering::SeqCst), 1);
+    let file = EasyFileSystem::root_inode(&efs).create("flush").unwrap();
+    file.write_at(0, &[1u8; 3 * BLOCK_SZ]);
+    assert_eq!(device.flushes.load(Ordering::SeqCst), 1);
+    // 无论有多少脏块，每次sync_all都只flush一次，没有脏块时也一样
+    block_cache_sync_all();
+    assert_eq!(device.flushes.load(Ordering::SeqCst), 2);
+    block_cache_sync_all();
+    assert_eq!(device.flushes.load(Ordering::SeqCst), 3);
+    // 写回单个块不会flush设备
+    let dyn_device: Arc<dyn BlockDevice> = device.clone();
+    file.write_at(0, &[2u8; BLOCK_SZ]);
+    easy_fs::flush_block(0, &dyn_device);
+    assert_eq!(device.flushes.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn efs_alloc_contiguous_test() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(2));
+    let bitmap = Bitmap::new(0, 2);
+    // 把第一个位图块填满，然后在中间留出5个bit的空洞
+    for _ in 0..BLOCK_SZ * 8 {
+        bitmap.alloc(&device).unwrap();
+    }
+    for bit in 100..105 {
+        bitmap.dealloc(&device, bit);
+    }
+    // 区间不会跨越位图块，所以6个bit只能从第二个块开头分配
+    assert_eq!(bitmap.alloc_contiguous(&device, 6), Some(BLOCK_SZ * 8));
+    assert_eq!(bitmap.alloc_contiguous(&device, 5), Some(100));
+    assert!((100..105).all(|bit| bitmap.is_allocated(&device, bit)));
+    assert!(bitmap.alloc_contiguous(&device, 0).is_none());
+    assert!(bitmap.alloc_contiguous(&device, BLOCK_SZ * 8 + 1).is_none());
+    // 只有一个位图块时找不到就返回None，且不修改位图
+    let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(1));
+    let bitmap = Bitmap::new(0, 1);
+    for _ in 0..BLOCK_SZ * 8 {
+        bitmap.alloc(&device).unwrap();
+    }
+    for bit in 100..105 {
+        bitmap.dealloc(&device, bit);
+    }
+    assert!(bitmap.alloc_contiguous(&device, 6).is_none());
+    assert_eq!(bitmap.allocated(&device), BLOCK_SZ * 8 - 5);
+    assert_eq!(bitmap.alloc_contiguous(&device, 5), Some(100));
+    // 超过limit的bit不会被分配
+    let bitmap = Bitmap::new(0, 1).with_limit(8);
+    let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(1));
+    assert!(bitmap.alloc_contiguous(&device, 9).is_none());
+    assert_eq!(bitmap.alloc_contiguous(&device, 8), Some(0));
+}
+
+#[test]
+fn efs_mount_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
+    let image = root_inode.create("inner.img").unwrap();
+    assert_eq!(image.write_at(0, &vec![0u8; MOUNT_IMAGE_BLOCKS * BLOCK_SZ]), MOUNT_IMAGE_BLOCKS * BLOCK_SZ);
+    let device = Arc::new(ImageDevice::new(image.clone()));
+    assert_eq!(device.blocks(), MOUNT_IMAGE_BLOCKS);
+    let inner = EasyFileSystem::create(device.clone(), MOUNT_IMAGE_BLOCKS as u32, 1);
+    let inner_root = Arc::new(EasyFileSystem::root_inode(&inner));
+    inner_root.create("hello").unwrap().write_at(0, b"from the image");
+    drop(inner_root);
+    drop(inner);
+    // 块缓存中的块也引用着设备，丢弃它们之后镜像文件才被放开
+    assert!(image.is_mounted_image());
+    let device: Arc<dyn BlockDevice> = device;
+    block_cache_drop_device(&device);
+    drop(device);
+    assert!(!image.is_mounted_image());
+
+    let mnt = root_inode.create_dir("mnt").unwrap();
+    mnt.create("hidden").unwrap();
+    let inner = EasyFileSystem::open(Arc::new(ImageDevice::new(image.clone()))).unwrap();
+    let file_inner = Arc::new(EasyFileSystem::root_inode(&inner));
+    assert_eq!(mnt.mount(file_inner.clone()), Ok(()));
+    assert_eq!(mnt.mount(file_inner), Err(MountError::Busy));
+    assert_eq!(image.mount(Arc::new(EasyFileSystem::root_inode(&inner))), Err(MountError::NotDir));
+    drop(inner);
+    // 挂载期间镜像文件的数据块不能被释放
+    let size = image.size();
+    assert!(!image.truncate(0));
+    assert!(!image.clear());
+    assert_eq!(image.punch_hole(0, BLOCK_SZ as u32), -1);
+    assert_eq!(root_inode.unlinkat("inner.img"), -1);
+    root_inode.create("other").unwrap();
+    assert_eq!(root_inode.rename2("other", &root_inode, "inner.img", RenameMode::Replace), Err(RenameError::Busy));
+    assert_eq!(image.size(), size);
+    // 路径经过挂载点时进入镜像中的文件系统，挂载点原来的内容被遮住
+    let hello = root_inode.find_path("mnt/hello").unwrap();
+    let mut buf = [0u8; 32];
+    let len = hello.read_at(0, &mut buf);
+    assert_eq!(&buf[..len], b"from the image");
+    assert!(root_inode.find_path("mnt/hidden").is_none());
+    let sub = root_inode.find_path("mnt").unwrap().create_dir("sub").unwrap();
+    assert_eq!(sub.abs_path().as_deref(), Some("/mnt/sub"));
+    assert_eq!(root_inode.find_path("mnt").unwrap().abs_path().as_deref(), Some("/mnt"));
+    // 镜像中的绝对路径符号链接从全局的"/"开始解析，而不是镜像自己的根目录
+    root_inode.create("outer").unwrap().write_at(0, b"outside");
+    let mounted = root_inode.find_path("mnt").unwrap();
+    mounted.create_symlink("abs", "/hello").unwrap();
+    mounted.create_symlink("out", "/outer").unwrap();
+    mounted.create_symlink("back", "/mnt/hello").unwrap();
+    drop(mounted);
+    assert!(root_inode.find_path("mnt/abs").is_none());
+    let len = root_inode.find_path("mnt/out").unwrap().read_at(0, &mut buf);
+    assert_eq!(&buf[..len], b"outside");
+    assert_eq!(root_inode.find_path("mnt/back").unwrap().size(), 14);
+    // 镜像中的文件还在使用时不能卸载
+    drop(sub);
+    let mounted_root = root_inode.find_path("mnt").unwrap();
+    assert_eq!(root_inode.find_path("mnt").unwrap().umount(), Err(MountError::Busy));
+    drop(hello);
+    assert_eq!(mnt.clone().umount(), Err(MountError::NotMounted));
+    assert_eq!(mounted_root.umount(), Ok(()));
+    assert!(!image.is_mounted_image());
+    assert!(root_inode.find_path("mnt/hello").is_none());
+    assert!(root_inode.find_path("mnt/hidden").is_some());
+    // 写回之后镜像文件中保存着在挂载期间创建的目录
+    block_cache_sync_all();
+    let inner = EasyFileSystem::open(Arc::new(ImageDevice::new(image))).unwrap();
+    let inner_root = EasyFileSystem::root_inode(&inner);
+    assert!(inner_root.find_path("sub").unwrap().is_dir());
+    assert!(inner_root.find_path("hello").is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_zero_on_alloc_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("f").unwrap();
+    assert_eq!(file.write_at(0, &[0xaau8; 4 * BLOCK_SZ]), 4 * BLOCK_SZ);
+    file.clear();
+    // 回收时已经清零过的块被重新分配时不再清零，读出来仍然都是0
+    assert!(file.truncate(4 * BLOCK_SZ));
+    let mut buf = [0xffu8; 4 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buf), 4 * BLOCK_SZ);
+    assert!(buf.iter().all(|&byte| byte == 0));
+    assert_eq!(efs.lock().zero_fills(), 0);
+    // 写入超过文件末尾时，空洞中的新块同样不必清零
+    assert_eq!(file.write_at(8 * BLOCK_SZ, &[1u8]), 1);
+    assert_eq!(file.read_at(5 * BLOCK_SZ, &mut buf[..BLOCK_SZ]), BLOCK_SZ);
+    assert!(buf[..BLOCK_SZ].iter().all(|&byte| byte == 0));
+    assert_eq!(efs.lock().zero_fills(), 0);
+    // 重新打开的镜像无法确定空闲块是否为0：模拟回收时的清零没有写回磁盘，空闲块中残留着旧数据
+    file.clear();
+    let first = efs.lock().alloc_data().unwrap();
+    efs.lock().dealloc_data(first);
+    block_cache_sync_all();
+    patch_block(&block_file, first as usize, 0, &[0xaau8; BLOCK_SZ]);
+    let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.find("f").unwrap();
+    assert!(file.truncate(4 * BLOCK_SZ));
+    assert_eq!(file.read_at(0, &mut buf), 4 * BLOCK_SZ);
+    assert!(buf.iter().all(|&byte| byte == 0));
+    assert_eq!(efs.lock().zero_fills(), 4);
+    // 本次打开之后回收的块已经清零，再次分配时不再清零
+    file.clear();
+    assert!(file.truncate(4 * BLOCK_SZ));
+    assert_eq!(efs.lock().zero_fills(), 4);
+    // 记住的已清零空闲块有上限，超出的块再次分配时重新清零
+    file.clear();
+    let mut fs = efs.lock();
+    let blocks: Vec<u32> = (0..ZEROED_FREE_MAX + 4).map(|_| fs.alloc_data().unwrap()).collect();
+    let fills = fs.zero_fills();
+    blocks.iter().for_each(|&block_id| fs.dealloc_data(block_id));
+    (0..ZEROED_FREE_MAX + 4).for_each(|_| {
+        fs.alloc_data().unwrap();
+    });
+    assert_eq!(fs.zero_fills(), fills + 4);
+    Ok(())
+}
+
+#[test]
+fn efs_free_inodes_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let free_inodes = || efs.lock().free_inodes();
+    // 只有根目录占用了一个inode
+    assert_eq!(efs.lock().total_inodes(), 4096);
+    assert_eq!(free_inodes(), 4095);
+    for i in 0..5 {
+        root_inode.create(&format!("f{}", i)).unwrap();
+    }
+    let dir = root_inode.create_dir("d").unwrap();
+    dir.create("g").unwrap();
+    assert_eq!(free_inodes(), 4095 - 7);
+    // 删除的目录树中的inode重新计入
+    assert_eq!(root_inode.remove_dir_all("d"), 0);
+    assert_eq!(free_inodes(), 4095 - 5);
+    // 重新打开后从索引位图得到同样的结果
+    block_cache_sync_all();
+    let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+    assert_eq!(efs.lock().free_inodes(), 4095 - 5);
+    Ok(())
+}
+
+#[test]
+fn efs_alloc_locality_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    const FILES: usize = 4;
+    const ROUNDS: usize = 16;
+    // 相邻两个块号之差的平均值，1表示完全连续
+    let average_gap = |blocks: &Vec<u32>| {
+        let gaps: u32 = blocks.windows(2).map(|w| w[1].abs_diff(w[0])).sum();
+        gaps as f64 / (blocks.len() - 1) as f64
+    };
+    // 首次适应：几个文件轮流每次分配一个块，块交替地分给各个文件
+    let efs = EasyFileSystem::create(Arc::new(MemBlockDevice::new(4096)), 4096, 1);
+    let mut first_fit: Vec<Vec<u32>> = vec![Vec::new(); FILES];
+    for _ in 0..ROUNDS {
+        for blocks in first_fit.iter_mut() {
+            blocks.push(efs.lock().alloc_data().unwrap());
+        }
+    }
+    // 同样交错地写几个文件，从数据位图的变化得到每次写新分配的块
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let files: Vec<_> = (0..FILES).map(|i| root_inode.create(&format!("f{}", i)).unwrap()).collect();
+    let allocated = || {
+        let fs = efs.lock();
+        fs.data_bitmap.allocated_bits(&fs.block_device)
+    };
+    let mut placed: Vec<Vec<u32>> = vec![Vec::new(); FILES];
+    let chunk = [7u8; BLOCK_SZ];
+    for round in 0..ROUNDS {
+        for (file, blocks) in files.iter().zip(placed.iter_mut()) {
+            let before = allocated();
+            assert_eq!(file.write_at(round * BLOCK_SZ, &chunk), BLOCK_SZ);
+            let new: Vec<usize> = allocated().into_iter().filter(|bit| !before.contains(bit)).collect();
+            assert_eq!(new.len(), 1);
+            blocks.push(new[0] as u32);
+        }
+    }
+    for (placed, first_fit) in placed.iter().zip(first_fit.iter()) {
+        assert!(average_gap(placed) < average_gap(first_fit));
+        assert_eq!(average_gap(placed), 1.0);
+    }
+    // 没有块被重复分配
+    let mut all: Vec<u32> = placed.concat();
+    all.sort();
+    all.dedup();
+    assert_eq!(all.len(), FILES * ROUNDS);
+    // 数据都还在
+    for file in files.iter() {
+        let mut buf = [0u8; BLOCK_SZ];
+        assert_eq!(file.read_at((ROUNDS - 1) * BLOCK_SZ, &mut buf), BLOCK_SZ);
+        assert_eq!(buf, chunk);
+    }
+    Ok(())
+}
+
+#[test]
+fn efs_alloc_near_wraps_test() {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let device: Arc<dyn BlockDevice> = Arc::new(MemBlockDevice::new(2));
+    let bitmap = Bitmap::new(0, 2).with_limit(BLOCK_SZ * 8 + 100);
+    assert_eq!(bitmap.alloc_near(&device, 70), Some(70));
+    assert_eq!(bitmap.alloc_near(&device, 70), Some(71));
+    // 目标超出限制时从头开始
+    assert_eq!(bitmap.alloc_near(&device, BLOCK_SZ * 8 + 100), Some(0));
+    assert_eq!(bitmap.alloc_contiguous_near(&device, 5, BLOCK_SZ * 8 + 95), Some(BLOCK_SZ * 8 + 95));
+    // 目标之后的bit都用完了，绕回到开头
+    assert_eq!(bitmap.alloc_near(&device, BLOCK_SZ * 8 + 99), Some(1));
+    // 跨过目标位置的区间在最后绕回时也能找到
+    for bit in 2..BLOCK_SZ * 8 + 95 {
+        if bit != 70 && bit != 71 && !(60..68).contains(&bit) {
+            bitmap.alloc_near(&device, bit).unwrap();
+        }
+    }
+    assert_eq!(bitmap.alloc_contiguous_near(&device, 8, 64), Some(60));
+    assert!(bitmap.alloc_near(&device, 0).is_none());
+    assert!(bitmap.alloc_contiguous_near(&device, 1, 0).is_none());
+}
+
+#[test]
+fn efs_prefetch_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    const BLOCKS: usize = 24;
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let data: Vec<u8> = (0..BLOCKS * BLOCK_SZ).map(|i| (i / BLOCK_SZ) as u8).collect();
+    let file = EasyFileSystem::root_inode(&efs).create("f").unwrap();
+    assert_eq!(file.write_at(0, &data), data.len());
+    drop(file);
+    block_cache_sync_all();
+    let misses = || BLOCK_CACHE_MANAGER.lock().misses();
+    // 隔一个块读一次，每次读完预读之后的window个块；重新打开镜像，保证缓存中没有这个文件的块
+    let strided_read = |window: usize| -> std::io::Result<usize> {
+        let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+        let file = EasyFileSystem::root_inode(&efs).find("f").unwrap();
+        let before = misses();
+        let mut buf = [0u8; 16];
+        for block in (0..BLOCKS).step_by(2) {
+            assert_eq!(file.read_at(block * BLOCK_SZ, &mut buf), buf.len());
+            assert_eq!(buf, [block as u8; 16]);
+            file.prefetch((block + 1) * BLOCK_SZ, window * BLOCK_SZ);
+        }
+        Ok(misses() - before)
+    };
+    // 不预读时每次读都不命中；预读之后数据块只有第一次不命中，
+    // 但预读会把较早进入缓存的DiskInode所在块挤出去，它可能要再读一次
+    let random = strided_read(0)?;
+    let sequential = strided_read(8)?;
+    assert!(random >= BLOCKS / 2);
+    assert!(sequential <= 3);
+    // 提前把整个范围读进缓存，之后的读全部命中；超出文件末尾的部分被忽略
+    let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+    let file = EasyFileSystem::root_inode(&efs).find("f").unwrap();
+    let prefetched = BLOCK_CACHE_MANAGER.lock().prefetched();
+    assert_eq!(file.prefetch(BLOCKS * BLOCK_SZ, BLOCK_SZ), 0);
+    assert_eq!(file.prefetch(BLOCKS * BLOCK_SZ - 1, 100 * BLOCK_SZ), 1);
+    assert_eq!(file.prefetch(0, 8 * BLOCK_SZ), 8);
+    // 已经在缓存中的块不会再读
+    assert_eq!(file.prefetch(0, 8 * BLOCK_SZ), 0);
+    assert_eq!(BLOCK_CACHE_MANAGER.lock().prefetched(), prefetched + 9);
+    let before = misses();
+    let mut buf = vec![0u8; 8 * BLOCK_SZ];
+    assert_eq!(file.read_at(0, &mut buf), buf.len());
+    assert_eq!(&buf[..], &data[..8 * BLOCK_SZ]);
+    assert_eq!(misses(), before);
+    Ok(())
+}
+
+#[test]
+fn efs_ordered_sync_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let data: Vec<u8> = (0..3 * BLOCK_SZ).map(|i| (i % 251 + 1) as u8).collect();
+    // 增长文件时依次写回3个数据块、数据位图和DiskInode所在的块，在每一次写之前崩溃
+    for writes in 0..=5 {
+        let block_file = open_test_image()?;
+        let device = Arc::new(FaultyBlockDevice::new(Arc::try_unwrap(block_file).ok().unwrap()));
+        let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+        let root_inode = EasyFileSystem::root_inode(&efs);
+        let file = root_inode.create("f").unwrap();
+        block_cache_sync_all();
+        device.crash_after(writes);
+        // 不调用 block_cache_sync_all，落盘的只有 write_at 自己按顺序写回的块
+        assert_eq!(file.write_at(0, &data), data.len());
+        let efs = EasyFileSystem::open(reopen_test_image()?).unwrap();
+        let file = EasyFileSystem::root_inode(&efs).find("f").unwrap();
+        let mut buffer = vec![0u8; data.len()];
+        let len = file.read_at(0, &mut buffer);
+        // 看到的要么是原来的空文件，要么是完整写入的数据，不会是指向未写入数据的块
+        if writes < 5 {
+            assert_eq!(len, 0);
+        } else {
+            assert_eq!(len, data.len());
+            assert_eq!(buffer, data);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn efs_shrink_to_fit_test() -> std::io::Result<()> {
+    // 26个直接索引，每个索引块128项
+    const DIRECT: usize = 26;
+    const PER_INDIRECT: usize = BLOCK_SZ / 4;
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file.clone(), 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let device: Arc<dyn BlockDevice> = block_file.clone();
+    let data_allocated = || efs.lock().data_bitmap.allocated(&device);
+    // 数据块加上索引块：一级索引块、二级索引块以及二级索引下的一级索引块
+    let blocks_for = |data_blocks: usize| {
+        let mut total = data_blocks;
+        if data_blocks > DIRECT {
+            total += 1;
+        }
+        if data_blocks > DIRECT + PER_INDIRECT {
+            total += 1 + (data_blocks - DIRECT - PER_INDIRECT + PER_INDIRECT - 1) / PER_INDIRECT;
+        }
+        total
+    };
+    let base = data_allocated();
+    let file = root_inode.create("f").unwrap();
+    let data: Vec<u8> = (0..(DIRECT + 3 * PER_INDIRECT + 10) * BLOCK_SZ).map(|i| (i % 251 + 1) as u8).collect();
+    assert_eq!(file.write_at(0, &data), data.len());
+    assert_eq!(data_allocated(), base + blocks_for(DIRECT + 3 * PER_INDIRECT + 10));
+    // 依次缩小到：二级索引中间、二级索引下一级索引块的边界、一级与二级索引的边界、
+    // 一级索引中间、直接索引与一级索引的边界、块的中间
+    let sizes = [
+        (DIRECT + PER_INDIRECT + PER_INDIRECT + 7) * BLOCK_SZ + 100,
+        (DIRECT + PER_INDIRECT + PER_INDIRECT) * BLOCK_SZ,
+        (DIRECT + PER_INDIRECT) * BLOCK_SZ,
+        (DIRECT + 5) * BLOCK_SZ,
+        DIRECT * BLOCK_SZ,
+        3 * BLOCK_SZ + 1,
+    ];
+    for &size in sizes.iter() {
+        assert!(file.truncate(size));
+        assert_eq!(file.size(), size);
+        assert_eq!(data_allocated(), base + blocks_for((size + BLOCK_SZ - 1) / BLOCK_SZ));
+        let mut buffer = vec![0u8; size];
+        assert_eq!(file.read_at(0, &mut buffer), size);
+        assert!(buffer == data[..size]);
+    }
+    // 清空的指针不会被再次使用：重新增长后新的部分都是0
+    assert!(file.truncate(DIRECT * BLOCK_SZ + PER_INDIRECT * BLOCK_SZ + 1));
+    let mut buffer = vec![0xffu8; BLOCK_SZ];
+    assert_eq!(file.read_at(DIRECT * BLOCK_SZ, &mut buffer), BLOCK_SZ);
+    assert!(buffer.iter().all(|&byte| byte == 0));
+    assert!(file.truncate(0));
+    assert_eq!(data_allocated(), base);
+    Ok(())
+}
+
+#[test]
+fn efs_journal_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    // 创建目录时依次写回日志、索引位图、新目录的DiskInode和数据块、父目录……以及清空日志，在每一次写之前崩溃
+    let mut created_at = None;
+    for writes in 0..=12 {
+        let block_file = open_test_image()?;
+        let device = Arc::new(FaultyBlockDevice::new(Arc::try_unwrap(block_file).ok().unwrap()));
+        let efs = EasyFileSystem::create(device.clone(), 4096, 1);
+        let root_inode = EasyFileSystem::root_inode(&efs);
+        root_inode.create("old").unwrap();
+        block_cache_sync_all();
+        device.crash_after(writes);
+        // create_dir 在返回之前自己写回并清空日志，不需要 block_cache_sync_all
+        assert!(root_inode.create_dir("d").is_some());
+        let reopened: Arc<dyn BlockDevice> = reopen_test_image()?;
+        let efs = EasyFileSystem::open(reopened.clone()).unwrap();
+        let allocated = efs.lock().inode_bitmap.allocated(&reopened);
+        let root_inode = EasyFileSystem::root_inode(&efs);
+        assert!(root_inode.validate_dir());
+        assert!(root_inode.find("old").is_some());
+        // 恢复后要么没有"d"也没有多分配的inode，要么"d"是完整的目录
+        match root_inode.find("d") {
+            Some(dir) => {
+                assert!(dir.is_dir() && dir.validate_dir());
+                assert_eq!(allocated, 3);
+                created_at.get_or_insert(writes);
+            }
+            None => {
+                assert_eq!(allocated, 2);
+                assert!(created_at.is_none());
+            }
+        }
+    }
+    assert!(created_at.is_some());
+    Ok(())
+}
+
+#[test]
+fn efs_journal_writeback_test() -> std::io::Result<()> {
+    let _guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let block_file = open_test_image()?;
+    let efs = EasyFileSystem::create(block_file, 4096, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("file").unwrap();
+    assert_eq!(file.write_at(0, &[1u8; BLOCK_SZ]), BLOCK_SZ);
+    block_cache_sync_all();
+    // 覆盖写不分配数据块，被修改的数据块留在缓存中，随后的创建只写回它自己修改的块
+    assert_eq!(file.write_at(0, &[2u8; BLOCK_SZ]), BLOCK_SZ);
+    assert!(block_cache_sync_dirty_limit(0).1 > 0);
+    assert!(root_inode.create("other").is_some());
+    assert!(block_cache_sync_dirty_limit(0).1 > 0);
+    let reopened: Arc<dyn BlockDevice> = reopen_test_image()?;
+    let efs = EasyFileSystem::open(reopened).unwrap();
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert!(root_inode.find("other").is_some());
+    assert!(root_inode.validate_dir());
+    Ok(())
+}
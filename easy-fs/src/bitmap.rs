@@ -1,4 +1,5 @@
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use super::{
     BlockDevice,
     BLOCK_SZ,
@@ -15,6 +16,7 @@ const BLOCK_BITS: usize = BLOCK_SZ * 8;
 pub struct Bitmap {
     start_block_id: usize, // 所在区域的起始块编号
     blocks: usize, // 区域的长度为多少个块
+    limit: usize, // 可以分配的bit数，可能小于这些块能表示的bit数
 }
 
 /// Decompose bits into (block_pos, bits64_pos, inner_pos)
@@ -30,28 +32,81 @@ impl Bitmap {
         Self {
             start_block_id,
             blocks,
+            limit: blocks * BLOCK_BITS,
         }
     }
+    /// Only allow the first `limit` bits to be allocated
+    /// 例如数据位图的最后一个块中只有一部分bit对应真实存在的数据块
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.min(self.blocks * BLOCK_BITS);
+        self
+    }
     /// Allocate a new block from a block device
     pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
-        for block_id in 0..self.blocks {
+        self.alloc_near(block_device, 0)
+    }
+    /// Allocate the first free bit at or after `goal`, wrapping around to the front of the bitmap
+    /// 目标之后都满了才回到位图开头找，所以只要还有空闲的bit就一定能分配到
+    pub fn alloc_near(&self, block_device: &Arc<dyn BlockDevice>, goal: usize) -> Option<usize> {
+        for (block_id, lo, hi) in self.scan_from(goal, 0) {
+            let base = block_id * BLOCK_BITS;
+            let pos = get_block_cache(
+                block_id + self.start_block_id,
+                Arc::clone(block_device),
+            ).lock().modify(0, |bitmap_block: &mut BitmapBlock| {
+                let bit = (lo / 64..(hi + 63) / 64).find_map(|bits64_pos| {
+                    let mut free = !bitmap_block[bits64_pos];
+                    // 去掉[lo, hi)之外的bit
+                    if bits64_pos * 64 < lo {
+                        free &= u64::MAX << (lo % 64);
+                    }
+                    if bits64_pos * 64 + 64 > hi {
+                        free &= u64::MAX >> (64 - hi % 64);
+                    }
+                    (free != 0).then(|| bits64_pos * 64 + free.trailing_zeros() as usize)
+                })?;
+                // modify cache
+                bitmap_block[bit / 64] |= 1u64 << (bit % 64);
+                Some(base + bit)
+            });
+            if pos.is_some() {
+                return pos;
+            }
+        }
+        None
+    }
+    /// Allocate `n` consecutive bits inside a single bitmap block, return the first one
+    /// 在每个位图块中从前往后找第一段足够长的空闲区间（首次适应），区间不会跨越位图块；找不到时返回None且不做任何修改
+    pub fn alloc_contiguous(&self, block_device: &Arc<dyn BlockDevice>, n: usize) -> Option<usize> {
+        self.alloc_contiguous_near(block_device, n, 0)
+    }
+    /// Like `alloc_contiguous`, but look for the run at or after `goal` first, wrapping around to the front
+    pub fn alloc_contiguous_near(
+        &self,
+        block_device: &Arc<dyn BlockDevice>,
+        n: usize,
+        goal: usize,
+    ) -> Option<usize> {
+        if n == 0 || n > BLOCK_BITS {
+            return None;
+        }
+        // 最后回到目标所在的位图块时，跨过目标位置的区间也要能找到
+        for (block_id, lo, hi) in self.scan_from(goal, n - 1) {
+            let base = block_id * BLOCK_BITS;
             let pos = get_block_cache(
-                block_id + self.start_block_id as usize,
+                block_id + self.start_block_id,
                 Arc::clone(block_device),
             ).lock().modify(0, |bitmap_block: &mut BitmapBlock| {
-                if let Some((bits64_pos, inner_pos)) = bitmap_block
-                    .iter()
-                    .enumerate()
-                    .find(|(_, bits64)| **bits64 != u64::MAX)
-                    .map(|(bits64_pos, bits64)| {
-                        (bits64_pos, bits64.trailing_ones() as usize)
-                    }) {
-                    // modify cache
-                    bitmap_block[bits64_pos] |= 1u64 << inner_pos;
-                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos as usize)
-                } else {
-                    None
+                let is_free = |bit: usize| bitmap_block[bit / 64] & (1u64 << (bit % 64)) == 0;
+                let mut run = 0usize;
+                let start = (lo..hi).find(|&bit| {
+                    run = if is_free(bit) { run + 1 } else { 0 };
+                    run == n
+                })? + 1 - n;
+                for bit in start..start + n {
+                    bitmap_block[bit / 64] |= 1u64 << (bit % 64);
                 }
+                Some(base + start)
             });
             if pos.is_some() {
                 return pos;
@@ -59,6 +114,27 @@ impl Bitmap {
         }
         None
     }
+    /// The order to scan the bitmap in when the allocation should land at or after `goal`:
+    /// (bitmap block, first bit, end bit) inside each block, bits beyond the limit excluded.
+    /// 从目标所在的位图块的目标位置开始，绕回开头后最后再看一次目标所在块中目标之前的部分（多看`overlap`个bit）
+    fn scan_from(&self, goal: usize, overlap: usize) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        let goal = if goal < self.limit { goal } else { 0 };
+        let (goal_block, goal_bit) = (goal / BLOCK_BITS, goal % BLOCK_BITS);
+        (0..=self.blocks).filter_map(move |i| {
+            let block_id = (goal_block + i) % self.blocks;
+            let base = block_id * BLOCK_BITS;
+            if base >= self.limit {
+                return None;
+            }
+            let bits = BLOCK_BITS.min(self.limit - base);
+            let (lo, hi) = match i {
+                0 => (goal_bit, bits),
+                i if i == self.blocks => (0, bits.min(goal_bit + overlap)),
+                _ => (0, bits),
+            };
+            (lo < hi).then(|| (block_id, lo, hi))
+        })
+    }
     /// Deallocate a block
     pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
         let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
@@ -70,6 +146,80 @@ impl Bitmap {
             bitmap_block[bits64_pos] -= 1u64 << inner_pos;
         });
     }
+    /// Whether `bit` is allocated, bits beyond the limit never are
+    pub fn is_allocated(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) -> bool {
+        if bit >= self.limit {
+            return false;
+        }
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .read(0, |bitmap_block: &BitmapBlock| {
+                bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0
+            })
+    }
+    /// Count how many bits are allocated
+    pub fn allocated(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        (0..self.blocks)
+            .map(|block_id| {
+                get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |bitmap_block: &BitmapBlock| {
+                        bitmap_block.iter().map(|bits64| bits64.count_ones() as usize).sum::<usize>()
+                    })
+            })
+            .sum()
+    }
+    /// The ids of the blocks holding this bitmap
+    pub fn block_ids(&self) -> core::ops::Range<usize> {
+        self.start_block_id..self.start_block_id + self.blocks
+    }
+    /// Count how many bits can still be allocated, bits beyond the limit are not counted
+    pub fn count_free(&self, block_device: &Arc<dyn BlockDevice>) -> usize {
+        self.limit - self.allocated(block_device)
+    }
+    /// List the allocated bits in ascending order
+    /// 逐个64位字扫描，全0的字直接跳过
+    pub fn allocated_bits(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<usize> {
+        let mut v: Vec<usize> = Vec::new();
+        for block_id in 0..self.blocks {
+            get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                .lock()
+                .read(0, |bitmap_block: &BitmapBlock| {
+                    for (bits64_pos, bits64) in bitmap_block.iter().enumerate() {
+                        let mut bits64 = *bits64;
+                        while bits64 != 0 {
+                            let inner_pos = bits64.trailing_zeros() as usize;
+                            v.push(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos);
+                            bits64 &= bits64 - 1;
+                        }
+                    }
+                });
+        }
+        v
+    }
+    /// Track `new_blocks` bits, e.g. after the area described by the bitmap grew or shrank
+    /// 增长时新覆盖的bit（包括新用到的位图块）在磁盘上必须全为0，缩小时被去掉的bit不能已分配，
+    /// 不满足时返回false且不做任何修改；需要更多的位图块时，由调用者保证其后的块可以用作位图
+    pub fn resize(&mut self, block_device: &Arc<dyn BlockDevice>, new_blocks: usize) -> bool {
+        let blocks = self.blocks.max((new_blocks + BLOCK_BITS - 1) / BLOCK_BITS);
+        let (lo, hi) = (self.limit.min(new_blocks), self.limit.max(new_blocks));
+        let covering = Self {
+            start_block_id: self.start_block_id,
+            blocks,
+            limit: hi,
+        };
+        if (lo..hi).any(|bit| covering.is_allocated(block_device, bit)) {
+            return false;
+        }
+        self.blocks = blocks;
+        self.limit = new_blocks;
+        true
+    }
+    /// How many bits can be allocated at most
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
     /// Get the max number of allocatable blocks
     /// 索引位图的每一个比特都代表了一个索引节点的分配情况
     /// 本函数返回本索引位图一共可以表示多少索引节点的状态（已分配/未分配）
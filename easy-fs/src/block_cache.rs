@@ -1,9 +1,14 @@
-use super::{BlockDevice, BLOCK_SZ};
+use super::{BlockDevice, BlockError, BLOCK_SZ};
 use alloc::collections::VecDeque;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
 use spin::Mutex;
 
+/// Counts every modification of any cached block, see `BlockCache::modified_seq`
+static MODIFY_SEQ: AtomicUsize = AtomicUsize::new(0);
+
 /// Cached block inside memory
 pub struct BlockCache {
     /// cached block data
@@ -18,19 +23,27 @@ pub struct BlockCache {
     /// whether the block is dirty
     /// 记录这个块从磁盘载入内存缓存之后，它有没有被修改过
     modified: bool,
+    /// value of the global modification counter at the last modification, 0 if never modified
+    /// 用来找出某个时刻之后被修改过的块，见 block_cache_sync_device_since
+    modified_seq: usize,
 }
 
 impl BlockCache {
     /// Load a new BlockCache from disk.
     pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        Self::try_new(block_id, block_device).expect("Error when loading a block!")
+    }
+    /// Load a new BlockCache from disk, or the error the device reported for the block
+    pub fn try_new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Result<Self, BlockError> {
         let mut cache = [0u8; BLOCK_SZ];
-        block_device.read_block(block_id, &mut cache);
-        Self {
+        block_device.try_read_block(block_id, &mut cache)?;
+        Ok(Self {
             cache,
             block_id,
             block_device,
             modified: false,
-        }
+            modified_seq: 0,
+        })
     }
     /// Get the address of an offset inside the cached block data
     /// 得到一个 BlockCache 内部的缓冲区中指定偏移量 offset 的字节地址
@@ -58,6 +71,7 @@ impl BlockCache {
         let type_size = core::mem::size_of::<T>();
         assert!(offset + type_size <= BLOCK_SZ);
         self.modified = true; // 由于这些数据结构目前位于内存中的缓冲区中，我们需要将 BlockCache 的 modified 标记为 true 表示该缓冲区已经被修改
+        self.modified_seq = MODIFY_SEQ.fetch_add(1, Ordering::Relaxed) + 1;
         let addr = self.addr_of_offset(offset);
         unsafe { &mut *(addr as *mut T) }
     }
@@ -70,16 +84,27 @@ impl BlockCache {
     pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
         f(self.get_mut(offset))
     }
+    /// Whether the cached data has been modified since it was loaded or last written back
+    pub fn is_dirty(&self) -> bool {
+        self.modified
+    }
     /// 将本缓存区中的所有数据更新到磁盘中（若block中的数据被修改了的话）
     pub fn sync(&mut self) {
+        self.try_sync().expect("Error when writing back a block!")
+    }
+    /// Write the block back if it is dirty, or return the error the device reported
+    /// 写回失败时块仍然是脏的，之后还会再次尝试写回
+    pub fn try_sync(&mut self) -> Result<(), BlockError> {
         if self.modified {
+            self.block_device.try_write_block(self.block_id, &self.cache)?;
             self.modified = false;
-            self.block_device.write_block(self.block_id, &self.cache);
         }
+        Ok(())
     }
 }
 
 impl Drop for BlockCache {
+    /// 被换出或丢弃时已经没有人能处理写回错误了，只能panic
     fn drop(&mut self) {
         self.sync()
     }
@@ -88,49 +113,142 @@ impl Drop for BlockCache {
 /// Use a block cache of 16 blocks
 const BLOCK_CACHE_SIZE: usize = 16;
 
+/// How many times to look for an evictable block again before giving up
+const DEFAULT_EVICT_RETRIES: usize = 8;
+
+/// Key of a cached block: (device id, block id)
+type CacheKey = (usize, usize);
+
+/// A block evicted from the cache, its key stays in `BlockCacheManager::in_flight` until `write_back_evicted`
+type Evicted = (CacheKey, Arc<Mutex<BlockCache>>);
+
+/// A cached block and the block evicted to make room for it
+type Inserted = (Arc<Mutex<BlockCache>>, Option<Evicted>);
+
+/// Wait a little before the `attempt`-th retry, longer after each failure
+fn spin_backoff(attempt: usize) {
+    for _ in 0..1usize << attempt.min(10) {
+        core::hint::spin_loop();
+    }
+}
+
 pub struct BlockCacheManager {
-    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+    queue: VecDeque<(CacheKey, Arc<Mutex<BlockCache>>)>,
+    /// 所有块都被借用时最多重试几次
+    evict_retries: usize,
+    /// 每次重试之前调用，参数是第几次重试（从1开始）
+    backoff: fn(usize),
+    /// 按需读入（不在缓存中）的块数，预读的块不计入
+    misses: usize,
+    /// 预读进缓存的块数
+    prefetched: usize,
+    /// keys of the blocks being loaded, or evicted and being written back, outside the manager lock
+    /// 在这期间别人不能读入同一个块：否则两个读入者会各放一份进缓存，或者读到被换出的脏块写回之前的旧数据
+    in_flight: Vec<CacheKey>,
 }
 
 impl BlockCacheManager {
     pub fn new() -> Self {
         Self {
             queue: VecDeque::new(),
+            evict_retries: DEFAULT_EVICT_RETRIES,
+            backoff: spin_backoff,
+            misses: 0,
+            prefetched: 0,
+            in_flight: Vec::new(),
         }
     }
-    /// 寻找对应与block_id的BlockCache，如果block_id对应的Block还没有缓存到内存，就先将块设备上的block读到缓存
-    pub fn get_block_cache(
+    /// How many blocks had to be loaded because they were not cached when asked for
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+    /// How many blocks were loaded ahead of use by `prefetch`
+    pub fn prefetched(&self) -> usize {
+        self.prefetched
+    }
+    /// Set how many times to retry when every cached block is in use, `None` restores the default
+    pub fn set_evict_retries(&mut self, retries: Option<usize>) {
+        self.evict_retries = retries.unwrap_or(DEFAULT_EVICT_RETRIES);
+    }
+    /// Set what to do before each retry, e.g. yield to other tasks in the kernel; `None` restores spinning
+    pub fn set_backoff(&mut self, backoff: Option<fn(usize)>) {
+        self.backoff = backoff.unwrap_or(spin_backoff);
+    }
+    /// Put a block loaded by the caller into the cache, return the cached block and the block evicted to make room
+    /// 不同设备上相同编号的块是不同的缓存项；块已经被别人放入缓存时返回已有的那一个，调用者读入的块直接丢弃
+    /// 被换出的块交给调用者在释放管理器的锁之后用 write_back_evicted 写回，写回之前它一直被标记为正在读写；
+    /// 缓存已满并且每个块都正被借用时把调用者读入的块原样还回去，由调用者稍后重试
+    pub fn insert(
         &mut self,
         block_id: usize,
-        block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        // 遍历整个队列试图找到一个编号相同的块缓存，如果找到了，会将块缓存管理器中保存的块缓存的引用复制一份并返回
-        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == block_id) {
-            Arc::clone(&pair.1)
-        } else {
-            // substitute
-            // 对应找不到的情况，此时必须将块从磁盘读入内存中的缓冲区。在实际读取之前，需要判断管理器保存的块缓存数量是否已经达到了上限
-            if self.queue.len() == BLOCK_CACHE_SIZE {
-                // from front to tail
-                if let Some((idx, _)) = self
-                    .queue
-                    .iter()
-                    .enumerate()
-                    .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
-                {
-                    self.queue.drain(idx..=idx); // 此处当将一个block缓存移出queue后，Rsut会自动调用相关的Drop()函数处理的，如果数据被修改，Drop()函数就会将数据刷回磁盘中
-                } else {
-                    panic!("Run out of BlockCache!");
+        block_device: &Arc<dyn BlockDevice>,
+        block_cache: Arc<Mutex<BlockCache>>,
+    ) -> Result<Inserted, Arc<Mutex<BlockCache>>> {
+        let key = (block_device.id(), block_id);
+        if let Some(pair) = self.queue.iter().find(|pair| pair.0 == key) {
+            return Ok((Arc::clone(&pair.1), None));
+        }
+        // substitute
+        // 需要判断管理器保存的块缓存数量是否已经达到了上限
+        let mut evicted = None;
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // from front to tail
+            if let Some((idx, _)) = self
+                .queue
+                .iter()
+                .enumerate()
+                .find(|(_, pair)| Arc::strong_count(&pair.1) == 1)
+            {
+                evicted = self.queue.remove(idx);
+                if let Some((evicted_key, _)) = evicted {
+                    self.in_flight.push(evicted_key);
                 }
+            } else {
+                return Err(block_cache);
             }
-            // load block into mem and push back
-            let block_cache = Arc::new(Mutex::new(BlockCache::new(
-                block_id,
-                Arc::clone(&block_device),
-            )));
-            self.queue.push_back((block_id, Arc::clone(&block_cache)));
-            block_cache
         }
+        self.queue.push_back((key, Arc::clone(&block_cache)));
+        Ok((block_cache, evicted))
+    }
+    /// Put a block read ahead of use into the cache, return the block evicted to make room
+    /// 与insert不同，缓存已满并且每个块都正被借用时直接放弃（返回None），预读不值得等待
+    pub fn prefetch(
+        &mut self,
+        block_id: usize,
+        block_device: &Arc<dyn BlockDevice>,
+        block_cache: Arc<Mutex<BlockCache>>,
+    ) -> Option<Option<Evicted>> {
+        let (_, evicted) = self.insert(block_id, block_device, block_cache).ok()?;
+        self.prefetched += 1;
+        Some(evicted)
+    }
+    /// Get the cached block `block_id` of `block_device` without loading it on a miss
+    /// 只查询，不会读磁盘，也不会改变缓存区的内容
+    pub fn peek(&self, block_id: usize, block_device: &Arc<dyn BlockDevice>) -> Option<Arc<Mutex<BlockCache>>> {
+        let key = (block_device.id(), block_id);
+        self.queue
+            .iter()
+            .find(|pair| pair.0 == key)
+            .map(|pair| Arc::clone(&pair.1))
+    }
+    /// Forget that the block `key` is being loaded or written back
+    fn clear_in_flight(&mut self, key: CacheKey) {
+        self.in_flight.retain(|&flying| flying != key);
+    }
+    /// Split the cached blocks into the idle ones (referenced only by the manager), oldest first, and
+    /// the number of dirty blocks among the borrowed ones
+    /// 正被借用的块缓存可能正在被修改，不能写回，只统计其中的脏块；空闲的块缓存只返回弱引用，理由见 block_cache_try_sync_all
+    fn idle_caches(&self) -> (Vec<Weak<Mutex<BlockCache>>>, usize) {
+        let mut idle = Vec::new();
+        let mut borrowed_dirty = 0usize;
+        for (_, cache) in self.queue.iter() {
+            if Arc::strong_count(cache) > 1 {
+                borrowed_dirty += cache.try_lock().map_or(1, |cache| cache.is_dirty() as usize);
+            } else {
+                idle.push(Arc::downgrade(cache));
+            }
+        }
+        (idle, borrowed_dirty)
     }
 }
 
@@ -143,20 +261,245 @@ lazy_static! {
 
 /// Get the block cache corresponding to the given block id and block device
 /// 获得block_id对应的在缓存区中的blockcache, 如果缓存区中没有的话就先去磁盘中读到缓存区中
+/// 设备读出错时panic，需要处理读错误时使用 try_get_block_cache
 pub fn get_block_cache(
     block_id: usize,
     block_device: Arc<dyn BlockDevice>,
 ) -> Arc<Mutex<BlockCache>> {
-    BLOCK_CACHE_MANAGER
+    try_get_block_cache(block_id, block_device).expect("Error when loading a block!")
+}
+
+/// Get the block cache of `block_id` on `block_device`, or the error the device reported when loading it
+/// 读入新块和写回被换出的块都不持有管理器的锁：块设备可能是另一个easy-fs中的镜像文件（挂载），读写它还要再获取块缓存；
+/// 这期间这两个块都被标记为正在读写，别人要获取它们时先等待，完成之后再查缓存；
+/// 读失败的块不会进入缓存；
+/// 所有块都正被借用时，其他核上的借用者很快就会归还，所以先释放管理器的锁等一会儿再重试，重试次数用完才返回CacheFull
+pub fn try_get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Result<Arc<Mutex<BlockCache>>, BlockError> {
+    let key = (block_device.id(), block_id);
+    let mut attempt = 0usize;
+    loop {
+        let mut manager = BLOCK_CACHE_MANAGER.lock();
+        if let Some(block_cache) = manager.peek(block_id, &block_device) {
+            return Ok(block_cache);
+        }
+        if !manager.in_flight.contains(&key) {
+            manager.in_flight.push(key);
+            break;
+        }
+        attempt += 1;
+        let backoff = manager.backoff;
+        drop(manager);
+        backoff(attempt);
+    }
+    // load block into mem and push back
+    let mut block_cache = match BlockCache::try_new(block_id, Arc::clone(&block_device)) {
+        Ok(block_cache) => Arc::new(Mutex::new(block_cache)),
+        Err(err) => {
+            BLOCK_CACHE_MANAGER.lock().clear_in_flight(key);
+            return Err(err);
+        }
+    };
+    let mut attempt = 0usize;
+    let (block_cache, evicted) = loop {
+        let mut manager = BLOCK_CACHE_MANAGER.lock();
+        match manager.insert(block_id, &block_device, block_cache) {
+            Ok(inserted) => {
+                manager.misses += 1;
+                manager.clear_in_flight(key);
+                break inserted;
+            }
+            Err(_) if attempt == manager.evict_retries => {
+                manager.clear_in_flight(key);
+                return Err(BlockError::CacheFull { block_id });
+            }
+            Err(returned) => {
+                block_cache = returned;
+                attempt += 1;
+                let backoff = manager.backoff;
+                drop(manager);
+                backoff(attempt);
+            }
+        }
+    };
+    write_back_evicted(evicted);
+    Ok(block_cache)
+}
+
+/// Write back a block evicted by `BlockCacheManager::insert` without holding the manager lock, then let it be loaded again
+fn write_back_evicted(evicted: Option<Evicted>) {
+    if let Some((key, block_cache)) = evicted {
+        // 被换出的块在这里被释放，如果数据被修改，先将数据刷回磁盘中；
+        // 同步全部缓存的人可能刚刚拿到它的引用，所以显式地写回，而不是指望Drop()
+        block_cache.lock().sync();
+        drop(block_cache);
+        BLOCK_CACHE_MANAGER.lock().clear_in_flight(key);
+    }
+}
+
+/// Load the blocks `block_ids` of `block_device` into the cache ahead of use, return how many were loaded
+/// 已经在缓存中或者正被别人读入的块跳过；读出错或者腾不出位置时停止预读，不报告错误，真正用到这些块时再处理
+pub fn block_cache_prefetch(block_ids: &[usize], block_device: &Arc<dyn BlockDevice>) -> usize {
+    let mut loaded = 0usize;
+    for &block_id in block_ids {
+        let key = (block_device.id(), block_id);
+        {
+            let mut manager = BLOCK_CACHE_MANAGER.lock();
+            if manager.peek(block_id, block_device).is_some() || manager.in_flight.contains(&key) {
+                continue;
+            }
+            manager.in_flight.push(key);
+        }
+        let block_cache = match BlockCache::try_new(block_id, Arc::clone(block_device)) {
+            Ok(block_cache) => Arc::new(Mutex::new(block_cache)),
+            Err(_) => {
+                BLOCK_CACHE_MANAGER.lock().clear_in_flight(key);
+                break;
+            }
+        };
+        let evicted = {
+            let mut manager = BLOCK_CACHE_MANAGER.lock();
+            let evicted = manager.prefetch(block_id, block_device, block_cache);
+            manager.clear_in_flight(key);
+            evicted
+        };
+        match evicted {
+            // 被换出的块在释放管理器的锁之后才写回
+            Some(evicted) => write_back_evicted(evicted),
+            None => break,
+        }
+        loaded += 1;
+    }
+    loaded
+}
+
+/// Write back the cached block `block_id` of `block_device` if it is cached and dirty, other blocks are left untouched
+/// 只刷新一个block，不在缓存区中的block无需处理
+pub fn flush_block(block_id: usize, block_device: &Arc<dyn BlockDevice>) {
+    let cache = BLOCK_CACHE_MANAGER.lock().peek(block_id, block_device);
+    if let Some(cache) = cache {
+        cache.lock().sync();
+    }
+}
+
+/// Get the block cache of `block_id` on `block_device` if it is resident, see `BlockCacheManager::peek`
+pub fn peek_block_cache(block_id: usize, block_device: &Arc<dyn BlockDevice>) -> Option<Arc<Mutex<BlockCache>>> {
+    BLOCK_CACHE_MANAGER.lock().peek(block_id, block_device)
+}
+
+/// Write back at most `max` dirty blocks, oldest first, return (how many were flushed, how many remain dirty)
+/// 供内核周期性地调用，把写回分散开，而不是在sys_sync时集中写回
+/// 只写回管理器之外没有人借用的块缓存，并且在释放管理器的锁之后才写回
+pub fn block_cache_sync_dirty_limit(max: usize) -> (usize, usize) {
+    let (idle, mut remaining) = BLOCK_CACHE_MANAGER.lock().idle_caches();
+    let mut flushed = 0usize;
+    for cache in idle.iter().filter_map(Weak::upgrade) {
+        let mut cache = cache.lock();
+        if !cache.is_dirty() {
+            continue;
+        }
+        // 写回失败的块仍然是脏的，留到下一次再写
+        if flushed < max && cache.try_sync().is_ok() {
+            flushed += 1;
+        } else {
+            remaining += 1;
+        }
+    }
+    (flushed, remaining)
+}
+
+/// Write back the dirty cached blocks of `block_device` only, oldest first
+/// 与 block_cache_try_sync_all 一样只持有弱引用，也不调用设备的flush
+pub fn block_cache_sync_device(block_device: &Arc<dyn BlockDevice>) -> Result<(), BlockError> {
+    block_cache_sync_device_since(block_device, 0)
+}
+
+/// Current value of the counter of block modifications, pass it to `block_cache_sync_device_since` later
+pub fn block_cache_modify_seq() -> usize {
+    MODIFY_SEQ.load(Ordering::Relaxed)
+}
+
+/// Write back the cached blocks of `block_device` modified after `block_cache_modify_seq` returned `seq`
+/// 更早被修改、之后没有再动过的脏块留在缓存中；期间被换出的块在换出时已经写回了
+pub fn block_cache_sync_device_since(block_device: &Arc<dyn BlockDevice>, seq: usize) -> Result<(), BlockError> {
+    let id = block_device.id();
+    let caches: Vec<Weak<Mutex<BlockCache>>> = BLOCK_CACHE_MANAGER
         .lock()
-        .get_block_cache(block_id, block_device)
+        .queue
+        .iter()
+        .filter(|pair| pair.0 .0 == id)
+        .map(|pair| Arc::downgrade(&pair.1))
+        .collect();
+    let mut result = Ok(());
+    for cache in caches.iter().filter_map(Weak::upgrade) {
+        let mut cache = cache.lock();
+        if cache.modified_seq <= seq {
+            continue;
+        }
+        if let Err(err) = cache.try_sync() {
+            result = result.and(Err(err));
+        }
+    }
+    result
+}
+
+/// Write back and drop every cached block of `block_device`, e.g. once the filesystem on it is unmounted
+/// 缓存项持有设备的引用，不丢弃它们设备就一直不会被释放（镜像设备被释放时才放开镜像文件）；
+/// 正被借用的块留在缓存中
+pub fn block_cache_drop_device(block_device: &Arc<dyn BlockDevice>) {
+    let id = block_device.id();
+    let mut dropped = Vec::new();
+    let mut manager = BLOCK_CACHE_MANAGER.lock();
+    manager.queue.retain(|(key, cache)| {
+        if key.0 == id && Arc::strong_count(cache) == 1 {
+            dropped.push((*key, Arc::clone(cache)));
+            false
+        } else {
+            true
+        }
+    });
+    manager.in_flight.extend(dropped.iter().map(|(key, _)| *key));
+    drop(manager);
+    // 和被换出的块一样，在释放管理器的锁之后才写回
+    for evicted in dropped {
+        write_back_evicted(Some(evicted));
+    }
 }
 
 /// Sync all block cache to block device
-/// 将缓存区中的所有数据都更新到磁盘中
+/// 将缓存区中的所有数据都更新到磁盘中，设备写出错时panic，需要处理写错误时使用 block_cache_try_sync_all
 pub fn block_cache_sync_all() {
-    let manager = BLOCK_CACHE_MANAGER.lock();
-    for (_, cache) in manager.queue.iter() {
-        cache.lock().sync();
+    block_cache_try_sync_all().expect("Error when writing back a block!")
+}
+
+/// Sync all block cache to block device, return the first error a device reported
+/// 先复制出所有块缓存的弱引用再逐个同步，不在持有管理器锁的同时等待块缓存的锁；
+/// 只用弱引用是因为写回镜像设备（见挂载）上的块时还要换入外层设备的块，这期间其他块缓存必须能被换出，
+/// 已经被换出的块在换出时就写回了，跳过即可；
+/// 某个块写回失败时继续写回其他块，失败的块仍然是脏的；
+/// 全部写回之后再让缓存中出现过的每个设备各flush一次
+pub fn block_cache_try_sync_all() -> Result<(), BlockError> {
+    let caches: Vec<Weak<Mutex<BlockCache>>> = BLOCK_CACHE_MANAGER
+        .lock()
+        .queue
+        .iter()
+        .map(|pair| Arc::downgrade(&pair.1))
+        .collect();
+    let mut devices: Vec<Arc<dyn BlockDevice>> = Vec::new();
+    let mut result = Ok(());
+    for cache in caches.iter().filter_map(Weak::upgrade) {
+        let mut cache = cache.lock();
+        if let Err(err) = cache.try_sync() {
+            result = result.and(Err(err));
+        }
+        if !devices.iter().any(|device| device.id() == cache.block_device.id()) {
+            devices.push(Arc::clone(&cache.block_device));
+        }
+    }
+    for device in devices {
+        device.flush();
     }
+    result
 }
@@ -1,9 +1,39 @@
 use core::any::Any;
 
+/// Why a block device failed to transfer a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The device reported an I/O error for the block
+    Io { block_id: usize },
+    /// The block lies beyond the end of the device
+    OutOfRange { block_id: usize },
+    /// Every cached block stayed in use after all the retries, there is no room to load the block
+    CacheFull { block_id: usize },
+}
+
 /// Trait for block devices
 /// which reads and writes data in the unit of blocks
 pub trait BlockDevice : Send + Sync + Any {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     fn write_block(&self, block_id: usize, buf: &[u8]);
-    
+    /// Read a block, reporting a device error instead of panicking
+    /// 默认调用read_block（出错时由实现自己panic）；能检测到错误的设备应重写它，块缓存只通过它读设备
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.read_block(block_id, buf);
+        Ok(())
+    }
+    /// Write a block, reporting a device error instead of panicking
+    /// 默认调用write_block；块缓存写回时只通过它写设备
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        self.write_block(block_id, buf);
+        Ok(())
+    }
+    /// Make the blocks written so far durable, for devices that buffer writes themselves
+    /// 默认什么都不做；block_cache_sync_all 在写回所有脏块之后对每个设备调用一次
+    fn flush(&self) {}
+    /// An id telling this device apart from every other live device, used to key the block cache
+    /// 默认使用设备对象的地址：块缓存持有设备的引用，所以缓存中的设备不会被释放，地址也不会被复用
+    fn id(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
 }
@@ -0,0 +1,88 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use lazy_static::*;
+use spin::Mutex;
+
+/// Max number of translations remembered per disk inode
+const BLOCK_MAP_SIZE: usize = 64;
+
+/// Key of a disk inode: (device id, block_id, block_offset)
+type BlockMapKey = (usize, usize, usize);
+
+/// A small cache from the inner block ids of a file to the block ids on the device
+/// 缓存的是地址转换（省去遍历间接索引块），而不是块的内容；任何改变DiskInode中索引的操作都必须清空它
+pub struct BlockMap {
+    map: BTreeMap<u32, u32>,
+    misses: usize,
+}
+
+impl BlockMap {
+    pub fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            misses: 0,
+        }
+    }
+    /// Translate `inner_id`, calling `translate` only if it is not cached yet
+    /// 满了之后淘汰下标最小的一项，顺序读大文件时它最不可能再被用到
+    pub fn get_or_translate(&mut self, inner_id: u32, translate: impl FnOnce() -> u32) -> u32 {
+        if let Some(block_id) = self.map.get(&inner_id) {
+            return *block_id;
+        }
+        self.misses += 1;
+        let block_id = translate();
+        if self.map.len() == BLOCK_MAP_SIZE {
+            let first = *self.map.keys().next().unwrap();
+            self.map.remove(&first);
+        }
+        self.map.insert(inner_id, block_id);
+        block_id
+    }
+    /// Forget every translation
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+    /// How many translations had to walk the disk inode
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+/// Hands out one BlockMap per disk inode, so that every Inode opened on the same file sees the same invalidations
+/// 与块缓存一样按(设备, 位置)区分：不同设备上同一位置的inode指向的数据块不同，不能共用
+pub struct BlockMapManager {
+    maps: BTreeMap<BlockMapKey, Weak<Mutex<BlockMap>>>,
+}
+
+impl BlockMapManager {
+    pub fn new() -> Self {
+        Self {
+            maps: BTreeMap::new(),
+        }
+    }
+    /// Get the block map of the disk inode at `key`, creating it if no Inode holds it now
+    pub fn get_block_map(&mut self, key: BlockMapKey) -> Arc<Mutex<BlockMap>> {
+        if let Some(map) = self.maps.get(&key).and_then(|map| map.upgrade()) {
+            return map;
+        }
+        // 顺便清理已经没有Inode使用的映射
+        self.maps.retain(|_, map| map.strong_count() > 0);
+        let map = Arc::new(Mutex::new(BlockMap::new()));
+        self.maps.insert(key, Arc::downgrade(&map));
+        map
+    }
+}
+
+lazy_static! {
+    /// The global block map manager
+    pub static ref BLOCK_MAP_MANAGER: Mutex<BlockMapManager> = Mutex::new(
+        BlockMapManager::new()
+    );
+}
+
+/// Get the block map shared by all the Inodes of the disk inode at (block_id, block_offset) on `device_id`
+pub fn get_block_map(device_id: usize, block_id: usize, block_offset: usize) -> Arc<Mutex<BlockMap>> {
+    BLOCK_MAP_MANAGER
+        .lock()
+        .get_block_map((device_id, block_id, block_offset))
+}
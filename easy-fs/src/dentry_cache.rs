@@ -0,0 +1,68 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+/// Max number of dentries cached
+const DENTRY_CACHE_SIZE: usize = 64;
+
+/// Position of a disk inode: (block_id, block_offset)
+type InodePos = (usize, usize);
+
+/// A cached lookup result: `name` under the directory at `parent` is the inode at `child`
+struct Dentry {
+    parent: InodePos,
+    name: String,
+    child: (u32, usize),
+}
+
+/// A small LRU cache of directory lookups, kept inside EasyFileSystem so it is protected by the fs lock
+/// 只缓存查找成功的结果，任何修改目录项的操作都必须让相应的缓存项失效
+pub struct DentryCache {
+    queue: VecDeque<Dentry>,
+    hits: usize,
+}
+
+impl DentryCache {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            hits: 0,
+        }
+    }
+    /// Look up `name` under `parent`, a hit is moved to the back of the queue
+    pub fn lookup(&mut self, parent: InodePos, name: &str) -> Option<(u32, usize)> {
+        let idx = self
+            .queue
+            .iter()
+            .position(|dentry| dentry.parent == parent && dentry.name == name)?;
+        let dentry = self.queue.remove(idx).unwrap();
+        let child = dentry.child;
+        self.queue.push_back(dentry);
+        self.hits += 1;
+        Some(child)
+    }
+    /// Remember that `name` under `parent` is the inode at `child`, evicting the least recently used entry if full
+    pub fn insert(&mut self, parent: InodePos, name: &str, child: (u32, usize)) {
+        self.invalidate(parent, name);
+        if self.queue.len() == DENTRY_CACHE_SIZE {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(Dentry {
+            parent,
+            name: String::from(name),
+            child,
+        });
+    }
+    /// Forget `name` under `parent`
+    pub fn invalidate(&mut self, parent: InodePos, name: &str) {
+        self.queue
+            .retain(|dentry| !(dentry.parent == parent && dentry.name == name));
+    }
+    /// Forget every entry under `parent`, used when the directory itself is freed
+    pub fn invalidate_dir(&mut self, parent: InodePos) {
+        self.queue.retain(|dentry| dentry.parent != parent);
+    }
+    /// How many lookups were answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
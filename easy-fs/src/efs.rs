@@ -1,17 +1,60 @@
-use alloc::sync::Arc;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use spin::Mutex;
 use super::{
     BlockDevice,
     Bitmap,
     SuperBlock,
+    EFS_VERSION,
+    inodes_per_block,
     DiskInode,
     DiskInodeType,
+    DirEntry,
+    DIRENT_SZ,
+    DentryCache,
+    PathCache,
     Inode,
     get_block_cache,
     block_cache_sync_all,
+    block_cache_modify_seq,
+    block_cache_sync_device_since,
+    flush_block,
+    JournalRecord,
 };
 use crate::BLOCK_SZ;
 
+/// Why a block device could not be opened as an easy-fs image
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpenError {
+    /// The magic number does not match, this is not an easy-fs image
+    BadMagic,
+    /// The image was created with a block size other than the compiled `BLOCK_SZ`
+    BlockSizeMismatch { image: u32, expected: u32 },
+    /// The image was created with an on-disk format other than the compiled `EFS_VERSION`
+    VersionMismatch { image: u32, expected: u32 },
+    /// The image was created with a `DiskInode` size other than the compiled one
+    InodeSizeMismatch { image: u32, expected: u32 },
+}
+
+/// Why a filesystem could not be resized
+#[derive(Debug, PartialEq, Eq)]
+pub enum GrowError {
+    /// The data blocks that would be cut off are still in use
+    InUse,
+    /// The data bitmap has no bits for that many data blocks, and it cannot grow in place
+    BitmapFull,
+    /// Too few blocks for the metadata areas and the journal
+    TooSmall,
+}
+
+/// How far apart the preferred data blocks of consecutive inodes are
+/// 不同文件新分配的数据块从各自的位置开始找，交错写入的几个文件也不会把块交替地分到一起
+const INODE_GOAL_SPREAD: u32 = 64;
+/// At most how many freed data blocks are remembered as zeroed, see `EasyFileSystem::zeroed_free`
+/// 集合放在内核堆上，不能随被删除文件的大小无限增长；超出的块再次分配时重新清零即可
+pub const ZEROED_FREE_MAX: usize = 1024;
+
 /// An easy fs over a block device
 pub struct EasyFileSystem {
     pub block_device: Arc<dyn BlockDevice>,
@@ -19,10 +62,37 @@ pub struct EasyFileSystem {
     pub data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// cache of recent directory lookups
+    pub(crate) dentry_cache: DentryCache,
+    /// cache of recent path resolutions from the root directory
+    pub(crate) path_cache: PathCache,
+    /// whether every free data block is known to be zero, only right after `create` zeroed the whole device
+    all_free_zeroed: bool,
+    /// free data blocks known to be zero because `dealloc_data` zeroed them since the image was opened,
+    /// at most `ZEROED_FREE_MAX` of them
+    zeroed_free: BTreeSet<u32>,
+    /// how many allocated data blocks had to be zeroed, see `zero_fills`
+    zero_fills: usize,
+    /// the block holding the journal of directory operations
+    journal_block: u32,
+    /// `block_cache_modify_seq` when the pending operation started changing blocks, see `journal_track`
+    journal_seq: Option<usize>,
+    /// how many open files refer to each inode, see `Inode::hold_open`
+    pub(crate) open_counts: BTreeMap<u32, usize>,
+    /// image files backing a mounted filesystem and how many times each is mounted, see `Inode::hold_image`
+    pub(crate) mounted_images: BTreeMap<u32, usize>,
+    /// inodes unlinked while still open, freed when the last open file is closed
+    /// 只记录在内存中：崩溃后这些inode和它们的数据块会泄漏
+    pub(crate) orphans: BTreeSet<u32>,
+    /// the shared root inode handed out by `root_inode_arc`
+    /// 根Inode持有整个文件系统，所以这里只能弱引用它，否则文件系统永远不会被释放，挂载后也无法卸载
+    root: Weak<Inode>,
 }
 
 /// A data block of block size
 type DataBlock = [u8; BLOCK_SZ];
+/// Raw bytes of a disk inode
+type DiskInodeBytes = [u8; core::mem::size_of::<DiskInode>()];
 
 impl EasyFileSystem {
     /// Create a filesystem from a block device
@@ -34,22 +104,37 @@ impl EasyFileSystem {
         // calculate block size of areas & create bitmaps
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
         let inode_num = inode_bitmap.maximum(); // 本索引位图区可表示多少个索引节点的状态
-        let inode_area_blocks =
-            ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SZ - 1) / BLOCK_SZ) as u32; // 索引节点区中block总个数（向上取整）
+        let inodes_per_block = inodes_per_block(core::mem::size_of::<DiskInode>())
+            .expect("A block must hold a whole number of disk inodes!");
+        let inode_area_blocks = ((inode_num + inodes_per_block - 1) / inodes_per_block) as u32; // 索引节点区中block总个数（向上取整）
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks; // 索引区总的block个数
-        let data_total_blocks = total_blocks - 1 - inode_total_blocks; // 磁盘中block总数减去超级块区域（占一个block）和索引区后剩下的都是数据区
+        assert!(total_blocks > 2 + inode_total_blocks, "Too few blocks for the inode area!");
+        let journal_block = total_blocks - 1; // 最后一个块用作目录操作的日志
+        let data_total_blocks = total_blocks - 2 - inode_total_blocks; // 磁盘中block总数减去超级块区域（占一个block）、索引区和日志块后剩下的都是数据区
         let data_bitmap_blocks = (data_total_blocks + 4096) / 4097; // 数据位图占的block个数
         let data_area_blocks = data_total_blocks - data_bitmap_blocks; // 实际用于存储数据的区域中block个数
         let data_bitmap = Bitmap::new(
             (1 + inode_bitmap_blocks + inode_area_blocks) as usize,
             data_bitmap_blocks as usize,
-        );
+        )
+        .with_limit(data_area_blocks as usize);
         let mut efs = Self {
             block_device: Arc::clone(&block_device),
             inode_bitmap,
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            dentry_cache: DentryCache::new(),
+            path_cache: PathCache::new(),
+            all_free_zeroed: true,
+            zeroed_free: BTreeSet::new(),
+            zero_fills: 0,
+            journal_block,
+            journal_seq: None,
+            open_counts: BTreeMap::new(),
+            mounted_images: BTreeMap::new(),
+            orphans: BTreeSet::new(),
+            root: Weak::new(),
         };
         // clear all blocks
         // 将物理磁盘上的所有空间都初始化为0（其实是在缓存区中做这件事，但是缓存区的大小大概率会比磁盘大，
@@ -75,11 +160,12 @@ impl EasyFileSystem {
                 inode_area_blocks,
                 data_bitmap_blocks,
                 data_area_blocks,
+                journal_block,
             );
         });
         // write back immediately
         // create a inode for root node "/"
-        assert_eq!(efs.alloc_inode(), 0);  // 将索引位图的第一个bit置为1
+        assert_eq!(efs.alloc_inode(), Some(0));  // 将索引位图的第一个bit置为1
         let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(0);
         get_block_cache(
             root_inode_block_id as usize,
@@ -88,18 +174,44 @@ impl EasyFileSystem {
         .lock()
         .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
             disk_inode.initialize(DiskInodeType::Directory);
+            // 根目录的".."指向它自己
+            assert!(efs.init_dir(disk_inode, 0, 0), "No space for the root directory!");
         });
         block_cache_sync_all();
         Arc::new(Mutex::new(efs))
     }
     /// Open a block device as a filesystem
-    /// 从一个已写入了 easy-fs 镜像的块设备上打开我们的 easy-fs
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+    /// 从一个已写入了 easy-fs 镜像的块设备上打开我们的 easy-fs，镜像不合法、格式版本或块大小与编译时不一致时返回错误；
+    /// 日志中还有没完成的目录操作（上次在操作中途崩溃）时先恢复它
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Mutex<Self>>, OpenError> {
         // read SuperBlock
-        get_block_cache(0, Arc::clone(&block_device))
+        let efs = get_block_cache(0, Arc::clone(&block_device))
             .lock()
             .read(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "Error loading EFS!");
+                if !super_block.is_valid() {
+                    return Err(OpenError::BadMagic);
+                }
+                // 不同版本的磁盘布局不同，不能按当前的布局解读旧镜像
+                if super_block.version != EFS_VERSION {
+                    return Err(OpenError::VersionMismatch {
+                        image: super_block.version,
+                        expected: EFS_VERSION,
+                    });
+                }
+                if super_block.block_size != BLOCK_SZ as u32 {
+                    return Err(OpenError::BlockSizeMismatch {
+                        image: super_block.block_size,
+                        expected: BLOCK_SZ as u32,
+                    });
+                }
+                // inode的位置是按编号和大小算出来的，大小不同就会读到错位的inode
+                let inode_size = core::mem::size_of::<DiskInode>() as u32;
+                if super_block.inode_size != inode_size {
+                    return Err(OpenError::InodeSizeMismatch {
+                        image: super_block.inode_size,
+                        expected: inode_size,
+                    });
+                }
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
                 let efs = Self {
@@ -111,19 +223,142 @@ impl EasyFileSystem {
                     data_bitmap: Bitmap::new(
                         (1 + inode_total_blocks) as usize,
                         super_block.data_bitmap_blocks as usize,
-                    ),
+                    )
+                    .with_limit(super_block.data_area_blocks as usize),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    dentry_cache: DentryCache::new(),
+                    path_cache: PathCache::new(),
+                    // 上次可能没有正常关闭：数据块的清零和数据位图的写回顺序不确定，空闲块中可能残留旧数据
+                    all_free_zeroed: false,
+                    zeroed_free: BTreeSet::new(),
+                    zero_fills: 0,
+                    journal_block: super_block.journal_block,
+                    journal_seq: None,
+                    open_counts: BTreeMap::new(),
+                    mounted_images: BTreeMap::new(),
+                    orphans: BTreeSet::new(),
+                    root: Weak::new(),
                 };
-                Arc::new(Mutex::new(efs))
-            })
+                Ok(Arc::new(Mutex::new(efs)))
+            })?;
+        Self::replay_journal(&efs);
+        Ok(efs)
+    }
+    /// Resize the filesystem to `new_total_blocks`, the device must have that many blocks
+    /// 只有数据区和其后的日志块随之变化：新的日志块放在最后一个块，原来的日志块成为普通的数据块；
+    /// 数据位图紧挨着数据区，不能增加块，所以数据区最多增长到数据位图最后一个块的所有bit都用上为止。
+    /// 缩小时被去掉的数据块（包括新的日志块）必须都是空闲的
+    pub fn grow(&mut self, new_total_blocks: u32) -> Result<(), GrowError> {
+        let data_area_blocks = new_total_blocks
+            .checked_sub(self.data_area_start_block + 1)
+            .filter(|&blocks| blocks > 0)
+            .ok_or(GrowError::TooSmall)?;
+        if data_area_blocks as usize > self.data_bitmap.maximum() {
+            return Err(GrowError::BitmapFull);
+        }
+        if !self.data_bitmap.resize(&self.block_device, data_area_blocks as usize) {
+            return Err(GrowError::InUse);
+        }
+        // 新的数据块中的内容未知，被截掉的块不再是空闲的数据块
+        self.all_free_zeroed = false;
+        let data_area_end = self.data_area_start_block + data_area_blocks;
+        self.zeroed_free.retain(|&block_id| block_id < data_area_end);
+        // 先写好空的新日志块，再让超级块指向它
+        let journal_block = new_total_blocks - 1;
+        get_block_cache(journal_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| data_block.fill(0));
+        flush_block(journal_block as usize, &self.block_device);
+        get_block_cache(0, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |super_block: &mut SuperBlock| {
+                super_block.total_blocks = new_total_blocks;
+                super_block.data_area_blocks = data_area_blocks;
+                super_block.journal_block = journal_block;
+            });
+        flush_block(0, &self.block_device);
+        self.journal_block = journal_block;
+        Ok(())
+    }
+    /// Finish or undo the directory operation left in the journal by a crash, then clear the journal
+    fn replay_journal(efs: &Arc<Mutex<Self>>) {
+        let (block_device, journal_block) = {
+            let fs = efs.lock();
+            (Arc::clone(&fs.block_device), fs.journal_block as usize)
+        };
+        let pending = get_block_cache(journal_block, block_device)
+            .lock()
+            .read(0, |record: &JournalRecord| record.op().is_some());
+        if pending {
+            Inode::recover(efs);
+            efs.lock().journal_end();
+        }
+    }
+    /// Record a directory operation in the journal before performing it
+    /// 记录立即写回，之后操作修改的块无论以什么顺序写回，崩溃后都能从记录恢复
+    pub(crate) fn journal_begin(&mut self, record: JournalRecord) {
+        get_block_cache(self.journal_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |journal: &mut JournalRecord| *journal = record);
+        flush_block(self.journal_block as usize, &self.block_device);
+        self.journal_track();
+    }
+    /// Start collecting the blocks the next journaled operation changes, if not started yet
+    /// 在写日志记录之前就要修改块的操作（如先分配inode才知道要记录的编号）需要提前调用
+    pub(crate) fn journal_track(&mut self) {
+        if self.journal_seq.is_none() {
+            self.journal_seq = Some(block_cache_modify_seq());
+        }
+    }
+    /// Write back the blocks the journaled operation changed, then clear its record
+    /// 只写回journal_begin之后被修改过的块，其他文件早先留下的脏块不受影响；
+    /// 写回出错时保留记录，下次打开时再恢复；调用时不能持有任何块缓存的锁
+    /// 没有开始收集时（打开时恢复崩溃前的操作）写回这个设备的所有脏块
+    pub(crate) fn journal_end(&mut self) {
+        let since = self.journal_seq.take().unwrap_or(0);
+        if block_cache_sync_device_since(&self.block_device, since).is_err() {
+            return;
+        }
+        get_block_cache(self.journal_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |journal: &mut JournalRecord| journal.clear());
+        flush_block(self.journal_block as usize, &self.block_device);
+    }
+    /// Read the pending journal record
+    pub(crate) fn journal_record<V>(&self, f: impl FnOnce(&JournalRecord) -> V) -> V {
+        get_block_cache(self.journal_block as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(0, f)
     }
     /// Get the root inode of the filesystem
     /// 创建root对应的inode
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        Self::inode_from_id(efs, 0)
+    }
+    /// Get the root inode of the filesystem, shared by all callers
+    /// 只要还有人持有之前返回的根，每次调用都返回同一个Arc；都被丢弃之后下次调用再创建一个新的
+    pub fn root_inode_arc(efs: &Arc<Mutex<Self>>) -> Arc<Inode> {
+        let cached = efs.lock().root.upgrade();
+        if let Some(root) = cached {
+            return root;
+        }
+        let root = Arc::new(Self::root_inode(efs));
+        let mut fs = efs.lock();
+        match fs.root.upgrade() {
+            Some(root) => root,
+            None => {
+                fs.root = Arc::downgrade(&root);
+                root
+            }
+        }
+    }
+    /// Get the inode with `inode_id`, which must be allocated
+    /// 与root_inode一样只是在内存中构造一个Inode，不检查索引位图
+    pub fn inode_from_id(efs: &Arc<Mutex<Self>>, inode_id: u32) -> Inode {
         let block_device = Arc::clone(&efs.lock().block_device);
         // acquire efs lock temporarily
-        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(0);
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(inode_id);
         // release efs lock
         Inode::new(
             block_id,
@@ -132,6 +367,24 @@ impl EasyFileSystem {
             block_device,
         )
     }
+    /// List the ids of all allocated inodes in ascending order, the root (0) is always the first
+    /// 只读地扫描索引位图，可用于实现find、配额统计等需要遍历所有文件的工具
+    pub fn iter_allocated_inodes(&self) -> Vec<u32> {
+        self.inode_bitmap
+            .allocated_bits(&self.block_device)
+            .into_iter()
+            .map(|bit| bit as u32)
+            .collect()
+    }
+    /// Total number of inodes, including the root
+    pub fn total_inodes(&self) -> usize {
+        self.inode_bitmap.maximum()
+    }
+    /// Number of inodes that can still be allocated
+    /// 根目录（inode 0）在创建文件系统时就已分配且永不释放，所以最多是 total_inodes() - 1
+    pub fn free_inodes(&self) -> usize {
+        self.inode_bitmap.count_free(&self.block_device)
+    }
     /// Get inode by id
     /// 获得此inode_id对应的DiskInode在磁盘中的block id和在block内的偏移量（每个block可以存储多个inode）
     pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
@@ -145,22 +398,118 @@ impl EasyFileSystem {
     pub fn get_data_block_id(&self, data_block_id: u32) -> u32 {
         self.data_area_start_block + data_block_id
     }
+    /// How many directory lookups were answered by the dentry cache
+    pub fn dentry_cache_hits(&self) -> usize {
+        self.dentry_cache.hits()
+    }
+    /// How many paths were resolved by the path cache without looking up their components
+    pub fn path_cache_hits(&self) -> usize {
+        self.path_cache.hits()
+    }
+    /// Forget the cached lookups of `name` under the directory at `parent`, including every path through it
+    pub(crate) fn forget_dirent(&mut self, parent: (usize, usize), name: &str) {
+        self.dentry_cache.invalidate(parent, name);
+        self.path_cache.invalidate(parent, name);
+    }
+    /// Forget the cached lookups of every entry under the directory at `parent`
+    pub(crate) fn forget_dir(&mut self, parent: (usize, usize)) {
+        self.dentry_cache.invalidate_dir(parent);
+        self.path_cache.invalidate_dir(parent);
+    }
     pub fn get_inode_area_start_block(&self) -> u32{
         self.inode_area_start_block
     }
-    /// Allocate a new inode
+    /// Allocate a new inode, None if the inode bitmap is full
     /// 在索引位图上分配一个bit，并返回它对应的在索引区的inode的inode_id(也就是索引区的第几个索引，注意一个block中包含了多个inode)
-    pub fn alloc_inode(&mut self) -> u32 {
-        self.inode_bitmap.alloc(&self.block_device).unwrap() as u32
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap.alloc(&self.block_device).map(|inode_id| inode_id as u32)
     }
-    /// Allocate a data block
-    /// 将data bitmap中的一个bit置0，并返回它对应的block_id
-    pub fn alloc_data(&mut self) -> u32 {
-        self.data_bitmap.alloc(&self.block_device).unwrap() as u32 + self.data_area_start_block
+    /// Deallocate an inode
+    /// 将inode_id对应的DiskInode清零（防止该inode_id被复用时读到旧的type/size），并将其在索引位图中对应的bit置0
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        assert_ne!(inode_id, 0, "Cannot dealloc the root inode!");
+        let (block_id, block_offset) = self.get_disk_inode_pos(inode_id);
+        get_block_cache(
+            block_id as usize,
+            Arc::clone(&self.block_device)
+        )
+        .lock()
+        .modify(block_offset, |disk_inode: &mut DiskInodeBytes| {
+            disk_inode.iter_mut().for_each(|p| { *p = 0; })
+        });
+        self.inode_bitmap.dealloc(&self.block_device, inode_id as usize)
     }
-    /// Deallocate a data block
-    /// 将block_id对应的数据块中的所有字节置0，并将其对应的在bitmap中的位置置0
-    pub fn dealloc_data(&mut self, block_id: u32) {
+    /// Write the "." and ".." entries into an empty directory, return false if the disk is full
+    pub fn init_dir(&mut self, disk_inode: &mut DiskInode, inode_id: u32, parent_inode_id: u32) -> bool {
+        let new_size = (2 * DIRENT_SZ) as u32;
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match self.alloc_data() {
+                Some(block_id) => v.push(block_id),
+                None => {
+                    v.into_iter().for_each(|block_id| self.dealloc_data(block_id));
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device);
+        disk_inode.write_at(0, DirEntry::new(".", inode_id).as_bytes(), &self.block_device);
+        disk_inode.write_at(
+            DIRENT_SZ,
+            DirEntry::new("..", parent_inode_id).as_bytes(),
+            &self.block_device,
+        );
+        true
+    }
+    /// Allocate a data block, which is guaranteed to be filled with zeros
+    /// 将data bitmap中的一个bit置1，并返回它对应的block_id，数据区已满时返回None
+    /// 文件增长时新分配的块因此不必再清零，只需清零原来最后一个块中超出原size的部分
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        self.alloc_data_near(self.data_area_start_block)
+    }
+    /// Allocate a zeroed data block, preferring the first free one at or after the block `goal`
+    /// 目标之后没有空闲块时退回到从数据区开头找
+    pub fn alloc_data_near(&mut self, goal: u32) -> Option<u32> {
+        let block_id = self.data_bitmap
+            .alloc_near(&self.block_device, self.goal_bit(goal))
+            .map(|bit| bit as u32 + self.data_area_start_block)?;
+        self.ensure_zeroed(block_id);
+        Some(block_id)
+    }
+    /// Allocate `n` consecutive data blocks filled with zeros, return the id of the first one
+    /// 只在数据位图的一个块内寻找连续的空闲块，找不到时返回None，调用者应退回到逐块分配
+    pub fn alloc_data_extent(&mut self, n: u32) -> Option<u32> {
+        self.alloc_data_extent_near(n, self.data_area_start_block)
+    }
+    /// Like `alloc_data_extent`, but prefer a run at or after the block `goal`
+    pub fn alloc_data_extent_near(&mut self, n: u32, goal: u32) -> Option<u32> {
+        let start = self.data_bitmap
+            .alloc_contiguous_near(&self.block_device, n as usize, self.goal_bit(goal))
+            .map(|bit| bit as u32 + self.data_area_start_block)?;
+        (start..start + n).for_each(|block_id| self.ensure_zeroed(block_id));
+        Some(start)
+    }
+    /// Where the data blocks of inode `inode_id` should preferably go when it has no data block to follow
+    /// 按inode编号把目标位置分散到整个数据区
+    pub fn inode_goal(&self, inode_id: u32) -> u32 {
+        let data_blocks = self.data_bitmap.limit().max(1) as u64;
+        self.data_area_start_block + (inode_id as u64 * INODE_GOAL_SPREAD as u64 % data_blocks) as u32
+    }
+    /// The data bitmap bit of the block `goal`, blocks outside the data area aim at its start
+    fn goal_bit(&self, goal: u32) -> usize {
+        goal.checked_sub(self.data_area_start_block).unwrap_or(0) as usize
+    }
+    /// Zero a just allocated data block unless it is known to be zero already
+    /// 常见情况下块在回收时已经被 dealloc_data 清零（或者是 create 清零过的），不会重复清零
+    fn ensure_zeroed(&mut self, block_id: u32) {
+        if self.all_free_zeroed || self.zeroed_free.remove(&block_id) {
+            return;
+        }
+        self.zero_block(block_id);
+        self.zero_fills += 1;
+    }
+    fn zero_block(&self, block_id: u32) {
         get_block_cache(
             block_id as usize,
             Arc::clone(&self.block_device)
@@ -169,6 +518,18 @@ impl EasyFileSystem {
         .modify(0, |data_block: &mut DataBlock| {
             data_block.iter_mut().for_each(|p| { *p = 0; })
         });
+    }
+    /// How many allocated data blocks were not known to be zero and had to be zeroed
+    pub fn zero_fills(&self) -> usize {
+        self.zero_fills
+    }
+    /// Deallocate a data block
+    /// 将block_id对应的数据块中的所有字节置0，并将其对应的在bitmap中的位置置0
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        self.zero_block(block_id);
+        if !self.all_free_zeroed && self.zeroed_free.len() < ZEROED_FREE_MAX {
+            self.zeroed_free.insert(block_id);
+        }
         self.data_bitmap.dealloc(
             &self.block_device,
             (block_id - self.data_area_start_block) as usize
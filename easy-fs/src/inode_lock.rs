@@ -0,0 +1,46 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use lazy_static::*;
+use spin::{Mutex, RwLock};
+
+/// Position of a disk inode: (device id, block_id, block_offset)
+type InodePos = (usize, usize, usize);
+
+/// Hands out one RwLock per disk inode, so that every Inode opened on the same file shares it
+/// 与块缓存一样按(设备, 位置)区分：挂载的镜像中的inode可能与外层文件系统中的inode位置相同；只保存弱引用，没有Inode再使用某个锁时它就会被释放
+pub struct InodeLockManager {
+    locks: BTreeMap<InodePos, Weak<RwLock<()>>>,
+}
+
+impl InodeLockManager {
+    pub fn new() -> Self {
+        Self {
+            locks: BTreeMap::new(),
+        }
+    }
+    /// Get the lock of the disk inode at `pos`, creating it if no Inode holds it now
+    pub fn get_inode_lock(&mut self, pos: InodePos) -> Arc<RwLock<()>> {
+        if let Some(lock) = self.locks.get(&pos).and_then(|lock| lock.upgrade()) {
+            return lock;
+        }
+        // 顺便清理已经没有Inode使用的锁
+        self.locks.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(RwLock::new(()));
+        self.locks.insert(pos, Arc::downgrade(&lock));
+        lock
+    }
+}
+
+lazy_static! {
+    /// The global inode lock manager
+    pub static ref INODE_LOCK_MANAGER: Mutex<InodeLockManager> = Mutex::new(
+        InodeLockManager::new()
+    );
+}
+
+/// Get the lock shared by all the Inodes of the disk inode at (block_id, block_offset) on `device_id`
+pub fn get_inode_lock(device_id: usize, block_id: usize, block_offset: usize) -> Arc<RwLock<()>> {
+    INODE_LOCK_MANAGER
+        .lock()
+        .get_inode_lock((device_id, block_id, block_offset))
+}
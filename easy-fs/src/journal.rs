@@ -0,0 +1,84 @@
+use super::NAME_LENGTH_LIMIT;
+
+/// The journal block holds no pending operation
+const JOURNAL_EMPTY: u32 = 0;
+/// Magic number of a pending record, anything else in the journal block is ignored
+const JOURNAL_MAGIC: u32 = 0x6a6e6c01;
+
+/// The directory operation a journal record describes
+/// 恢复时创建和链接被撤销（还没返回给调用者，撤销总是安全的），删除和重命名则被重做
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalOp {
+    /// `names[0]` is being created in `dirs[0]` as inode `inodes[0]`
+    Create = 1,
+    /// `names[0]` in `dirs[0]` is being linked to inode `inodes[0]`
+    Link = 2,
+    /// `names[0]` referring to inode `inodes[0]` is being removed from `dirs[0]`
+    Unlink = 3,
+    /// `names[0]` in `dirs[0]` (inode `inodes[0]`) is being renamed to `names[1]` in `dirs[1]`,
+    /// overwriting inode `inodes[1]` if any
+    Rename = 4,
+    /// `names[0]` in `dirs[0]` (inode `inodes[0]`) and `names[1]` in `dirs[1]` (inode `inodes[1]`) are being swapped
+    Exchange = 5,
+}
+
+impl JournalOp {
+    fn from_u32(op: u32) -> Option<Self> {
+        match op {
+            1 => Some(Self::Create),
+            2 => Some(Self::Link),
+            3 => Some(Self::Unlink),
+            4 => Some(Self::Rename),
+            5 => Some(Self::Exchange),
+            _ => None,
+        }
+    }
+}
+
+/// An intent record in the journal block, written before a directory operation and cleared after it
+/// 记录的是操作的意图（哪个目录里的哪个名字、哪个inode），而不是被修改的块：
+/// 操作进行中块缓存随时可能换出其中的某些块，恢复时根据磁盘上的实际状态把操作撤销或补完
+#[repr(C)]
+pub struct JournalRecord {
+    magic: u32,
+    op: u32,
+    pub dirs: [u32; 2],
+    pub inodes: [u32; 2],
+    names: [[u8; NAME_LENGTH_LIMIT + 1]; 2],
+}
+
+impl JournalRecord {
+    /// A record of `op` on (`dir`, `name`, `inode_id`) pairs, the second one is unused by create/link/unlink
+    pub fn new(op: JournalOp, first: (u32, &str, u32), second: (u32, &str, u32)) -> Self {
+        let mut record = Self {
+            magic: JOURNAL_MAGIC,
+            op: op as u32,
+            dirs: [first.0, second.0],
+            inodes: [first.2, second.2],
+            names: [[0u8; NAME_LENGTH_LIMIT + 1]; 2],
+        };
+        for (slot, name) in record.names.iter_mut().zip([first.1, second.1]) {
+            let len = name.len().min(NAME_LENGTH_LIMIT);
+            slot[..len].copy_from_slice(&name.as_bytes()[..len]);
+        }
+        record
+    }
+    /// Mark the journal block as holding no pending operation
+    pub fn clear(&mut self) {
+        self.magic = JOURNAL_EMPTY;
+    }
+    /// The pending operation, None if the journal is empty
+    pub fn op(&self) -> Option<JournalOp> {
+        if self.magic != JOURNAL_MAGIC {
+            return None;
+        }
+        JournalOp::from_u32(self.op)
+    }
+    /// The `i`-th name of the record
+    pub fn name(&self, i: usize) -> &str {
+        let name = &self.names[i];
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        core::str::from_utf8(&name[..len]).unwrap_or("")
+    }
+}
@@ -2,17 +2,25 @@ use core::fmt::{Debug, Formatter, Result};
 use super::{
     BLOCK_SZ,
     BlockDevice,
+    BlockMap,
     get_block_cache,
 };
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
+use spin::Mutex;
 
 /// Magic number for sanity check
 const EFS_MAGIC: u32 = 0x3b800001;
+/// On-disk format version, bump it whenever the layout of the super block, inodes or dirents changes
+pub const EFS_VERSION: u32 = 5;
 /// The max number of direct inodes
-const INODE_DIRECT_COUNT: usize = 28;
+/// 原来是28个，让出的8个字节用来存放目录的块配额
+const INODE_DIRECT_COUNT: usize = 26;
 /// The max length of inode name
-const NAME_LENGTH_LIMIT: usize = 27;
+pub(crate) const NAME_LENGTH_LIMIT: usize = 27;
+/// `block_quota` of a directory without a quota
+const NO_QUOTA: u32 = u32::MAX;
 /// The max number of indirect1 inodes
 const INODE_INDIRECT1_COUNT: usize = BLOCK_SZ / 4;
 /// The max number of indirect2 inodes
@@ -34,6 +42,14 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// the block size the image was created with
+    pub block_size: u32,
+    /// the on-disk format version the image was created with
+    pub version: u32,
+    /// the size of a disk inode the image was created with
+    pub inode_size: u32,
+    /// the block holding the journal of directory operations, the last block of the device
+    pub journal_block: u32,
 }
 
 impl Debug for SuperBlock {
@@ -44,6 +60,10 @@ impl Debug for SuperBlock {
             .field("inode_area_blocks", &self.inode_area_blocks)
             .field("data_bitmap_blocks", &self.data_bitmap_blocks)
             .field("data_area_blocks", &self.data_area_blocks)
+            .field("block_size", &self.block_size)
+            .field("version", &self.version)
+            .field("inode_size", &self.inode_size)
+            .field("journal_block", &self.journal_block)
             .finish()
     }
 }
@@ -57,6 +77,7 @@ impl SuperBlock {
         inode_area_blocks: u32,
         data_bitmap_blocks: u32,
         data_area_blocks: u32,
+        journal_block: u32,
     ) {
         *self = Self {
             magic: EFS_MAGIC,
@@ -65,6 +86,10 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            block_size: BLOCK_SZ as u32,
+            version: EFS_VERSION,
+            inode_size: core::mem::size_of::<DiskInode>() as u32,
+            journal_block,
         }
     }
     /// Check if a super block is valid using efs magic
@@ -73,11 +98,27 @@ impl SuperBlock {
     }
 }
 
+/// How many disk inodes of `inode_size` bytes fit in a block, None if they do not fill it exactly
+/// 有剩余空间时按块内偏移计算的inode位置会和按编号计算的错开，inode还可能跨越块的边界
+pub fn inodes_per_block(inode_size: usize) -> Option<usize> {
+    (inode_size != 0 && BLOCK_SZ % inode_size == 0).then(|| BLOCK_SZ / inode_size)
+}
+
+// DiskInode增大或缩小后不能整除块大小时编译失败
+const _: () = assert!(
+    BLOCK_SZ % core::mem::size_of::<DiskInode>() == 0,
+    "a block must hold a whole number of disk inodes"
+);
+
 /// Type of a disk inode
+/// 只占一个字节，后面的padding用来存放权限位，DiskInode的大小仍然是128字节
+#[repr(u8)]
 #[derive(PartialEq)]
 pub enum DiskInodeType {
     File,
     Directory,
+    /// 数据区保存的是链接指向的路径
+    SymLink,
 }
 
 /// A indirect block
@@ -86,15 +127,27 @@ type IndirectBlock = [u32; BLOCK_SZ / 4];
 type DataBlock = [u8; BLOCK_SZ];
 
 /// A disk inode
+/// 数据块编号为0表示空洞（0号块是超级块，不可能是数据块）：读出来都是0，写之前要先为它分配数据块
 #[repr(C)]
 pub struct DiskInode {
     pub size: u32,
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// max number of blocks the subtree under this directory may take, NO_QUOTA for none
+    block_quota: u32,
+    /// number of blocks charged to `block_quota`
+    blocks_used: u32,
     type_: DiskInodeType,
+    /// INODE_APPEND_ONLY etc., takes the padding byte after `type_`
+    flags: u8,
+    /// rwxrwxrwx permission bits
+    perm: u16,
 }
 
+/// Only appending to the file is allowed, see `DiskInode::append_only`
+const INODE_APPEND_ONLY: u8 = 1;
+
 impl DiskInode {
     /// Initialize a disk inode, as well as all direct inodes under it
     /// indirect1 and indirect2 block are allocated only when they are needed
@@ -103,8 +156,59 @@ impl DiskInode {
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.block_quota = NO_QUOTA;
+        self.blocks_used = 0;
+        self.flags = 0;
+        self.perm = match type_ {
+            DiskInodeType::File => 0o644,
+            DiskInodeType::Directory => 0o755,
+            DiskInodeType::SymLink => 0o777,
+        };
         self.type_ = type_;
     }
+    /// The rwxrwxrwx permission bits
+    pub fn perm(&self) -> u16 {
+        self.perm
+    }
+    /// Set the rwxrwxrwx permission bits, bits above them are ignored
+    pub fn set_perm(&mut self, perm: u16) {
+        self.perm = perm & 0o777;
+    }
+    /// Whether only appends are allowed: writes before the end of file, shrinking and punching holes are refused
+    pub fn append_only(&self) -> bool {
+        self.flags & INODE_APPEND_ONLY != 0
+    }
+    /// Allow only appends to the file or lift the restriction
+    pub fn set_append_only(&mut self, append_only: bool) {
+        if append_only {
+            self.flags |= INODE_APPEND_ONLY;
+        } else {
+            self.flags &= !INODE_APPEND_ONLY;
+        }
+    }
+    /// The block quota of this directory, None if it has none
+    pub fn block_quota(&self) -> Option<u32> {
+        if self.block_quota == NO_QUOTA {
+            None
+        } else {
+            Some(self.block_quota)
+        }
+    }
+    /// Number of blocks charged to the block quota of this directory
+    pub fn blocks_used(&self) -> u32 {
+        self.blocks_used
+    }
+    /// Set the block quota of this directory and the blocks already charged to it
+    /// 取消配额时用量也清零
+    pub fn set_block_quota(&mut self, quota: Option<u32>, used: u32) {
+        self.block_quota = quota.unwrap_or(NO_QUOTA);
+        self.blocks_used = if quota.is_some() { used } else { 0 };
+    }
+    /// Charge `allocated` more blocks to the block quota of this directory and give `freed` back
+    /// 跨配额目录移动的文件释放的块可能从未计入这里，所以用量最少减到0
+    pub fn charge_blocks(&mut self, allocated: u32, freed: u32) {
+        self.blocks_used = (self.blocks_used + allocated).saturating_sub(freed);
+    }
     /// Whether this inode is a directory
     pub fn is_dir(&self) -> bool {
         self.type_ == DiskInodeType::Directory
@@ -114,6 +218,10 @@ impl DiskInode {
     pub fn is_file(&self) -> bool {
         self.type_ == DiskInodeType::File
     }
+    /// Whether this inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        self.type_ == DiskInodeType::SymLink
+    }
     /// Get the number of data blocks corresponding to size
     /// 目前本DiskInode保存的数据一共用了数据区的多少个block
     pub fn data_blocks(&self) -> u32 {
@@ -184,6 +292,47 @@ impl DiskInode {
             })
         }
     }
+    /// Iterate over the ids of data blocks `start..end` (inner ids) of current disk inode
+    /// 每个索引块只会被读取一次，适合顺序访问大文件中连续的多个数据块
+    pub fn data_block_ids<'a>(
+        &'a self,
+        start: usize,
+        end: usize,
+        block_device: &'a Arc<dyn BlockDevice>,
+    ) -> DataBlockIds<'a> {
+        DataBlockIds {
+            disk_inode: self,
+            block_device,
+            next: start,
+            end,
+            indirect1: None,
+            indirect2: None,
+            sub_indirect1: None,
+        }
+    }
+    /// Number of data blocks actually allocated, holes are not counted
+    pub fn allocated_blocks(&self, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        self.data_block_ids(0, self.data_blocks() as usize, block_device)
+            .filter(|&block_id| block_id != 0)
+            .count() as u32
+    }
+    /// Get the ids of the index blocks (indirect1, indirect2 and its sub indirect1 blocks) of current disk inode
+    /// 与 data_block_ids 一起即为本DiskInode在数据区引用的全部block
+    pub fn index_block_ids(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let data_blocks = self.data_blocks() as usize;
+        let mut v: Vec<u32> = Vec::new();
+        if data_blocks > DIRECT_BOUND {
+            v.push(self.indirect1);
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            v.push(self.indirect2);
+            let sub_blocks =
+                (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT;
+            let indirect2 = read_indirect_block(self.indirect2, block_device);
+            v.extend_from_slice(&indirect2[..sub_blocks]);
+        }
+        v
+    }
     /// Inncrease the size of current disk inode
     /// 将new_blocks中的所有block_id放到本DiskInode中
     pub fn increase_size(
@@ -268,96 +417,69 @@ impl DiskInode {
         });
     }
 
-    pub fn decrease_size(
-        &mut self,
-        new_size: u32,
-        block_device: &Arc<dyn BlockDevice>,
-    ) -> Vec<u32> {
-        let mut v: Vec<u32> = Vec::new();
-        let new_blocks_needed = Self::total_blocks(new_size) as usize;
-        let mut data_blocks = self.data_blocks() as usize;
+    /// Shrink current disk inode to `new_size` bytes, return the blocks no longer needed for the caller to deallocate
+    /// 返回的是new_size之后的数据块（空洞除外）以及因此变空的索引块（一级、二级索引块和二级索引下的一级索引块），
+    /// 指向它们的指针都被清零；new_size恰好落在块边界或索引边界上时，边界之前的块都保留
+    pub fn shrink_to_fit(&mut self, new_size: u32, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        assert!(new_size <= self.size);
+        let old_blocks = self.data_blocks() as usize;
+        let new_blocks = Self::_data_blocks(new_size) as usize;
         self.size = new_size;
-        let mut current_blocks = new_blocks_needed;
+        let mut v: Vec<u32> = Vec::new();
         // direct
-        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
-            v.push(self.direct[current_blocks]);
-            self.direct[current_blocks] = 0;
-            current_blocks += 1;
-        }
-        // indirect1 block
-        if data_blocks > INODE_DIRECT_COUNT {
-            v.push(self.indirect1);
-            data_blocks -= INODE_DIRECT_COUNT;
-            current_blocks = 0;
-        } else {
-            return v;
+        for i in new_blocks.min(DIRECT_BOUND)..old_blocks.min(DIRECT_BOUND) {
+            v.push(self.direct[i]);
+            self.direct[i] = 0;
         }
         // indirect1
-        get_block_cache(
-            self.indirect1 as usize,
-            Arc::clone(block_device),
-        )
-        .lock()
-        .modify(0, |indirect1: &mut IndirectBlock| {
-            while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
-                v.push(indirect1[current_blocks]);
-                //indirect1[current_blocks] = 0;
-                current_blocks += 1;
-            }
-        });
-        self.indirect1 = 0;
-        // indirect2 block
-        if data_blocks > INODE_INDIRECT1_COUNT {
-            v.push(self.indirect2);
-            data_blocks -= INODE_INDIRECT1_COUNT;
-        } else {
-            return v;
-        }
-        // indirect2
-        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
-        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
-        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
-        get_block_cache(
-            self.indirect2 as usize,
-            Arc::clone(block_device),
-        )
-        .lock()
-        .modify(0, |indirect2: &mut IndirectBlock| {
-            // full indirect1 blocks
-            for i in 0..a1 {
-                v.push(indirect2[i]);
-                get_block_cache(
-                    indirect2[i] as usize,
-                    Arc::clone(block_device),
-                )
+        if old_blocks > DIRECT_BOUND {
+            let start = new_blocks.clamp(DIRECT_BOUND, INDIRECT1_BOUND) - DIRECT_BOUND;
+            let end = old_blocks.min(INDIRECT1_BOUND) - DIRECT_BOUND;
+            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
                 .lock()
                 .modify(0, |indirect1: &mut IndirectBlock| {
-                    for j in 0..INODE_INDIRECT1_COUNT {
+                    for j in start..end {
                         v.push(indirect1[j]);
-                        //indirect1[j] = 0;
+                        indirect1[j] = 0;
                     }
                 });
-                //indirect2[i] = 0;
+            if new_blocks <= DIRECT_BOUND {
+                v.push(self.indirect1);
+                self.indirect1 = 0;
             }
-            // last indirect1 block
-            if b1 > 0 {
-                v.push(indirect2[a1]);
-                get_block_cache(
-                    indirect2[a1] as usize,
-                    Arc::clone(block_device),
-                )
+        }
+        // indirect2
+        if old_blocks > INDIRECT1_BOUND {
+            let start = new_blocks.max(INDIRECT1_BOUND) - INDIRECT1_BOUND;
+            let end = old_blocks - INDIRECT1_BOUND;
+            get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
                 .lock()
-                .modify(0, |indirect1: &mut IndirectBlock| {
-                    for j in 0..b1 {
-                        v.push(indirect1[j]);
-                        //indirect1[j] = 0;
+                .modify(0, |indirect2: &mut IndirectBlock| {
+                    // 逐个处理与 start..end 相交的二级索引下的一级索引块
+                    for a in start / INODE_INDIRECT1_COUNT..(end + INODE_INDIRECT1_COUNT - 1) / INODE_INDIRECT1_COUNT {
+                        let base = a * INODE_INDIRECT1_COUNT;
+                        let lo = start.max(base) - base;
+                        let hi = end.min(base + INODE_INDIRECT1_COUNT) - base;
+                        get_block_cache(indirect2[a] as usize, Arc::clone(block_device))
+                            .lock()
+                            .modify(0, |indirect1: &mut IndirectBlock| {
+                                for j in lo..hi {
+                                    v.push(indirect1[j]);
+                                    indirect1[j] = 0;
+                                }
+                            });
+                        if lo == 0 {
+                            v.push(indirect2[a]);
+                            indirect2[a] = 0;
+                        }
                     }
                 });
-                //indirect2[a1] = 0;
+            if new_blocks <= INDIRECT1_BOUND {
+                v.push(self.indirect2);
+                self.indirect2 = 0;
             }
-        });
-        self.indirect2 = 0;
-        v
+        }
+        without_holes(v)
     }
     /// Clear size to zero and return blocks that should be deallocated
     /// and clear the block contents to zero later
@@ -380,7 +502,7 @@ impl DiskInode {
             data_blocks -= INODE_DIRECT_COUNT;
             current_blocks = 0;
         } else {
-            return v;
+            return without_holes(v);
         }
         // indirect1
         get_block_cache(
@@ -401,7 +523,7 @@ impl DiskInode {
             v.push(self.indirect2);
             data_blocks -= INODE_INDIRECT1_COUNT;
         } else {
-            return v;
+            return without_holes(v);
         }
         // indirect2
         assert!(data_blocks <= INODE_INDIRECT2_COUNT);
@@ -447,19 +569,47 @@ impl DiskInode {
             }
         });
         self.indirect2 = 0;
-        v
+        without_holes(v)
     }
 
     /// Read data from current disk inode
     /// 将文件内容从 offset 字节开始的部分（从block缓存区中读）读到内存中的缓冲区 buf 中，并返回实际读到的字节数
+    /// 读到文件末尾为止：返回值是min(buf.len(), size - offset)，只有buf的前这么多字节会被写入；offset不小于size时返回0
     pub fn read_at(
         &self,
         offset: usize,
         buf: &mut [u8],
         block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        self.read_blocks(offset, buf, block_device, |inner_id| self.get_block_id(inner_id, block_device))
+    }
+    /// Read data from current disk inode like `read_at`, looking up the block ids in `block_map` first
+    /// 已缓存的数据块不再遍历间接索引块，未缓存的转换结果会被放入block_map；
+    /// block_map只在每次转换时短暂加锁，同一文件的多个读者之间不会互相阻塞
+    pub fn read_at_mapped(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+        block_map: &Mutex<BlockMap>,
+    ) -> usize {
+        self.read_blocks(offset, buf, block_device, |inner_id| {
+            block_map
+                .lock()
+                .get_or_translate(inner_id, || self.get_block_id(inner_id, block_device))
+        })
+    }
+    /// Read data from current disk inode, `block_id` translates an inner id to the block id on the device
+    fn read_blocks(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+        mut block_id: impl FnMut(u32) -> u32,
     ) -> usize {
         let mut start = offset;
-        let end = (offset + buf.len()).min(self.size as usize);
+        // offset可能是用户给出的任意值，相加时不能溢出
+        let end = offset.saturating_add(buf.len()).min(self.size as usize);
         if start >= end {
             return 0;
         }
@@ -472,15 +622,15 @@ impl DiskInode {
             // read and update read size
             let block_read_size = end_current_block - start;
             let dst = &mut buf[read_size..read_size + block_read_size];
-            get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
-                Arc::clone(block_device),
-            )
-            .lock()
-            .read(0, |data_block: &DataBlock| {
-                let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
-                dst.copy_from_slice(src);
-            });
+            match block_id(start_block as u32) {
+                0 => dst.fill(0),
+                block_id => get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        let src = &data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size];
+                        dst.copy_from_slice(src);
+                    }),
+            }
             read_size += block_read_size;
             // move to next block
             if end_current_block == end { break; }
@@ -489,10 +639,106 @@ impl DiskInode {
         }
         read_size
     }
+    /// Read data from current disk inode like `read_at`, resolving the block ids of the whole span at once
+    /// 与read_at的结果完全相同，但不会为每个数据块重新遍历一遍间接索引块
+    pub fn read_at_range(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let end = offset.saturating_add(buf.len()).min(self.size as usize);
+        if offset >= end {
+            return 0;
+        }
+        let mut start = offset;
+        let mut read_size = 0usize;
+        let block_ids = self.data_block_ids(offset / BLOCK_SZ, (end + BLOCK_SZ - 1) / BLOCK_SZ, block_device);
+        for block_id in block_ids {
+            let end_current_block = ((start / BLOCK_SZ + 1) * BLOCK_SZ).min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            if block_id == 0 {
+                dst.fill(0);
+            } else {
+                get_block_cache(block_id as usize, Arc::clone(block_device))
+                    .lock()
+                    .read(0, |data_block: &DataBlock| {
+                        dst.copy_from_slice(&data_block[start % BLOCK_SZ..start % BLOCK_SZ + block_read_size]);
+                    });
+            }
+            read_size += block_read_size;
+            start = end_current_block;
+        }
+        read_size
+    }
+    /// Read every dirent slot of current directory, used or not, in order
+    /// 按块读出整个目录再切分成目录项，每个数据块只查找一次块缓存，而不是每个目录项一次
+    pub fn read_all_dirents(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<DirEntry> {
+        let count = self.size as usize / DIRENT_SZ;
+        let mut bytes = vec![0u8; count * DIRENT_SZ];
+        assert_eq!(self.read_at_range(0, &mut bytes, block_device), bytes.len());
+        bytes
+            .chunks_exact(DIRENT_SZ)
+            .map(|chunk| {
+                let mut dirent = DirEntry::empty();
+                dirent.as_bytes_mut().copy_from_slice(chunk);
+                dirent
+            })
+            .collect()
+    }
+    /// Fill `start..end` of current disk inode with zeros
+    /// size must be adjusted properly beforehand; holes are already zeros and are skipped
+    pub fn zero_range(&mut self, start: usize, end: usize, block_device: &Arc<dyn BlockDevice>) {
+        let zeros = [0u8; BLOCK_SZ];
+        let mut pos = start;
+        while pos < end {
+            let len = (end - pos).min(BLOCK_SZ - pos % BLOCK_SZ);
+            if self.get_block_id((pos / BLOCK_SZ) as u32, block_device) != 0 {
+                self.write_at(pos, &zeros[..len], block_device);
+            }
+            pos += len;
+        }
+    }
+    /// Point the inner block `inner_id` at `block_id`, the index blocks covering it must exist already
+    /// block_id为0时就在这里打出一个空洞
+    pub fn set_block_id(&mut self, inner_id: u32, block_id: u32, block_device: &Arc<dyn BlockDevice>) {
+        let inner_id = inner_id as usize;
+        if inner_id < INODE_DIRECT_COUNT {
+            self.direct[inner_id] = block_id;
+            return;
+        }
+        let (indirect1, index) = if inner_id < INDIRECT1_BOUND {
+            (self.indirect1, inner_id - INODE_DIRECT_COUNT)
+        } else {
+            let last = inner_id - INDIRECT1_BOUND;
+            (read_indirect_block(self.indirect2, block_device)[last / INODE_INDIRECT1_COUNT], last % INODE_INDIRECT1_COUNT)
+        };
+        get_block_cache(indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| indirect1[index] = block_id);
+    }
+    /// Turn the inner blocks `start..end` into holes and return the data blocks they used, size is left unchanged
+    /// 已经是空洞的块被跳过；索引块即使全部指向空洞也不释放
+    pub fn punch_hole(&mut self, start: usize, end: usize, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let block_ids: Vec<u32> = self.data_block_ids(start, end, block_device).collect();
+        for (inner_id, &block_id) in (start..end).zip(block_ids.iter()) {
+            if block_id != 0 {
+                self.set_block_id(inner_id as u32, 0, block_device);
+            }
+        }
+        without_holes(block_ids)
+    }
+    /// Fill the part of `start..end` inside the block holding `start` with zeros
+    /// 文件从start增长到end时只有原来最后一个块中超出原size的部分可能残留旧数据，之后的块都是新分配的，alloc_data保证它们为0
+    pub fn zero_tail(&mut self, start: usize, end: usize, block_device: &Arc<dyn BlockDevice>) {
+        let tail_end = ((start + BLOCK_SZ - 1) / BLOCK_SZ * BLOCK_SZ).min(end);
+        self.zero_range(start, tail_end, block_device);
+    }
     /// Write data into current disk inode
     /// size must be adjusted properly beforehand
     /// 将buf中的数据写入本DiskInode对应的在数据区的blcok中，开始的位置为offset。
-    /// 这不是插入数据，会将原数据覆盖
+    /// 这不是插入数据，会将原数据覆盖；写到的块都不能是空洞
     pub fn write_at(
         &mut self,
         offset: usize,
@@ -502,6 +748,9 @@ impl DiskInode {
         let mut start = offset;
         let end = (offset + buf.len()).min(self.size as usize);
         assert!(start <= end);
+        if start == end {
+            return 0;
+        }
         let mut start_block = start / BLOCK_SZ;
         let mut write_size = 0usize;
         loop {
@@ -510,8 +759,10 @@ impl DiskInode {
             end_current_block = end_current_block.min(end);
             // write and update write size
             let block_write_size = end_current_block - start;
+            let block_id = self.get_block_id(start_block as u32, block_device);
+            assert_ne!(block_id, 0, "Writing into a hole!");
             get_block_cache(
-                self.get_block_id(start_block as u32, block_device) as usize,
+                block_id as usize,
                 Arc::clone(block_device)
             )
             .lock()
@@ -530,6 +781,64 @@ impl DiskInode {
     }
 }
 
+/// Drop the holes (block id 0) from the block ids taken out of a disk inode
+fn without_holes(mut v: Vec<u32>) -> Vec<u32> {
+    v.retain(|&block_id| block_id != 0);
+    v
+}
+
+/// Read a whole indirect block out of the block cache
+fn read_indirect_block(block_id: u32, block_device: &Arc<dyn BlockDevice>) -> IndirectBlock {
+    get_block_cache(block_id as usize, Arc::clone(block_device))
+        .lock()
+        .read(0, |indirect_block: &IndirectBlock| *indirect_block)
+}
+
+/// A cursor over the data block ids of a disk inode, see `DiskInode::data_block_ids`
+/// 记住已经读过的一级/二级索引块，顺序遍历时每个索引块只读一次
+pub struct DataBlockIds<'a> {
+    disk_inode: &'a DiskInode,
+    block_device: &'a Arc<dyn BlockDevice>,
+    next: usize,
+    end: usize,
+    indirect1: Option<IndirectBlock>,
+    indirect2: Option<IndirectBlock>,
+    /// (index in indirect2, content) of the indirect1 block under indirect2 being walked
+    sub_indirect1: Option<(usize, IndirectBlock)>,
+}
+
+impl<'a> Iterator for DataBlockIds<'a> {
+    type Item = u32;
+    fn next(&mut self) -> Option<u32> {
+        if self.next >= self.end {
+            return None;
+        }
+        let inner_id = self.next;
+        self.next += 1;
+        if inner_id < DIRECT_BOUND {
+            return Some(self.disk_inode.direct[inner_id]);
+        }
+        if inner_id < INDIRECT1_BOUND {
+            if self.indirect1.is_none() {
+                self.indirect1 = Some(read_indirect_block(self.disk_inode.indirect1, self.block_device));
+            }
+            return self.indirect1.as_ref().map(|block| block[inner_id - DIRECT_BOUND]);
+        }
+        let last = inner_id - INDIRECT1_BOUND;
+        let outer = last / INODE_INDIRECT1_COUNT;
+        if self.sub_indirect1.as_ref().map(|(idx, _)| *idx) != Some(outer) {
+            if self.indirect2.is_none() {
+                self.indirect2 = Some(read_indirect_block(self.disk_inode.indirect2, self.block_device));
+            }
+            let sub_id = self.indirect2.as_ref().unwrap()[outer];
+            self.sub_indirect1 = Some((outer, read_indirect_block(sub_id, self.block_device)));
+        }
+        self.sub_indirect1
+            .as_ref()
+            .map(|(_, block)| block[last % INODE_INDIRECT1_COUNT])
+    }
+}
+
 /// A directory entry
 /// 既是目录项也是文件项
 #[repr(C)]
@@ -585,4 +894,13 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// Whether this is the "." or ".." entry of a directory
+    pub fn is_dot(&self) -> bool {
+        self.name() == "." || self.name() == ".."
+    }
+    /// Whether this entry is in use
+    /// 0号inode是根目录，只会被"."和".."引用，因此其余inode_number为0的目录项都是空位
+    pub fn is_used(&self) -> bool {
+        self.inode_number != 0 || self.is_dot()
+    }
 }
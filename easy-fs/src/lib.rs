@@ -8,12 +8,31 @@ mod efs;
 mod bitmap;
 mod vfs;
 mod block_cache;
+mod dentry_cache;
+mod path_cache;
+mod inode_lock;
+mod block_map;
+mod mount;
+mod journal;
 
 /// Use a block size of 512 bytes
 pub const BLOCK_SZ: usize = 512;
-pub use block_dev::BlockDevice;
-pub use efs::EasyFileSystem;
-pub use vfs::Inode;
+pub use block_dev::{BlockDevice, BlockError};
+pub use efs::{EasyFileSystem, GrowError, OpenError, ZEROED_FREE_MAX};
+pub use layout::{inodes_per_block, EFS_VERSION};
+pub use vfs::{CreateError, Inode, DirEntryInfo, RenameError, RenameMode};
 use layout::*;
-use bitmap::Bitmap;
-use block_cache::{get_block_cache, block_cache_sync_all};
+pub use bitmap::Bitmap;
+pub use block_cache::{
+    block_cache_drop_device, block_cache_modify_seq, block_cache_prefetch, block_cache_sync_all, block_cache_sync_device,
+    block_cache_sync_device_since, block_cache_sync_dirty_limit, block_cache_try_sync_all, flush_block, peek_block_cache,
+    try_get_block_cache, BLOCK_CACHE_MANAGER,
+};
+use block_cache::get_block_cache;
+use dentry_cache::DentryCache;
+use path_cache::PathCache;
+use inode_lock::get_inode_lock;
+use block_map::{get_block_map, BlockMap};
+pub use mount::{ImageDevice, MountError};
+use mount::{Mount, MountKey, MOUNT_TABLE};
+use journal::{JournalOp, JournalRecord};
@@ -0,0 +1,113 @@
+use super::{try_get_block_cache, BlockDevice, BlockError, Inode, BLOCK_SZ};
+use alloc::vec::Vec;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::*;
+use spin::Mutex;
+
+/// Key of a disk inode: (device id, block_id, block_offset)
+pub type MountKey = (usize, usize, usize);
+
+/// Why mounting or unmounting a filesystem failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum MountError {
+    /// The mount point is not a directory
+    NotDir,
+    /// Something is already mounted at the mount point, or the mounted filesystem is still in use
+    Busy,
+    /// The directory is not the root of a mounted filesystem
+    NotMounted,
+}
+
+/// A filesystem grafted onto a directory of another one
+pub struct Mount {
+    /// the directory covered by the mounted filesystem
+    pub mountpoint: Arc<Inode>,
+    /// the root directory of the mounted filesystem
+    pub root: Arc<Inode>,
+}
+
+/// All the mounted filesystems, keyed by their mount points
+/// 路径解析每走到一个目录都会查询这张表，走到挂载点时切换到被挂载的文件系统的根目录
+pub struct MountTable {
+    pub mounts: BTreeMap<MountKey, Mount>,
+}
+
+impl MountTable {
+    pub fn new() -> Self {
+        Self {
+            mounts: BTreeMap::new(),
+        }
+    }
+}
+
+/// A block device backed by a regular file of an easy-fs, e.g. an image file to be mounted
+/// 设备的大小就是打开时文件的大小（按块向下取整）：越界的读得到全0，越界的写被忽略，文件不会因此变大；
+/// 镜像文件中的空洞也是这样处理的，所以要挂载的镜像不应该有空洞
+///
+/// 创建时就记下镜像文件的每个数据块在外层设备上的编号，之后直接读写外层设备的块缓存，不经过镜像文件的Inode：
+/// 换出镜像中的脏块可能发生在读镜像文件的过程中，那时镜像文件的inode锁和DiskInode所在块的缓存锁都被持有着。
+/// 因此设备存在期间镜像文件被`Inode::hold_image`钉住，截断、清空、打洞、删除和被重命名覆盖都会失败
+pub struct ImageDevice {
+    image: Arc<Inode>,
+    device: Arc<dyn BlockDevice>,
+    block_ids: Vec<u32>,
+}
+
+impl ImageDevice {
+    pub fn new(image: Arc<Inode>) -> Self {
+        image.hold_image();
+        let (device, mut block_ids) = image.data_block_ids();
+        block_ids.truncate(image.size() / BLOCK_SZ);
+        Self { image, device, block_ids }
+    }
+    /// Number of whole blocks in the image
+    pub fn blocks(&self) -> usize {
+        self.block_ids.len()
+    }
+}
+
+impl BlockDevice for ImageDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.try_read_block(block_id, buf).expect("Error when reading an image block!");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf).expect("Error when writing an image block!");
+    }
+    /// 外层设备读出错时，错误原样报告出去（块号是外层设备上的块号）
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        match self.block_ids.get(block_id) {
+            Some(&id) if id != 0 => try_get_block_cache(id as usize, Arc::clone(&self.device))?
+                .lock()
+                .read(0, |data: &[u8; BLOCK_SZ]| buf.copy_from_slice(data)),
+            _ => buf.fill(0),
+        }
+        Ok(())
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
+        if let Some(&id) = self.block_ids.get(block_id).filter(|&&id| id != 0) {
+            try_get_block_cache(id as usize, Arc::clone(&self.device))?
+                .lock()
+                .modify(0, |data: &mut [u8; BLOCK_SZ]| data.copy_from_slice(buf));
+        }
+        Ok(())
+    }
+    /// 镜像文件的数据块也在块缓存中，写回它们才算真正写到了磁盘上
+    fn flush(&self) {
+        self.image.fsync();
+    }
+}
+
+impl Drop for ImageDevice {
+    /// 卸载后被挂载的文件系统连同设备一起被释放，镜像文件才恢复成普通文件
+    fn drop(&mut self) {
+        self.image.release_image();
+    }
+}
+
+lazy_static! {
+    /// The global mount table
+    pub static ref MOUNT_TABLE: Mutex<MountTable> = Mutex::new(
+        MountTable::new()
+    );
+}
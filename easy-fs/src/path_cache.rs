@@ -0,0 +1,77 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Max number of paths cached
+const PATH_CACHE_SIZE: usize = 32;
+
+/// Position of a disk inode: (block_id, block_offset)
+type InodePos = (usize, usize);
+
+/// A cached resolution of a path from the root directory
+struct CachedPath {
+    /// the path with empty components removed, e.g. "a/b/c"
+    path: String,
+    /// (directory, name) of every component, in order
+    steps: Vec<(InodePos, String)>,
+    target: InodePos,
+    /// the quota directory of the resolved inode, see `Inode::quota_dir`
+    quota_dir: Option<InodePos>,
+}
+
+/// A small LRU cache of whole path resolutions, kept inside EasyFileSystem so it is protected by the fs lock
+/// 只缓存从根目录出发、不经过符号链接、挂载点和"."/".."的路径；
+/// 路径中任何一个分量的目录项被修改时，以它为前缀的所有路径都会失效
+pub struct PathCache {
+    queue: VecDeque<CachedPath>,
+    hits: usize,
+}
+
+impl PathCache {
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            hits: 0,
+        }
+    }
+    /// Look up `path`, a hit is moved to the back of the queue
+    pub fn lookup(&mut self, path: &str) -> Option<(InodePos, Option<InodePos>)> {
+        let idx = self.queue.iter().position(|cached| cached.path == path)?;
+        let cached = self.queue.remove(idx).unwrap();
+        let found = (cached.target, cached.quota_dir);
+        self.queue.push_back(cached);
+        self.hits += 1;
+        Some(found)
+    }
+    /// Remember that `path`, reached through `steps`, is the inode at `target`, evicting the least recently used entry if full
+    pub fn insert(&mut self, path: String, steps: Vec<(InodePos, String)>, target: InodePos, quota_dir: Option<InodePos>) {
+        self.queue.retain(|cached| cached.path != path);
+        if self.queue.len() == PATH_CACHE_SIZE {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(CachedPath {
+            path,
+            steps,
+            target,
+            quota_dir,
+        });
+    }
+    /// Forget every path going through `name` under `parent`
+    pub fn invalidate(&mut self, parent: InodePos, name: &str) {
+        self.queue
+            .retain(|cached| !cached.steps.iter().any(|step| step.0 == parent && step.1 == name));
+    }
+    /// Forget every path going through the directory at `parent`
+    pub fn invalidate_dir(&mut self, parent: InodePos) {
+        self.queue
+            .retain(|cached| !cached.steps.iter().any(|step| step.0 == parent));
+    }
+    /// Forget everything, e.g. when a quota or a mount changes what a path resolves to
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+    /// How many paths were resolved from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
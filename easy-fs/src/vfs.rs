@@ -1,20 +1,114 @@
 use super::{
-    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
-    EasyFileSystem, DIRENT_SZ,
+    block_cache_drop_device, block_cache_prefetch, block_cache_sync_all, flush_block, get_block_cache, get_block_map, get_inode_lock, BlockDevice, BlockMap, DirEntry, DiskInode, DiskInodeType,
+    EasyFileSystem, JournalOp, JournalRecord, Mount, MountError, MountKey, DIRENT_SZ, MOUNT_TABLE,
 };
 use crate::BLOCK_SZ;
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
-use spin::{Mutex, MutexGuard};
+use spin::{Mutex, MutexGuard, RwLock};
+
+/// The max number of symbolic links followed while resolving one path
+const SYMLINK_MAX_DEPTH: usize = 40;
+
+/// A directory entry as seen by the user: name, inode id and whether it is a directory
+pub struct DirEntryInfo {
+    pub name: String,
+    pub inode_id: u32,
+    pub is_dir: bool,
+    /// index of the dirent slot holding this entry, unused slots in between are counted too
+    /// 可以作为目录流的位置：下一项从 slot + 1 开始找
+    pub slot: usize,
+}
+
+/// Why creating a new entry in a directory failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum CreateError {
+    /// The name is already used in the directory
+    Exists,
+    /// No free inode or data block is left for the new entry
+    NoSpace,
+}
+
+/// What `rename2` does when `new_name` already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameMode {
+    /// Overwrite it (it must not be a directory)
+    Replace,
+    /// Fail with `RenameError::Exists`
+    NoReplace,
+    /// Swap the two entries, `new_name` must exist
+    Exchange,
+}
+
+/// Why renaming failed
+#[derive(Debug, PartialEq, Eq)]
+pub enum RenameError {
+    /// A name is "." or "..", or a directory would be moved into its own subtree
+    Invalid,
+    /// `old_name` does not exist, or `new_name` does not exist in `RenameMode::Exchange`
+    NotFound,
+    /// `new_name` exists in `RenameMode::NoReplace`
+    Exists,
+    /// `new_name` is a directory and cannot be overwritten
+    IsDir,
+    /// `old_name` is a directory but `new_name` is not
+    NotDir,
+    /// No data block is left for the new entry
+    NoSpace,
+    /// `new_name` is the image file of a mounted filesystem and cannot be overwritten
+    Busy,
+}
+
+/// The block quota one operation is checked against: read before the operation, settled after it
+/// 配额目录的DiskInode可能与正在修改的DiskInode位于同一个块中，修改后者时不能再去锁这个块，
+/// 所以先读出剩余的配额，操作完成后再把用量的变化记到配额目录上；两步都在fs锁内完成
+struct QuotaCharge {
+    /// position of the disk inode of the directory holding the quota
+    dir: (usize, usize),
+    /// blocks that may still be allocated
+    left: u32,
+    allocated: u32,
+    freed: u32,
+}
+
+impl QuotaCharge {
+    /// Take `blocks` from the quota, return false if not enough is left
+    fn take(&mut self, blocks: u32) -> bool {
+        if blocks > self.left {
+            return false;
+        }
+        self.left -= blocks;
+        self.allocated += blocks;
+        true
+    }
+    fn give_back(&mut self, blocks: u32) {
+        self.left = self.left.saturating_add(blocks);
+        self.freed += blocks;
+    }
+}
 
 /// Virtual filesystem layer over easy-fs
 /// 每一个DiskInode都对应一个Inode，Inode记录了DiskInode在磁盘上的位置（在哪个磁盘上的哪个Block中的哪个位置）
+///
+/// Lock ordering: inode lock -> fs lock -> block cache lock -> block cache manager lock.
+/// 块映射（block_map）的锁只在读文件时转换一个数据块的地址期间持有，位于本inode所在块的缓存锁之后、其他块的缓存锁之前
+/// 读写文件内容时先获取本文件的inode锁（读共享、写独占），只在分配/回收数据块时短暂持有fs锁；
+/// 目录项的增删和索引节点的分配/回收仍然只在fs锁的保护下进行
 pub struct Inode {
     block_id: usize,
     block_offset: usize,
     fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
+    /// shared by all the Inodes of the same disk inode, guards its size and data
+    lock: Arc<RwLock<()>>,
+    /// shared by all the Inodes of the same disk inode, caches the translation of its data block ids
+    block_map: Arc<Mutex<BlockMap>>,
+    /// position of the disk inode of the nearest directory with a block quota above current inode, on the path
+    /// current inode was found through; None if there is none
+    /// 配额跟随查找路径：通过配额目录外的硬链接访问同一个文件时不计入配额
+    quota_dir: Option<(usize, usize)>,
 }
 
 impl Inode {
@@ -29,9 +123,18 @@ impl Inode {
             block_id: block_id as usize,
             block_offset,
             fs,
+            block_map: get_block_map(block_device.id(), block_id as usize, block_offset),
+            lock: get_inode_lock(block_device.id(), block_id as usize, block_offset),
             block_device,
+            quota_dir: None,
         }
     }
+    /// Create the Inode of an entry under current directory, charged to the same quota as the entries created by it
+    fn child(&self, block_id: u32, block_offset: usize) -> Self {
+        let mut inode = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+        inode.quota_dir = self.governing_dir();
+        inode
+    }
     /// Call a function over a disk inode to read it
     /// 找到Diskinode（就是调用者对应的Diskinode)所在的block cache获得DiskInode中的信息，然后根据这些信息去操纵跟它绑定的存在数据区的数据
     fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
@@ -51,71 +154,478 @@ impl Inode {
     fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
         // assert it is a directory
         assert!(disk_inode.is_dir());
-        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-        let mut dirent = DirEntry::empty();
-        for i in 0..file_count {
-            assert_eq!(
-                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
-                DIRENT_SZ,
-            );
-            if dirent.inode_number() != 0 && dirent.name() == name {
-                return Some(dirent.inode_number() as u32);
-            }
-        }
-        None
+        disk_inode
+            .read_all_dirents(&self.block_device)
+            .iter()
+            .find(|dirent| dirent.is_used() && dirent.name() == name)
+            .map(|dirent| dirent.inode_number())
     }
     /// Find inode under current inode by name
     /// 找到这个名字代表的文件在块设备中的DiskInode，并返回相应的Inode(self对应的DiskInode必须是一个目录，否则会报错)
+    /// 先查dentry cache，未命中时再扫描目录项，并把找到的结果放入缓存
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
-        })
+        let mut fs = self.fs.lock();
+        let (block_id, block_offset) = match fs.dentry_cache.lookup(self.pos(), name) {
+            Some(pos) => pos,
+            None => {
+                let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+                let pos = fs.get_disk_inode_pos(inode_id);
+                fs.dentry_cache.insert(self.pos(), name, pos);
+                pos
+            }
+        };
+        Some(Arc::new(self.child(block_id, block_offset)))
+    }
+    /// Position of current inode on disk, the key of its entries in the dentry cache
+    fn pos(&self) -> (usize, usize) {
+        (self.block_id, self.block_offset)
+    }
+    /// Find inode by a path relative to current inode, e.g. "a/b/c"
+    /// 逐级查找路径中的每一个分量，空分量（开头、结尾或连续的'/'）会被跳过，因此空路径返回自身
+    /// 遇到符号链接时会跟随它继续查找，跟随次数超过 SYMLINK_MAX_DEPTH 时（如链接成环）返回None
+    /// 走到挂载点时进入挂载在它上面的文件系统的根目录
+    /// 从根目录出发的路径先查path cache，命中时不再逐级查找
+    pub fn find_path(&self, path: &str) -> Option<Arc<Inode>> {
+        let key = self.path_cache_key(path);
+        if let Some(key) = key.as_ref() {
+            let cached = self.fs.lock().path_cache.lookup(key);
+            if let Some(((block_id, block_offset), quota_dir)) = cached {
+                let mut inode = Self::new(block_id as u32, block_offset, self.fs.clone(), self.block_device.clone());
+                inode.quota_dir = quota_dir;
+                return Some(Arc::new(inode));
+            }
+        }
+        let mut inode = Self::new(
+            self.block_id as u32,
+            self.block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        );
+        inode.quota_dir = self.quota_dir;
+        let inode = Self::cross_mount(Arc::new(inode));
+        let mut follows = 0;
+        // 根目录上挂载了别的文件系统时不缓存
+        let mut steps = if inode.mount_key() == self.mount_key() { Some(Vec::new()) } else { None };
+        let found = Self::resolve_path(inode, path, &mut follows, &mut steps)?;
+        if let (Some(key), Some(steps)) = (key, steps.filter(|steps| !steps.is_empty())) {
+            self.fs.lock().path_cache.insert(key, steps, found.pos(), found.quota_dir);
+        }
+        Some(found)
+    }
+    /// The key of `path` in the path cache when resolved from current inode, None if it must not be cached:
+    /// current inode is not the root directory, or the path contains "." or ".."
+    fn path_cache_key(&self, path: &str) -> Option<String> {
+        if self.quota_dir.is_some() || self.fs.lock().get_disk_inode_pos(0) != (self.block_id as u32, self.block_offset) {
+            return None;
+        }
+        let mut key = String::new();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if name == "." || name == ".." {
+                return None;
+            }
+            if !key.is_empty() {
+                key.push('/');
+            }
+            key.push_str(name);
+        }
+        Some(key)
+    }
+    /// Resolve `path` from `dir`, recording the (directory, name) of every component in `steps`;
+    /// `steps` is set to None once a symbolic link or a mount point is passed
+    fn resolve_path(
+        dir: Arc<Inode>,
+        path: &str,
+        follows: &mut usize,
+        steps: &mut Option<Vec<((usize, usize), String)>>,
+    ) -> Option<Arc<Inode>> {
+        let mut inode = dir;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if !inode.is_dir() {
+                return None;
+            }
+            if let Some(steps) = steps.as_mut() {
+                steps.push((inode.pos(), String::from(name)));
+            }
+            let next = inode.find(name)?;
+            if !next.is_symlink() {
+                let key = next.mount_key();
+                inode = Self::cross_mount(next);
+                if inode.mount_key() != key {
+                    *steps = None;
+                }
+                continue;
+            }
+            *steps = None;
+            *follows += 1;
+            if *follows > SYMLINK_MAX_DEPTH {
+                return None;
+            }
+            // 绝对路径从全局根目录开始解析，相对路径从链接所在的目录开始解析
+            let target = next.symlink_target();
+            let base = if target.starts_with('/') {
+                Self::global_root(&inode)
+            } else {
+                inode
+            };
+            inode = Self::resolve_path(base, &target, follows, steps)?;
+        }
+        Some(inode)
+    }
+    /// Get the root directory of the whole tree `inode` is in
+    /// 被挂载的文件系统的根目录不是"/"：沿着挂载表一路找到挂载点所在的文件系统，直到没有被挂载在别处的那一个
+    fn global_root(inode: &Inode) -> Arc<Inode> {
+        let mut root = Arc::new(EasyFileSystem::root_inode(&inode.fs));
+        while let Some(mountpoint) = root.mounted_at() {
+            root = Arc::new(EasyFileSystem::root_inode(&mountpoint.fs));
+        }
+        root
+    }
+    /// Where this inode is on disk, telling it apart from the inodes of every other mounted filesystem
+    fn mount_key(&self) -> MountKey {
+        (self.block_device.id(), self.block_id, self.block_offset)
+    }
+    /// Switch to the root of the filesystem mounted at `inode`, or keep `inode` if it is not a mount point
+    /// 挂载点上又挂载了文件系统时继续切换，直到最后挂载的那一个
+    fn cross_mount(mut inode: Arc<Inode>) -> Arc<Inode> {
+        let table = MOUNT_TABLE.lock();
+        while let Some(mount) = table.mounts.get(&inode.mount_key()) {
+            inode = Arc::clone(&mount.root);
+        }
+        inode
+    }
+    /// Whether another filesystem is mounted at this directory
+    pub fn is_mountpoint(&self) -> bool {
+        MOUNT_TABLE.lock().mounts.contains_key(&self.mount_key())
+    }
+    /// Get the mount point of the filesystem whose root directory is this inode
+    fn mounted_at(&self) -> Option<Arc<Inode>> {
+        let key = self.mount_key();
+        MOUNT_TABLE
+            .lock()
+            .mounts
+            .values()
+            .find(|mount| mount.root.mount_key() == key)
+            .map(|mount| Arc::clone(&mount.mountpoint))
+    }
+    /// Mount the filesystem whose root directory is `root` at this directory
+    /// 挂载之后经过这个目录的路径都会进入被挂载的文件系统，目录原来的内容被遮住，卸载之后恢复
+    pub fn mount(self: &Arc<Self>, root: Arc<Inode>) -> Result<(), MountError> {
+        if !self.is_dir() || !root.is_dir() {
+            return Err(MountError::NotDir);
+        }
+        // 经过挂载点的路径从此解析到被挂载的文件系统中
+        self.fs.lock().path_cache.clear();
+        let mut table = MOUNT_TABLE.lock();
+        let key = self.mount_key();
+        if table.mounts.contains_key(&key) {
+            return Err(MountError::Busy);
+        }
+        table.mounts.insert(key, Mount {
+            mountpoint: Arc::clone(self),
+            root,
+        });
+        Ok(())
+    }
+    /// Unmount the filesystem whose root directory is this inode
+    /// 被挂载的文件系统中还有其他Inode在使用（打开的文件、进程的当前目录、挂载在其中的文件系统等）时返回Busy
+    pub fn umount(self: Arc<Self>) -> Result<(), MountError> {
+        let key = self.mount_key();
+        drop(self);
+        let mut table = MOUNT_TABLE.lock();
+        let (mountpoint, mount) = table
+            .mounts
+            .iter()
+            .find(|(_, mount)| mount.root.mount_key() == key)
+            .ok_or(MountError::NotMounted)?;
+        // 只剩挂载表还引用着根目录和文件系统
+        if Arc::strong_count(&mount.root) > 1 || Arc::strong_count(&mount.root.fs) > 1 {
+            return Err(MountError::Busy);
+        }
+        let mountpoint = *mountpoint;
+        let mount = table.mounts.remove(&mountpoint).unwrap();
+        drop(table);
+        // 文件系统随挂载一起释放之后，再丢弃它在块缓存中的块，设备（镜像文件）才会被释放
+        let device = Arc::clone(&mount.root.block_device);
+        drop(mount);
+        block_cache_drop_device(&device);
+        Ok(())
+    }
+    /// Read the path stored in a symbolic link
+    fn symlink_target(&self) -> String {
+        let mut buf = vec![0u8; self.size()];
+        let len = self.read_at(0, &mut buf);
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    }
+    /// Read the target of current inode without following it, None if it is not a symbolic link
+    pub fn readlink(&self) -> Option<String> {
+        if self.is_symlink() {
+            Some(self.symlink_target())
+        } else {
+            None
+        }
+    }
+    /// Whether current inode is a directory
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// Whether current inode is a symbolic link
+    pub fn is_symlink(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_symlink())
+    }
+    /// Whether two inodes belong to the same filesystem, links and renames cannot cross filesystems
+    pub fn same_fs(&self, other: &Inode) -> bool {
+        Arc::ptr_eq(&self.fs, &other.fs)
     }
     /// Increase the size of a disk inode
     /// 向efs申请需要的在数据区的block，将这些block对应的id存到DiskInode中，并将这个block在data bitmap中的相应bit置1
+    /// 优先申请一段连续的block，让文件的数据尽量连续存放；找不到足够长的空闲区间时再逐块申请
+    /// 数据区空间不足或超出块配额时释放已申请的block并返回false，size保持不变
     fn increase_size(
         &self,
         new_size: u32,
         disk_inode: &mut DiskInode,
         fs: &mut MutexGuard<EasyFileSystem>,
-    ) {
+        charge: &mut Option<QuotaCharge>,
+    ) -> bool {
         if new_size < disk_inode.size {
-            return;
+            return true;
         }
         let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        if !charge.as_mut().map_or(true, |charge| charge.take(blocks_needed)) {
+            return false;
+        }
+        self.block_map.lock().clear();
+        let goal = self.alloc_goal(disk_inode.data_blocks() as usize, disk_inode, fs);
+        if let Some(start) = fs.alloc_data_extent_near(blocks_needed, goal) {
+            disk_inode.increase_size(new_size, (start..start + blocks_needed).collect(), &self.block_device);
+            return true;
+        }
         let mut v: Vec<u32> = Vec::new();
         for _ in 0..blocks_needed {
-            v.push(fs.alloc_data());
+            let goal = v.last().map_or(goal, |block_id| block_id + 1);
+            match fs.alloc_data_near(goal) {
+                Some(block_id) => v.push(block_id),
+                None => {
+                    v.into_iter().for_each(|block_id| fs.dealloc_data(block_id));
+                    if let Some(charge) = charge.as_mut() {
+                        charge.give_back(blocks_needed);
+                    }
+                    return false;
+                }
+            }
         }
         disk_inode.increase_size(new_size, v, &self.block_device);
+        true
+    }
+    /// Where to look for a free block for the data block `inner_id` of this file
+    /// 紧跟在前一个数据块之后；前一个块不存在（文件开头或空洞）时按inode编号选一个位置
+    fn alloc_goal(&self, inner_id: usize, disk_inode: &DiskInode, fs: &MutexGuard<EasyFileSystem>) -> u32 {
+        match inner_id.checked_sub(1).map(|prev| disk_inode.get_block_id(prev as u32, &self.block_device)) {
+            Some(block_id) if block_id != 0 => block_id + 1,
+            _ => fs.inode_goal(self.get_inode_id(fs)),
+        }
+    }
+    /// Increase the size of a disk inode as close to `new_size` as the free space allows
+    /// 空间或配额不足时逐块增长，返回最终的size
+    fn increase_size_up_to(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+        charge: &mut Option<QuotaCharge>,
+    ) -> u32 {
+        if !self.increase_size(new_size, disk_inode, fs, charge) {
+            while disk_inode.size < new_size {
+                let next = ((disk_inode.size / BLOCK_SZ as u32 + 1) * BLOCK_SZ as u32).min(new_size);
+                if !self.increase_size(next, disk_inode, fs, charge) {
+                    break;
+                }
+            }
+        }
+        disk_inode.size
+    }
+    /// Allocate data blocks for the holes among the blocks holding `start..end`, return where the part of
+    /// `start..end` backed by data blocks ends and how many holes were filled
+    /// 空间或配额不足时停在第一个没能分配的空洞处；新分配的数据块都是0，与空洞读出来的内容相同
+    fn fill_holes(
+        &self,
+        start: usize,
+        end: usize,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+        charge: &mut Option<QuotaCharge>,
+    ) -> (usize, usize) {
+        if start >= end {
+            return (end, 0);
+        }
+        let first = start / BLOCK_SZ;
+        let block_ids: Vec<u32> = disk_inode
+            .data_block_ids(first, (end + BLOCK_SZ - 1) / BLOCK_SZ, &self.block_device)
+            .collect();
+        let mut backed_end = end;
+        let mut filled = 0usize;
+        for (inner_id, _) in (first..).zip(block_ids).filter(|&(_, block_id)| block_id == 0) {
+            if !charge.as_mut().map_or(true, |charge| charge.take(1)) {
+                backed_end = (inner_id * BLOCK_SZ).max(start);
+                break;
+            }
+            let goal = self.alloc_goal(inner_id, disk_inode, fs);
+            match fs.alloc_data_near(goal) {
+                Some(block_id) => {
+                    disk_inode.set_block_id(inner_id as u32, block_id, &self.block_device);
+                    filled += 1;
+                }
+                None => {
+                    if let Some(charge) = charge.as_mut() {
+                        charge.give_back(1);
+                    }
+                    backed_end = (inner_id * BLOCK_SZ).max(start);
+                    break;
+                }
+            }
+        }
+        // 空洞的地址转换（0）可能已经被缓存
+        self.block_map.lock().clear();
+        (backed_end, filled)
+    }
+    /// Write back the data blocks `start..end` (inner ids) first, then the index blocks and the data bitmap,
+    /// and the block holding the disk inode last
+    /// 文件新分配了数据块之后按这个顺序写回：在任何一步之后崩溃，磁盘上的DiskInode都不会指向还没写入数据的块
+    fn ordered_sync(&self, start: usize, end: usize) {
+        let (data, index) = self.read_disk_inode(|disk_inode| {
+            let data: Vec<u32> = disk_inode
+                .data_block_ids(start, end, &self.block_device)
+                .filter(|&block_id| block_id != 0)
+                .collect();
+            (data, disk_inode.index_block_ids(&self.block_device))
+        });
+        let bitmap = self.fs.lock().data_bitmap.block_ids();
+        for block_id in data.into_iter().chain(index).map(|block_id| block_id as usize).chain(bitmap) {
+            flush_block(block_id, &self.block_device);
+        }
+        flush_block(self.block_id, &self.block_device);
+    }
+    /// Append a dirent to current directory, return false if the disk is full
+    /// 目录的size是已分配的容量，其中未使用的目录项全为0：优先填入第一个空位，
+    /// 没有空位时把容量翻倍（至少一个块），这样创建N个文件只需要O(log N)次扩容；空间不足以翻倍时只增加一项
+    fn append_dirent(&self, name: &str, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) -> bool {
+        let mut charge = self.begin_charge();
+        let appended = self.modify_disk_inode(|root_inode| {
+            let capacity = (root_inode.size as usize) / DIRENT_SZ;
+            let free_slot = root_inode
+                .read_all_dirents(&self.block_device)
+                .iter()
+                .position(|dirent| !dirent.is_used());
+            let slot = match free_slot {
+                Some(slot) => slot,
+                None => {
+                    let old_size = root_inode.size;
+                    let doubled = (capacity * 2).max(BLOCK_SZ / DIRENT_SZ);
+                    if !self.increase_size((doubled * DIRENT_SZ) as u32, root_inode, fs, &mut charge)
+                        && !self.increase_size(((capacity + 1) * DIRENT_SZ) as u32, root_inode, fs, &mut charge)
+                    {
+                        return false;
+                    }
+                    // 之前缩小目录时留在最后一个块中的旧目录项必须清掉
+                    root_inode.zero_tail(old_size as usize, root_inode.size as usize, &self.block_device);
+                    capacity
+                }
+            };
+            let dirent = DirEntry::new(name, inode_id);
+            root_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            true
+        });
+        self.settle_charge(charge);
+        appended
     }
     fn decrease_size(
         &self,
         new_size: u32,
         disk_inode: &mut DiskInode,
         fs: &mut MutexGuard<EasyFileSystem>,
+        charge: &mut Option<QuotaCharge>,
     ) {
         if new_size > disk_inode.size {
             return;
         }
-        let data_blocks_dealloc = disk_inode.decrease_size(new_size, &self.block_device);
+        self.block_map.lock().clear();
+        let data_blocks_dealloc = disk_inode.shrink_to_fit(new_size, &self.block_device);
+        if let Some(charge) = charge.as_mut() {
+            charge.give_back(data_blocks_dealloc.len() as u32);
+        }
         for data_block in data_blocks_dealloc.into_iter() {
             fs.dealloc_data(data_block);
         }
     }
+    /// The directory whose block quota the blocks of current inode are charged to:
+    /// itself if it is a directory with a quota, otherwise the one it was found under
+    fn governing_dir(&self) -> Option<(usize, usize)> {
+        if self.read_disk_inode(|disk_inode| disk_inode.block_quota().is_some()) {
+            Some(self.pos())
+        } else {
+            self.quota_dir
+        }
+    }
+    /// Read the quota left for the blocks of current inode, must be called with the fs lock held
+    /// and outside of modify_disk_inode
+    fn begin_charge(&self) -> Option<QuotaCharge> {
+        let dir = self.governing_dir()?;
+        let (quota, used) = get_block_cache(dir.0, Arc::clone(&self.block_device))
+            .lock()
+            .read(dir.1, |disk_inode: &DiskInode| (disk_inode.block_quota(), disk_inode.blocks_used()));
+        Some(QuotaCharge {
+            dir,
+            left: quota?.saturating_sub(used),
+            allocated: 0,
+            freed: 0,
+        })
+    }
+    /// Record the blocks allocated and freed under a charge on the directory holding the quota
+    fn settle_charge(&self, charge: Option<QuotaCharge>) {
+        if let Some(charge) = charge {
+            if charge.allocated != charge.freed {
+                get_block_cache(charge.dir.0, Arc::clone(&self.block_device))
+                    .lock()
+                    .modify(charge.dir.1, |disk_inode: &mut DiskInode| {
+                        disk_inode.charge_blocks(charge.allocated, charge.freed)
+                    });
+            }
+        }
+    }
+    /// Give `blocks` freed under current directory back to its quota
+    fn refund_quota(&self, blocks: u32) {
+        let mut charge = self.begin_charge();
+        if let Some(charge) = charge.as_mut() {
+            charge.give_back(blocks);
+        }
+        self.settle_charge(charge);
+    }
 
+    /// Create a file under current inode by name
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.try_create(name).ok()
+    }
+    /// Create a file under current inode by name, telling apart an existing name from a full disk
+    /// 检查名字是否存在和创建都在同一次fs锁内完成，可用于实现O_EXCL
+    pub fn try_create(&self, name: &str) -> Result<Arc<Inode>, CreateError> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// Create a directory under current inode by name
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory).ok()
+    }
+    /// Create a symbolic link named `name` under current inode pointing to `target`
+    /// target只是一个路径字符串，创建时不检查它是否存在
+    pub fn create_symlink(&self, name: &str, target: &str) -> Option<Arc<Inode>> {
+        let inode = self.create_inode(name, DiskInodeType::SymLink).ok()?;
+        inode.write_at(0, target.as_bytes());
+        Some(inode)
+    }
     /// Create inode under current inode by name
     /// 其实是在构建一个对应的DiskInode，在最后返回一个Inode,
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Result<Arc<Inode>, CreateError> {
         let mut fs = self.fs.lock();
         if self
             .modify_disk_inode(|root_inode| {
@@ -126,58 +636,85 @@ impl Inode {
             })
             .is_some()
         {
-            return None;
+            return Err(CreateError::Exists);
+        }
+        // 新目录存放"."和".."的那个数据块也计入配额
+        let mut charge = self.begin_charge();
+        if type_ == DiskInodeType::Directory && !charge.as_mut().map_or(true, |charge| charge.take(1)) {
+            return Err(CreateError::NoSpace);
         }
         // create a new file
         // alloc a inode with an indirect block
-        let new_inode_id = fs.alloc_inode();
+        fs.journal_track();
+        let new_inode_id = fs.alloc_inode().ok_or(CreateError::NoSpace)?;
+        let dir_id = self.get_inode_id(&fs);
+        fs.journal_begin(JournalRecord::new(JournalOp::Create, (dir_id, name, new_inode_id), (0, "", 0)));
+        let created = self.init_child(name, type_, new_inode_id, charge, &mut fs);
+        fs.journal_end();
+        created
+        // release efs lock automatically by compiler
+    }
+    /// Initialize the freshly allocated inode `new_inode_id` and add it to current directory as `name`
+    fn init_child(
+        &self,
+        name: &str,
+        type_: DiskInodeType,
+        new_inode_id: u32,
+        charge: Option<QuotaCharge>,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Result<Arc<Inode>, CreateError> {
         // initialize inode
         // 创建一个对应的DiskInode并将其写入磁盘中（实际是写入对应的缓存区了）
         //首先根据DiskInode的id计算出它所在的block的id以及在block内的偏移
         let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
 
         // 将这个DiskInode初始化（在内存缓存区中）
-        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+        let initialized = get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                let is_dir = type_ == DiskInodeType::Directory;
+                new_inode.initialize(type_);
+                if is_dir {
+                    let parent_inode_id = self.get_inode_id(fs);
+                    fs.init_dir(new_inode, new_inode_id, parent_inode_id)
+                } else {
+                    true
+                }
             });
+        if !initialized {
+            fs.dealloc_inode(new_inode_id);
+            return Err(CreateError::NoSpace);
+        }
+        self.settle_charge(charge);
 
-        // 将文件（DiskInode）对应的DirEntry写入目录（self）指向的在数据区的block中
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
-        });
+        // 将文件（DiskInode）对应的DirEntry写入目录（self）指向的在数据区的block中，空间不足时撤销新建的inode
+        fs.forget_dirent(self.pos(), name);
+        if !self.append_dirent(name, new_inode_id, fs) {
+            let freed = self.free_inode_tree(new_inode_id, fs);
+            self.refund_quota(freed);
+            return Err(CreateError::NoSpace);
+        }
 
+        self.check_dir(fs);
         let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
-        block_cache_sync_all();
         // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
-            self.fs.clone(),
-            self.block_device.clone(),
-        )))
-        // release efs lock automatically by compiler
+        Ok(Arc::new(self.child(block_id, block_offset)))
     }
 
+    /// 在当前目录下为oldpath创建一个名为newpath的硬链接，newpath已存在时返回-1
     pub fn linkat(&self, oldpath: &str, newpath: &str) -> isize {
+        if oldpath == newpath {
+            return -1;
+        }
         let mut fs = self.fs.lock();
         let inode_id: u32;
         match self.read_disk_inode(|root_inode| {
             // assert it is a directory
             assert!(root_inode.is_dir());
-            // has the file been created?
+            // has the file been created? and is the new name still free?
+            if self.find_inode_id(newpath, root_inode).is_some() {
+                return None;
+            }
             self.find_inode_id(oldpath, root_inode)
         }) {
             Some(inode) => {
@@ -185,61 +722,568 @@ impl Inode {
             }
             None => return -1,
         }
-        self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
-            // write dirent
-            let dirent = DirEntry::new(newpath, inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
-        });
-        0
+        fs.forget_dirent(self.pos(), newpath);
+        let dir_id = self.get_inode_id(&fs);
+        fs.journal_begin(JournalRecord::new(JournalOp::Link, (dir_id, newpath, inode_id), (0, "", 0)));
+        let ret = if self.append_dirent(newpath, inode_id, &mut fs) { 0 } else { -1 };
+        self.check_dir(&fs);
+        fs.journal_end();
+        ret
+    }
+    /// Create a dirent `name` under current directory referring to `target`, which may live in any directory
+    /// name已存在或磁盘空间不足时返回-1
+    pub fn link(&self, name: &str, target: &Inode) -> isize {
+        if name == "." || name == ".." {
+            return -1;
+        }
+        let mut fs = self.fs.lock();
+        if self.read_disk_inode(|root_inode| self.find_inode_id(name, root_inode)).is_some() {
+            return -1;
+        }
+        let inode_id = target.get_inode_id(&fs);
+        fs.forget_dirent(self.pos(), name);
+        let dir_id = self.get_inode_id(&fs);
+        fs.journal_begin(JournalRecord::new(JournalOp::Link, (dir_id, name, inode_id), (0, "", 0)));
+        let ret = if self.append_dirent(name, inode_id, &mut fs) { 0 } else { -1 };
+        self.check_dir(&fs);
+        fs.journal_end();
+        ret
     }
-    /// 只能由目录的Inode调用
+    /// Remove the dirent `name` under current directory, freeing its inode once nothing links to it
+    /// 只能由目录的Inode调用；目录只有为空（只剩"."和".."）时才能删除，非空目录、挂载点和挂载着的镜像文件返回-1
     pub fn unlinkat(&self, name: &str) -> isize {
+        if name == "." || name == ".." {
+            return -1;
+        }
+        // 挂载点不能被删除；挂载表不能在持有文件系统锁时获取，所以先取出位置再释放锁
+        let pos = {
+            let fs = self.fs.lock();
+            self.read_disk_inode(|root_inode| self.find_inode_id(name, root_inode))
+                .map(|inode_id| fs.get_disk_inode_pos(inode_id))
+        };
+        if let Some((block_id, block_offset)) = pos {
+            let key = (self.block_device.id(), block_id as usize, block_offset);
+            if MOUNT_TABLE.lock().mounts.contains_key(&key) {
+                return -1;
+            }
+        }
         let mut fs = self.fs.lock();
-        let mut mark = -1;
+        let inode_id = match self.read_disk_inode(|root_inode| self.find_inode_id(name, root_inode)) {
+            Some(inode_id) => inode_id,
+            None => return -1,
+        };
+        // 挂载着的镜像文件不能被删除
+        if fs.mounted_images.contains_key(&inode_id) {
+            return -1;
+        }
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let target = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+        let is_empty = target.read_disk_inode(|disk_inode| {
+            !disk_inode.is_dir()
+                || disk_inode
+                    .read_all_dirents(&self.block_device)
+                    .iter()
+                    .all(|dirent| !dirent.is_used() || dirent.is_dot())
+        });
+        if !is_empty {
+            return -1;
+        }
+        let dir_id = self.get_inode_id(&fs);
+        fs.journal_begin(JournalRecord::new(JournalOp::Unlink, (dir_id, name, inode_id), (0, "", 0)));
+        self.remove_dirent(name, &mut fs);
+        self.check_dir(&fs);
+        // 最后一个链接被删除的文件或空目录：还有打开的文件时推迟到最后一个关闭时再释放
+        if !self.is_linked(inode_id, &fs) {
+            self.free_unlinked(inode_id, &mut fs);
+        }
+        fs.journal_end();
+        0
+    }
+    /// Remove the dirent `name` from current directory, return whether it was found
+    fn remove_dirent(&self, name: &str, fs: &mut MutexGuard<EasyFileSystem>) -> bool {
+        fs.forget_dirent(self.pos(), name);
+        let mut mark = false;
         self.modify_disk_inode(|root_inode| {
             // assert it is a directory
             assert!(root_inode.is_dir());
 
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    root_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
-                if dirent.name().eq(name) {
-                    root_inode.read_at(
-                        (file_count - 1) * DIRENT_SZ,
-                        dirent.as_bytes_mut(),
-                        &self.block_device,
-                    );
-                    root_inode.write_at(i * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
-                    self.decrease_size(((file_count - 1) * DIRENT_SZ) as u32, root_inode, &mut fs);
-                    mark = 0;
-                    break;
+            let dirents = root_inode.read_all_dirents(&self.block_device);
+            if let Some(i) = dirents.iter().position(|dirent| dirent.is_used() && dirent.name().eq(name)) {
+                // 用最后一个在用的目录项覆盖被删除的目录项，再把它原来的位置清零，目录的容量保持不变
+                let last = dirents.iter().rposition(|dirent| dirent.is_used()).unwrap();
+                if i != last {
+                    root_inode.write_at(i * DIRENT_SZ, dirents[last].as_bytes(), &self.block_device);
                 }
+                root_inode.write_at(last * DIRENT_SZ, DirEntry::empty().as_bytes(), &self.block_device);
+                mark = true;
             }
         });
         mark
     }
-
-    pub fn get_diskinodetype(&self) -> (usize, bool) {
+    /// Check that current directory is consistent: its size is a multiple of DIRENT_SZ
+    /// and every used dirent refers to an allocated inode
+    pub fn validate_dir(&self) -> bool {
+        let fs = self.fs.lock();
+        self.dir_is_valid(&fs)
+    }
+    fn dir_is_valid(&self, fs: &MutexGuard<EasyFileSystem>) -> bool {
+        self.read_disk_inode(|root_inode| {
+            if !root_inode.is_dir() || root_inode.size as usize % DIRENT_SZ != 0 {
+                return false;
+            }
+            root_inode.read_all_dirents(&self.block_device).iter().all(|dirent| {
+                !dirent.is_used()
+                    || fs.inode_bitmap.is_allocated(&self.block_device, dirent.inode_number() as usize)
+            })
+        })
+    }
+    /// With the `validate` feature, panic as soon as a directory operation leaves current directory inconsistent
+    fn check_dir(&self, _fs: &MutexGuard<EasyFileSystem>) {
+        #[cfg(feature = "validate")]
+        assert!(self.dir_is_valid(_fs), "Directory is inconsistent!");
+    }
+    /// Move the live dirents of current directory to the front, then free the space taken by the holes
+    /// and the unused capacity
+    /// 目录项之间的相对顺序保持不变；目录本来就是紧凑的时候什么都不做
+    pub fn compact(&self) {
+        let mut fs = self.fs.lock();
+        let mut charge = self.begin_charge();
+        self.modify_disk_inode(|root_inode| {
+            // assert it is a directory
+            assert!(root_inode.is_dir());
+            let dirents = root_inode.read_all_dirents(&self.block_device);
+            let file_count = dirents.len();
+            let mut live_count = 0usize;
+            for (i, dirent) in dirents.iter().enumerate() {
+                if !dirent.is_used() {
+                    continue;
+                }
+                if live_count != i {
+                    root_inode.write_at(live_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+                }
+                live_count += 1;
+            }
+            if live_count < file_count {
+                self.decrease_size((live_count * DIRENT_SZ) as u32, root_inode, &mut fs, &mut charge);
+            }
+        });
+        self.settle_charge(charge);
+    }
+    /// Remove `name` under current directory, and everything under it if it is a directory
+    /// 目录树中每个文件的数据块和DiskInode都会被释放，树外指向其中文件的硬链接会因此失效
+    pub fn remove_dir_all(&self, name: &str) -> isize {
+        if name == "." || name == ".." {
+            return -1;
+        }
+        let mut fs = self.fs.lock();
+        let inode_id = match self.read_disk_inode(|root_inode| {
+            assert!(root_inode.is_dir());
+            self.find_inode_id(name, root_inode)
+        }) {
+            Some(inode_id) => inode_id,
+            None => return -1,
+        };
+        let freed = self.free_inode_tree(inode_id, &mut fs);
+        self.remove_dirent(name, &mut fs);
+        self.refund_quota(freed);
+        0
+    }
+    /// Whether any directory of the filesystem still has a (non-dot) dirent referring to `inode_id`
+    /// 硬链接可以在别的目录中，所以要扫描所有已分配的目录
+    fn is_linked(&self, inode_id: u32, fs: &MutexGuard<EasyFileSystem>) -> bool {
+        fs.iter_allocated_inodes().into_iter().any(|dir_id| {
+            let (block_id, block_offset) = fs.get_disk_inode_pos(dir_id);
+            let dir = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+            dir.read_disk_inode(|disk_inode| disk_inode.is_dir() && dir.find_name(inode_id, disk_inode).is_some())
+        })
+    }
+    /// Free `inode_id` whose last dirent was just removed from current directory, or mark it as an orphan
+    /// to be freed by the last `release_open` if it is still open
+    fn free_unlinked(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        if fs.open_counts.contains_key(&inode_id) {
+            fs.orphans.insert(inode_id);
+        } else {
+            let freed = self.free_inode_tree(inode_id, fs);
+            self.refund_quota(freed);
+        }
+    }
+    /// Count current inode as opened once more, an unlinked inode is not freed while it is open
+    /// 应与 release_open 成对调用，例如内核打开文件和关闭最后一个fd时
+    pub fn hold_open(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = self.get_inode_id(&fs);
+        *fs.open_counts.entry(inode_id).or_insert(0) += 1;
+    }
+    /// Undo one `hold_open`, freeing current inode if it was unlinked and this was its last open
+    pub fn release_open(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = self.get_inode_id(&fs);
+        let count = match fs.open_counts.get_mut(&inode_id) {
+            Some(count) => count,
+            None => return,
+        };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        fs.open_counts.remove(&inode_id);
+        if fs.orphans.remove(&inode_id) {
+            let freed = self.free_inode_tree(inode_id, &mut fs);
+            self.refund_quota(freed);
+        }
+    }
+    /// Pin current file as the image of a mounted filesystem: it is held open, and it cannot be truncated,
+    /// cleared, hole-punched, unlinked or renamed over until the matching `release_image`
+    /// 挂载的文件系统直接读写镜像文件的数据块，这些块被释放后再分配给别的文件就会同时破坏两个文件系统
+    pub(crate) fn hold_image(&self) {
+        self.hold_open();
+        let mut fs = self.fs.lock();
+        let inode_id = self.get_inode_id(&fs);
+        *fs.mounted_images.entry(inode_id).or_insert(0) += 1;
+    }
+    /// Undo one `hold_image`
+    pub(crate) fn release_image(&self) {
+        let mut fs = self.fs.lock();
+        let inode_id = self.get_inode_id(&fs);
+        if let Some(count) = fs.mounted_images.get_mut(&inode_id) {
+            *count -= 1;
+            if *count == 0 {
+                fs.mounted_images.remove(&inode_id);
+            }
+        }
+        drop(fs);
+        self.release_open();
+    }
+    /// Whether current file is the image of a mounted filesystem, see `hold_image`
+    pub fn is_mounted_image(&self) -> bool {
         let fs = self.fs.lock();
+        fs.mounted_images.contains_key(&self.get_inode_id(&fs))
+    }
+    /// Free the inode `inode_id` with its data, recursing into it if it is a directory
+    /// 返回应当还给当前目录所在配额的块数：自己带有配额的子目录中的块记在它自己的配额上，不计入其中
+    fn free_inode_tree(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) -> u32 {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let inode = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+        let has_quota = inode.read_disk_inode(|disk_inode| disk_inode.block_quota().is_some());
+        let children = inode.read_disk_inode(|disk_inode| {
+            if !disk_inode.is_dir() {
+                return Vec::new();
+            }
+            disk_inode
+                .read_all_dirents(&self.block_device)
+                .iter()
+                .filter(|dirent| dirent.is_used() && !dirent.is_dot())
+                .map(|dirent| dirent.inode_number())
+                .collect::<Vec<u32>>()
+        });
+        let mut freed = 0u32;
+        for child in children {
+            freed += inode.free_inode_tree(child, fs);
+        }
+        // 这个inode之后可能被复用，它名下的缓存项都不再有效
+        fs.forget_dir(inode.pos());
+        inode.block_map.lock().clear();
+        inode.modify_disk_inode(|disk_inode| {
+            for data_block in disk_inode.clear_size(&self.block_device) {
+                fs.dealloc_data(data_block);
+                freed += 1;
+            }
+        });
+        fs.dealloc_inode(inode_id);
+        if has_quota {
+            0
+        } else {
+            freed
+        }
+    }
 
+    /// Rename `old_name` under current directory to `new_name` under `new_dir`
+    /// 如果new_name已存在（且不是目录）就覆盖它：先把新的目录项写到被覆盖者的位置，再删除旧的目录项，
+    /// 最后被覆盖的inode在new_dir中已没有链接时释放它，中途崩溃也不会丢失源文件
+    pub fn rename(&self, old_name: &str, new_dir: &Inode, new_name: &str) -> isize {
+        match self.rename2(old_name, new_dir, new_name, RenameMode::Replace) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
+    }
+    /// Rename `old_name` under current directory to `new_name` under `new_dir`, `mode` decides what happens
+    /// to an existing `new_name`
+    /// 检查new_name是否存在和随后的修改在同一次fs锁内完成，其间其他人不能创建或删除这个名字；
+    /// 交换时只是原地互换两个目录项中的inode编号，不分配也不释放inode
+    pub fn rename2(&self, old_name: &str, new_dir: &Inode, new_name: &str, mode: RenameMode) -> Result<(), RenameError> {
+        if old_name == "." || old_name == ".." || new_name == "." || new_name == ".." {
+            return Err(RenameError::Invalid);
+        }
+        let mut fs = self.fs.lock();
+        let src_id = self.read_disk_inode(|root_inode| {
+            assert!(root_inode.is_dir());
+            self.find_inode_id(old_name, root_inode)
+        }).ok_or(RenameError::NotFound)?;
+        let victim_id = new_dir.read_disk_inode(|root_inode| {
+            assert!(root_inode.is_dir());
+            new_dir.find_inode_id(new_name, root_inode)
+        });
+        fs.forget_dirent(new_dir.pos(), new_name);
+        let (old_parent_id, new_parent_id) = (self.get_inode_id(&fs), new_dir.get_inode_id(&fs));
+        let src_is_dir = self.inode_is_dir(src_id, &fs);
+        match (victim_id, mode) {
+            (Some(_), RenameMode::NoReplace) => return Err(RenameError::Exists),
+            (None, RenameMode::Exchange) => return Err(RenameError::NotFound),
+            // 新旧名字指向同一个inode（包括重命名为自己），什么都不用做
+            (Some(victim_id), _) if victim_id == src_id => return Ok(()),
+            // 目录不能被移动到它自己的子树中，否则这棵子树就与根目录断开了
+            _ if src_is_dir && self.is_ancestor(src_id, new_parent_id, &fs) => return Err(RenameError::Invalid),
+            (Some(victim_id), RenameMode::Exchange) => {
+                // 交换时被交换的目录同样会被移动到old_name所在的目录下
+                if self.inode_is_dir(victim_id, &fs) && self.is_ancestor(victim_id, old_parent_id, &fs) {
+                    return Err(RenameError::Invalid);
+                }
+                fs.journal_begin(JournalRecord::new(
+                    JournalOp::Exchange,
+                    (old_parent_id, old_name, src_id),
+                    (new_parent_id, new_name, victim_id),
+                ));
+                fs.forget_dirent(self.pos(), old_name);
+                new_dir.set_dirent_inode(new_name, src_id);
+                self.set_dirent_inode(old_name, victim_id);
+                if new_parent_id != old_parent_id {
+                    self.set_parent(src_id, new_parent_id, &mut fs);
+                    self.set_parent(victim_id, old_parent_id, &mut fs);
+                }
+                fs.journal_end();
+                return Ok(());
+            }
+            (Some(victim_id), _) if fs.mounted_images.contains_key(&victim_id) => return Err(RenameError::Busy),
+            (Some(victim_id), _) => {
+                if self.inode_is_dir(victim_id, &fs) {
+                    return Err(RenameError::IsDir);
+                }
+                if src_is_dir {
+                    return Err(RenameError::NotDir);
+                }
+            }
+            (None, _) => {}
+        }
+        fs.journal_begin(JournalRecord::new(
+            JournalOp::Rename,
+            (old_parent_id, old_name, src_id),
+            (new_parent_id, new_name, victim_id.unwrap_or(u32::MAX)),
+        ));
+        let renamed = self.rename_dirent(old_name, new_dir, new_name, src_id, victim_id, &mut fs);
+        fs.journal_end();
+        renamed
+    }
+    /// Move the dirent `old_name` (inode `src_id`) under current directory to `new_name` under `new_dir`,
+    /// overwriting `victim_id` if any
+    fn rename_dirent(
+        &self,
+        old_name: &str,
+        new_dir: &Inode,
+        new_name: &str,
+        src_id: u32,
+        victim_id: Option<u32>,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Result<(), RenameError> {
+        if victim_id.is_some() {
+            // 原地把被覆盖的目录项指向源inode
+            new_dir.set_dirent_inode(new_name, src_id);
+        } else if !new_dir.append_dirent(new_name, src_id, fs) {
+            return Err(RenameError::NoSpace);
+        }
+        self.remove_dirent(old_name, fs);
+        // 目录被移动到别的目录下时，需要更新它的".."
+        let new_parent_id = new_dir.get_inode_id(fs);
+        if new_parent_id != self.get_inode_id(fs) {
+            self.set_parent(src_id, new_parent_id, fs);
+        }
+        if let Some(victim_id) = victim_id {
+            // 被覆盖的文件可能还有其他目录中的硬链接，只有哪里都不再引用它时才释放
+            if !new_dir.is_linked(victim_id, fs) {
+                new_dir.free_unlinked(victim_id, fs);
+            }
+        }
+        Ok(())
+    }
+    /// Finish or undo the directory operation left in the journal of `efs`, see [`JournalOp`]
+    /// 只根据目录项当前指向哪个inode来决定要做什么，重复执行是安全的（恢复途中再次崩溃时记录仍在）；
+    /// 被撤销的新目录的数据块和被重命名覆盖的inode不会被释放，泄漏总比释放一个状态不明的块安全
+    pub(crate) fn recover(efs: &Arc<Mutex<EasyFileSystem>>) {
+        let (op, dir_ids, inodes, names) = efs.lock().journal_record(|record| {
+            (record.op(), record.dirs, record.inodes, [String::from(record.name(0)), String::from(record.name(1))])
+        });
+        let op = match op {
+            Some(op) => op,
+            None => return,
+        };
+        let dir_valid = |dir_id: u32| {
+            let fs = efs.lock();
+            fs.inode_bitmap.is_allocated(&fs.block_device, dir_id as usize)
+        };
+        if !dir_valid(dir_ids[0]) || !dir_valid(dir_ids[1]) {
+            return;
+        }
+        let dirs = [EasyFileSystem::inode_from_id(efs, dir_ids[0]), EasyFileSystem::inode_from_id(efs, dir_ids[1])];
+        // 记录中的目录已经不是目录时什么都不做
+        if !dirs.iter().all(|dir| dir.read_disk_inode(|disk_inode| disk_inode.is_dir())) {
+            return;
+        }
+        let points_to = |dir: &Inode, name: &str, inode_id: u32| {
+            dir.read_disk_inode(|disk_inode| dir.find_inode_id(name, disk_inode)) == Some(inode_id)
+        };
+        let mut fs = efs.lock();
+        match op {
+            // 创建和链接被撤销，删除被重做：都是去掉指向记录中inode的那个目录项
+            JournalOp::Create | JournalOp::Link | JournalOp::Unlink => {
+                if points_to(&dirs[0], &names[0], inodes[0]) {
+                    dirs[0].remove_dirent(&names[0], &mut fs);
+                }
+                if op == JournalOp::Create && inodes[0] != 0 && fs.inode_bitmap.is_allocated(&fs.block_device, inodes[0] as usize) {
+                    fs.dealloc_inode(inodes[0]);
+                }
+            }
+            JournalOp::Rename => {
+                dirs[1].point_dirent(&names[1], inodes[0], &mut fs);
+                let same_dirent = dir_ids[0] == dir_ids[1] && names[0] == names[1];
+                if !same_dirent && points_to(&dirs[0], &names[0], inodes[0]) {
+                    dirs[0].remove_dirent(&names[0], &mut fs);
+                }
+                if dir_ids[0] != dir_ids[1] {
+                    dirs[0].set_parent(inodes[0], dir_ids[1], &mut fs);
+                }
+            }
+            JournalOp::Exchange => {
+                dirs[1].point_dirent(&names[1], inodes[0], &mut fs);
+                dirs[0].point_dirent(&names[0], inodes[1], &mut fs);
+                if dir_ids[0] != dir_ids[1] {
+                    dirs[0].set_parent(inodes[0], dir_ids[1], &mut fs);
+                    dirs[0].set_parent(inodes[1], dir_ids[0], &mut fs);
+                }
+            }
+        }
+    }
+    /// Point the dirent `name` under current directory to `inode_id`, adding the dirent if it is missing
+    fn point_dirent(&self, name: &str, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        fs.forget_dirent(self.pos(), name);
+        if self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode)).is_some() {
+            self.set_dirent_inode(name, inode_id);
+        } else {
+            self.append_dirent(name, inode_id, fs);
+        }
+    }
+    /// Point the dirent `name` under current directory to `inode_id` in place
+    fn set_dirent_inode(&self, name: &str, inode_id: u32) {
+        self.modify_disk_inode(|root_inode| {
+            let dirents = root_inode.read_all_dirents(&self.block_device);
+            if let Some(i) = dirents.iter().position(|dirent| dirent.is_used() && dirent.name() == name) {
+                let dirent = DirEntry::new(name, inode_id);
+                root_inode.write_at(i * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            }
+        });
+    }
+    /// Whether inode `inode_id` is a directory
+    fn inode_is_dir(&self, inode_id: u32, fs: &MutexGuard<EasyFileSystem>) -> bool {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .read(block_offset, |disk_inode: &DiskInode| disk_inode.is_dir())
+    }
+    /// Whether directory `ancestor_id` is `dir_id` itself or one of its ancestors
+    /// 与abs_path一样沿着".."向上走到根目录；目录结构损坏出现环时最多走inode总数那么多步
+    fn is_ancestor(&self, ancestor_id: u32, dir_id: u32, fs: &MutexGuard<EasyFileSystem>) -> bool {
+        let mut inode_id = dir_id;
+        for _ in 0..=fs.inode_bitmap.maximum() {
+            if inode_id == ancestor_id {
+                return true;
+            }
+            if inode_id == 0 {
+                return false;
+            }
+            let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+            let dir = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+            inode_id = match dir.read_disk_inode(|disk_inode| dir.find_inode_id("..", disk_inode)) {
+                Some(parent_id) => parent_id,
+                None => return false,
+            };
+        }
+        false
+    }
+    /// Point ".." of `inode_id` to `parent_id` if it is a directory
+    fn set_parent(&self, inode_id: u32, parent_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let inode = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+        fs.forget_dirent(inode.pos(), "..");
+        inode.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                let dirent = DirEntry::new("..", parent_id);
+                disk_inode.write_at(DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            }
+        });
+    }
+    /// Get the absolute path of current directory, e.g. "/a/b"
+    /// 沿着".."逐级向上走到根目录，在每个父目录中找到指向子目录的目录项来得到名字；
+    /// 当前inode不是目录或者目录结构已损坏时返回None
+    pub fn abs_path(&self) -> Option<String> {
+        let fs = self.fs.lock();
+        let mut names: Vec<String> = Vec::new();
+        let mut inode_id = self.get_inode_id(&fs);
+        let mut dir = Self::new(self.block_id as u32, self.block_offset, self.fs.clone(), self.block_device.clone());
+        // 根目录的".."指向自己（inode 0）
+        while inode_id != 0 {
+            let parent_id = dir.read_disk_inode(|disk_inode| {
+                if disk_inode.is_dir() { dir.find_inode_id("..", disk_inode) } else { None }
+            })?;
+            let (block_id, block_offset) = fs.get_disk_inode_pos(parent_id);
+            let parent = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+            let name = parent.read_disk_inode(|disk_inode| parent.find_name(inode_id, disk_inode))?;
+            names.push(name);
+            if names.len() > fs.inode_bitmap.maximum() {
+                return None;
+            }
+            inode_id = parent_id;
+            dir = parent;
+        }
+        drop(fs);
+        // 走到了被挂载的文件系统的根目录时，从挂载点继续向上
+        let mut path = match dir.mounted_at() {
+            Some(mountpoint) => mountpoint.abs_path()?,
+            None => String::new(),
+        };
+        if path == "/" {
+            path.clear();
+        }
+        if names.is_empty() && path.is_empty() {
+            return Some(String::from("/"));
+        }
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        Some(path)
+    }
+    /// Find the name of a (non-dot) dirent referring to `inode_id` under a directory disk inode
+    fn find_name(&self, inode_id: u32, disk_inode: &DiskInode) -> Option<String> {
+        disk_inode
+            .read_all_dirents(&self.block_device)
+            .iter()
+            .find(|dirent| dirent.is_used() && !dirent.is_dot() && dirent.inode_number() == inode_id)
+            .map(|dirent| String::from(dirent.name()))
+    }
+    /// Get the inode id of current inode from its position on disk
+    fn get_inode_id(&self, fs: &MutexGuard<EasyFileSystem>) -> u32 {
         let inode_size = core::mem::size_of::<DiskInode>();
         let inodes_per_block = (BLOCK_SZ / inode_size) as u32;
-        let ino = (self.block_id - (fs.get_inode_area_start_block() as usize))
+        ((self.block_id - (fs.get_inode_area_start_block() as usize))
             * inodes_per_block as usize
-            + self.block_offset / inode_size;
+            + self.block_offset / inode_size) as u32
+    }
+
+    /// Id of the block device holding current inode, inode ids are only unique on the same device
+    pub fn device_id(&self) -> usize {
+        self.block_device.id()
+    }
+    pub fn get_diskinodetype(&self) -> (usize, bool) {
+        let fs = self.fs.lock();
+        let ino = self.get_inode_id(&fs) as usize;
         let mut mode = false;
         self.read_disk_inode(|inode| {
             mode = inode.is_dir();
@@ -247,70 +1291,433 @@ impl Inode {
         (ino, mode)
     }
 
-    pub fn get_nlink(&self, inode_num: usize) -> usize {
-        let _fs = self.fs.lock();
-        let mut nlink = 0usize;
-        self.read_disk_inode(|root_inode| {
-            // assert it is a directory
-            assert!(root_inode.is_dir());
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    root_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
-                if inode_num == (dirent.inode_number() as usize) {
-                    nlink += 1;
+    /// Get the rwxrwxrwx permission bits of current inode
+    pub fn perm(&self) -> u16 {
+        self.read_disk_inode(|disk_inode| disk_inode.perm())
+    }
+    /// Set the rwxrwxrwx permission bits of current inode
+    /// 权限只在打开文件等时候检查，已经打开的文件不受影响
+    pub fn set_perm(&self, perm: u16) {
+        let _inode = self.lock.write();
+        self.modify_disk_inode(|disk_inode| disk_inode.set_perm(perm));
+    }
+    /// Whether current inode only allows appends
+    pub fn append_only(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.append_only())
+    }
+    /// Allow only appends to current file or lift the restriction, return false for a directory
+    /// 与权限位一样，已经打开的文件也立即受到限制
+    pub fn set_append_only(&self, append_only: bool) -> bool {
+        let _inode = self.lock.write();
+        self.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                return false;
+            }
+            disk_inode.set_append_only(append_only);
+            true
+        })
+    }
+    /// The block quota of current directory and the blocks charged to it, None if it has no quota
+    pub fn block_quota(&self) -> Option<(u32, u32)> {
+        self.read_disk_inode(|disk_inode| Some((disk_inode.block_quota()?, disk_inode.blocks_used())))
+    }
+    /// Limit the blocks the subtree under current directory may take to `quota`, or lift the limit with None
+    /// 设置时统计子树中已经占用的块作为初始用量，已经超出配额时之后的分配都会失败；
+    /// 配额只管到最近的一层：子树中带有自己配额的目录，其下的块只计入它自己的配额。不是目录时返回false
+    pub fn set_block_quota(&self, quota: Option<u32>) -> bool {
+        let mut fs = self.fs.lock();
+        if !self.read_disk_inode(|disk_inode| disk_inode.is_dir()) {
+            return false;
+        }
+        let used = self.subtree_blocks(&fs);
+        self.modify_disk_inode(|disk_inode| disk_inode.set_block_quota(quota, used));
+        // 缓存的路径解析结果中记录了配额目录
+        fs.path_cache.clear();
+        true
+    }
+    /// Blocks taken by current inode and everything under it, except the subtrees of directories with their own quota
+    fn subtree_blocks(&self, fs: &MutexGuard<EasyFileSystem>) -> u32 {
+        let (blocks, children) = self.read_disk_inode(|disk_inode| {
+            let mut v: Vec<u32> = Vec::new();
+            if disk_inode.is_dir() {
+                for dirent in disk_inode.read_all_dirents(&self.block_device) {
+                    if dirent.is_used() && !dirent.is_dot() {
+                        v.push(dirent.inode_number());
+                    }
                 }
             }
+            (DiskInode::total_blocks(disk_inode.size), v)
         });
-        nlink
+        let children: u32 = children
+            .into_iter()
+            .map(|inode_id| {
+                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+                let child = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+                if child.block_quota().is_some() {
+                    0
+                } else {
+                    child.subtree_blocks(fs)
+                }
+            })
+            .sum();
+        blocks + children
+    }
+
+    /// Count the dirents referring to current inode in every directory of its filesystem
+    /// 与is_linked一样扫描所有目录；"."和".."也算在内，所以目录的链接数是2加上子目录数
+    pub fn nlink(&self) -> usize {
+        let fs = self.fs.lock();
+        let inode_id = self.get_inode_id(&fs);
+        fs.iter_allocated_inodes()
+            .into_iter()
+            .map(|dir_id| {
+                let (block_id, block_offset) = fs.get_disk_inode_pos(dir_id);
+                let dir = Self::new(block_id, block_offset, self.fs.clone(), self.block_device.clone());
+                dir.read_disk_inode(|disk_inode| dir.count_links(inode_id, disk_inode))
+            })
+            .sum()
+    }
+    /// Count the dirents referring to `inode_id` under a disk inode, 0 if it is not a directory
+    fn count_links(&self, inode_id: u32, disk_inode: &DiskInode) -> usize {
+        if !disk_inode.is_dir() {
+            return 0;
+        }
+        disk_inode
+            .read_all_dirents(&self.block_device)
+            .iter()
+            .filter(|dirent| dirent.is_used() && dirent.inode_number() == inode_id)
+            .count()
     }
     /// List inodes under current inode
-    /// 只有目录项可以调用
+    /// 只有目录项可以调用，空位以及"."和".."不会被列出
     pub fn ls(&self) -> Vec<String> {
         let _fs = self.fs.lock();
         self.read_disk_inode(|disk_inode| {
-            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-            let mut v: Vec<String> = Vec::new();
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
-                v.push(String::from(dirent.name()));
-            }
-            v
+            disk_inode
+                .read_all_dirents(&self.block_device)
+                .iter()
+                .filter(|dirent| dirent.is_used() && !dirent.is_dot())
+                .map(|dirent| String::from(dirent.name()))
+                .collect()
         })
     }
-    /// Read data from current inode
+    /// Read all valid entries under current inode
+    /// 与ls类似，但同时返回每个目录项的inode_id以及它是否为目录（需要读取目标DiskInode的类型），
+    /// "."和".."也会被返回
+    pub fn read_dir(&self) -> Vec<DirEntryInfo> {
+        let fs = self.fs.lock();
+        let entries = self.read_disk_inode(|disk_inode| {
+            assert!(disk_inode.is_dir());
+            disk_inode
+                .read_all_dirents(&self.block_device)
+                .iter()
+                .enumerate()
+                .filter(|(_, dirent)| dirent.is_used())
+                .map(|(i, dirent)| (String::from(dirent.name()), dirent.inode_number(), i))
+                .collect::<Vec<(String, u32, usize)>>()
+        });
+        entries
+            .into_iter()
+            .map(|(name, inode_id, slot)| {
+                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+                let is_dir = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+                    .lock()
+                    .read(block_offset, |disk_inode: &DiskInode| disk_inode.is_dir());
+                DirEntryInfo {
+                    name,
+                    inode_id,
+                    is_dir,
+                    slot,
+                }
+            })
+            .collect()
+    }
+    /// How many data block ids read_at had to translate by walking the disk inode
+    pub fn block_map_misses(&self) -> usize {
+        self.block_map.lock().misses()
+    }
+    /// Get the size of data in current inode
+    pub fn size(&self) -> usize {
+        let _inode = self.lock.read();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+    /// Number of data blocks actually allocated to current inode
+    /// 有空洞的文件占用的块数少于 size 对应的块数
+    pub fn allocated_blocks(&self) -> usize {
+        let _inode = self.lock.read();
+        self.read_disk_inode(|disk_inode| disk_inode.allocated_blocks(&self.block_device) as usize)
+    }
+    /// Read data from current inode, return how many bytes were read
+    /// 返回值被文件大小截断：短于buf.len()说明读到了文件末尾，返回0当且仅当offset已经到达或超过末尾（或buf为空），
+    /// buf中超出返回值的部分保持不变。读取本身不会失败，块设备出错时直接panic
+    /// 目录只能通过ls/read_dir等接口访问，对目录调用时什么都不读，返回0
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
-        let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+        let _inode = self.lock.read();
+        self.read_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                return 0;
+            }
+            disk_inode.read_at_mapped(offset, buf, &self.block_device, &self.block_map)
+        })
+    }
+    /// Load the data blocks covering `offset..offset + len` into the block cache, return how many were loaded
+    /// 只是提示：超出文件末尾的部分和空洞被忽略，缓存放不下时少读一些
+    pub fn prefetch(&self, offset: usize, len: usize) -> usize {
+        let block_ids: Vec<usize> = {
+            let _inode = self.lock.read();
+            self.read_disk_inode(|disk_inode| {
+                let end = offset.saturating_add(len).min(disk_inode.size as usize);
+                if disk_inode.is_dir() || offset >= end {
+                    return Vec::new();
+                }
+                disk_inode
+                    .data_block_ids(offset / BLOCK_SZ, (end + BLOCK_SZ - 1) / BLOCK_SZ, &self.block_device)
+                    .filter(|&block_id| block_id != 0)
+                    .map(|block_id| block_id as usize)
+                    .collect()
+            })
+        };
+        block_cache_prefetch(&block_ids, &self.block_device)
+    }
+    /// Read data from current inode, resolving the block ids of the whole span at once
+    /// 结果与read_at相同，一次读取跨越很多数据块（如整个大文件）时更快
+    pub fn read_at_range(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _inode = self.lock.read();
+        self.read_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                return 0;
+            }
+            disk_inode.read_at_range(offset, buf, &self.block_device)
+        })
     }
     /// Write data to current inode
+    /// 只覆盖已有数据块的写入只写入块缓存，在块缓存被换出或调用 block_cache_sync_all 时才会写回磁盘，
+    /// 需要持久化时由调用者显式同步；分配了新的数据块时，写入之后立即按顺序写回（见ordered_sync）
+    /// 磁盘空间不足时只写入能放下的部分，返回实际写入的字节数
+    /// 目录只能通过create/linkat/unlinkat等接口修改，对目录调用时什么都不写，返回0；
+    /// 追加专用的文件在offset小于size时同样什么都不写，返回0；写入的末尾超过u32::MAX（文件大小的上限）时也返回0
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
-        let mut fs = self.fs.lock();
+        let end = match offset.checked_add(buf.len()).filter(|&end| end <= u32::MAX as usize) {
+            Some(end) => end,
+            None => return 0,
+        };
+        let _inode = self.lock.write();
+        let (old_size, size, backed_end, allocated) = {
+            let mut fs = self.fs.lock();
+            let mut charge = self.begin_charge();
+            let sizes = self.modify_disk_inode(|disk_inode| {
+                if disk_inode.is_dir() || (disk_inode.append_only() && offset < disk_inode.size as usize) {
+                    return None;
+                }
+                let old_size = disk_inode.size as usize;
+                let old_blocks = disk_inode.data_blocks();
+                let size = self.increase_size_up_to(end as u32, disk_inode, &mut fs, &mut charge) as usize;
+                let (backed_end, filled) = self.fill_holes(offset, end.min(size), disk_inode, &mut fs, &mut charge);
+                Some((old_size, size, backed_end, filled > 0 || disk_inode.data_blocks() > old_blocks))
+            });
+            self.settle_charge(charge);
+            match sizes {
+                Some(sizes) => sizes,
+                None => return 0,
+            }
+        };
+        let written = self.modify_disk_inode(|disk_inode| {
+            // 从原来的文件末尾到offset之间的空洞读出来应当都是0
+            if offset > old_size {
+                disk_inode.zero_tail(old_size, offset.min(size), &self.block_device);
+            }
+            if offset > size {
+                return 0;
+            }
+            disk_inode.write_at(offset, &buf[..backed_end - offset], &self.block_device)
+        });
+        if allocated {
+            self.ordered_sync(offset.min(old_size) / BLOCK_SZ, (size + BLOCK_SZ - 1) / BLOCK_SZ);
+        }
+        written
+    }
+    /// Append data to the end of current inode, return the new size
+    /// 在inode写锁的保护下读取当前size并从文件末尾写入，保证多个追加者之间不会互相覆盖；
+    /// 对目录调用或者追加后的大小超过u32::MAX时返回0
+    pub fn append(&self, buf: &[u8]) -> usize {
+        let _inode = self.lock.write();
+        let (offset, backed_end, allocated) = {
+            let mut fs = self.fs.lock();
+            let mut charge = self.begin_charge();
+            let offsets = self.modify_disk_inode(|disk_inode| {
+                if disk_inode.is_dir() {
+                    return None;
+                }
+                let offset = disk_inode.size as usize;
+                let end = offset.checked_add(buf.len()).filter(|&end| end <= u32::MAX as usize)?;
+                let old_blocks = disk_inode.data_blocks();
+                let size = self.increase_size_up_to(end as u32, disk_inode, &mut fs, &mut charge);
+                // 原来的最后一个块可能被打成了空洞
+                let (backed_end, filled) = self.fill_holes(offset, size as usize, disk_inode, &mut fs, &mut charge);
+                Some((offset, backed_end, filled > 0 || disk_inode.data_blocks() > old_blocks))
+            });
+            self.settle_charge(charge);
+            match offsets {
+                Some(offsets) => offsets,
+                None => return 0,
+            }
+        };
         let size = self.modify_disk_inode(|disk_inode| {
-            self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs);
-            disk_inode.write_at(offset, buf, &self.block_device)
+            disk_inode.write_at(offset, &buf[..backed_end - offset], &self.block_device);
+            disk_inode.size as usize
         });
-        block_cache_sync_all();
+        if allocated {
+            self.ordered_sync(offset / BLOCK_SZ, (size + BLOCK_SZ - 1) / BLOCK_SZ);
+        }
         size
     }
+    /// Set the size of current inode to `new_size`, freeing or allocating data blocks as needed
+    /// 增长时新增的部分被清零；空间不足、是目录、是挂载着的镜像或者要缩小追加专用的文件时size保持不变并返回false
+    pub fn truncate(&self, new_size: usize) -> bool {
+        let _inode = self.lock.write();
+        let mut fs = self.fs.lock();
+        if fs.mounted_images.contains_key(&self.get_inode_id(&fs)) {
+            return false;
+        }
+        let mut charge = self.begin_charge();
+        let truncated = self.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                return false;
+            }
+            let old_size = disk_inode.size as usize;
+            if new_size < old_size && disk_inode.append_only() {
+                return false;
+            }
+            if new_size >= old_size {
+                if !self.increase_size(new_size as u32, disk_inode, &mut fs, &mut charge) {
+                    return false;
+                }
+                disk_inode.zero_tail(old_size, new_size, &self.block_device);
+                return true;
+            }
+            self.decrease_size(new_size as u32, disk_inode, &mut fs, &mut charge);
+            true
+        });
+        self.settle_charge(charge);
+        truncated
+    }
+    /// Grow current inode to `size` bytes, allocating every data block it needs up front
+    /// 所需的数据块要么全部分配成功，要么一个都不分配并返回-1；新分配的数据块都是0，
+    /// 所以只需清零原来最后一个块中超出原size的部分，不必像write_at那样拷贝数据；size不大于当前大小时什么都不做；
+    /// 目录不能预分配，返回-1
+    pub fn preallocate(&self, size: u32) -> isize {
+        let _inode = self.lock.write();
+        let mut fs = self.fs.lock();
+        let mut charge = self.begin_charge();
+        let ret = self.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() {
+                return -1;
+            }
+            let old_size = disk_inode.size as usize;
+            if size as usize <= old_size {
+                return 0;
+            }
+            if !self.increase_size(size, disk_inode, &mut fs, &mut charge) {
+                return -1;
+            }
+            disk_inode.zero_tail(old_size, size as usize, &self.block_device);
+            0
+        });
+        self.settle_charge(charge);
+        ret
+    }
+    /// Deallocate the data blocks fully covered by `offset..offset + len`, the range reads back as zeros
+    /// 文件大小不变；只覆盖了一部分的块（包括文件末尾所在的块）原地清零；之后写入空洞时再重新分配数据块。
+    /// 目录、追加专用的文件和挂载着的镜像不能打洞，返回-1
+    pub fn punch_hole(&self, offset: u32, len: u32) -> isize {
+        let _inode = self.lock.write();
+        let mut fs = self.fs.lock();
+        if fs.mounted_images.contains_key(&self.get_inode_id(&fs)) {
+            return -1;
+        }
+        let mut charge = self.begin_charge();
+        let ret = self.modify_disk_inode(|disk_inode| {
+            if disk_inode.is_dir() || disk_inode.append_only() {
+                return -1;
+            }
+            let size = disk_inode.size as usize;
+            let start = (offset as usize).min(size);
+            let end = (offset as usize + len as usize).min(size);
+            if start >= end {
+                return 0;
+            }
+            // 文件末尾之后本来就是0，所以一直打到文件末尾时最后一个块也算被完整覆盖
+            let first_full = (start + BLOCK_SZ - 1) / BLOCK_SZ;
+            let last_full = if end == size { (end + BLOCK_SZ - 1) / BLOCK_SZ } else { end / BLOCK_SZ };
+            if first_full < last_full {
+                self.block_map.lock().clear();
+                let freed = disk_inode.punch_hole(first_full, last_full, &self.block_device);
+                if let Some(charge) = charge.as_mut() {
+                    charge.give_back(freed.len() as u32);
+                }
+                for data_block in freed {
+                    fs.dealloc_data(data_block);
+                }
+            }
+            let head_end = (first_full * BLOCK_SZ).min(end);
+            disk_inode.zero_range(start, head_end, &self.block_device);
+            disk_inode.zero_range((last_full * BLOCK_SZ).max(head_end), end, &self.block_device);
+            0
+        });
+        self.settle_charge(charge);
+        ret
+    }
+    /// The device holding this file and the ids of its data blocks on it, in file order
+    pub(crate) fn data_block_ids(&self) -> (Arc<dyn BlockDevice>, Vec<u32>) {
+        let _inode = self.lock.read();
+        let block_ids = self.read_disk_inode(|disk_inode| {
+            disk_inode
+                .data_block_ids(0, disk_inode.data_blocks() as usize, &self.block_device)
+                .collect()
+        });
+        (Arc::clone(&self.block_device), block_ids)
+    }
+    /// Write back the dirty cached blocks of current inode only: its data blocks, index blocks and the block holding its disk inode
+    /// 与 block_cache_sync_all 不同，其他文件在缓存区中的脏块不会被写回
+    pub fn fsync(&self) {
+        let _inode = self.lock.read();
+        let block_ids = self.read_disk_inode(|disk_inode| {
+            let mut v: Vec<u32> = disk_inode
+                .data_block_ids(0, disk_inode.data_blocks() as usize, &self.block_device)
+                .collect();
+            v.extend(disk_inode.index_block_ids(&self.block_device));
+            v.retain(|&block_id| block_id != 0);
+            v
+        });
+        for block_id in block_ids {
+            flush_block(block_id as usize, &self.block_device);
+        }
+        flush_block(self.block_id, &self.block_device);
+    }
     /// Clear the data in current inode
-    pub fn clear(&self) {
+    /// 挂载着的镜像不能被清空，什么都不做并返回false
+    pub fn clear(&self) -> bool {
+        let _inode = self.lock.write();
         let mut fs = self.fs.lock();
+        if fs.mounted_images.contains_key(&self.get_inode_id(&fs)) {
+            return false;
+        }
+        let mut charge = self.begin_charge();
+        self.block_map.lock().clear();
         self.modify_disk_inode(|disk_inode| {
             let size = disk_inode.size;
             let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            // 有空洞时释放的块更少
+            assert!(data_blocks_dealloc.len() <= DiskInode::total_blocks(size) as usize);
+            if let Some(charge) = charge.as_mut() {
+                charge.give_back(data_blocks_dealloc.len() as u32);
+            }
             for data_block in data_blocks_dealloc.into_iter() {
                 fs.dealloc_data(data_block);
             }
         });
+        self.settle_charge(charge);
         block_cache_sync_all();
+        true
     }
 }
@@ -61,7 +61,7 @@ impl OSInode {
 lazy_static! {
     /// The root of all inodes, or '/' in short
     pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone()).expect("Error loading EFS!");
         Arc::new(EasyFileSystem::root_inode(&efs))
     };
 }
@@ -7,10 +7,29 @@ pub const MEMORY_END: usize = 0x88000000;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const MAX_SYSCALL_NUM: usize = 500;
+/// 每个进程最多同时打开的文件描述符数，fd_table按需增长到这个长度
+pub const MAX_FD: usize = 128;
+/// 每次时钟中断最多写回多少个脏块
+pub const WRITEBACK_BLOCKS_PER_TICK: usize = 4;
+/// 读文件之后默认预读的块数
+pub const READ_AHEAD_BLOCKS: usize = 2;
+/// fadvise(SEQUENTIAL)之后预读的块数，块缓存一共只有16块
+pub const SEQUENTIAL_READ_AHEAD_BLOCKS: usize = 8;
+
+/// 每隔多少次时钟中断向随机数发生器混入一次当前时间
+pub const RANDOM_RESEED_TICKS: usize = 100;
 
 pub const BIG_STRIDE: usize = usize::MAX / 1_000_000;
 pub const DEFAULT_PRIOR: usize = 16;
+/// 主动让出CPU的进程最多累积的加速等级，每级退还八分之一个stride
+pub const MAX_YIELD_BOOST: usize = 4;
+/// 为多少个核（hart）预留Processor；目前内核只在启动核上运行，见 task::processor
+pub const MAX_CORES: usize = 4;
 
+/// start为0的mmap由内核在[MMAP_BASE, MMAP_END)中挑选地址，远高于用户程序和固定地址mmap常用的区域
+pub const MMAP_BASE: usize = 0x20_0000_0000;
+/// Sv39下用户地址空间（低半部分）的上界
+pub const MMAP_END: usize = 0x40_0000_0000;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 pub const CLOCK_FREQ: usize = 12500000;
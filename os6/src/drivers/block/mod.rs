@@ -2,7 +2,7 @@ mod virtio_blk;
 
 use lazy_static::*;
 use alloc::sync::Arc;
-use easy_fs::BlockDevice;
+use easy_fs::{BlockDevice, BlockError};
 type BlockDeviceImpl = virtio_blk::VirtIOBlock;
 
 lazy_static! {
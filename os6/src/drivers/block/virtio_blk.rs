@@ -11,7 +11,7 @@ use crate::mm::{
     StepByOne,
     kernel_token,
 };
-use super::BlockDevice;
+use super::{BlockDevice, BlockError};
 use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use lazy_static::*;
@@ -29,14 +29,22 @@ lazy_static! {
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        self.0.exclusive_access()
-        .read_block(block_id, buf)
+        self.try_read_block(block_id, buf)
         .expect("Error when reading VirtIOBlk");
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.try_write_block(block_id, buf)
+        .expect("Error when writing VirtIOBlk");
+    }
+    fn try_read_block(&self, block_id: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.0.exclusive_access()
+        .read_block(block_id, buf)
+        .map_err(|_| BlockError::Io { block_id })
+    }
+    fn try_write_block(&self, block_id: usize, buf: &[u8]) -> Result<(), BlockError> {
         self.0.exclusive_access()
         .write_block(block_id, buf)
-        .expect("Error when writing VirtIOBlk");
+        .map_err(|_| BlockError::Io { block_id })
     }
 }
 
@@ -1,6 +1,10 @@
     .section .text.entry
     .globl _start
 _start:
+    # keep hartid (passed by SBI in a0) in tp while in the kernel,
+    # __alltraps reloads it from TrapContext since user apps may change tp.
+    # Only the boot hart ever gets here, the kernel runs on a single hart
+    mv tp, a0
     la sp, boot_stack_top
     call rust_main
 
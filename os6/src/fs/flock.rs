@@ -0,0 +1,75 @@
+//! Advisory whole-file locks taken by flock
+
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// The holders of the flock lock of one inode, each identified by its open file
+/// 同一个打开的文件（dup/fork共享）只算一个持有者，重复加锁是把已有的锁转换成新的类型
+#[derive(Default)]
+struct FileLock {
+    shared: Vec<usize>,
+    exclusive: Option<usize>,
+}
+
+impl FileLock {
+    fn is_free(&self) -> bool {
+        self.shared.is_empty() && self.exclusive.is_none()
+    }
+    fn release(&mut self, owner: usize) {
+        self.shared.retain(|&holder| holder != owner);
+        if self.exclusive == Some(owner) {
+            self.exclusive = None;
+        }
+    }
+}
+
+lazy_static! {
+    /// The flock locks of every inode with at least one holder, keyed by (device id, inode id)
+    /// 不同挂载的文件系统中inode编号会重复，必须带上设备才能区分
+    static ref FILE_LOCKS: UPSafeCell<BTreeMap<(usize, usize), FileLock>> = unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Try to lock inode `ino` on device `dev` for the open file `owner`, shared or exclusive,
+/// return false if others hold it
+/// 持有者已经持有的锁会被转换成新的类型；失败时已持有的锁保持不变
+pub fn try_lock(dev: usize, ino: usize, owner: usize, exclusive: bool) -> bool {
+    let mut locks = FILE_LOCKS.exclusive_access();
+    let lock = locks.entry((dev, ino)).or_default();
+    let held_by_others = lock.exclusive.map_or(false, |holder| holder != owner)
+        || (exclusive && lock.shared.iter().any(|&holder| holder != owner));
+    if held_by_others {
+        if lock.is_free() {
+            locks.remove(&(dev, ino));
+        }
+        return false;
+    }
+    lock.release(owner);
+    if exclusive {
+        lock.exclusive = Some(owner);
+    } else {
+        lock.shared.push(owner);
+    }
+    true
+}
+
+/// Release the lock the open file `owner` holds on inode `ino` on device `dev`, if any
+pub fn unlock(dev: usize, ino: usize, owner: usize) {
+    let mut locks = FILE_LOCKS.exclusive_access();
+    if let Some(lock) = locks.get_mut(&(dev, ino)) {
+        lock.release(owner);
+        if lock.is_free() {
+            locks.remove(&(dev, ino));
+        }
+    }
+}
+
+/// Release every lock held by the open file `owner`, called when it is closed
+pub fn unlock_all(owner: usize) {
+    let mut locks = FILE_LOCKS.exclusive_access();
+    locks.retain(|_, lock| {
+        lock.release(owner);
+        !lock.is_free()
+    });
+}
@@ -1,15 +1,29 @@
 use alloc::string::String;
 use easy_fs::{
+    block_cache_drop_device,
+    block_cache_sync_all,
+    block_cache_sync_dirty_limit,
+    BlockDevice,
+    BLOCK_SZ,
+    CreateError,
     EasyFileSystem,
-    Inode
+    ImageDevice,
+    Inode,
+    DirEntryInfo,
+    MountError,
+    RenameError,
+    RenameMode,
 };
 use crate::drivers::BLOCK_DEVICE;
+use crate::config::{READ_AHEAD_BLOCKS, WRITEBACK_BLOCKS_PER_TICK};
 use crate::sync::UPSafeCell;
 use alloc::sync::Arc;
 use lazy_static::*;
 use bitflags::*;
+use alloc::vec;
 use alloc::vec::Vec;
-use super::{File, StatMode};
+use super::{File, FsError, StatMode, SEEK_SET, SEEK_CUR, SEEK_END};
+use super::flock::unlock_all;
 use crate::mm::UserBuffer;
 
 /// A wrapper around a filesystem inode
@@ -17,6 +31,7 @@ use crate::mm::UserBuffer;
 pub struct OSInode {
     readable: bool,
     writable: bool,
+    append: bool,
     inner: UPSafeCell<OSInodeInner>,
 }
 
@@ -24,37 +39,101 @@ pub struct OSInode {
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
+    /// small sequential writes not yet passed to the inode, they always end at `offset`
+    /// 只缓存落在同一个已分配数据块内的写入，flush时不需要分配新块，因此不会因为磁盘已满而丢失数据
+    wbuf: Vec<u8>,
+    /// how many times `Inode::write_at` was called through this file
+    write_at_calls: usize,
+    /// a flush wrote fewer bytes than were buffered, reported once by the next write or fsync
+    /// 缓冲的写入已经向用户报告成功，flush失败时只能留到之后的write或fsync再返回错误
+    write_error: bool,
+    /// the dirent slot from which getdents continues, only used for directories
+    /// 按目录项槽位计数（包括空槽），所以跳过空槽不会影响位置
+    dir_pos: usize,
+    /// how many blocks after the read range are loaded into the block cache after each read, see fadvise
+    read_ahead: usize,
+}
+
+impl OSInodeInner {
+    /// Write `buf` at `offset`, buffering it if it continues the buffered bytes within the same block
+    /// 缓冲的数据在flush之前对其他打开同一文件的OSInode不可见，所以读、seek、关闭以及通过inode()
+    /// 直接访问文件之前都要先flush
+    fn write_buffered(&mut self, buf: &[u8]) -> usize {
+        let block_end = (self.offset / BLOCK_SZ + 1) * BLOCK_SZ;
+        if self.offset + buf.len() <= block_end {
+            if self.wbuf.is_empty() {
+                // 只有当前块已经分配（位于文件最后一个块之内）时才开始缓冲
+                let allocated_end = (self.inode.size() + BLOCK_SZ - 1) / BLOCK_SZ * BLOCK_SZ;
+                if self.offset < allocated_end {
+                    self.wbuf.extend_from_slice(buf);
+                    self.offset += buf.len();
+                    return buf.len();
+                }
+            } else {
+                self.wbuf.extend_from_slice(buf);
+                self.offset += buf.len();
+                return buf.len();
+            }
+        }
+        self.flush();
+        self.write_at_calls += 1;
+        let write_size = self.inode.write_at(self.offset, buf);
+        self.offset += write_size;
+        write_size
+    }
+    /// Pass the buffered bytes to the inode
+    fn flush(&mut self) {
+        if self.wbuf.is_empty() {
+            return;
+        }
+        let start = self.offset - self.wbuf.len();
+        self.write_at_calls += 1;
+        // 缓冲区所在的块已经分配，只有文件在此期间被其他fd截断或打洞、而磁盘又已满时才会写不完
+        if self.inode.write_at(start, &self.wbuf) < self.wbuf.len() {
+            self.write_error = true;
+        }
+        self.wbuf.clear();
+    }
 }
 
 impl OSInode {
     /// Construct an OS inode from a inode
+    /// 打开期间inode即使被删除了最后一个链接也不会被释放，见 Drop
     pub fn new(
         readable: bool,
         writable: bool,
+        append: bool,
         inode: Arc<Inode>,
     ) -> Self {
+        inode.hold_open();
         Self {
             readable,
             writable,
+            append,
             inner: unsafe { UPSafeCell::new(OSInodeInner {
                 offset: 0,
                 inode,
+                wbuf: Vec::new(),
+                write_at_calls: 0,
+                write_error: false,
+                dir_pos: 0,
+                read_ahead: READ_AHEAD_BLOCKS,
             })},
         }
     }
+    /// How many times the writes through this file reached `Inode::write_at`
+    pub fn write_at_calls(&self) -> usize {
+        self.inner.exclusive_access().write_at_calls
+    }
     /// Read all data inside a inode into vector
+    /// 一次读出从当前offset到文件末尾的全部数据，避免逐块重新解析间接索引
     pub fn read_all(&self) -> Vec<u8> {
         let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            v.extend_from_slice(&buffer[..len]);
-        }
+        inner.flush();
+        let mut v: Vec<u8> = vec![0u8; inner.inode.size().saturating_sub(inner.offset)];
+        let len = inner.inode.read_at_range(inner.offset, &mut v);
+        v.truncate(len);
+        inner.offset += len;
         v
     }
 }
@@ -62,8 +141,8 @@ impl OSInode {
 lazy_static! {
     /// The root of all inodes, or '/' in short
     pub static ref ROOT_INODE: Arc<Inode> = {
-        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
-        Arc::new(EasyFileSystem::root_inode(&efs))
+        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone()).expect("Error loading EFS!");
+        EasyFileSystem::root_inode_arc(&efs)
     };
 }
 
@@ -84,6 +163,15 @@ bitflags! {
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        /// together with CREATE, create missing parent directories like `mkdir -p`
+        const MKDIR_PARENTS = 1 << 12;
+        /// together with CREATE, fail with EEXIST if the name already exists
+        const EXCL = 1 << 7;
+        /// fail unless the path is a directory
+        const DIRECTORY = 1 << 16;
+        /// close the fd on exec
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -102,62 +190,522 @@ impl OpenFlags {
     }
 }
 
-/// Open a file by path
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+bitflags! {
+    /// The permissions asked for by access, an empty mode only checks existence
+    pub struct AccessMode: u32 {
+        const F_OK = 0;
+        const X_OK = 1 << 0;
+        const W_OK = 1 << 1;
+        const R_OK = 1 << 2;
+    }
+}
+
+/// Split a path into (parent, name), e.g. "a/b/c" -> ("a/", "c"), "/" -> ("/", "")
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(pos) => (&path[..pos + 1], &path[pos + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Find an inode by path, relative paths are resolved from `base`
+pub fn find_path_at(base: &Arc<Inode>, path: &str) -> Option<Arc<Inode>> {
+    if path.starts_with('/') {
+        ROOT_INODE.find_path(path)
+    } else {
+        base.find_path(path)
+    }
+}
+
+/// Find the directory at `path`, creating every missing directory along the way
+/// 路径中已存在的分量必须是目录（或指向目录的符号链接），否则返回NotDir
+fn mkdir_parents_at(base: &Arc<Inode>, path: &str) -> Result<Arc<Inode>, FsError> {
+    let mut dir = if path.starts_with('/') {
+        ROOT_INODE.clone()
+    } else {
+        base.clone()
+    };
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        dir = match dir.find_path(name) {
+            Some(inode) if inode.is_dir() => inode,
+            Some(_) => return Err(FsError::NotDir),
+            None => dir.create_dir(name).ok_or(FsError::NoSpace)?,
+        };
+    }
+    Ok(dir)
+}
+
+/// Check the type of an existing inode against the open flags
+/// 带 DIRECTORY 时只能打开目录；目录不能以可写方式打开（目录项只能通过 link/unlink 等修改）
+fn check_open_type(inode: &Arc<Inode>, flags: OpenFlags, writable: bool) -> Result<(), FsError> {
+    let is_dir = inode.is_dir();
+    if flags.contains(OpenFlags::DIRECTORY) && !is_dir {
+        return Err(FsError::Failed);
+    }
+    if writable && is_dir {
+        return Err(FsError::IsDir);
+    }
+    Ok(())
+}
+
+/// Open a file by path, relative paths are resolved from `base`
+pub fn open_file_at(base: &Arc<Inode>, path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, FsError> {
     let (readable, writable) = flags.read_write();
+    let append = flags.contains(OpenFlags::APPEND);
+    if flags.contains(OpenFlags::TRUNC) && !writable {
+        // 只读打开时不允许清空文件
+        return Err(FsError::Failed);
+    }
+    if flags.contains(OpenFlags::CREATE | OpenFlags::DIRECTORY) {
+        // 新建的总是普通文件，不能满足 DIRECTORY
+        return Err(FsError::Invalid);
+    }
+    // 打开已存在的文件时要检查的权限
+    let mut mode = AccessMode::empty();
+    if readable {
+        mode |= AccessMode::R_OK;
+    }
+    if writable {
+        mode |= AccessMode::W_OK;
+    }
+    let (parent_path, name) = split_path(path);
+    let parent = if flags.contains(OpenFlags::CREATE | OpenFlags::MKDIR_PARENTS) {
+        mkdir_parents_at(base, parent_path)?
+    } else {
+        find_path_at(base, parent_path).ok_or(FsError::NotFound)?
+    };
+    if name.is_empty() {
+        // 路径以'/'结尾（如"/"），打开的是目录本身
+        check_open_type(&parent, flags, writable)?;
+        return Ok(Arc::new(OSInode::new(
+            readable,
+            writable,
+            append,
+            parent,
+        )));
+    }
+    if !parent.is_dir() {
+        return Err(FsError::NotDir);
+    }
+    if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) {
+        // 名字是否存在在创建时同一次fs锁内检查，即使最后一个分量是符号链接也视为已存在
+        return parent.try_create(name)
+            .map(|inode| Arc::new(OSInode::new(readable, writable, append, inode)))
+            .map_err(|err| match err {
+                CreateError::Exists => FsError::Exists,
+                CreateError::NoSpace => FsError::NoSpace,
+            });
+    }
+    // 最后一个分量是符号链接时打开它指向的文件
     if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(
+        if let Some(inode) = parent.find_path(name) {
+            check_open_type(&inode, flags, writable)?;
+            // 已存在的文件会被清空，这也是一次写入
+            if !perm_allows(&inode, mode | AccessMode::W_OK) || inode.append_only() {
+                return Err(FsError::Access);
+            }
+            // clear size，挂载着的镜像不能被清空
+            if !inode.clear() {
+                return Err(FsError::Busy);
+            }
+            Ok(Arc::new(OSInode::new(
                 readable,
                 writable,
+                append,
                 inode,
             )))
         } else {
-            // create file
-            ROOT_INODE.create(name)
+            // create file，名字不存在时创建失败只可能是空间不足
+            parent.create(name)
                 .map(|inode| {
                     Arc::new(OSInode::new(
                         readable,
                         writable,
+                        append,
                         inode,
                     ))
                 })
+                .ok_or(FsError::NoSpace)
         }
     } else {
-        ROOT_INODE.find(name)
-            .map(|inode| {
-                if flags.contains(OpenFlags::TRUNC) {
-                    inode.clear();
-                }
-                Arc::new(OSInode::new(
-                    readable,
-                    writable,
-                    inode
-                ))
-            })
+        let inode = parent.find_path(name).ok_or(FsError::NotFound)?;
+        if !perm_allows(&inode, mode) {
+            return Err(FsError::Access);
+        }
+        check_open_type(&inode, flags, writable)?;
+        if flags.contains(OpenFlags::TRUNC) {
+            // 追加专用的文件不能被清空
+            if inode.append_only() {
+                return Err(FsError::Access);
+            }
+            if !inode.clear() {
+                return Err(FsError::Busy);
+            }
+        }
+        Ok(Arc::new(OSInode::new(
+            readable,
+            writable,
+            append,
+            inode,
+        )))
+    }
+}
+
+/// Open a file by path from the root directory
+pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    open_file_at(&ROOT_INODE, name, flags).ok()
+}
+
+/// Create a directory by path, relative paths are resolved from `base`
+pub fn mkdir_at(base: &Arc<Inode>, path: &str) -> isize {
+    let (parent_path, name) = split_path(path);
+    if name.is_empty() {
+        return -1;
+    }
+    match find_path_at(base, parent_path) {
+        Some(parent) if parent.is_dir() => {
+            if parent.create_dir(name).is_some() { 0 } else { -1 }
+        }
+        _ => -1,
+    }
+}
+
+/// Create a symbolic link at `linkpath` pointing to `target`, relative paths are resolved from `base`
+pub fn symlink_at(base: &Arc<Inode>, target: &str, linkpath: &str) -> isize {
+    let (parent_path, name) = split_path(linkpath);
+    if name.is_empty() || target.is_empty() {
+        return -1;
+    }
+    match find_path_at(base, parent_path) {
+        Some(parent) if parent.is_dir() => {
+            if parent.create_symlink(name, target).is_some() { 0 } else { -1 }
+        }
+        _ => -1,
+    }
+}
+
+/// Read the target of the symbolic link at `path` without following it, relative paths are resolved from `base`
+/// 只跟随路径中间的链接，最后一个分量不是符号链接时返回None
+pub fn readlink_at(base: &Arc<Inode>, path: &str) -> Option<String> {
+    let (parent, name) = find_parent_at(base, path).ok()?;
+    if name.is_empty() {
+        return None;
+    }
+    parent.find(name)?.readlink()
+}
+
+/// Check whether `path` exists and allows `mode`, relative paths are resolved from `base`
+/// 目前还没有权限位：存在的路径都可读可写，只有目录可以执行（即进入）
+pub fn access_at(base: &Arc<Inode>, path: &str, mode: AccessMode) -> isize {
+    match find_path_at(base, path) {
+        Some(inode) if perm_allows(&inode, mode) => 0,
+        _ => -1,
+    }
+}
+
+/// Whether the owner permission bits of `inode` allow every access in `mode`
+/// 系统中只有一个用户，所以只检查属主的rwx位
+fn perm_allows(inode: &Inode, mode: AccessMode) -> bool {
+    let owner = AccessMode::from_bits_truncate((inode.perm() >> 6) as u32);
+    owner.contains(mode)
+}
+
+/// Set the rwxrwxrwx permission bits of the inode at `path`, relative paths are resolved from `base`
+pub fn chmod_at(base: &Arc<Inode>, path: &str, perm: u32) -> isize {
+    match find_path_at(base, path) {
+        Some(inode) => {
+            inode.set_perm(perm as u16);
+            0
+        }
+        None => FsError::NotFound.into(),
+    }
+}
+
+/// Make the file at `path` append-only or lift the restriction, relative paths are resolved from `base`
+pub fn chattr_at(base: &Arc<Inode>, path: &str, append_only: bool) -> isize {
+    match find_path_at(base, path) {
+        Some(inode) => {
+            if inode.set_append_only(append_only) { 0 } else { FsError::Invalid.into() }
+        }
+        None => FsError::NotFound.into(),
+    }
+}
+
+/// Limit the blocks the subtree under the directory at `path` may take, relative paths are resolved from `base`
+/// blocks为负数时取消配额
+pub fn setquota_at(base: &Arc<Inode>, path: &str, blocks: isize) -> isize {
+    let quota = match blocks {
+        blocks if blocks < 0 => None,
+        blocks if blocks < u32::MAX as isize => Some(blocks as u32),
+        _ => return FsError::Invalid.into(),
+    };
+    match find_path_at(base, path) {
+        Some(inode) => {
+            if inode.set_block_quota(quota) { 0 } else { FsError::NotDir.into() }
+        }
+        None => FsError::NotFound.into(),
+    }
+}
+
+/// Resize the file at `path` to `len` bytes, relative paths are resolved from `base`
+pub fn truncate_at(base: &Arc<Inode>, path: &str, len: usize) -> isize {
+    match find_path_at(base, path) {
+        Some(inode) if !inode.is_dir() && perm_allows(&inode, AccessMode::W_OK) => {
+            if inode.truncate(len) {
+                0
+            } else if inode.is_mounted_image() {
+                FsError::Busy.into()
+            } else {
+                -1
+            }
+        }
+        _ => -1,
     }
 }
 
-/// 创建一个文件的一个硬链接
-pub fn linkat(oldpath: &str, newpath: &str) -> isize{
-    ROOT_INODE.linkat(oldpath, newpath)
+/// Rename `old_path` to `new_path`, relative paths are resolved from `base`
+/// 两个父目录都必须存在；目录不能被移动到它自己或它的子目录之下，交换时两个方向都要检查
+pub fn rename_at(base: &Arc<Inode>, old_path: &str, new_path: &str, mode: RenameMode) -> isize {
+    let (old_parent_path, old_name) = split_path(old_path);
+    let (new_parent_path, new_name) = split_path(new_path);
+    if old_name.is_empty() || new_name.is_empty() {
+        return -1;
+    }
+    let (old_parent, new_parent) = match (
+        find_path_at(base, old_parent_path),
+        find_path_at(base, new_parent_path),
+    ) {
+        (Some(old_parent), Some(new_parent)) if old_parent.is_dir() && new_parent.is_dir() => {
+            (old_parent, new_parent)
+        }
+        _ => return -1,
+    };
+    if !old_parent.same_fs(&new_parent) {
+        return FsError::CrossDevice.into();
+    }
+    let src = match old_parent.find(old_name) {
+        Some(src) => src,
+        None => return -1,
+    };
+    if moves_under_itself(&src, &new_parent) {
+        return -1;
+    }
+    if mode == RenameMode::Exchange {
+        if let Some(dst) = new_parent.find(new_name) {
+            if moves_under_itself(&dst, &old_parent) {
+                return -1;
+            }
+        }
+    }
+    match old_parent.rename2(old_name, &new_parent, new_name, mode) {
+        Ok(()) => 0,
+        Err(RenameError::Exists) => FsError::Exists.into(),
+        Err(RenameError::Busy) => FsError::Busy.into(),
+        Err(RenameError::NotDir) => FsError::NotDir.into(),
+        Err(_) => -1,
+    }
+}
+
+/// Whether moving `inode` into `new_parent` would put a directory under itself
+fn moves_under_itself(inode: &Inode, new_parent: &Inode) -> bool {
+    if !inode.is_dir() {
+        return false;
+    }
+    match (inode.abs_path(), new_parent.abs_path()) {
+        (Some(path), Some(new_parent_path)) => {
+            new_parent_path == path || new_parent_path.starts_with(&(path + "/"))
+        }
+        _ => false,
+    }
+}
+
+/// Get (ino, nlink, mode) of an inode
+pub fn stat_inode(inode: &Inode) -> (usize, usize, StatMode) {
+    let (ino, mode) = inode.get_diskinodetype();
+    let nlink = inode.nlink();
+
+    let mode = match mode {
+        false => StatMode::FILE,
+        true => StatMode::DIR,
+    };
+    (ino, nlink, mode | StatMode::from_bits_truncate(inode.perm() as u32))
+}
+
+/// 将块缓存中所有被修改过的块写回磁盘，文件写入本身不会立即落盘
+pub fn sync_all() {
+    block_cache_sync_all();
+}
+
+/// Mount the easy-fs in the image file `image` at the directory `path`, relative paths are resolved from `base`
+/// 镜像文件被当作块设备，挂载期间截断、清空、打洞、删除它或用rename覆盖它都返回-EBUSY；镜像中的修改和其他文件一样在sync时写回
+pub fn mount_at(base: &Arc<Inode>, image: Arc<Inode>, path: &str) -> isize {
+    let mountpoint = match find_path_at(base, path) {
+        Some(inode) => inode,
+        None => return FsError::NotFound.into(),
+    };
+    if image.is_dir() {
+        return FsError::Invalid.into();
+    }
+    let device: Arc<dyn BlockDevice> = Arc::new(ImageDevice::new(image));
+    let ret = match EasyFileSystem::open(Arc::clone(&device)) {
+        Ok(efs) => match mountpoint.mount(Arc::new(EasyFileSystem::root_inode(&efs))) {
+            Ok(()) => return 0,
+            Err(MountError::NotDir) => FsError::NotDir.into(),
+            Err(_) => FsError::Busy.into(),
+        },
+        Err(_) => FsError::Invalid.into(),
+    };
+    // 没有挂载成功，丢弃已经读入缓存的块，镜像文件才会被放开
+    block_cache_drop_device(&device);
+    ret
+}
+
+/// Unmount the filesystem mounted at `path`, relative paths are resolved from `base`
+/// 卸载之后立即写回，镜像文件中就有了挂载期间的所有修改
+pub fn umount_at(base: &Arc<Inode>, path: &str) -> isize {
+    let root = match find_path_at(base, path) {
+        Some(inode) => inode,
+        None => return FsError::NotFound.into(),
+    };
+    match root.umount() {
+        Ok(()) => {
+            block_cache_sync_all();
+            0
+        }
+        Err(MountError::Busy) => FsError::Busy.into(),
+        Err(_) => FsError::Invalid.into(),
+    }
+}
+
+/// Write back a few of the oldest dirty blocks, called on every timer tick
+/// 把写回分散到每个时钟中断中，sys_sync时需要写回的块就少了
+pub fn writeback_tick() {
+    block_cache_sync_dirty_limit(WRITEBACK_BLOCKS_PER_TICK);
+}
+
+/// Find the parent directory of `path` and the last name in it, relative paths are resolved from `base`
+fn find_parent_at<'a>(base: &Arc<Inode>, path: &'a str) -> Result<(Arc<Inode>, &'a str), FsError> {
+    let (parent_path, name) = split_path(path);
+    match find_path_at(base, parent_path) {
+        Some(parent) if parent.is_dir() => Ok((parent, name)),
+        Some(_) => Err(FsError::NotDir),
+        None => Err(FsError::NotFound),
+    }
+}
+
+/// 创建一个文件的一个硬链接，old_path和new_path分别相对于old_base和new_base解析
+/// old_path不存在返回ENOENT，new_path已存在（包括与old_path相同）返回EEXIST，不能为目录创建硬链接
+pub fn link_at(old_base: &Arc<Inode>, old_path: &str, new_base: &Arc<Inode>, new_path: &str) -> isize {
+    let src = match find_path_at(old_base, old_path) {
+        Some(src) => src,
+        None => return FsError::NotFound.into(),
+    };
+    let (parent, name) = match find_parent_at(new_base, new_path) {
+        Ok(pair) => pair,
+        Err(err) => return err.into(),
+    };
+    if name.is_empty() || src.is_dir() {
+        return FsError::Failed.into();
+    }
+    if !src.same_fs(&parent) {
+        return FsError::CrossDevice.into();
+    }
+    if parent.find(name).is_some() {
+        return FsError::Exists.into();
+    }
+    match parent.link(name, &src) {
+        0 => 0,
+        _ => FsError::NoSpace.into(),
+    }
 }
 
-/// 取消一个文件路径到文件的链接
-pub fn unlinkat(name: &str) -> isize {
-    ROOT_INODE.unlinkat(name)
+/// 取消一个文件路径到文件的链接，相对路径从base开始解析
+pub fn unlink_at(base: &Arc<Inode>, path: &str) -> isize {
+    let (parent, name) = match find_parent_at(base, path) {
+        Ok(pair) => pair,
+        Err(err) => return err.into(),
+    };
+    match parent.unlinkat(name) {
+        0 => 0,
+        _ => match parent.find(name) {
+            None => FsError::NotFound.into(),
+            Some(inode) if inode.is_mounted_image() || inode.is_mountpoint() => FsError::Busy.into(),
+            Some(inode) if inode.is_dir() => FsError::NotEmpty.into(),
+            Some(_) => FsError::Failed.into(),
+        },
+    }
 }
 
 
 
 
+impl Drop for OSInode {
+    /// 关闭文件（最后一个引用被释放）时写回缓冲的数据，可写的文件还要把它的块写回磁盘；
+    /// dup/fork出的fd共享同一个Arc<OSInode>，所以只有最后一个fd被关闭时才会执行一次。
+    /// 打开期间被删除的文件在最后一个打开它的OSInode关闭时才被释放；通过它加的flock锁也在此释放
+    fn drop(&mut self) {
+        unlock_all(self as *const Self as usize);
+        let mut inner = self.inner.exclusive_access();
+        inner.flush();
+        if self.writable {
+            inner.inode.fsync();
+        }
+        inner.inode.release_open();
+    }
+}
+
+/// 关闭最后一个引用时文件的数据块和inode所在块都被写回磁盘，关闭其他引用时不写回
+#[allow(unused)]
+pub fn flush_on_close_test() {
+    let name = "flush_on_close_test";
+    let file = open_file(name, OpenFlags::CREATE | OpenFlags::WRONLY).unwrap();
+    let inode = file.inode().unwrap();
+    // 分配新数据块的写入会立即写回，先分配好数据块再覆盖写入
+    assert_eq!(inode.write_at(0, &[0u8; 2 * BLOCK_SZ]), 2 * BLOCK_SZ);
+    assert_eq!(inode.write_at(0, &[0x5au8; 2 * BLOCK_SZ]), 2 * BLOCK_SZ);
+    let (_, dirty_open) = block_cache_sync_dirty_limit(0);
+    let dup = file.clone();
+    drop(file);
+    assert_eq!(block_cache_sync_dirty_limit(0).1, dirty_open);
+    drop(dup);
+    // 两个数据块和inode所在的块
+    let (_, dirty_closed) = block_cache_sync_dirty_limit(0);
+    assert!(dirty_open - dirty_closed >= 3);
+    drop(inode);
+    assert_eq!(unlink_at(&ROOT_INODE, name), 0);
+    info!("flush_on_close_test passed!");
+}
+
+/// 逐字节顺序写入时，缓冲让每个数据块最多只调用两次Inode::write_at（块内第一个字节分配数据块，越过块边界时flush）
+#[allow(unused)]
+pub fn write_coalescing_test() {
+    static mut BYTE: [u8; 1] = [0];
+    let name = "write_coalescing_test";
+    let file = open_file(name, OpenFlags::CREATE | OpenFlags::RDWR).unwrap();
+    for i in 0..4096 {
+        let buf = unsafe {
+            BYTE[0] = (i % 251) as u8;
+            UserBuffer::new(vec![&mut BYTE[..]])
+        };
+        assert_eq!(file.write(buf), 1);
+    }
+    assert!(file.write_at_calls() <= 2 * 4096 / BLOCK_SZ);
+    // seek之前先flush，读回的数据完整
+    assert_eq!(file.seek(0, SEEK_SET), 0);
+    let data = file.read_all();
+    assert_eq!(data.len(), 4096);
+    assert!(data.iter().enumerate().all(|(i, byte)| *byte == (i % 251) as u8));
+    drop(file);
+    assert_eq!(unlink_at(&ROOT_INODE, name), 0);
+    info!("write_coalescing_test passed!");
+}
+
 impl File for OSInode {
     fn readable(&self) -> bool { self.readable }
     fn writable(&self) -> bool { self.writable }
     fn read(&self, mut buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
+        inner.flush();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
             let read_size = inner.inode.read_at(inner.offset, *slice);
@@ -167,31 +715,109 @@ impl File for OSInode {
             inner.offset += read_size;
             total_read_size += read_size;
         }
+        // 顺序读时下一次多半从这里继续
+        if total_read_size > 0 && inner.read_ahead > 0 {
+            inner.inode.prefetch(inner.offset, inner.read_ahead * BLOCK_SZ);
+        }
         total_read_size
     }
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
+        // 之前缓冲的写入没能写回，这次写入失败
+        if core::mem::take(&mut inner.write_error) {
+            return 0;
+        }
+        // 追加专用的文件只能从文件末尾开始写，不能覆盖已有的内容
+        if !self.append && inner.inode.append_only() {
+            inner.flush();
+            if inner.offset < inner.inode.size() {
+                return 0;
+            }
+        }
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
-            assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
+            let write_size = if self.append {
+                // 追加模式下忽略当前offset，总是写到文件末尾
+                let old_size = inner.inode.size();
+                inner.offset = inner.inode.append(*slice);
+                inner.offset - old_size
+            } else {
+                inner.write_buffered(*slice)
+            };
             total_write_size += write_size;
+            // 磁盘已满，只写入了一部分
+            if write_size < slice.len() {
+                break;
+            }
         }
         total_write_size
     }
 
     fn get_stat(&self) -> (usize, usize, StatMode){
         let mut inner = self.inner.exclusive_access();
-        // inner.inode
- 
-        let (ino, mode) = inner.inode.get_diskinodetype();
-        let nlink = ROOT_INODE.get_nlink(ino);
-
-        let mode = match mode {
-            false => StatMode::FILE,
-            true => StatMode::DIR,
+        inner.flush();
+        stat_inode(&inner.inode)
+    }
+
+    fn get_usage(&self) -> (usize, usize) {
+        let mut inner = self.inner.exclusive_access();
+        inner.flush();
+        (inner.inode.size(), inner.inode.allocated_blocks())
+    }
+
+    fn seek(&self, offset: isize, whence: usize) -> isize {
+        let mut inner = self.inner.exclusive_access();
+        inner.flush();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.size() as isize,
+            _ => return -1,
         };
-        (ino, nlink, mode)
+        // 允许越过文件末尾，之后的写入会留下一段空洞
+        let new_offset = base + offset;
+        if new_offset < 0 {
+            return -1;
+        }
+        inner.offset = new_offset as usize;
+        new_offset
+    }
+
+    fn inode(&self) -> Option<Arc<Inode>> {
+        let mut inner = self.inner.exclusive_access();
+        inner.flush();
+        Some(inner.inode.clone())
+    }
+
+    fn take_write_error(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.flush();
+        core::mem::take(&mut inner.write_error)
+    }
+
+    fn set_read_ahead(&self, blocks: usize) -> bool {
+        self.inner.exclusive_access().read_ahead = blocks;
+        true
+    }
+
+    fn read_dir(&self) -> Option<Vec<DirEntryInfo>> {
+        let inner = self.inner.exclusive_access();
+        let (_, is_dir) = inner.inode.get_diskinodetype();
+        if is_dir {
+            let mut entries = inner.inode.read_dir();
+            entries.retain(|entry| entry.slot >= inner.dir_pos);
+            Some(entries)
+        } else {
+            None
+        }
+    }
+
+    fn seek_dir(&self, pos: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        if !inner.inode.is_dir() {
+            return false;
+        }
+        inner.dir_pos = pos;
+        true
     }
 }
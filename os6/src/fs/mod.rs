@@ -1,17 +1,138 @@
 mod stdio;
 mod inode;
+mod pipe;
+mod flock;
 
 use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use easy_fs::{DirEntryInfo, Inode};
 
 /// The common abstraction of all IO resources
 pub trait File : Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
+    /// Whether a read would make progress right now without blocking, used by poll
+    fn read_ready(&self) -> bool {
+        true
+    }
+    /// Whether a write would make progress right now without blocking, used by poll
+    fn write_ready(&self) -> bool {
+        true
+    }
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
     fn get_stat(&self) -> (usize, usize, StatMode);
+    /// Logical size in bytes and allocated data blocks, only disk files occupy any
+    fn get_usage(&self) -> (usize, usize) {
+        (0, 0)
+    }
+    /// List the entries from the directory stream position on if this file is a directory
+    fn read_dir(&self) -> Option<Vec<DirEntryInfo>> {
+        None
+    }
+    /// Move the directory stream position to dirent slot `pos`, return false if this file is not a directory
+    fn seek_dir(&self, _pos: usize) -> bool {
+        false
+    }
+    /// Reposition the offset, return the new offset or -1
+    fn seek(&self, _offset: isize, _whence: usize) -> isize {
+        -1
+    }
+    /// The underlying easy-fs inode, if this file is backed by one
+    fn inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
+    /// Whether an earlier buffered write could not be completed, the error is cleared once taken
+    fn take_write_error(&self) -> bool {
+        false
+    }
+    /// Set how many blocks to read ahead after each read, return false if this file does not read ahead
+    fn set_read_ahead(&self, _blocks: usize) -> bool {
+        false
+    }
+}
+
+/// Why a filesystem syscall failed, the value of each variant is the (negative) errno returned to user space
+#[repr(isize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    /// generic failure without a more specific code
+    Failed = -1,
+    /// ENOENT: no such file or directory
+    NotFound = -2,
+    /// EBADF: bad file descriptor
+    BadFd = -9,
+    /// EWOULDBLOCK: a non-blocking flock found the lock held by others
+    WouldBlock = -11,
+    /// EACCES: the permission bits forbid the access
+    Access = -13,
+    /// EFAULT: a buffer or path passed in points outside the mapped user memory
+    Fault = -14,
+    /// EBUSY: the mount point or the mounted filesystem is in use, or the file is the image of a mounted filesystem
+    Busy = -16,
+    /// EEXIST: file exists
+    Exists = -17,
+    /// EXDEV: links and renames cannot cross filesystems
+    CrossDevice = -18,
+    /// ENOTDIR: a component of the path is not a directory
+    NotDir = -20,
+    /// EISDIR: a directory cannot be opened for writing
+    IsDir = -21,
+    /// EINVAL: e.g. the image is not an easy-fs, or the directory is not a mount point
+    Invalid = -22,
+    /// EMFILE: the process already has MAX_FD open file descriptors
+    TooManyFiles = -24,
+    /// ENOSPC: no space left on device
+    NoSpace = -28,
+    /// ENOTEMPTY: a directory to be removed still has entries
+    NotEmpty = -39,
 }
 
+impl From<FsError> for isize {
+    fn from(err: FsError) -> isize {
+        err as isize
+    }
+}
+
+/// Resolve `*at` paths from the current working directory instead of a directory fd
+pub const AT_FDCWD: usize = -100isize as usize;
+
+/// Seek from the beginning of the file
+pub const SEEK_SET: usize = 0;
+/// Seek from the current offset
+pub const SEEK_CUR: usize = 1;
+/// Seek from the end of the file
+pub const SEEK_END: usize = 2;
+
+/// fallocate: keep the file size unchanged
+pub const FALLOC_FL_KEEP_SIZE: usize = 0x1;
+/// fallocate: deallocate the range, must be used together with FALLOC_FL_KEEP_SIZE
+pub const FALLOC_FL_PUNCH_HOLE: usize = 0x2;
+
+/// fadvise: no particular access pattern, use the default read-ahead window
+pub const POSIX_FADV_NORMAL: usize = 0;
+/// fadvise: random access, do not read ahead
+pub const POSIX_FADV_RANDOM: usize = 1;
+/// fadvise: sequential access, read ahead aggressively
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+/// fadvise: the range will be accessed soon, load it into the block cache now
+pub const POSIX_FADV_WILLNEED: usize = 3;
+
+/// flock: take a shared lock
+pub const LOCK_SH: usize = 1;
+/// flock: take an exclusive lock
+pub const LOCK_EX: usize = 2;
+/// flock: together with LOCK_SH or LOCK_EX, fail with EWOULDBLOCK instead of waiting
+pub const LOCK_NB: usize = 4;
+/// flock: release the lock
+pub const LOCK_UN: usize = 8;
+
+/// renameat2: fail with EEXIST instead of overwriting an existing destination
+pub const RENAME_NOREPLACE: u32 = 0x1;
+/// renameat2: atomically swap the source and the destination, both must exist
+pub const RENAME_EXCHANGE: u32 = 0x2;
+
 /// The stat of a inode
 #[repr(C)]
 #[derive(Debug)]
@@ -24,8 +145,73 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// logical size in bytes
+    pub size: u64,
+    /// number of data blocks actually allocated, less than size / BLOCK_SZ for sparse files
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 5],
+}
+
+/// Everything known about an inode, filled by getattr in one call
+/// easy-fs不记录时间戳，atime/mtime/ctime总是0
+#[repr(C)]
+#[derive(Debug)]
+pub struct XAttr {
+    /// inode number
+    pub ino: u64,
+    /// file type and mode
+    pub mode: StatMode,
+    /// number of hard links
+    pub nlink: u32,
+    /// logical size in bytes
+    pub size: u64,
+    /// number of data blocks actually allocated
+    pub blocks: u64,
+    /// time of last access
+    pub atime: u64,
+    /// time of last modification
+    pub mtime: u64,
+    /// time of last status change
+    pub ctime: u64,
+    /// rwxrwxrwx permission bits
+    pub perm: u32,
+    /// unused pad
+    pad: u32,
+}
+
+/// A segment of a user buffer used by readv/writev
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IoVec {
+    /// start address of the segment
+    pub base: *const u8,
+    /// length of the segment in bytes
+    pub len: usize,
+}
+
+/// An entry of the fd array passed to poll
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PollFd {
+    /// the file descriptor to check
+    pub fd: i32,
+    /// the events the caller is interested in
+    pub events: PollEvents,
+    /// the events that are ready, filled in by the kernel
+    pub revents: PollEvents,
+}
+
+bitflags! {
+    /// The events of a PollFd
+    pub struct PollEvents: i16 {
+        /// there is data to read, or all the write ends are closed
+        const POLLIN   = 1;
+        /// writing now will not block
+        const POLLOUT  = 4;
+        /// the fd is not open
+        const POLLNVAL = 0x20;
+    }
 }
 
 bitflags! {
@@ -37,8 +223,16 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// mask of the rwxrwxrwx permission bits
+        const PERM  = 0o777;
     }
 }    
 
 pub use stdio::{Stdin, Stdout};
-pub use inode::{OSInode, open_file, OpenFlags, list_apps, linkat, unlinkat};
+pub use pipe::{Pipe, make_pipe};
+pub use flock::{try_lock, unlock};
+pub use inode::{
+    OSInode, open_file, open_file_at, OpenFlags, list_apps, link_at, unlink_at, find_path_at,
+    stat_inode, mkdir_at, symlink_at, readlink_at, sync_all, access_at, AccessMode, chmod_at,
+    setquota_at, chattr_at, truncate_at, rename_at, mount_at, umount_at, writeback_tick, write_coalescing_test, flush_on_close_test, ROOT_INODE,
+};
@@ -33,6 +33,7 @@ mod config;
 mod lang_items;
 mod logging;
 mod mm;
+mod random;
 mod sbi;
 mod sync;
 mod syscall;
@@ -58,16 +59,24 @@ fn clear_bss() {
 
 #[no_mangle]
 /// the rust entry-point of os
+/// 只有启动核（hart 0）会进入这里，其他核一直停在SBI中没有被唤醒，内核按单核运行
 pub fn rust_main() -> ! {
     clear_bss();
     logging::init();
     println!("[kernel] Hello, world!");
     mm::init();
     mm::remap_test();
+    mm::lazy_mmap_test();
     trap::init();
     trap::enable_timer_interrupt();
     timer::set_next_trigger();
+    random::init();
     fs::list_apps();
+    fs::write_coalescing_test();
+    fs::flush_on_close_test();
+    task::processor_test();
+    task::syscall_times_test();
+    task::stride_wrap_test();
     task::add_initproc();
     task::run_tasks();
     panic!("Unreachable in rust_main!");
@@ -1,14 +1,16 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 
-use super::{frame_alloc, get_num_empty_frame, vpn_range_is_unused, vpn_range_is_used, FrameTracker};
+use super::{frame_alloc, get_num_empty_frame, vpn_range_is_unused, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE, MMIO};
+use crate::config::{MEMORY_END, MMAP_BASE, MMAP_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE, MMIO};
 use crate::sync::UPSafeCell;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ops::Deref;
+use easy_fs::Inode;
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -91,16 +93,28 @@ impl MemorySet {
         );
     }
 
-    pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> isize {
+    /// start为0时由内核挑选一段空闲的区域，成功时返回选中的起始地址；指定了start时成功返回0，失败都返回-1
+    pub fn mmap(&mut self, start: usize, len: usize, port: usize, backing: MapBacking) -> isize {
         let len_n = (len - 1 + PAGE_SIZE) / PAGE_SIZE;
+        let chosen = start == 0;
+        let start = if chosen {
+            match self.find_free_area(len_n) {
+                Some(vpn) => VirtAddr::from(vpn).0,
+                None => return -1,
+            }
+        } else {
+            start
+        };
         let start_n = start / PAGE_SIZE;
-        let pt = &mut self.page_table;
+        let pt = &self.page_table;
+        // mmap的区域只记录下来，每一页在首次访问触发缺页时才分配物理页，所以不需要预先保证有足够的空闲物理页
 
+        // 与已有映射重叠的区域直接拒绝，否则会覆盖页表项
         if VirtAddr(start).page_offset() != 0
             || (port & !0x7) != 0
             || port & 0x7 == 0
-            || get_num_empty_frame() < len_n
             || !vpn_range_is_unused(pt, start_n, len_n)
+            || (start_n..start_n + len_n).any(|vpn| self.lazy_area_contains(VirtPageNum(vpn)))
         {
             -1
         } else {
@@ -118,29 +132,171 @@ impl MemorySet {
                     map_perm |= MapPermission::X;
                 }
 
-                self.insert_framed_area(
-                    VirtAddr::from(VirtPageNum::from(start_n)),
-                    VirtAddr::from(VirtPageNum::from(len_n + start_n)),
-                    map_perm,
+                self.push(
+                    MapArea::new(
+                        VirtAddr::from(VirtPageNum::from(start_n)),
+                        VirtAddr::from(VirtPageNum::from(len_n + start_n)),
+                        MapType::Framed,
+                        map_perm,
+                    )
+                    .with_backing(backing)
+                    .lazily(),
+                    None,
                 );
-                0
+                if chosen {
+                    start as isize
+                } else {
+                    0
+                }
             }
         }
     }
 
+    /// Find the lowest `len_n` free pages in [MMAP_BASE, MMAP_END)
+    /// 与逻辑段重叠时直接跳到该段末尾，否则只有页表中零散的映射才逐页前进
+    fn find_free_area(&self, len_n: usize) -> Option<VirtPageNum> {
+        let mut start_n = MMAP_BASE / PAGE_SIZE;
+        let end_n = MMAP_END / PAGE_SIZE;
+        while start_n + len_n <= end_n {
+            let overlap = self.areas.iter().find(|area| {
+                area.vpn_range.get_start().0 < start_n + len_n && start_n < area.vpn_range.get_end().0
+            });
+            match overlap {
+                Some(area) => start_n = area.vpn_range.get_end().0,
+                None if vpn_range_is_unused(&self.page_table, start_n, len_n) => {
+                    return Some(VirtPageNum(start_n))
+                }
+                None => start_n += 1,
+            }
+        }
+        None
+    }
+
+    /// 只有[start, start + len)中的每一页都已被映射时才解除映射，否则返回-1且不做任何修改
     pub fn munmap(&mut self, start: usize, len: usize) -> isize {
-        let pt = &mut self.page_table;
-        if start % PAGE_SIZE != 0 || !vpn_range_is_used(pt, start, len) || len % PAGE_SIZE != 0 {
-            -1
-        } else {
-            let start_va = start / PAGE_SIZE;
-            let end_va = (len + start) / PAGE_SIZE;
+        if start % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 || len == 0 {
+            return -1;
+        }
+        let start_n = start / PAGE_SIZE;
+        let len_n = len / PAGE_SIZE;
+        if !(start_n..start_n + len_n).all(|vpn| self.vpn_is_mapped(VirtPageNum(vpn))) {
+            return -1;
+        }
+        self.remove_range(VirtPageNum(start_n), VirtPageNum(start_n + len_n));
+        0
+    }
+
+    /// 内核即将直接写入[start, start + len)处的用户内存（如read的缓冲区），内核按物理地址写入时不会触发缺页，
+    /// 因此需要先对其中被共享的只读页做写时复制
+    pub fn prepare_user_write(&mut self, start: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            // 还没有分配物理页的mmap页也要先分配，否则内核找不到要写入的物理页
+            let needs_fault = match self.translate(vpn).filter(|pte| pte.is_valid()) {
+                Some(pte) => !pte.writable(),
+                None => self.lazy_area_contains(vpn),
+            };
+            if needs_fault {
+                self.handle_page_fault(vpn.into());
+            }
+        }
+    }
+
+    /// 内核即将直接读取[start, start + len)处的用户内存（如write的缓冲区），其中还没有分配物理页的mmap页
+    /// 没有有效的页表项，要先分配（文件映射还要从文件读入），否则内核找不到要读取的物理页
+    pub fn prepare_user_read(&mut self, start: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        for vpn in VPNRange::new(start_vpn, end_vpn) {
+            if !self.translate(vpn).map_or(false, |pte| pte.is_valid()) && self.lazy_area_contains(vpn) {
+                self.handle_page_fault(vpn.into());
+            }
+        }
+    }
 
-            self.remove_area_with_start_vpn(VirtPageNum(start_va));
-            0
+    /// 内核即将读取从start开始、以'\0'结尾的字符串（如路径），长度事先未知，
+    /// 只能逐页分配其中还没有分配物理页的mmap页，直到在页中找到'\0'或者遇到未映射的页
+    pub fn prepare_user_str(&mut self, start: usize) {
+        let mut va = start;
+        loop {
+            self.prepare_user_read(va, 1);
+            let pte = match self.translate(VirtAddr::from(va).floor()).filter(|pte| pte.is_valid()) {
+                Some(pte) => pte,
+                None => return,
+            };
+            let offset = VirtAddr::from(va).page_offset();
+            if pte.ppn().get_bytes_array()[offset..].contains(&0) {
+                return;
+            }
+            va += PAGE_SIZE - offset;
         }
     }
 
+    /// vpn已被映射：页表项有效，或者落在mmap区域中尚未分配物理页的部分
+    fn vpn_is_mapped(&self, vpn: VirtPageNum) -> bool {
+        self.translate(vpn).map_or(false, |pte| pte.is_valid()) || self.lazy_area_contains(vpn)
+    }
+
+    fn lazy_area_contains(&self, vpn: VirtPageNum) -> bool {
+        self.areas.iter().any(|area| area.is_lazy() && area.contains(vpn))
+    }
+
+    /// 处理缺页：如果va落在mmap区域中尚未分配物理页的页上就分配一页（文件映射还要从文件读入），物理页不足时失败，
+    /// 如果写的是fork后被设为只读的共享页就进行写时复制，返回是否处理成功
+    pub fn handle_page_fault(&mut self, va: VirtAddr) -> bool {
+        let vpn = va.floor();
+        if let Some(pte) = self.translate(vpn).filter(|pte| pte.is_valid()) {
+            // 页已存在：只有可写逻辑段中的只读页是写时复制，其余都是真正的权限错误
+            if pte.writable() {
+                return false;
+            }
+            let page_table = &mut self.page_table;
+            return self
+                .areas
+                .iter_mut()
+                .find(|area| area.contains(vpn))
+                .map_or(false, |area| area.copy_on_write(page_table, vpn));
+        }
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.is_lazy() && area.contains(vpn))
+        {
+            Some(area) => area.fault_in(&mut self.page_table, vpn),
+            None => false,
+        }
+    }
+
+    /// 解除[start_vpn, end_vpn)范围内的映射，与之部分相交的逻辑段会被切分，只保留范围外的部分
+    fn remove_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        let mut kept = Vec::new();
+        for mut area in self.areas.drain(..) {
+            let (l, r) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            if r <= start_vpn || l >= end_vpn {
+                kept.push(area);
+                continue;
+            }
+            if l < start_vpn {
+                let rest = area.split_off(start_vpn);
+                kept.push(area);
+                area = rest;
+            }
+            if end_vpn < area.vpn_range.get_end() {
+                let tail = area.split_off(end_vpn);
+                kept.push(tail);
+            }
+            area.unmap(&mut self.page_table);
+        }
+        self.areas = kept;
+    }
+
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
@@ -286,22 +442,34 @@ impl MemorySet {
         )
     }
     /// Copy an identical user_space
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// 用户可访问的页不再立即复制：父子进程共享同一物理页并都被设为只读，谁先写谁在缺页时复制（写时复制）。
+    /// TrapContext由内核直接按物理地址写入，绕过了页表的权限检查，所以仍然要立即复制
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+            let mut new_area = MapArea::from_another(area);
+            if !area.map_perm.contains(MapPermission::U) {
+                memory_set.push(new_area, None);
+                for vpn in area.vpn_range {
+                    let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
+                continue;
+            }
+            // mmap区域中尚未分配的页不在data_frames中，子进程访问时会自己分配（或从文件加载）
+            let pte_flags = PTEFlags::from_bits((area.map_perm - MapPermission::W).bits).unwrap();
+            for (vpn, frame) in area.data_frames.iter() {
+                user_space.page_table.remap(*vpn, frame.ppn, pte_flags);
+                memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                new_area.data_frames.insert(*vpn, frame.clone());
             }
+            memory_set.areas.push(new_area);
         }
         memory_set
     }
@@ -324,9 +492,54 @@ impl MemorySet {
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// fork之后父子进程共享物理页（写时复制），物理页在最后一个引用者释放它时才被回收
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    backing: MapBacking,
+    /// 是否在首次访问时才分配物理页（mmap的区域）
+    lazy: bool,
+}
+
+/// where the contents of a framed map area come from
+#[derive(Clone)]
+pub enum MapBacking {
+    /// zero-filled pages
+    Anonymous,
+    /// private mapping of a file, pages are read from `inode` starting at `offset` on first access,
+    /// and later writes only change the in-memory copy
+    File { inode: MappedInode, offset: usize },
+}
+
+/// An inode held open by a file mapping
+/// 映射存在期间文件即使被删除了最后一个链接也不会被释放；拆分或fork复制出的逻辑段各自持有一次
+pub struct MappedInode(Arc<Inode>);
+
+impl MappedInode {
+    pub fn new(inode: Arc<Inode>) -> Self {
+        inode.hold_open();
+        Self(inode)
+    }
+}
+
+impl Clone for MappedInode {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Deref for MappedInode {
+    type Target = Inode;
+    fn deref(&self) -> &Inode {
+        &self.0
+    }
+}
+
+impl Drop for MappedInode {
+    /// 逻辑段被munmap或随地址空间回收时释放
+    fn drop(&mut self) {
+        self.0.release_open();
+    }
 }
 
 impl MapArea {
@@ -343,15 +556,70 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            backing: MapBacking::Anonymous,
+            lazy: false,
         }
     }
+    pub fn with_backing(mut self, backing: MapBacking) -> Self {
+        self.backing = backing;
+        self
+    }
+    /// 不预先分配物理页，每一页在首次访问触发缺页时才分配
+    pub fn lazily(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+    pub fn is_lazy(&self) -> bool {
+        self.lazy
+    }
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
     pub fn from_another(another: &MapArea) -> Self {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            backing: another.backing.clone(),
+            lazy: another.lazy,
+        }
+    }
+    /// 在at处把逻辑段一分为二，self保留[start, at)，返回[at, end)，已分配的物理页随之转移
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        self.vpn_range = VPNRange::new(start, at);
+        let backing = match &self.backing {
+            MapBacking::Anonymous => MapBacking::Anonymous,
+            MapBacking::File { inode, offset } => MapBacking::File {
+                inode: inode.clone(),
+                offset: offset + (at.0 - start.0) * PAGE_SIZE,
+            },
+        };
+        Self {
+            vpn_range: VPNRange::new(at, end),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            backing,
+            lazy: self.lazy,
+        }
+    }
+    /// 为首次访问的页分配物理页，文件映射还要从文件读入对应的内容，没有空闲物理页时返回false
+    pub fn fault_in(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map(vpn, frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, Arc::new(frame));
+        if let MapBacking::File { inode, offset } = &self.backing {
+            let pos = offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            // 超过文件末尾的部分保持为0
+            inode.read_at(pos, self.data_frames[&vpn].ppn.get_bytes_array());
         }
+        true
     }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
@@ -362,7 +630,7 @@ impl MapArea {
             MapType::Framed => {
                 let frame = frame_alloc().unwrap();
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
@@ -379,13 +647,47 @@ impl MapArea {
         }
         page_table.unmap(vpn);
     }
+    /// 写时复制：vpn上的物理页还被其他地址空间共享时复制一份，然后恢复写权限
+    /// 只有逻辑段本身可写、而页表项被fork设为只读时才需要处理，返回是否处理成功
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let frame = match self.data_frames.get(&vpn) {
+            Some(frame) => frame.clone(),
+            None => return false,
+        };
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        // 除了刚刚clone的这一份之外只剩自己持有，说明其他地址空间已经放弃了这个页，直接恢复写权限即可
+        if Arc::strong_count(&frame) == 2 {
+            page_table.remap(vpn, frame.ppn, pte_flags);
+            return true;
+        }
+        let new_frame = match frame_alloc() {
+            Some(new_frame) => new_frame,
+            None => return false,
+        };
+        new_frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(frame.ppn.get_bytes_array());
+        page_table.remap(vpn, new_frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, Arc::new(new_frame));
+        true
+    }
     pub fn map(&mut self, page_table: &mut PageTable) {
+        if self.is_lazy() {
+            return;
+        }
         for vpn in self.vpn_range {
             self.map_one(page_table, vpn);
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
+            if self.is_lazy() && !self.data_frames.contains_key(&vpn) {
+                continue;
+            }
             self.unmap_one(page_table, vpn);
         }
     }
@@ -453,3 +755,31 @@ pub fn remap_test() {
         .executable());
     info!("remap_test passed!");
 }
+
+/// mmap的区域只在被访问的页上分配物理页，munmap同时处理已分配和未分配的页
+#[allow(unused)]
+pub fn lazy_mmap_test() {
+    const START: usize = 0x1000_0000;
+    let data_frames = |memory_set: &MemorySet| -> usize {
+        memory_set.areas.iter().map(|area| area.data_frames.len()).sum()
+    };
+    let free_frames = get_num_empty_frame();
+    let mut memory_set = MemorySet::new_bare();
+    assert_eq!(memory_set.mmap(START, 100 * PAGE_SIZE, 0x3, MapBacking::Anonymous), 0);
+    assert_eq!(data_frames(&memory_set), 0);
+    for page in [0, 42, 99].iter() {
+        assert!(memory_set.handle_page_fault(VirtAddr::from(START + page * PAGE_SIZE + 8)));
+    }
+    assert_eq!(data_frames(&memory_set), 3);
+    assert!(memory_set.translate(VirtAddr::from(START + 42 * PAGE_SIZE).floor()).unwrap().is_valid());
+    assert!(memory_set.translate(VirtAddr::from(START + PAGE_SIZE).floor()).map_or(true, |pte| !pte.is_valid()));
+    // 区域之外的缺页不处理
+    assert!(!memory_set.handle_page_fault(VirtAddr::from(START + 100 * PAGE_SIZE)));
+    assert_eq!(memory_set.munmap(START, 100 * PAGE_SIZE), 0);
+    assert_eq!(data_frames(&memory_set), 0);
+    assert!(memory_set.translate(VirtAddr::from(START).floor()).map_or(true, |pte| !pte.is_valid()));
+    // 页表本身占用的物理页随地址空间一起释放
+    drop(memory_set);
+    assert_eq!(get_num_empty_frame(), free_frames);
+    info!("lazy_mmap_test passed!");
+}
@@ -16,10 +16,10 @@ mod page_table;
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 pub use address::{StepByOne, VPNRange};
 pub use frame_allocator::{frame_alloc, frame_dealloc, get_num_empty_frame, FrameTracker};
-pub use memory_set::{remap_test, kernel_token};
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
+pub use memory_set::{remap_test, lazy_mmap_test, kernel_token};
+pub use memory_set::{MapBacking, MapPermission, MappedInode, MemorySet, KERNEL_SPACE};
 pub use page_table::{translated_byte_buffer, translated_refmut, translated_ref, translated_str, PageTableEntry, translate_va,
-vpn_range_is_unused, vpn_range_is_used};
+vpn_range_is_unused};
 pub use page_table::{PTEFlags, PageTable, UserBuffer};
 
 /// initiate heap allocator, frame allocator and kernel space
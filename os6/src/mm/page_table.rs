@@ -1,6 +1,5 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
 
-use crate::config::PAGE_SIZE;
 
 
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
@@ -129,6 +128,12 @@ impl PageTable {
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
         *pte = PageTableEntry::empty();
     }
+    /// Point an already mapped vpn to `ppn` with new flags
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).copied()
     }
@@ -148,7 +153,8 @@ impl PageTable {
 }
 
 /// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// 其中某一页没有有效的页表项时返回None，调用者应返回-EFAULT
+pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
     let end = start + len;
@@ -156,7 +162,7 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = page_table.translate(vpn).filter(|pte| pte.is_valid())?.ppn();
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -167,18 +173,19 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Some(v)
 }
 
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
+/// 字符串经过没有有效页表项的页时返回None
+pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
     let page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
+        let pte = page_table
+            .translate(VirtAddr::from(va).floor())
+            .filter(|pte| pte.is_valid())?;
+        let ch = pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()];
         if ch == 0 {
             break;
         } else {
@@ -186,7 +193,7 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
             va += 1;
         }
     }
-    string
+    Some(string)
 }
 
 pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
@@ -267,41 +274,11 @@ impl Iterator for UserBufferIterator {
     }
 }
 
-/// 如果给定的虚拟地址范围中的地址在给定的pagetable中都没被占用（被关联了物理页），返回true
-pub fn vpn_range_is_unused(pt: &mut PageTable, start: usize, len: usize) -> bool {
-    let mut result = true;
-    for id in (start..(start + len)).filter(|x| (x % PAGE_SIZE == 0)) {
-        let va = VirtPageNum::from(id);
-        let tmp = pt.find_pte(va);
-
-        if let Some(pte) = tmp {
-            result = !pte.is_valid();
-        }
-        if result == false {
-            break;
-        }
-    }
-    result
-}
-
-/// 如果给定的虚拟地址范围中的地址在给定的pagetable中都被占用（被关联了物理页），返回true
-pub fn vpn_range_is_used(pt: &PageTable, start: usize, len: usize) -> bool {
-    let mut result = true;
-    for id in (start..(start + len)).filter(|x| (x % PAGE_SIZE == 0)) {
-        let va = VirtPageNum::from(id);
-        let tmp = pt.find_pte(va);
-
-        if let Some(pte) = tmp {
-            result = pte.is_valid();
-        }
-        result = match tmp {
-            Some(pte) => pte.is_valid(),
-            None => false,
-        };
-        if result == false {
-            break;
-        }
-
-    }
-    result
+/// 如果从start开始的len个虚拟页在给定的pagetable中都没被占用（被关联了物理页），返回true
+/// start和len都以页为单位
+pub fn vpn_range_is_unused(pt: &PageTable, start: usize, len: usize) -> bool {
+    (start..(start + len)).all(|vpn| match pt.find_pte(VirtPageNum::from(vpn)) {
+        Some(pte) => !pte.is_valid(),
+        None => true,
+    })
 }
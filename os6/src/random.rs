@@ -0,0 +1,69 @@
+//! A pseudo-random number generator behind sys_getrandom
+//!
+//! 用xorshift64生成随机数，启动时以当前时间为种子，之后每隔一段时钟中断再混入一次当前时间。
+//! 它只是不可预测性很弱的伪随机数，**不能**用于密钥等需要密码学安全的场合
+
+use crate::config::RANDOM_RESEED_TICKS;
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_us;
+use lazy_static::*;
+
+/// xorshift64 state, never zero
+struct XorShift64 {
+    state: u64,
+    /// timer ticks since the last reseed
+    ticks: usize,
+}
+
+impl XorShift64 {
+    fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0, ticks: 0 };
+        rng.mix(seed);
+        rng
+    }
+    /// Mix `seed` into the state
+    /// 乘一个奇数常数把时间的低位变化扩散到整个状态上；状态为0时xorshift只会一直输出0，必须避开
+    fn mix(&mut self, seed: u64) {
+        self.state ^= seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        if self.state == 0 {
+            self.state = 0x2545_F491_4F6C_DD1D;
+        }
+    }
+    fn next(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+}
+
+lazy_static! {
+    static ref RNG: UPSafeCell<XorShift64> =
+        unsafe { UPSafeCell::new(XorShift64::new(get_time_us() as u64)) };
+}
+
+/// Seed the generator from the current time
+pub fn init() {
+    RNG.exclusive_access().mix(get_time_us() as u64);
+}
+
+/// Called on every timer interrupt, mix the current time in every `RANDOM_RESEED_TICKS` ticks
+pub fn reseed_tick() {
+    let mut rng = RNG.exclusive_access();
+    rng.ticks += 1;
+    if rng.ticks >= RANDOM_RESEED_TICKS {
+        rng.ticks = 0;
+        rng.mix(get_time_us() as u64);
+    }
+}
+
+/// Fill `buf` with pseudo-random bytes
+pub fn fill_random(buf: &mut [u8]) {
+    let mut rng = RNG.exclusive_access();
+    for chunk in buf.chunks_mut(8) {
+        let bytes = rng.next().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
@@ -1,5 +1,7 @@
 //! Synchronization and interior mutability primitives
 
 mod up;
+mod spin;
 
 pub use up::UPSafeCell;
+pub use spin::SpinMutex;
@@ -0,0 +1,37 @@
+//! A simple spin lock which can be shared between harts
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use lock_api::{GuardSend, RawMutex};
+
+/// The raw spin lock, busy waiting until the lock is released
+pub struct RawSpinLock {
+    locked: AtomicBool,
+}
+
+unsafe impl RawMutex for RawSpinLock {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        locked: AtomicBool::new(false),
+    };
+
+    type GuardMarker = GuardSend;
+
+    fn lock(&self) {
+        while !self.try_lock() {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// A mutex protected by [`RawSpinLock`], safe to use across harts
+pub type SpinMutex<T> = lock_api::Mutex<RawSpinLock, T>;
@@ -1,15 +1,32 @@
 //! File and filesystem-related syscalls
 
+use crate::config::MAX_FD;
 use crate::mm::translated_byte_buffer;
-use crate::mm::{translated_str, translate_va};
-use crate::mm::translated_refmut;
+use crate::mm::translate_va;
+use crate::mm::{translated_ref, translated_refmut};
 use crate::task::current_user_token;
 use crate::task::current_task;
-use crate::fs::{open_file, linkat, unlinkat};
+use crate::task::{prepare_user_read, prepare_user_write, suspend_current_and_run_next, translated_user_str};
+use crate::task::{FdEntry, FdFlags};
+use crate::fs::{
+    open_file_at, link_at, unlink_at, make_pipe, find_path_at, stat_inode, mkdir_at, symlink_at, readlink_at, sync_all,
+    access_at, AccessMode, truncate_at, rename_at, chmod_at, setquota_at, chattr_at, mount_at, umount_at,
+};
 use crate::fs::OpenFlags;
-use crate::fs::Stat;
+use crate::fs::{FsError, IoVec, PollEvents, PollFd, Stat, XAttr, AT_FDCWD, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE, SEEK_CUR, SEEK_SET};
+use crate::fs::{RENAME_EXCHANGE, RENAME_NOREPLACE};
+use crate::fs::{POSIX_FADV_NORMAL, POSIX_FADV_RANDOM, POSIX_FADV_SEQUENTIAL, POSIX_FADV_WILLNEED};
+use crate::fs::{try_lock, unlock, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
+use crate::config::{READ_AHEAD_BLOCKS, SEQUENTIAL_READ_AHEAD_BLOCKS};
+use crate::timer::get_time_us;
 use crate::mm::UserBuffer;
 use alloc::sync::Arc;
+use easy_fs::{Inode, RenameMode};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// How many bytes sendfile copies per read_at/write_at round
+const SENDFILE_CHUNK: usize = 4096;
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let token = current_user_token();
@@ -18,13 +35,23 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     if fd >= inner.fd_table.len() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.write(
-            UserBuffer::new(translated_byte_buffer(token, buf, len))
-        ) as isize
+        prepare_user_read(buf as usize, len);
+        let buffers = match translated_byte_buffer(token, buf, len) {
+            Some(buffers) => buffers,
+            None => return FsError::Fault.into(),
+        };
+        let written = file.write(UserBuffer::new(buffers));
+        // 磁盘上的文件一个字节都写不进去时，说明试图覆盖追加专用的文件，或者磁盘已满、超出了目录的块配额
+        if written == 0 && len > 0 {
+            if let Some(inode) = file.inode() {
+                return if inode.append_only() { -1 } else { FsError::NoSpace.into() };
+            }
+        }
+        written as isize
     } else {
         -1
     }
@@ -37,32 +64,242 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
     if fd >= inner.fd_table.len() {
         return -1;
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(
-            UserBuffer::new(translated_byte_buffer(token, buf, len))
-        ) as isize
+        prepare_user_write(buf as usize, len);
+        match translated_byte_buffer(token, buf, len) {
+            Some(buffers) => file.read(UserBuffer::new(buffers)) as isize,
+            None => FsError::Fault.into(),
+        }
     } else {
         -1
     }
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+/// 从文件的offset处读取，不使用也不修改fd自身的读写位置，返回实际读取的字节数
+/// fd不存在、不可读或者不是普通文件（如管道）时返回-1
+pub fn sys_pread(fd: usize, buf: *mut u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let inode = match inner.fd_table.get(fd) {
+        Some(Some(FdEntry { file, .. })) if file.readable() => match file.inode() {
+            Some(inode) => inode,
+            None => return -1,
+        },
+        _ => return -1,
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    prepare_user_write(buf as usize, len);
+    let buffers = match translated_byte_buffer(token, buf, len) {
+        Some(buffers) => buffers,
+        None => return FsError::Fault.into(),
+    };
+    let mut total_read_size = 0usize;
+    for slice in buffers {
+        let read_size = inode.read_at(offset + total_read_size, slice);
+        total_read_size += read_size;
+        if read_size < slice.len() {
+            break;
+        }
+    }
+    total_read_size as isize
+}
+
+/// 写入到文件的offset处，不使用也不修改fd自身的读写位置，返回实际写入的字节数
+/// fd不存在、不可写或者不是普通文件（如管道）时返回-1
+pub fn sys_pwrite(fd: usize, buf: *const u8, len: usize, offset: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let inode = match inner.fd_table.get(fd) {
+        Some(Some(FdEntry { file, .. })) if file.writable() => match file.inode() {
+            Some(inode) => inode,
+            None => return -1,
+        },
+        _ => return -1,
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    // 追加专用的文件不能覆盖已有的内容
+    if inode.append_only() && offset < inode.size() {
+        return -1;
+    }
+    prepare_user_read(buf as usize, len);
+    let buffers = match translated_byte_buffer(token, buf, len) {
+        Some(buffers) => buffers,
+        None => return FsError::Fault.into(),
+    };
+    let mut total_write_size = 0usize;
+    for slice in buffers {
+        let write_size = inode.write_at(offset + total_write_size, slice);
+        total_write_size += write_size;
+        // 磁盘已满，只写入了一部分
+        if write_size < slice.len() {
+            break;
+        }
+    }
+    total_write_size as isize
+}
+
+/// 在内核中把in_fd的数据复制到out_fd，最多count字节，返回实际复制的字节数，两个fd都必须是普通文件。
+/// offset非空时从*offset处读取并把新的位置写回*offset，in_fd自身的位置不变；否则从in_fd的当前位置读取并移动它。
+/// out_fd总是从它的当前位置写入，追加模式在这里不生效
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: *mut usize, count: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (in_file, out_file) = match (inner.fd_table.get(in_fd), inner.fd_table.get(out_fd)) {
+        (Some(Some(in_entry)), Some(Some(out_entry))) if in_entry.file.readable() && out_entry.file.writable() => {
+            (in_entry.file.clone(), out_entry.file.clone())
+        }
+        _ => return -1,
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    let (in_inode, out_inode) = match (in_file.inode(), out_file.inode()) {
+        (Some(in_inode), Some(out_inode)) => (in_inode, out_inode),
+        _ => return -1,
+    };
+    let mut in_offset = if offset.is_null() {
+        in_file.seek(0, SEEK_CUR) as usize
+    } else {
+        prepare_user_write(offset as usize, core::mem::size_of::<usize>());
+        *translated_ref(token, offset)
+    };
+    let mut out_offset = out_file.seek(0, SEEK_CUR) as usize;
+    let mut buffer = vec![0u8; SENDFILE_CHUNK.min(count)];
+    let mut copied = 0usize;
+    while copied < count {
+        let len = (count - copied).min(buffer.len());
+        let read_size = in_inode.read_at(in_offset, &mut buffer[..len]);
+        if read_size == 0 {
+            break;
+        }
+        let write_size = out_inode.write_at(out_offset, &buffer[..read_size]);
+        in_offset += write_size;
+        out_offset += write_size;
+        copied += write_size;
+        // 磁盘已满，只写入了一部分
+        if write_size < read_size {
+            break;
+        }
+    }
+    if offset.is_null() {
+        in_file.seek(in_offset as isize, SEEK_SET);
+    } else {
+        *translated_refmut(token, offset) = in_offset;
+    }
+    out_file.seek(out_offset as isize, SEEK_SET);
+    copied as isize
+}
+
+/// 把用户态的iovec数组中的每一段都翻译出来，拼成一个UserBuffer，其中某一段没有映射时返回None
+fn translated_iovec(token: usize, iov: *const IoVec, iovcnt: usize) -> Option<UserBuffer> {
+    prepare_user_read(iov as usize, iovcnt * core::mem::size_of::<IoVec>());
+    let mut buffers = Vec::new();
+    for i in 0..iovcnt {
+        let iovec = *translated_ref(token, unsafe { iov.add(i) });
+        if iovec.len > 0 {
+            prepare_user_read(iovec.base as usize, iovec.len);
+            buffers.extend(translated_byte_buffer(token, iovec.base, iovec.len)?);
+        }
+    }
+    Some(UserBuffer::new(buffers))
+}
+
+/// 一次系统调用依次写出iovcnt段缓冲区，返回写入的总字节数
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    let token = current_user_token();
     let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        match translated_iovec(token, iov, iovcnt) {
+            Some(buffers) => file.write(buffers) as isize,
+            None => FsError::Fault.into(),
+        }
+    } else {
+        -1
+    }
+}
+
+/// 一次系统调用依次读入iovcnt段缓冲区，返回读到的总字节数
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
     let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        prepare_user_read(iov as usize, iovcnt * core::mem::size_of::<IoVec>());
+        for i in 0..iovcnt {
+            let iovec = *translated_ref(token, unsafe { iov.add(i) });
+            prepare_user_write(iovec.base as usize, iovec.len);
+        }
+        match translated_iovec(token, iov, iovcnt) {
+            Some(buffers) => file.read(buffers) as isize,
+            None => FsError::Fault.into(),
+        }
+    } else {
+        -1
+    }
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        file.seek(offset, whence)
+    } else {
+        -1
+    }
+}
+
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let task = current_task().unwrap();
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = task.inner_exclusive_access().cwd.clone();
+    match open_file_at(
+        &cwd,
         path.as_str(),
         OpenFlags::from_bits(flags).unwrap()
     ) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+        Ok(inode) => {
+            let mut inner = task.inner_exclusive_access();
+            let fd = match inner.alloc_fd() {
+                Some(fd) => fd,
+                None => return FsError::TooManyFiles.into(),
+            };
+            let mut entry = FdEntry::new(inode);
+            if OpenFlags::from_bits_truncate(flags).contains(OpenFlags::CLOEXEC) {
+                entry.flags = FdFlags::CLOEXEC;
+            }
+            inner.fd_table[fd] = Some(entry);
+            fd as isize
+        }
+        Err(err) => err.into(),
     }
 }
 
@@ -79,43 +316,713 @@ pub fn sys_close(fd: usize) -> isize {
     0
 }
 
+/// 创建一个管道，并将读端和写端的fd依次写回用户空间的pipe数组
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return FsError::TooManyFiles.into(),
+    };
+    inner.fd_table[read_fd] = Some(FdEntry::new(pipe_read));
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            // 写端没有位置时把读端也关掉
+            inner.fd_table[read_fd] = None;
+            return FsError::TooManyFiles.into();
+        }
+    };
+    inner.fd_table[write_fd] = Some(FdEntry::new(pipe_write));
+    inner
+        .memory_set
+        .prepare_user_write(pipe as usize, 2 * core::mem::size_of::<usize>());
+    *translated_refmut(token, pipe) = read_fd;
+    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    0
+}
+
+/// 检查nfds个fd是否可以不阻塞地读写，结果写回每一项的revents，返回就绪的fd个数。
+/// 没有fd就绪时让出CPU等待，直到有fd就绪或者超过timeout毫秒（返回0）；timeout为负数时一直等待。
+/// fd为负数的项被忽略，未打开的fd报告POLLNVAL
+pub fn sys_poll(fds: *mut PollFd, nfds: usize, timeout: isize) -> isize {
+    let token = current_user_token();
+    let deadline = if timeout >= 0 {
+        Some(get_time_us() + timeout as usize * 1000)
+    } else {
+        None
+    };
+    prepare_user_write(fds as usize, nfds * core::mem::size_of::<PollFd>());
+    loop {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        let mut ready = 0isize;
+        for i in 0..nfds {
+            let pollfd = translated_refmut(token, unsafe { fds.add(i) });
+            let events = pollfd.events;
+            pollfd.revents = if pollfd.fd < 0 {
+                PollEvents::empty()
+            } else if let Some(Some(entry)) = inner.fd_table.get(pollfd.fd as usize) {
+                let mut revents = PollEvents::empty();
+                if events.contains(PollEvents::POLLIN) && entry.file.readable() && entry.file.read_ready() {
+                    revents |= PollEvents::POLLIN;
+                }
+                if events.contains(PollEvents::POLLOUT) && entry.file.writable() && entry.file.write_ready() {
+                    revents |= PollEvents::POLLOUT;
+                }
+                revents
+            } else {
+                PollEvents::POLLNVAL
+            };
+            if !pollfd.revents.is_empty() {
+                ready += 1;
+            }
+        }
+        // release current task TCB manually before yielding
+        drop(inner);
+        drop(task);
+        if ready > 0 {
+            return ready;
+        }
+        if let Some(deadline) = deadline {
+            if get_time_us() >= deadline {
+                return 0;
+            }
+        }
+        suspend_current_and_run_next();
+    }
+}
+
+/// 复制一个文件描述符到最小的空闲位置。
+/// 新旧两个fd共享同一个 `Arc<dyn File>`，因此也共享同一个读写偏移（offset存放在OSInode中）；
+/// 但CLOEXEC等描述符标志属于fd本身，新fd上总是清空的
+pub fn sys_dup(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if inner.fd_table[fd].is_none() {
+        return -1;
+    }
+    let new_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return FsError::TooManyFiles.into(),
+    };
+    let file = Arc::clone(&inner.fd_table[fd].as_ref().unwrap().file);
+    inner.fd_table[new_fd] = Some(FdEntry::new(file));
+    new_fd as isize
+}
+
+/// 将oldfd复制到newfd，如果newfd已被占用则先关闭它，必要时扩充fd_table；newfd不能达到MAX_FD
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if old_fd >= inner.fd_table.len() || new_fd >= MAX_FD {
+        return -1;
+    }
+    if inner.fd_table[old_fd].is_none() {
+        return -1;
+    }
+    if old_fd == new_fd {
+        return new_fd as isize;
+    }
+    while inner.fd_table.len() <= new_fd {
+        inner.fd_table.push(None);
+    }
+    let file = Arc::clone(&inner.fd_table[old_fd].as_ref().unwrap().file);
+    inner.fd_table[new_fd] = Some(FdEntry::new(file));
+    new_fd as isize
+}
+
+/// 与dup2相同，但oldfd与newfd相同时返回-1；flags中只能包含CLOEXEC，它被设置在newfd上，exec时newfd会被关闭
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) if (flags - OpenFlags::CLOEXEC).is_empty() => flags,
+        _ => return -1,
+    };
+    if old_fd == new_fd || sys_dup2(old_fd, new_fd) < 0 {
+        return -1;
+    }
+    if flags.contains(OpenFlags::CLOEXEC) {
+        let task = current_task().unwrap();
+        let mut inner = task.inner_exclusive_access();
+        inner.fd_table[new_fd].as_mut().unwrap().flags = FdFlags::CLOEXEC;
+    }
+    new_fd as isize
+}
+
 // YOUR JOB: 扩展 easy-fs 和内核以实现以下三个 syscall
 pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
     let token = current_user_token();
+    prepare_user_write(st as usize, core::mem::size_of::<Stat>());
     let ppn = translate_va(token, st as usize).unwrap();
     let st = ppn.0 as *mut Stat;
 
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return FsError::BadFd.into();
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
         let (ino, nlink, mode) = file.get_stat();
+        let (size, blocks) = file.get_usage();
         unsafe {
             (*st).ino = ino as u64;
             (*st).mode = mode;
             (*st).nlink = nlink as u32;
+            (*st).size = size as u64;
+            (*st).blocks = blocks as u64;
         }
         0
+    } else {
+        FsError::BadFd.into()
+    }
+}
+
+/// 把目录fd的读取位置移回开头，之后的getdents重新从第一项开始返回
+pub fn sys_rewinddir(fd: usize) -> isize {
+    sys_seekdir(fd, 0)
+}
+
+/// 把目录fd的读取位置设为第pos个目录项槽位（从0开始，包括空槽）
+pub fn sys_seekdir(fd: usize, pos: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(entry)) => entry.file.clone(),
+        _ => return FsError::BadFd.into(),
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    if file.seek_dir(pos) {
+        0
+    } else {
+        FsError::NotDir.into()
+    }
+}
+
+/// 把所有缓存中的修改写回磁盘
+pub fn sys_sync() -> isize {
+    sync_all();
+    0
+}
+
+/// 把可读写打开的镜像文件image_fd中的easy-fs挂载到目录mountpoint上，之后经过mountpoint的路径都进入镜像中的文件系统
+pub fn sys_mount(image_fd: usize, mountpoint: *const u8) -> isize {
+    let path = match translated_user_str(mountpoint) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(image_fd) {
+        Some(Some(entry)) if entry.file.readable() && entry.file.writable() => entry.file.clone(),
+        _ => return FsError::BadFd.into(),
+    };
+    let cwd = inner.cwd.clone();
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    match file.inode() {
+        Some(image) => mount_at(&cwd, image, path.as_str()),
+        None => FsError::Invalid.into(),
+    }
+}
+
+/// 卸载挂载在mountpoint上的文件系统，其中还有打开的文件或进程的当前目录时返回-EBUSY
+pub fn sys_umount(mountpoint: *const u8) -> isize {
+    let path = match translated_user_str(mountpoint) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    umount_at(&cwd, path.as_str())
+}
+
+/// 只把fd对应文件自己的数据块、索引块和DiskInode写回磁盘，fd不是磁盘上的文件时返回-1
+pub fn sys_fsync(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    if let Some(entry) = &inner.fd_table[fd] {
+        let file = entry.file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        match file.inode() {
+            Some(inode) => {
+                inode.fsync();
+                // 缓冲的写入在flush时没能全部写回
+                if file.take_write_error() {
+                    return FsError::NoSpace.into();
+                }
+                0
+            }
+            None => -1,
+        }
     } else {
         -1
     }
 }
 
-pub fn sys_linkat(old_name: *const u8, new_name: *const u8) -> isize {
+/// mode为0时预先分配[offset, offset + len)需要的所有数据块，文件不够大时扩展它；
+/// mode为 FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE 时释放这一段完整覆盖的数据块，读出来都是0，文件大小不变
+pub fn sys_fallocate(fd: usize, mode: usize, offset: usize, len: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(entry)) if entry.file.writable() => entry.file.clone(),
+        _ => return FsError::BadFd.into(),
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    let inode = match file.inode() {
+        Some(inode) => inode,
+        None => return FsError::Invalid.into(),
+    };
+    if offset.checked_add(len).map_or(true, |end| end > u32::MAX as usize) {
+        return FsError::Invalid.into();
+    }
+    match mode {
+        0 => match inode.preallocate((offset + len) as u32) {
+            0 => 0,
+            _ => FsError::NoSpace.into(),
+        },
+        mode if mode == FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE => match inode.punch_hole(offset as u32, len as u32) {
+            0 => 0,
+            _ if inode.is_mounted_image() => FsError::Busy.into(),
+            _ => FsError::Invalid.into(),
+        },
+        _ => FsError::Invalid.into(),
+    }
+}
+
+/// 告诉内核将怎样访问文件：RANDOM关闭预读，SEQUENTIAL加大预读窗口，NORMAL恢复默认；
+/// WILLNEED立即把[offset, offset + len)读进块缓存，len为0表示直到文件末尾
+pub fn sys_fadvise(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(entry)) => entry.file.clone(),
+        _ => return FsError::BadFd.into(),
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    let read_ahead = match advice {
+        POSIX_FADV_NORMAL => READ_AHEAD_BLOCKS,
+        POSIX_FADV_RANDOM => 0,
+        POSIX_FADV_SEQUENTIAL => SEQUENTIAL_READ_AHEAD_BLOCKS,
+        POSIX_FADV_WILLNEED => {
+            return match file.inode() {
+                Some(inode) => {
+                    inode.prefetch(offset, if len == 0 { usize::MAX } else { len });
+                    0
+                }
+                None => FsError::Invalid.into(),
+            };
+        }
+        _ => return FsError::Invalid.into(),
+    };
+    if file.set_read_ahead(read_ahead) { 0 } else { FsError::Invalid.into() }
+}
+
+/// Take or release the advisory lock on the whole file `fd` refers to, see LOCK_*
+/// 锁属于打开的文件：dup/fork出的fd共享同一把锁，最后一个fd关闭时自动释放；
+/// 不带LOCK_NB时让出CPU直到拿到锁为止
+pub fn sys_flock(fd: usize, op: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(entry)) => entry.file.clone(),
+        _ => return FsError::BadFd.into(),
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    drop(task);
+    let (dev, ino) = match file.inode() {
+        Some(inode) => (inode.device_id(), inode.get_diskinodetype().0),
+        None => return FsError::Invalid.into(),
+    };
+    // 同一个打开的文件的所有fd指向同一个对象，用它的地址标识持有者
+    let owner = Arc::as_ptr(&file) as *const () as usize;
+    let exclusive = match op & !LOCK_NB {
+        LOCK_SH => false,
+        LOCK_EX => true,
+        LOCK_UN => {
+            unlock(dev, ino, owner);
+            return 0;
+        }
+        _ => return FsError::Invalid.into(),
+    };
+    while !try_lock(dev, ino, owner, exclusive) {
+        if op & LOCK_NB != 0 {
+            return FsError::WouldBlock.into();
+        }
+        suspend_current_and_run_next();
+    }
+    0
+}
+
+/// 按路径获取文件状态，无需先打开文件
+pub fn sys_stat(path: *const u8, st: *mut Stat) -> isize {
     let token = current_user_token();
-    let old_name = translated_str(token, old_name);
-    let new_name = translated_str(token, new_name);
-    linkat(old_name.as_str(), new_name.as_str())
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    let inode = match find_path_at(&cwd, path.as_str()) {
+        Some(inode) => inode,
+        None => return -1,
+    };
+    prepare_user_write(st as usize, core::mem::size_of::<Stat>());
+    let ppn = translate_va(token, st as usize).unwrap();
+    let st = ppn.0 as *mut Stat;
+    let (ino, nlink, mode) = stat_inode(&inode);
+    unsafe {
+        (*st).ino = ino as u64;
+        (*st).mode = mode;
+        (*st).nlink = nlink as u32;
+        (*st).size = inode.size() as u64;
+        (*st).blocks = inode.allocated_blocks() as u64;
+    }
+    0
+}
+
+/// 按路径一次取得inode的全部元数据
+pub fn sys_getattr(path: *const u8, attr: *mut XAttr) -> isize {
+    let token = current_user_token();
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    let inode = match find_path_at(&cwd, path.as_str()) {
+        Some(inode) => inode,
+        None => return FsError::NotFound.into(),
+    };
+    prepare_user_write(attr as usize, core::mem::size_of::<XAttr>());
+    let ppn = translate_va(token, attr as usize).unwrap();
+    let attr = ppn.0 as *mut XAttr;
+    let (ino, nlink, mode) = stat_inode(&inode);
+    unsafe {
+        (*attr).ino = ino as u64;
+        (*attr).mode = mode;
+        (*attr).nlink = nlink as u32;
+        (*attr).size = inode.size() as u64;
+        (*attr).blocks = inode.allocated_blocks() as u64;
+        (*attr).atime = 0;
+        (*attr).mtime = 0;
+        (*attr).ctime = 0;
+        (*attr).perm = inode.perm() as u32;
+    }
+    0
+}
+
+/// 检查路径是否存在以及是否具有mode所要求的权限，满足返回0，否则返回-1
+pub fn sys_access(path: *const u8, mode: usize) -> isize {
+    // mode中只能包含R/W/X三位
+    let mode = match AccessMode::from_bits(mode as u32) {
+        Some(flags) if flags.bits() as usize == mode => flags,
+        _ => return -1,
+    };
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    access_at(&cwd, path.as_str(), mode)
+}
 
+/// 修改path对应的文件或目录的rwxrwxrwx权限位，只影响之后的open/access，已经打开的fd不受影响
+pub fn sys_chmod(path: *const u8, perm: u32) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    chmod_at(&cwd, path.as_str(), perm)
 }
 
-pub fn sys_unlinkat(name: *const u8) -> isize {
+/// 限制path目录下的整个子树最多占用blocks个块，blocks为负数时取消限制；超出配额的写入返回-ENOSPC
+pub fn sys_setquota(path: *const u8, blocks: isize) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    setquota_at(&cwd, path.as_str(), blocks)
+}
+
+/// 把path处的文件设为追加专用（append_only非0）或取消这一限制：追加专用的文件只能在末尾追加，
+/// 覆盖已有内容的写入返回-1，也不能被截短、清空或打洞
+pub fn sys_chattr(path: *const u8, append_only: usize) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    chattr_at(&cwd, path.as_str(), append_only != 0)
+}
+
+/// 不打开文件，直接按路径把文件截断（或扩展）到len字节，目录和没有写权限的文件不能被截断
+pub fn sys_truncate(path: *const u8, len: usize) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    truncate_at(&cwd, path.as_str(), len)
+}
+
+/// 把old_path重命名为new_path，new_path已存在（且不是目录）时会被覆盖
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    let old_path = match translated_user_str(old_path) {
+        Some(old_path) => old_path,
+        None => return FsError::Fault.into(),
+    };
+    let new_path = match translated_user_str(new_path) {
+        Some(new_path) => new_path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    rename_at(&cwd, old_path.as_str(), new_path.as_str(), RenameMode::Replace)
+}
+
+/// 带flags的rename：RENAME_NOREPLACE在new_path已存在时返回-EEXIST，RENAME_EXCHANGE原子地交换两个已存在的名字；
+/// 两个flag不能同时使用
+pub fn sys_renameat2(old_path: *const u8, new_path: *const u8, flags: u32) -> isize {
+    let mode = match flags {
+        0 => RenameMode::Replace,
+        RENAME_NOREPLACE => RenameMode::NoReplace,
+        RENAME_EXCHANGE => RenameMode::Exchange,
+        _ => return FsError::Invalid.into(),
+    };
+    let old_path = match translated_user_str(old_path) {
+        Some(old_path) => old_path,
+        None => return FsError::Fault.into(),
+    };
+    let new_path = match translated_user_str(new_path) {
+        Some(new_path) => new_path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    rename_at(&cwd, old_path.as_str(), new_path.as_str(), mode)
+}
+
+/// 把当前工作目录的绝对路径（以'\0'结尾）写入buf，返回写入的字节数，缓冲区放不下时返回-1
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
     let token = current_user_token();
-    let name = translated_str(token, name);
-    unlinkat(name.as_str())
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    let mut path = match cwd.abs_path() {
+        Some(path) => path.into_bytes(),
+        None => return -1,
+    };
+    path.push(0);
+    if path.len() > len {
+        return -1;
+    }
+    prepare_user_write(buf as usize, path.len());
+    let user_buf = match translated_byte_buffer(token, buf, path.len()) {
+        Some(buffers) => UserBuffer::new(buffers),
+        None => return FsError::Fault.into(),
+    };
+    for (i, byte) in user_buf.into_iter().enumerate() {
+        unsafe {
+            *byte = path[i];
+        }
+    }
+    path.len() as isize
+}
+
+/// 切换当前进程的工作目录，目标必须是一个目录
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let task = current_task().unwrap();
+    let cwd = task.inner_exclusive_access().cwd.clone();
+    match find_path_at(&cwd, path.as_str()) {
+        Some(inode) if inode.is_dir() => {
+            task.inner_exclusive_access().cwd = inode;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Get the directory `dirfd` refers to, AT_FDCWD means the current working directory
+/// dirfd不是打开的文件时返回EBADF，不是目录时返回ENOTDIR
+fn dirfd_inode(dirfd: usize) -> Result<Arc<Inode>, FsError> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if dirfd == AT_FDCWD {
+        return Ok(inner.cwd.clone());
+    }
+    match inner.fd_table.get(dirfd) {
+        Some(Some(FdEntry { file, .. })) => match file.inode() {
+            Some(inode) if inode.is_dir() => Ok(inode),
+            _ => Err(FsError::NotDir),
+        },
+        _ => Err(FsError::BadFd),
+    }
+}
+
+pub fn sys_mkdirat(dirfd: usize, path: *const u8) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    match dirfd_inode(dirfd) {
+        Ok(base) => mkdir_at(&base, path.as_str()),
+        Err(err) => err.into(),
+    }
+}
+
+pub fn sys_linkat(old_dirfd: usize, old_path: *const u8, new_dirfd: usize, new_path: *const u8) -> isize {
+    let old_path = match translated_user_str(old_path) {
+        Some(old_path) => old_path,
+        None => return FsError::Fault.into(),
+    };
+    let new_path = match translated_user_str(new_path) {
+        Some(new_path) => new_path,
+        None => return FsError::Fault.into(),
+    };
+    match (dirfd_inode(old_dirfd), dirfd_inode(new_dirfd)) {
+        (Ok(old_base), Ok(new_base)) => {
+            link_at(&old_base, old_path.as_str(), &new_base, new_path.as_str())
+        }
+        (Err(err), _) | (_, Err(err)) => err.into(),
+    }
+}
+
+pub fn sys_unlinkat(dirfd: usize, path: *const u8) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    match dirfd_inode(dirfd) {
+        Ok(base) => unlink_at(&base, path.as_str()),
+        Err(err) => err.into(),
+    }
+}
+
+/// 为old创建硬链接new，两个路径都相对于当前工作目录解析
+pub fn sys_link(old_path: *const u8, new_path: *const u8) -> isize {
+    let old_path = match translated_user_str(old_path) {
+        Some(old_path) => old_path,
+        None => return FsError::Fault.into(),
+    };
+    let new_path = match translated_user_str(new_path) {
+        Some(new_path) => new_path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    link_at(&cwd, old_path.as_str(), &cwd, new_path.as_str())
+}
+
+/// 删除path对应的目录项，path相对于当前工作目录解析
+pub fn sys_unlink(path: *const u8) -> isize {
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    unlink_at(&cwd, path.as_str())
+}
+
+pub fn sys_symlink(target: *const u8, linkpath: *const u8) -> isize {
+    let target = match translated_user_str(target) {
+        Some(target) => target,
+        None => return FsError::Fault.into(),
+    };
+    let linkpath = match translated_user_str(linkpath) {
+        Some(linkpath) => linkpath,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    symlink_at(&cwd, target.as_str(), linkpath.as_str())
+}
+
+/// 读取符号链接本身保存的路径（不以'\0'结尾），最多拷贝len个字节，返回路径的完整长度
+/// path不存在或不是符号链接时返回-1
+pub fn sys_readlink(path: *const u8, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return FsError::Fault.into(),
+    };
+    let cwd = current_task().unwrap().inner_exclusive_access().cwd.clone();
+    let target = match readlink_at(&cwd, path.as_str()) {
+        Some(target) => target.into_bytes(),
+        None => return -1,
+    };
+    let copy_len = target.len().min(len);
+    prepare_user_write(buf as usize, copy_len);
+    let user_buf = match translated_byte_buffer(token, buf, copy_len) {
+        Some(buffers) => UserBuffer::new(buffers),
+        None => return FsError::Fault.into(),
+    };
+    for (i, byte) in user_buf.into_iter().enumerate() {
+        unsafe {
+            *byte = target[i];
+        }
+    }
+    target.len() as isize
+}
+
+/// 将目录项序列化到用户缓冲区中，每一项的格式为：
+/// name_len(u8) | name(name_len bytes) | inode_id(u32, little endian) | type(u8, 1 表示目录)
+/// 返回写入的字节数，如果缓冲区连一项都放不下则返回-1
+pub fn sys_getdents(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -1;
+    }
+    let file = match &inner.fd_table[fd] {
+        Some(entry) => entry.file.clone(),
+        None => return -1,
+    };
+    // release current task TCB manually to avoid multi-borrow
+    drop(inner);
+    let entries = match file.read_dir() {
+        Some(entries) => entries,
+        None => return -1,
+    };
+    let mut data: Vec<u8> = Vec::new();
+    for entry in entries.iter() {
+        let name = entry.name.as_bytes();
+        if data.len() + 1 + name.len() + 4 + 1 > len {
+            if data.is_empty() {
+                return -1;
+            }
+            break;
+        }
+        data.push(name.len() as u8);
+        data.extend_from_slice(name);
+        data.extend_from_slice(&entry.inode_id.to_le_bytes());
+        data.push(entry.is_dir as u8);
+        // 下一次从这一项之后继续
+        file.seek_dir(entry.slot + 1);
+    }
+    prepare_user_write(buf as usize, data.len());
+    let user_buf = match translated_byte_buffer(token, buf, data.len()) {
+        Some(buffers) => UserBuffer::new(buffers),
+        None => return FsError::Fault.into(),
+    };
+    for (i, byte) in user_buf.into_iter().enumerate() {
+        unsafe {
+            *byte = data[i];
+        }
+    }
+    data.len() as isize
 }
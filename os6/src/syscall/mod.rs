@@ -10,13 +10,41 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 24;
+const SYSCALL_DUP2: usize = 25;
+const SYSCALL_DUP3: usize = 26;
+const SYSCALL_FLOCK: usize = 32;
+const SYSCALL_MKDIRAT: usize = 34;
 const SYSCALL_UNLINKAT: usize = 35;
+const SYSCALL_SYMLINKAT: usize = 36;
 const SYSCALL_LINKAT: usize = 37;
+const SYSCALL_RENAMEAT: usize = 38;
+const SYSCALL_UMOUNT2: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_TRUNCATE: usize = 45;
+const SYSCALL_FALLOCATE: usize = 47;
+const SYSCALL_ACCESS: usize = 48;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_FCHMODAT: usize = 53;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_READV: usize = 65;
+const SYSCALL_WRITEV: usize = 66;
+const SYSCALL_PREAD: usize = 67;
+const SYSCALL_PWRITE: usize = 68;
+const SYSCALL_PPOLL: usize = 73;
+const SYSCALL_SENDFILE: usize = 71;
+const SYSCALL_READLINKAT: usize = 78;
+const SYSCALL_STAT: usize = 79;
 const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_GET_TIME: usize = 169;
@@ -24,13 +52,24 @@ const SYSCALL_GETPID: usize = 172;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_RENAMEAT2: usize = 276;
+const SYSCALL_GETRANDOM: usize = 278;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_FADVISE: usize = 223;
 const SYSCALL_SET_PRIORITY: usize = 140;
 const SYSCALL_TASK_INFO: usize = 410;
-
-use crate::config::MAX_SYSCALL_NUM;
+const SYSCALL_GET_CPU_TIME: usize = 411;
+const SYSCALL_REWINDDIR: usize = 412;
+const SYSCALL_SEEKDIR: usize = 413;
+const SYSCALL_SETQUOTA: usize = 414;
+const SYSCALL_SCHED_STATS: usize = 415;
+const SYSCALL_CHATTR: usize = 416;
+const SYSCALL_GETATTR: usize = 417;
+/// legacy numbers from asm-generic, riscv has no plain link/unlink
+const SYSCALL_LINK: usize = 1025;
+const SYSCALL_UNLINK: usize = 1026;
 
 use crate::task::plus_one_to_syscall_used;
 
@@ -39,23 +78,49 @@ pub mod process;
 
 use fs::*;
 use process::*;
-use crate::fs::Stat;
+use crate::fs::{IoVec, PollFd, Stat, XAttr};
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
-    if syscall_id < MAX_SYSCALL_NUM {
-        plus_one_to_syscall_used(syscall_id);
-    }
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    plus_one_to_syscall_used(syscall_id);
 
 
     match syscall_id {
-        SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8),
-        SYSCALL_UNLINKAT => sys_unlinkat(args[1] as *const u8),
+        SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_LINKAT => sys_linkat(args[0], args[1] as *const u8, args[2], args[3] as *const u8),
+        SYSCALL_RENAMEAT => sys_rename(args[1] as *const u8, args[3] as *const u8),
+        SYSCALL_RENAMEAT2 => sys_renameat2(args[1] as *const u8, args[3] as *const u8, args[4] as u32),
+        SYSCALL_UNLINKAT => sys_unlinkat(args[0], args[1] as *const u8),
+        SYSCALL_SYMLINKAT => sys_symlink(args[0] as *const u8, args[2] as *const u8),
+        SYSCALL_MKDIRAT => sys_mkdirat(args[0], args[1] as *const u8),
+        SYSCALL_UMOUNT2 => sys_umount(args[0] as *const u8),
+        SYSCALL_MOUNT => sys_mount(args[0], args[1] as *const u8),
+        SYSCALL_TRUNCATE => sys_truncate(args[0] as *const u8, args[1]),
+        SYSCALL_FALLOCATE => sys_fallocate(args[0], args[1], args[2], args[3]),
+        SYSCALL_ACCESS => sys_access(args[1] as *const u8, args[2]),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_FCHMODAT => sys_chmod(args[1] as *const u8, args[2] as u32),
         SYSCALL_OPEN => sys_open(args[1] as *const u8, args[2] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_GETDENTS => sys_getdents(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_PREAD => sys_pread(args[0], args[1] as *mut u8, args[2], args[3]),
+        SYSCALL_PWRITE => sys_pwrite(args[0], args[1] as *const u8, args[2], args[3]),
+        SYSCALL_SENDFILE => sys_sendfile(args[0], args[1], args[2] as *mut usize, args[3]),
+        SYSCALL_PPOLL => sys_poll(args[0] as *mut PollFd, args[1], args[2] as isize),
+        SYSCALL_READLINKAT => sys_readlink(args[1] as *const u8, args[2] as *mut u8, args[3]),
+        SYSCALL_STAT => sys_stat(args[0] as *const u8, args[1] as *mut Stat),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYSCALL_SYNC => sys_sync(),
+        SYSCALL_FSYNC => sys_fsync(args[0]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
         SYSCALL_GETPID => sys_getpid(),
@@ -63,10 +128,22 @@ pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
         SYSCALL_EXEC => sys_exec(args[0] as *const u8),
         SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
         SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_GETRANDOM => sys_getrandom(args[0] as *mut u8, args[1]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_FADVISE => sys_fadvise(args[0], args[1], args[2], args[3]),
+        SYSCALL_FLOCK => sys_flock(args[0], args[1]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
+        SYSCALL_GET_CPU_TIME => sys_get_cpu_time(),
+        SYSCALL_REWINDDIR => sys_rewinddir(args[0]),
+        SYSCALL_SEEKDIR => sys_seekdir(args[0], args[1]),
+        SYSCALL_SETQUOTA => sys_setquota(args[0] as *const u8, args[1] as isize),
+        SYSCALL_SCHED_STATS => sys_sched_stats(args[0] as *mut SchedStat, args[1]),
+        SYSCALL_CHATTR => sys_chattr(args[0] as *const u8, args[1]),
+        SYSCALL_GETATTR => sys_getattr(args[0] as *const u8, args[1] as *mut XAttr),
+        SYSCALL_LINK => sys_link(args[0] as *const u8, args[1] as *const u8),
+        SYSCALL_UNLINK => sys_unlink(args[0] as *const u8),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
         _ => panic!("Unsupported syscall_id: {}", syscall_id),
     }
@@ -1,16 +1,18 @@
 //! Process management syscalls
 
-use crate::mm::{translated_refmut, translated_ref, translated_str, translate_va};
+use crate::mm::{translated_byte_buffer, translated_refmut, translated_ref, translate_va, MapBacking, MappedInode};
 use crate::task::{
     add_task, current_task, current_user_token, exit_current_and_run_next,
     suspend_current_and_run_next, TaskStatus, set_priority_for_current_task,
-    get_status_of_current_task, get_syscall_times_of_current_task, get_start_time_of_current_task, mmap, munmap
+    get_status_of_current_task, get_syscall_times_of_current_task, get_start_time_of_current_task, get_cpu_time_of_current_task, mmap, munmap,
+    prepare_user_write, ready_tasks, translated_user_str, FdEntry,
 };
 use crate::fs::{open_file, OpenFlags};
 use crate::timer::get_time_us;
+use crate::random::fill_random;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use crate::config::MAX_SYSCALL_NUM;
+use crate::config::{MAX_SYSCALL_NUM, PAGE_SIZE};
 use alloc::string::String;
 
 #[repr(C)]
@@ -20,6 +22,7 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
+#[repr(C)]
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -27,6 +30,17 @@ pub struct TaskInfo {
     pub time: usize,
 }
 
+/// Scheduling state of one task, see sys_sched_stats
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SchedStat {
+    pub pid: usize,
+    pub prio: usize,
+    pub stride: usize,
+    pub pass: usize,
+    pub run_count: usize,
+}
+
 pub fn sys_exit(exit_code: i32) -> ! {
     debug!("[kernel] Application exited with code {}", exit_code);
     exit_current_and_run_next(exit_code);
@@ -60,8 +74,10 @@ pub fn sys_fork() -> isize {
 
 /// Syscall Exec which accepts the elf path
 pub fn sys_exec(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return -1,
+    };
     if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
         let task = current_task().unwrap();
@@ -102,6 +118,9 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         // ++++ temporarily access child TCB exclusively
         let exit_code = child.inner_exclusive_access().exit_code;
         // ++++ release child PCB
+        inner
+            .memory_set
+            .prepare_user_write(exit_code_ptr as usize, core::mem::size_of::<i32>());
         *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
         found_pid as isize
     } else {
@@ -113,6 +132,7 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 // YOUR JOB: 引入虚地址后重写 sys_get_time
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let token = current_user_token();
+    prepare_user_write(ts as usize, core::mem::size_of::<TimeVal>());
     let ppn = translate_va(token, ts as usize).unwrap();
     let ts = ppn.0 as *mut TimeVal;
     let us = get_time_us();
@@ -126,21 +146,84 @@ pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
 }
 
 // YOUR JOB: 引入虚地址后重写 sys_task_info
+/// TaskInfo有2KB左右，在用户栈上可能跨越两个页面，所以按页分段复制，而不是只翻译起始地址
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     let token = current_user_token();
-    let ppn = translate_va(token, ti as usize).unwrap();
-    let ti = ppn.0 as *mut TaskInfo;
-
-    unsafe {
-        *ti = TaskInfo{
-            status: get_status_of_current_task(),
-            syscall_times: get_syscall_times_of_current_task(),
-            time: (get_time_us() - get_start_time_of_current_task()) / 1_000,
-        }
+    let len = core::mem::size_of::<TaskInfo>();
+    prepare_user_write(ti as usize, len);
+    let info = TaskInfo {
+        status: get_status_of_current_task(),
+        syscall_times: get_syscall_times_of_current_task(),
+        time: (get_time_us() - get_start_time_of_current_task()) / 1_000,
+    };
+    let bytes = unsafe { core::slice::from_raw_parts(&info as *const TaskInfo as *const u8, len) };
+    let mut copied = 0usize;
+    let buffers = match translated_byte_buffer(token, ti as *const u8, len) {
+        Some(buffers) => buffers,
+        None => return -1,
+    };
+    for slice in buffers {
+        slice.copy_from_slice(&bytes[copied..copied + slice.len()]);
+        copied += slice.len();
     }
     0
 }
 
+/// 用内核的伪随机数填满buf，返回写入的字节数
+/// 随机数来自以时间为种子的xorshift，不是密码学安全的
+pub fn sys_getrandom(buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    prepare_user_write(buf as usize, len);
+    let buffers = match translated_byte_buffer(token, buf as *const u8, len) {
+        Some(buffers) => buffers,
+        None => return -1,
+    };
+    for slice in buffers {
+        fill_random(slice);
+    }
+    len as isize
+}
+
+/// 返回当前进程在CPU上实际运行的总时间（微秒）
+pub fn sys_get_cpu_time() -> isize {
+    get_cpu_time_of_current_task() as isize
+}
+
+/// 把当前进程和就绪队列中每个进程的调度状态写入stats（当前进程在第一项），最多写入len项，返回总的进程数；
+/// 只读取调度状态，不影响调度
+pub fn sys_sched_stats(stats: *mut SchedStat, len: usize) -> isize {
+    let token = current_user_token();
+    let mut tasks = ready_tasks();
+    tasks.insert(0, current_task().unwrap());
+    let all: Vec<SchedStat> = tasks
+        .iter()
+        .map(|task| {
+            let schedule = &task.inner_exclusive_access().schedule;
+            SchedStat {
+                pid: task.pid.0,
+                prio: schedule.prio,
+                stride: schedule.stride,
+                pass: schedule.pass,
+                run_count: schedule.run_count,
+            }
+        })
+        .collect();
+    let count = all.len().min(len);
+    let size = count * core::mem::size_of::<SchedStat>();
+    prepare_user_write(stats as usize, size);
+    let bytes = unsafe { core::slice::from_raw_parts(all.as_ptr() as *const u8, size) };
+    let mut copied = 0usize;
+    let buffers = match translated_byte_buffer(token, stats as *const u8, size) {
+        Some(buffers) => buffers,
+        None => return -1,
+    };
+    for slice in buffers {
+        slice.copy_from_slice(&bytes[copied..copied + slice.len()]);
+        copied += slice.len();
+    }
+    all.len() as isize
+}
+
 // YOUR JOB: 实现sys_set_priority，为任务添加优先级
 pub fn sys_set_priority(prio: isize) -> isize {
     if prio < 2 {
@@ -151,8 +234,26 @@ pub fn sys_set_priority(prio: isize) -> isize {
 }
 
 // YOUR JOB: 扩展内核以实现 sys_mmap 和 sys_munmap
-pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
-    mmap(start, len, port)
+/// fd为-1时映射匿名内存，否则私有地映射fd对应文件从offset开始的内容
+/// start为0时由内核挑选地址并返回它，否则成功返回0
+pub fn sys_mmap(start: usize, len: usize, port: usize, fd: usize, offset: usize) -> isize {
+    let backing = if fd as isize == -1 {
+        MapBacking::Anonymous
+    } else {
+        if offset % PAGE_SIZE != 0 {
+            return -1;
+        }
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        match inner.fd_table.get(fd) {
+            Some(Some(FdEntry { file, .. })) if file.readable() => match file.inode() {
+                Some(inode) => MapBacking::File { inode: MappedInode::new(inode), offset },
+                None => return -1,
+            },
+            _ => return -1,
+        }
+    };
+    mmap(start, len, port, backing)
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
@@ -163,8 +264,10 @@ pub fn sys_munmap(start: usize, len: usize) -> isize {
 // YOUR JOB: 实现 sys_spawn 系统调用
 // ALERT: 注意在实现 SPAWN 时不需要复制父进程地址空间，SPAWN != FORK + EXEC 
 pub fn sys_spawn(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
+    let path = match translated_user_str(path) {
+        Some(path) => path,
+        None => return -1,
+    };
 
     if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
         let all_data = app_inode.read_all();
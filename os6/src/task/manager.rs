@@ -5,11 +5,26 @@
 
 use super::task::Schedule;
 use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
+use crate::config::BIG_STRIDE;
+use crate::sync::SpinMutex;
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 use lazy_static::*;
 
+/// Compare two passes which may have wrapped around.
+/// 只要所有pass之间的差距不超过BIG_STRIDE（远小于isize::MAX），
+/// 回绕减法结果的符号就能正确反映先后关系
+fn pass_cmp(a: usize, b: usize) -> Ordering {
+    (a.wrapping_sub(b) as isize).cmp(&0)
+}
+
+/// Index of the smallest pass, the first one among equals
+fn min_pass_index(passes: &[usize]) -> Option<usize> {
+    (0..passes.len()).min_by(|a, b| pass_cmp(passes[*a], passes[*b]))
+}
+
 pub struct TaskManager {
     ready_queue: VecDeque<Arc<TaskControlBlock>>,
 }
@@ -23,7 +38,24 @@ impl TaskManager {
         }
     }
     /// Add process back to ready queue
+    /// 从未运行过的新进程（start_time为0）的pass会被设为就绪队列中的最小pass，
+    /// 使它既不会插队到所有进程之前，也不会因为pass落后太多而长期霸占CPU或被饿死；
+    /// 其他进程在这里结算让出CPU带来的加速（见Schedule::settle_boost）
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let min_pass = self
+            .ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().schedule.pass)
+            .min_by(|a, b| pass_cmp(*a, *b));
+        {
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.schedule.settle_boost();
+            if task_inner.start_time == 0 {
+                if let Some(pass) = min_pass {
+                    task_inner.schedule.pass = pass;
+                }
+            }
+        }
         self.ready_queue.push_back(task);
     }
     /// Take a process out of the ready queue
@@ -36,48 +68,93 @@ impl TaskManager {
         if self.ready_queue.len() == 0 {
             return None;
         }
-        let mut result_id = (0..self.ready_queue.len())
-            .min_by_key(|id| self.ready_queue[*id].inner_exclusive_access().schedule.pass);
-
-        if self.ready_queue[result_id.unwrap()]
-            .inner_exclusive_access()
-            .schedule
-            .pass
-            == usize::MAX
-        {
-            for item in self.ready_queue.iter_mut() {
-                let schedule_tmp = &mut item.inner_exclusive_access().schedule;
-                schedule_tmp.update_pass(false);
-            }
-            // 重新选取即将在CPU中运行的task
-            result_id = (0..self.ready_queue.len())
-                .min_by_key(|id| self.ready_queue[*id].inner_exclusive_access().schedule.pass);
-        }
+        let passes: Vec<usize> = self
+            .ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().schedule.pass)
+            .collect();
+        let result_id = min_pass_index(&passes);
 
         let mut result = self.ready_queue.remove(result_id.unwrap());
         {
             let schedule_tmp = &mut result.as_mut().unwrap().inner_exclusive_access().schedule;
-            schedule_tmp.update_pass(true);
+            schedule_tmp.update_pass();
+            schedule_tmp.run_count += 1;
         }
         result
     }
+    /// The tasks in the ready queue, in queue order
+    pub fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>> {
+        self.ready_queue.iter().cloned().collect()
+    }
+}
+
+/// Drive passes across the wrap boundary of usize and check that the task picked each round
+/// is the one with the smallest pass as if passes never wrapped
+#[allow(unused)]
+pub fn stride_wrap_test() {
+    // pass分别是即将回绕的大数和已经回绕的小数，彼此之间的差距都不超过BIG_STRIDE
+    let mut schedules: Vec<Schedule> = [
+        (2usize, usize::MAX - BIG_STRIDE + 10),
+        (3, usize::MAX - BIG_STRIDE / 2),
+        (16, 3),
+    ]
+    .iter()
+    .map(|&(prio, pass)| Schedule {
+        prio,
+        pass,
+        stride: BIG_STRIDE / prio,
+        run_count: 0,
+        boost: 0,
+        yielded: false,
+    })
+    .collect();
+    // 用不会溢出的u128记录“真实”的pass作为参照
+    let mut truth: Vec<u128> = schedules
+        .iter()
+        .map(|schedule| match schedule.pass < BIG_STRIDE {
+            true => schedule.pass as u128 + usize::MAX as u128 + 1,
+            false => schedule.pass as u128,
+        })
+        .collect();
+    for _ in 0..200 {
+        let passes: Vec<usize> = schedules.iter().map(|schedule| schedule.pass).collect();
+        let picked = min_pass_index(&passes).unwrap();
+        let expected = (0..truth.len()).min_by_key(|i| truth[*i]).unwrap();
+        assert_eq!(picked, expected);
+        schedules[picked].update_pass();
+        schedules[picked].run_count += 1;
+        truth[picked] += schedules[picked].stride as u128;
+    }
+    // 所有pass都已回绕过至少一次，优先级越高运行次数越多
+    assert!(truth.iter().all(|pass| *pass > usize::MAX as u128));
+    assert!(schedules[0].run_count < schedules[1].run_count);
+    assert!(schedules[1].run_count < schedules[2].run_count);
+    assert_eq!(min_pass_index(&[]), None);
+    info!("stride_wrap_test passed!");
 }
 
 lazy_static! {
     /// TASK_MANAGER instance through lazy_static!
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    /// The ready queue is protected by a spin lock so it could be shared by all harts,
+    /// but the kernel only runs on the boot hart for now, see `current_processor`
+    pub static ref TASK_MANAGER: SpinMutex<TaskManager> = SpinMutex::new(TaskManager::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
 }
 
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
+}
+
+/// A snapshot of the ready queue
+pub fn ready_tasks() -> Vec<Arc<TaskControlBlock>> {
+    TASK_MANAGER.lock().ready_tasks()
 }
 
 /// 根据stride scheduling从TaskManager中pop出一个task
 pub fn stride_scheduling_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().stride_scheduling()
+    TASK_MANAGER.lock().stride_scheduling()
 }
@@ -27,19 +27,29 @@ use crate::config::PAGE_SIZE;
 use crate::timer::get_time_us;
 pub use crate::syscall::process::TaskInfo;
 use crate::fs::{open_file, OpenFlags};
-pub use task::{TaskControlBlock, TaskStatus};
+pub use task::{FdEntry, FdFlags, TaskControlBlock, TaskStatus};
 
 pub use context::TaskContext;
-pub use manager::add_task;
+pub use manager::{add_task, ready_tasks, stride_wrap_test};
 pub use pid::{pid_alloc, KernelStack, PidHandle};
 pub use processor::{
     current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task, set_priority_for_current_task,
-    get_status_of_current_task, get_syscall_times_of_current_task, get_start_time_of_current_task, plus_one_to_syscall_used,
-    mmap, munmap
+    get_status_of_current_task, get_syscall_times_of_current_task, get_start_time_of_current_task, get_cpu_time_of_current_task, plus_one_to_syscall_used,
+    mmap, munmap, handle_page_fault, prepare_user_read, prepare_user_write, translated_user_str, hartid, processor_test, syscall_times_test
 };
 
 /// Make current task suspended and switch to the next task
+/// 用于主动让出CPU（yield、等待I/O），重新调度时会得到一点加速
 pub fn suspend_current_and_run_next() {
+    suspend_current(true);
+}
+
+/// Preempt current task when its time slice is used up and switch to the next task
+pub fn preempt_current_and_run_next() {
+    suspend_current(false);
+}
+
+fn suspend_current(yielded: bool) {
     // There must be an application running.
     let task = take_current_task().unwrap();
 
@@ -48,10 +58,12 @@ pub fn suspend_current_and_run_next() {
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.schedule.yielded = yielded;
     drop(task_inner);
     // ---- release current PCB
 
     // push back to ready queue.
+    // 上下文要到schedule中的__switch才保存，只在单核上运行时才不会有其他核提前取走它
     add_task(task);
     // jump to scheduling cycle
     schedule(task_cx_ptr);
@@ -80,6 +92,8 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     // ++++++ release parent PCB
 
     inner.children.clear();
+    // close all files, so that e.g. pipe ends held by this task are released
+    inner.fd_table.clear();
     // deallocate user space
     inner.memory_set.recycle_data_pages();
     drop(inner);
@@ -7,10 +7,12 @@
 use super::__switch;
 use super::{fetch_task, stride_scheduling_task, TaskStatus};
 use super::{TaskContext, TaskControlBlock};
-use crate::config::{BIG_STRIDE, MAX_SYSCALL_NUM};
+use crate::config::{BIG_STRIDE, MAX_CORES, MAX_SYSCALL_NUM, PAGE_SIZE};
+use crate::mm::{translated_str, MapBacking, VirtAddr};
 use crate::sync::UPSafeCell;
 use crate::timer::get_time_us;
 use crate::trap::TrapContext;
+use alloc::string::String;
 use alloc::sync::Arc;
 use lazy_static::*;
 
@@ -20,6 +22,8 @@ pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
     /// The basic control flow of each core, helping to select and switch process
     idle_task_cx: TaskContext,
+    /// The time (in us) when the current task was switched in
+    switch_in_time: usize,
 }
 
 impl Processor {
@@ -27,6 +31,7 @@ impl Processor {
         Self {
             current: None,
             idle_task_cx: TaskContext::zero_init(),
+            switch_in_time: 0,
         }
     }
     fn get_idle_task_cx_ptr(&mut self) -> *mut TaskContext {
@@ -62,12 +67,21 @@ impl Processor {
             .start_time
     }
 
-    fn plus_one_to_syscall_used(&mut self, syscall_id: usize) {
+    /// Get the cpu time of current task, including the slice it is running now
+    fn get_cpu_time_of_current_task(&self) -> usize {
         self.current
-            .as_mut()
+            .as_ref()
             .unwrap()
             .inner_exclusive_access()
-            .syscall_times[syscall_id] += 1;
+            .cpu_time_us
+            + (get_time_us() - self.switch_in_time)
+    }
+
+    fn plus_one_to_syscall_used(&mut self, syscall_id: usize) {
+        count_syscall(
+            &mut self.current.as_mut().unwrap().inner_exclusive_access().syscall_times,
+            syscall_id,
+        );
     }
 
     fn initialize_start_time_of_current_task(&mut self) {
@@ -102,14 +116,18 @@ impl Processor {
             .stride = BIG_STRIDE / prio;
     }
 
-    fn mmap(&mut self, start: usize, len: usize, port: usize) -> isize {
+    fn mmap(&mut self, start: usize, len: usize, port: usize, backing: MapBacking) -> isize {
+        // port只能包含R/W/X三位且至少包含一位，start必须页对齐，len不能为0
+        if (port & !0x7) != 0 || (port & 0x7) == 0 || start % PAGE_SIZE != 0 || len == 0 {
+            return -1;
+        }
         let memory_set = &mut self
             .current
             .as_mut()
             .unwrap()
             .inner_exclusive_access()
             .memory_set;
-        memory_set.mmap(start, len, port)
+        memory_set.mmap(start, len, port, backing)
     }
 
     fn munmap(&mut self, start: usize, len: usize) -> isize {
@@ -124,8 +142,62 @@ impl Processor {
 }
 
 lazy_static! {
-    /// PROCESSOR instance through lazy_static!
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One Processor per hart, indexed by hartid
+    /// 目前只启动了一个核，只有PROCESSOR[0]会被用到；其余的槽位为将来支持多核预留
+    pub static ref PROCESSOR: [UPSafeCell<Processor>; MAX_CORES] =
+        [(); MAX_CORES].map(|_| unsafe { UPSafeCell::new(Processor::new()) });
+}
+
+/// Get the id of the current hart, which is kept in tp since boot
+/// 从用户态trap进内核时tp由__alltraps从TrapContext中恢复，不会是用户程序留下的值
+pub fn hartid() -> usize {
+    let id: usize;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+/// Get the Processor of the current hart
+/// 每个核只会访问属于自己的Processor；但TCB仍然用UPSafeCell保护，而且让出CPU的任务在__switch
+/// 保存它的上下文之前就回到了就绪队列，所以内核只能在单核上运行，真正启动其他核之前这两点都要先改掉
+fn current_processor() -> &'static UPSafeCell<Processor> {
+    &PROCESSOR[hartid()]
+}
+
+/// Check that the current hart addresses its own Processor slot
+#[allow(unused)]
+pub fn processor_test() {
+    let id = hartid();
+    assert!(id < MAX_CORES);
+    assert!(core::ptr::eq(current_processor(), &PROCESSOR[id]));
+    // nothing is running on this hart yet
+    assert!(current_task().is_none());
+    assert!(take_current_task().is_none());
+    info!("processor_test passed on hart {}!", id);
+}
+
+/// Count one call of `syscall_id`, ids out of the range of `syscall_times` are not counted
+/// 用户可以传入任意的系统调用号，直接下标访问越界会让内核panic
+fn count_syscall(syscall_times: &mut [u32; MAX_SYSCALL_NUM], syscall_id: usize) {
+    if let Some(times) = syscall_times.get_mut(syscall_id) {
+        *times += 1;
+    }
+}
+
+/// 越界的系统调用号既不panic，也不修改任何一项计数
+#[allow(unused)]
+pub fn syscall_times_test() {
+    let mut syscall_times = [0u32; MAX_SYSCALL_NUM];
+    count_syscall(&mut syscall_times, MAX_SYSCALL_NUM);
+    count_syscall(&mut syscall_times, usize::MAX);
+    assert!(syscall_times.iter().all(|times| *times == 0));
+    count_syscall(&mut syscall_times, MAX_SYSCALL_NUM - 1);
+    count_syscall(&mut syscall_times, 0);
+    assert_eq!(syscall_times[MAX_SYSCALL_NUM - 1], 1);
+    assert_eq!(syscall_times[0], 1);
+    assert_eq!(syscall_times.iter().sum::<u32>(), 2);
+    info!("syscall_times_test passed!");
 }
 
 /// The main part of process execution and scheduling
@@ -134,7 +206,7 @@ lazy_static! {
 /// and switch the process through __switch
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = current_processor().exclusive_access();
         if let Some(task) = stride_scheduling_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
@@ -143,7 +215,8 @@ pub fn run_tasks() {
             task_inner.task_status = TaskStatus::Running;
             drop(task_inner);
             // release coming task TCB manually
-            processor.current = Some(task);
+            processor.current = Some(task.clone());
+            processor.switch_in_time = get_time_us();
             // release processor manually
 
             drop(processor);
@@ -154,18 +227,21 @@ pub fn run_tasks() {
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // 任务被切换出来后才结算这段时间，空闲循环中的等待不计入任何任务
+            let switch_in_time = current_processor().exclusive_access().switch_in_time;
+            task.inner_exclusive_access().cpu_time_us += get_time_us() - switch_in_time;
         }
     }
 }
 
 /// Get current task through take, leaving a None in its place
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().exclusive_access().take_current()
 }
 
 /// Get a copy of the current task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().exclusive_access().current()
 }
 
 /// Get token of the address space of current task
@@ -185,7 +261,7 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
 
 /// Return to idle control flow for new scheduling
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor().exclusive_access();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
@@ -195,7 +271,7 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
 
 /// Get the mutable reference to trap context of current task
 pub fn set_priority_for_current_task(prio: isize) -> isize {
-    PROCESSOR
+    current_processor()
         .exclusive_access()
         .set_priority_for_current_task(prio as usize);
     prio
@@ -203,43 +279,79 @@ pub fn set_priority_for_current_task(prio: isize) -> isize {
 
 /// Get the status of current task
 pub fn get_status_of_current_task() -> TaskStatus {
-    PROCESSOR.exclusive_access().get_status_of_current_task()
+    current_processor().exclusive_access().get_status_of_current_task()
 }
 
 /// Get the syscall_times of current task
 pub fn get_syscall_times_of_current_task() -> [u32; MAX_SYSCALL_NUM] {
-    PROCESSOR
+    current_processor()
         .exclusive_access()
         .get_syscall_times_of_current_task()
 }
 
 /// Get the start_time of current task
 pub fn get_start_time_of_current_task() -> usize {
-    PROCESSOR
+    current_processor()
         .exclusive_access()
         .get_start_time_of_current_task()
 }
 
+/// Get the cpu time (in us) of current task
+pub fn get_cpu_time_of_current_task() -> usize {
+    current_processor()
+        .exclusive_access()
+        .get_cpu_time_of_current_task()
+}
+
 /// 当一个系统调用被调用时，给它的调用次数加一
 pub fn plus_one_to_syscall_used(syscall_id: usize) {
-    PROCESSOR
+    current_processor()
         .exclusive_access()
         .plus_one_to_syscall_used(syscall_id)
 }
 
 /// 记录task在CPU中第一次运行的时刻
 pub fn initialize_start_time_of_current_task() {
-    PROCESSOR
+    current_processor()
         .exclusive_access()
         .initialize_start_time_of_current_task();
 }
 
-pub fn mmap(start: usize, len: usize, port: usize) -> isize {
-    PROCESSOR.exclusive_access().mmap(start, len, port)
+pub fn mmap(start: usize, len: usize, port: usize, backing: MapBacking) -> isize {
+    current_processor().exclusive_access().mmap(start, len, port, backing)
 }
 
 pub fn munmap(start: usize, len: usize) -> isize {
-    PROCESSOR.exclusive_access().munmap(start, len)
+    current_processor().exclusive_access().munmap(start, len)
+}
+
+/// 内核写入当前任务[start, start + len)处的内存之前调用，先完成其中共享页的写时复制
+pub fn prepare_user_write(start: usize, len: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.prepare_user_write(start, len);
+}
+
+/// 内核读取当前任务[start, start + len)处的内存之前调用，先为其中还没有分配物理页的mmap页分配物理页
+pub fn prepare_user_read(start: usize, len: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.prepare_user_read(start, len);
+}
+
+/// 读出当前任务ptr处以'\0'结尾的字符串（如路径），字符串经过未映射的地址时返回None
+pub fn translated_user_str(ptr: *const u8) -> Option<String> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.prepare_user_str(ptr as usize);
+    translated_str(inner.memory_set.token(), ptr)
+}
+
+/// 尝试处理当前任务的缺页异常（按需分配mmap区域中的页），返回是否处理成功
+pub fn handle_page_fault(va: usize) -> bool {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.handle_page_fault(VirtAddr::from(va))
 }
 
 
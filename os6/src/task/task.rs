@@ -2,8 +2,8 @@
 
 use super::TaskContext;
 use super::{pid_alloc, KernelStack, PidHandle};
-use crate::config::{BIG_STRIDE, DEFAULT_PRIOR, MAX_SYSCALL_NUM, TRAP_CONTEXT};
-use crate::fs::{File, Stdin, Stdout};
+use crate::config::{BIG_STRIDE, DEFAULT_PRIOR, MAX_FD, MAX_SYSCALL_NUM, MAX_YIELD_BOOST, TRAP_CONTEXT};
+use crate::fs::{File, Stdin, Stdout, ROOT_INODE};
 use crate::mm::translated_refmut;
 use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
 use crate::sync::UPSafeCell;
@@ -12,6 +12,35 @@ use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use core::cell::RefMut;
+use easy_fs::Inode;
+
+bitflags! {
+    /// Flags of a single file descriptor, not shared with other fds referring to the same file
+    pub struct FdFlags: u8 {
+        /// close the fd when the process execs a new program
+        const CLOEXEC = 1 << 0;
+    }
+}
+
+/// An occupied slot of the fd table
+/// 克隆FdEntry只克隆Arc，dup/fork得到的fd与原fd指向同一个打开文件描述（OSInode），共享读写位置；
+/// 只有flags属于fd自身
+#[derive(Clone)]
+pub struct FdEntry {
+    /// the open file description, shared by every fd duplicated from it
+    pub file: Arc<dyn File + Send + Sync>,
+    pub flags: FdFlags,
+}
+
+impl FdEntry {
+    /// A new fd referring to `file`, with no flags set
+    pub fn new(file: Arc<dyn File + Send + Sync>) -> Self {
+        Self {
+            file,
+            flags: FdFlags::empty(),
+        }
+    }
+}
 
 /// Task control block structure
 ///
@@ -49,7 +78,9 @@ pub struct TaskControlBlockInner {
     pub children: Vec<Arc<TaskControlBlock>>,
     /// It is set when active exit or execution error occurs
     pub exit_code: i32,
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    pub fd_table: Vec<Option<FdEntry>>,
+    /// 当前工作目录，相对路径从这里开始解析
+    pub cwd: Arc<Inode>,
 
     /// 当前进程的优先级
     pub schedule: Schedule,
@@ -57,6 +88,8 @@ pub struct TaskControlBlockInner {
     /// 当前进程的状态
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
     pub start_time: usize,
+    /// 进程在CPU上实际运行的总时间（微秒），不包括在就绪队列中等待的时间
+    pub cpu_time_us: usize,
 }
 
 pub struct Schedule {
@@ -64,22 +97,32 @@ pub struct Schedule {
     pub prio: usize,
     pub pass: usize,
     pub stride: usize,
-    pub pass_backup: usize,
+    /// 被stride调度选中运行的次数，只用于观察调度是否公平
+    pub run_count: usize,
+    /// 因主动让出CPU而获得的加速等级，不超过MAX_YIELD_BOOST
+    pub boost: usize,
+    /// 上一次离开CPU是否是主动让出（而不是被时钟中断抢占）
+    pub yielded: bool,
 }
 
 impl Schedule {
-    pub fn update_pass(&mut self, mark: bool) {
-        if mark {
-            let pass = self.pass + self.stride;
-            if pass < self.pass {
-                self.pass_backup = pass;
-                self.pass = usize::MAX;
-            } else {
-                self.pass = pass;
-            }
+    /// 每次被调度后pass增加一个stride，溢出时直接回绕，
+    /// 比较大小时使用回绕减法（见TaskManager::stride_scheduling），因此无需特殊处理
+    pub fn update_pass(&mut self) {
+        self.pass = self.pass.wrapping_add(self.stride);
+    }
+    /// 重新加入就绪队列时结算加速：主动让出的进程加速等级加一，并退还一部分刚刚加上的stride；
+    /// 被抢占的进程（用完了整个时间片）加速等级减一，逐渐回到普通的stride调度。
+    /// 每次退还最多半个stride，pass仍然严格递增，所以长期来看各进程的份额依然正比于优先级
+    pub fn settle_boost(&mut self) {
+        if self.yielded {
+            self.boost = (self.boost + 1).min(MAX_YIELD_BOOST);
+            let refund = self.stride / (2 * MAX_YIELD_BOOST) * self.boost;
+            self.pass = self.pass.wrapping_sub(refund);
         } else {
-            self.pass = self.pass_backup;
+            self.boost = self.boost.saturating_sub(1);
         }
+        self.yielded = false;
     }
 }
 
@@ -102,12 +145,15 @@ impl TaskControlBlockInner {
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
-    pub fn alloc_fd(&mut self) -> usize {
+    /// 没有空闲位置时在末尾增加一项，已经有MAX_FD个fd时返回None
+    pub fn alloc_fd(&mut self) -> Option<usize> {
         if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
-        } else {
+            Some(fd)
+        } else if self.fd_table.len() < MAX_FD {
             self.fd_table.push(None);
-            self.fd_table.len() - 1
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
         }
     }
 }
@@ -148,23 +194,27 @@ impl TaskControlBlock {
                     exit_code: 0,
                     fd_table: alloc::vec![
                         // 0 -> stdin
-                        Some(Arc::new(Stdin)),
+                        Some(FdEntry::new(Arc::new(Stdin))),
                         // 1 -> stdout
-                        Some(Arc::new(Stdout)),
+                        Some(FdEntry::new(Arc::new(Stdout))),
                         // 2 -> stderr
-                        Some(Arc::new(Stdout)),
+                        Some(FdEntry::new(Arc::new(Stdout))),
                     ],
+                    cwd: ROOT_INODE.clone(),
 
                     schedule: Schedule {
                         /// 当前进程的优先级
                         prio: DEFAULT_PRIOR,
                         pass: 0,
                         stride: BIG_STRIDE / DEFAULT_PRIOR,
-                        pass_backup: 0,
+                        run_count: 0,
+                        boost: 0,
+                        yielded: false,
                     },
 
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    cpu_time_us: 0,
                 })
             },
         };
@@ -191,6 +241,12 @@ impl TaskControlBlock {
         let mut inner = self.inner_exclusive_access();
         // substitute memory_set
         inner.memory_set = memory_set;
+        // 关闭所有设置了CLOEXEC的fd
+        for fd in inner.fd_table.iter_mut() {
+            if fd.as_ref().map_or(false, |entry| entry.flags.contains(FdFlags::CLOEXEC)) {
+                fd.take();
+            }
+        }
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
         // initialize trap_cx
@@ -209,7 +265,7 @@ impl TaskControlBlock {
         // ---- access parent PCB exclusively
         let mut parent_inner = self.inner_exclusive_access();
         // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -218,15 +274,9 @@ impl TaskControlBlock {
         let pid_handle = pid_alloc();
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        // clone all fds from parent to child
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        // clone all fds from parent to child, together with their flags
+        // 父子进程共享同一个Arc<OSInode>，任何一方seek/read/write之后另一方的读写位置也随之改变（与POSIX一致）
+        let new_fd_table: Vec<Option<FdEntry>> = parent_inner.fd_table.clone();
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
@@ -241,17 +291,21 @@ impl TaskControlBlock {
                     children: Vec::new(),
                     exit_code: 0,
                     fd_table: new_fd_table,
+                    cwd: parent_inner.cwd.clone(),
 
                     schedule: Schedule {
                         /// 当前进程的优先级
                         prio: DEFAULT_PRIOR,
                         pass: 0,
                         stride: BIG_STRIDE / DEFAULT_PRIOR,
-                        pass_backup: 0,
+                        run_count: 0,
+                        boost: 0,
+                        yielded: false,
                     },
 
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    cpu_time_us: 0,
                 })
             },
         });
@@ -284,15 +338,12 @@ impl TaskControlBlock {
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
 
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        // clone all fds from parent to child
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
+        // clone all fds from parent to child, except those closed on exec since spawn runs a new program
+        let new_fd_table: Vec<Option<FdEntry>> = parent_inner
+            .fd_table
+            .iter()
+            .map(|fd| fd.clone().filter(|entry| !entry.flags.contains(FdFlags::CLOEXEC)))
+            .collect();
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
@@ -307,17 +358,21 @@ impl TaskControlBlock {
                     children: Vec::new(),
                     exit_code: 0,
                     fd_table: new_fd_table,
+                    cwd: parent_inner.cwd.clone(),
 
                     schedule: Schedule {
                         /// 当前进程的优先级
                         prio: DEFAULT_PRIOR,
                         pass: 0,
                         stride: BIG_STRIDE / DEFAULT_PRIOR,
-                        pass_backup: 0,
+                        run_count: 0,
+                        boost: 0,
+                        yielded: false,
                     },
 
                     syscall_times: [0; MAX_SYSCALL_NUM],
                     start_time: 0,
+                    cpu_time_us: 0,
                 })
             },
         });
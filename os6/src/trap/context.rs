@@ -17,6 +17,9 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Virtual address of trap handler entry point in kernel
     pub trap_handler: usize,
+    /// Id of the hart this context was last restored on, loaded into tp by __alltraps
+    /// 用户程序可以随意修改tp，所以内核不能信任trap时tp中的值
+    pub hartid: usize,
 }
 
 impl TrapContext {
@@ -40,6 +43,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            hartid: 0,
         };
         cx.set_sp(sp);
         cx
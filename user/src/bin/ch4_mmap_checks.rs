@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::mmap;
+
+/*
+理想结果：非对齐的start、长度为0、非法的port以及与已有映射重叠的 mmap 都返回 -1，
+最终输出 Test mmap checks OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    let prot: usize = 3;
+    assert_eq!(mmap(start + 1, len, prot), -1);
+    assert_eq!(mmap(start, 0, prot), -1);
+    assert_eq!(mmap(start, len, 0), -1);
+    assert_eq!(mmap(start, len, prot | 8), -1);
+    assert_eq!(0, mmap(start, len * 2, prot));
+    // 与[start, start + 2 * len)部分重叠
+    assert_eq!(mmap(start + len, len * 2, prot), -1);
+    assert_eq!(mmap(start - len, len * 2, prot), -1);
+    // 紧邻已有映射的区域可以正常映射
+    assert_eq!(0, mmap(start + len * 2, len, prot));
+    let addr: *mut u8 = (start + len * 2) as *mut u8;
+    unsafe {
+        *addr = 42;
+        assert_eq!(*addr, 42);
+    }
+    println!("Test mmap checks OK!");
+    0
+}
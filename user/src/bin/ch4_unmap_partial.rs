@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap};
+
+/*
+理想结果：范围内有未映射页的 munmap 返回 -1 且不影响已有映射，
+最终输出 Test unmap partial OK!
+*/
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let len: usize = 4096;
+    let prot: usize = 3;
+    assert_eq!(0, mmap(start, len * 2, prot));
+    for i in 0..len * 2 {
+        let addr: *mut u8 = (start + i) as *mut u8;
+        unsafe { *addr = i as u8; }
+    }
+    // 第三页没有被映射，整个 munmap 失败
+    assert_eq!(munmap(start, len * 3), -1);
+    for i in 0..len * 2 {
+        let addr: *mut u8 = (start + i) as *mut u8;
+        unsafe { assert_eq!(*addr, i as u8); }
+    }
+    // 只解除第一页的映射，第二页仍然可以访问
+    assert_eq!(munmap(start, len), 0);
+    let addr: *mut u8 = (start + len) as *mut u8;
+    unsafe { assert_eq!(*addr, 0); }
+    assert_eq!(munmap(start + len, len), 0);
+    assert_eq!(munmap(start + len, len), -1);
+    println!("Test unmap partial OK!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, mmap, waitpid};
+
+/*
+理想结果：父进程映射一块40MB的内存后同时存在两个子进程。没有写时复制时三份拷贝远超物理内存，
+有写时复制时只有被写入的页才会被复制；子进程的写入不影响父进程，最终输出 Test cow fork OK!
+*/
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 10240;
+
+fn page(start: usize, i: usize) -> *mut usize {
+    (start + i * PAGE_SIZE) as *mut usize
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let start: usize = 0x10000000;
+    assert_eq!(mmap(start, PAGES * PAGE_SIZE, 3), 0);
+    for i in (0..PAGES).step_by(1024) {
+        unsafe { *page(start, i) = i };
+    }
+    let mut children = [0usize; 2];
+    for (id, child) in children.iter_mut().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            // 子进程先看到父进程写入的内容，再写入自己的值
+            for i in (0..PAGES).step_by(1024) {
+                unsafe {
+                    assert_eq!(*page(start, i), i);
+                    *page(start, i) = i + 1000 * (id + 1);
+                    assert_eq!(*page(start, i), i + 1000 * (id + 1));
+                }
+            }
+            exit(0);
+        }
+        assert!(pid > 0);
+        *child = pid as usize;
+    }
+    for pid in children {
+        let mut exit_code: i32 = -1;
+        assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+        assert_eq!(exit_code, 0);
+    }
+    for i in (0..PAGES).step_by(1024) {
+        unsafe { assert_eq!(*page(start, i), i) };
+    }
+    println!("Test cow fork OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_cpu_time, get_time, waitpid, yield_};
+
+/*
+忙等的进程应当比不断让出CPU的进程累计更多的CPU时间，
+输出　Test cpu time OK! 就算正确。
+*/
+
+const MAX_TIME: isize = 500;
+
+fn busy_loop() -> isize {
+    let start = get_time();
+    while get_time() - start < MAX_TIME {}
+    get_cpu_time()
+}
+
+fn yield_loop() -> isize {
+    let start = get_time();
+    while get_time() - start < MAX_TIME {
+        yield_();
+    }
+    get_cpu_time()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let busy = fork();
+    if busy == 0 {
+        exit((busy_loop() / 1000) as i32);
+    }
+    let lazy = fork();
+    if lazy == 0 {
+        exit((yield_loop() / 1000) as i32);
+    }
+    let mut busy_ms: i32 = 0;
+    let mut lazy_ms: i32 = 0;
+    assert_eq!(waitpid(busy as usize, &mut busy_ms), busy);
+    assert_eq!(waitpid(lazy as usize, &mut lazy_ms), lazy);
+    println!("busy: {} ms, yield: {} ms", busy_ms, lazy_ms);
+    assert!(busy_ms > lazy_ms * 2);
+    println!("Test cpu time OK!");
+    0
+}
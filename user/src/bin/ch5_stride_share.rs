@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, set_priority, waitpid};
+
+/*
+三个不同优先级的子进程同时计数，count 应当基本正比于 priority，
+输出　Test stride share OK! 就算正确。
+*/
+
+fn spin_delay() {
+    let mut j = true;
+    for _ in 0..10 {
+        j = !j;
+    }
+}
+
+const MAX_TIME: isize = 2000;
+const PRIORITIES: [isize; 3] = [4, 8, 16];
+
+fn count_during(prio: isize, start_time: isize) -> isize {
+    let mut acc = 0;
+    set_priority(prio);
+    loop {
+        spin_delay();
+        acc += 1;
+        if acc % 400 == 0 && get_time() - start_time > MAX_TIME {
+            return acc;
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 父进程使用最高优先级，尽快把三个子进程都创建出来
+    set_priority(64);
+    let start_time = get_time();
+    let mut pids = [0isize; 3];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            let count = count_during(*prio, start_time);
+            exit((count / 1000) as i32);
+        }
+        pids[i] = pid;
+    }
+    let mut ratios = [0isize; 3];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let mut count: i32 = 0;
+        assert_eq!(waitpid(pids[i] as usize, &mut count), pids[i]);
+        ratios[i] = count as isize * 1000 / prio;
+        println!("priority = {}, count = {}k, ratio = {}", prio, count, ratios[i]);
+    }
+    let max = *ratios.iter().max().unwrap();
+    let min = *ratios.iter().min().unwrap();
+    // 允许 30% 的误差
+    assert!(min * 13 / 10 >= max);
+    println!("Test stride share OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, close, mkdir, open, AccessMode, OpenFlags};
+
+/// 测试 access 对已存在的文件、不存在的文件以及目录的检查结果，输出　Test access OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "faccess\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(access(fname, AccessMode::F_OK), 0);
+    assert_eq!(access(fname, AccessMode::R_OK | AccessMode::W_OK), 0);
+    // 普通文件不能被执行（进入）
+    assert_eq!(access(fname, AccessMode::X_OK), -1);
+
+    assert_eq!(access("faccess_missing\0", AccessMode::F_OK), -1);
+    assert_eq!(access("faccess_missing\0", AccessMode::R_OK), -1);
+
+    let dname = "daccess\0";
+    assert_eq!(mkdir(dname), 0);
+    assert_eq!(access(dname, AccessMode::F_OK), 0);
+    assert_eq!(access(dname, AccessMode::R_OK | AccessMode::W_OK | AccessMode::X_OK), 0);
+    println!("Test access OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, write, OpenFlags};
+
+/// 测试两个以追加模式打开的文件描述符交替写入，输出　Test append OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fappend\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+
+    let fa = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    let fb = open(fname, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fa > 0 && fb > 0);
+    let (fa, fb) = (fa as usize, fb as usize);
+    for _ in 0..3 {
+        assert_eq!(write(fa, b"aaaa"), 4);
+        assert_eq!(write(fb, b"bb"), 2);
+    }
+    close(fa);
+    close(fb);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 64];
+    let read_len = read(fd, &mut buffer) as usize;
+    close(fd);
+    assert_eq!(
+        "aaaabbaaaabbaaaabb",
+        core::str::from_utf8(&buffer[..read_len]).unwrap()
+    );
+    println!("Test append OK!");
+    0
+}
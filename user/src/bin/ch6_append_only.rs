@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chattr, close, fallocate, lseek, open, pwrite, read, truncate, unlink, write, OpenFlags, EACCES,
+    FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE, SEEK_SET,
+};
+
+/// 测试追加专用的文件：只能在末尾追加，不能覆盖、截短或清空，输出　Test append only OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fappend_only\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"first\n"), 6);
+    close(fd as usize);
+    assert_eq!(chattr("fappend_only\0", true), 0);
+    // 追加模式打开时可以继续追加
+    let fd = open("fappend_only\0", OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"second\n"), 7);
+    close(fd as usize);
+    // 普通方式打开时，从文件末尾写入也可以，回到前面覆盖则被拒绝
+    let fd = open("fappend_only\0", OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(lseek(fd, 13, SEEK_SET), 13);
+    assert_eq!(write(fd, b"third\n"), 6);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(write(fd, b"oops"), -1);
+    assert_eq!(pwrite(fd, b"oops", 3), -1);
+    assert!(fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE, 0, 512) < 0);
+    let mut buffer = [0u8; 32];
+    let len = read(fd, &mut buffer) as usize;
+    assert_eq!(&buffer[..len], b"first\nsecond\nthird\n");
+    close(fd);
+    assert!(truncate("fappend_only\0", 0) < 0);
+    assert_eq!(open("fappend_only\0", OpenFlags::WRONLY | OpenFlags::TRUNC), -EACCES);
+    // 取消限制之后恢复正常
+    assert_eq!(chattr("fappend_only\0", false), 0);
+    assert_eq!(truncate("fappend_only\0", 0), 0);
+    assert!(chattr("/\0", true) < 0);
+    unlink("fappend_only\0");
+    println!("Test append only OK!");
+    0
+}
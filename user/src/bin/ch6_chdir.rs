@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, mkdir, open, read, write, OpenFlags, ENOENT};
+
+/// 测试工作目录切换后按相对路径打开文件，输出　Test chdir OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, cwd!";
+    // 目录可能在之前的运行中已经创建过
+    mkdir("chdir_sub\0");
+    assert_eq!(chdir("chdir_sub\0"), 0);
+
+    // 在子目录中按相对路径创建文件
+    let fd = open("rel\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    write(fd, test_str.as_bytes());
+    close(fd);
+
+    // 回到根目录后，相对路径 chdir_sub/rel 和绝对路径都应当指向同一个文件
+    assert_eq!(chdir("/\0"), 0);
+    let mut buffer = [0u8; 32];
+    for path in ["chdir_sub/rel\0", "/chdir_sub/rel\0"] {
+        let fd = open(path, OpenFlags::RDONLY);
+        assert!(fd > 0);
+        let fd = fd as usize;
+        let read_len = read(fd, &mut buffer) as usize;
+        close(fd);
+        assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    }
+    // 根目录下没有 rel
+    assert_eq!(open("rel\0", OpenFlags::RDONLY), -ENOENT);
+    // 不能 chdir 到普通文件或不存在的路径
+    assert_eq!(chdir("chdir_sub/rel\0"), -1);
+    assert_eq!(chdir("not_exist\0"), -1);
+    println!("Test chdir OK!");
+    0
+}
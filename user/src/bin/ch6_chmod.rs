@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{access, chmod, close, open, read, stat, truncate, unlink, write, AccessMode, OpenFlags, Stat, StatMode, EACCES, ENOENT};
+
+/// 测试chmod修改权限位后open/access按权限检查，输出　Test chmod OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fchmod\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"hello"), 5);
+    assert_eq!(access(fname, AccessMode::W_OK), 0);
+    // 改为只读之后，已经打开的fd仍然可写
+    assert_eq!(chmod(fname, 0o444), 0);
+    assert_eq!(write(fd as usize, b"!"), 1);
+    close(fd as usize);
+    let st = Stat::new();
+    assert_eq!(stat(fname, &st), 0);
+    assert_eq!(st.mode, StatMode::FILE | StatMode::from_bits_truncate(0o444));
+    // 以写方式打开、清空或截断只读文件都会失败，内容保持不变
+    assert_eq!(open(fname, OpenFlags::WRONLY), -EACCES);
+    assert_eq!(open(fname, OpenFlags::RDWR), -EACCES);
+    assert_eq!(open(fname, OpenFlags::CREATE | OpenFlags::WRONLY), -EACCES);
+    assert_eq!(truncate(fname, 0), -1);
+    assert_eq!(access(fname, AccessMode::W_OK), -1);
+    assert_eq!(access(fname, AccessMode::R_OK), 0);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 8];
+    assert_eq!(read(fd as usize, &mut buffer), 6);
+    assert_eq!(&buffer[..6], b"hello!");
+    close(fd as usize);
+    // 去掉读权限后不能读打开，加上执行权限后access(X_OK)成功
+    assert_eq!(chmod(fname, 0o300), 0);
+    assert_eq!(open(fname, OpenFlags::RDONLY), -EACCES);
+    assert_eq!(access(fname, AccessMode::X_OK | AccessMode::W_OK), 0);
+    assert_eq!(chmod(fname, 0o644), 0);
+    let fd = open(fname, OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(chmod("not_exist\0", 0o644), -ENOENT);
+    unlink(fname);
+    println!("Test chmod OK!");
+    0
+}
@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, open, read, unlink, write, OpenFlags};
+
+/// 测试不调用sync，关闭文件后重新打开仍能读到写入的数据，输出　Test close flush OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fclose_flush\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let copy = dup(fd);
+    assert!(copy > 0);
+    assert_eq!(write(fd, b"written "), 8);
+    // 关闭其中一个fd之后另一个仍然可以继续写
+    close(fd);
+    assert_eq!(write(copy as usize, b"before close"), 12);
+    close(copy as usize);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buffer), 20);
+    assert_eq!(&buffer[..20], b"written before close");
+    close(fd as usize);
+    unlink(fname);
+    println!("Test close flush OK!");
+    0
+}
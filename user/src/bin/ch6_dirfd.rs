@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, linkat, mkdir, mkdirat, open, stat, unlinkat, OpenFlags, Stat, StatMode, AT_FDCWD,
+    EBADF, ENOENT, ENOTDIR,
+};
+
+/// 测试通过目录的fd在子目录中创建和删除目录项，输出　Test dirfd OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/sub\0"), 0);
+    let dirfd = open("/sub\0", OpenFlags::RDONLY);
+    assert!(dirfd > 0);
+    let st = Stat::new();
+
+    // 相对于/sub创建目录
+    assert_eq!(mkdirat(dirfd, "d\0"), 0);
+    assert_eq!(stat("/sub/d\0", &st), 0);
+    assert_eq!(st.mode - StatMode::PERM, StatMode::DIR);
+
+    // 在/sub中创建硬链接，以及从根目录链接到/sub中
+    let fd = open("/sub/f\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(stat("/sub/f\0", &st), 0);
+    let ino = st.ino;
+    assert_eq!(linkat(dirfd, "f\0", dirfd, "g\0"), 0);
+    assert_eq!(linkat(dirfd, "f\0", AT_FDCWD, "/fdirfd\0"), 0);
+    assert_eq!(stat("/sub/g\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(stat("/fdirfd\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(linkat(dirfd, "missing\0", dirfd, "h\0"), -ENOENT);
+
+    // 删除/sub中的目录项
+    assert_eq!(unlinkat(dirfd, "g\0"), 0);
+    assert_eq!(stat("/sub/g\0", &st), -1);
+    assert_eq!(unlinkat(dirfd, "g\0"), -ENOENT);
+    assert_eq!(unlinkat(AT_FDCWD, "/fdirfd\0"), 0);
+    assert_eq!(stat("/sub/f\0", &st), 0);
+
+    // 普通文件不能作为dirfd，没有打开的fd也不行
+    let fd = open("/sub/f\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(mkdirat(fd, "x\0"), -ENOTDIR);
+    close(fd as usize);
+    assert_eq!(unlinkat(fd, "f\0"), -EBADF);
+    assert_eq!(unlinkat(dirfd, "f\0"), 0);
+    close(dirfd as usize);
+    println!("Test dirfd OK!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup2, open, read, write, OpenFlags};
+
+/// 测试 dup/dup2 以及把标准输出重定向到文件，输出　Test dup OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fdup\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+
+    // dup出来的fd与原fd共享偏移，两次写入应当首尾相接
+    let fd2 = dup(fd);
+    assert!(fd2 > 0);
+    let fd2 = fd2 as usize;
+    assert_eq!(write(fd, b"abc"), 3);
+    assert_eq!(write(fd2, b"def"), 3);
+    close(fd2);
+
+    // 把标准输出重定向到文件，再恢复
+    let saved = dup(1);
+    assert!(saved > 0);
+    let saved = saved as usize;
+    assert_eq!(dup2(fd, 1), 1);
+    println!("redirected");
+    assert_eq!(dup2(saved, 1), 1);
+    close(saved);
+    close(fd);
+    assert_eq!(dup(fd), -1);
+    assert_eq!(dup2(fd, 1), -1);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 64];
+    let read_len = read(fd, &mut buffer) as usize;
+    close(fd);
+    assert_eq!(
+        "abcdefredirected\n",
+        core::str::from_utf8(&buffer[..read_len]).unwrap()
+    );
+    println!("Test dup OK!");
+    0
+}
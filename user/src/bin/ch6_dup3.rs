@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup2, dup3, exec, fork, open, read, waitpid, write, OpenFlags};
+
+/// 测试dup3：oldfd与newfd相同或flags非法时失败，设置了CLOEXEC的fd在exec之后被关闭，
+/// 没有设置的fd仍然可用，输出　Test dup3 OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fdup3\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(dup3(fd, fd, OpenFlags::CLOEXEC), -1);
+    assert_eq!(dup3(fd, 21, OpenFlags::APPEND), -1);
+    assert_eq!(dup3(99, 21, OpenFlags::CLOEXEC), -1);
+    // 20号fd在exec之后保留，21号fd在exec时被关闭
+    assert_eq!(dup2(fd, 20), 20);
+    assert_eq!(dup3(fd, 21, OpenFlags::CLOEXEC), 21);
+    close(fd);
+
+    let pid = fork();
+    if pid == 0 {
+        // 子进程在exec之前仍然可以使用21号fd
+        assert_eq!(write(21, b"fork "), 5);
+        exec("ch6_dup3_child\0", &[0 as *const u8]);
+        panic!("exec ch6_dup3_child failed!");
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // 父进程自己没有exec，21号fd不受影响
+    assert_eq!(write(21, b"parent"), 6);
+    close(20);
+    close(21);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 32];
+    assert_eq!(read(fd as usize, &mut buffer), 15);
+    assert_eq!(&buffer[..15], b"fork execparent");
+    close(fd as usize);
+    println!("Test dup3 OK!");
+    0
+}
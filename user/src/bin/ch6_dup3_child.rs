@@ -0,0 +1,19 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::write;
+
+/// 由ch6_dup3在fork之后exec：20号fd应当仍然打开，设置了CLOEXEC的21号fd应当已经关闭
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if write(21, b"leaked") != -1 {
+        return 1;
+    }
+    if write(20, b"exec") != 4 {
+        return 2;
+    }
+    0
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fstat, link, open, unlink, OpenFlags, Stat, EBADF, EEXIST, ENOENT};
+
+/// 测试文件系统相关系统调用返回具体的错误码，输出　Test errno OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(open("ferrno_missing\0", OpenFlags::RDONLY), -ENOENT);
+
+    let fd = open("ferrno_a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let fd = open("ferrno_b\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(link("ferrno_a\0", "ferrno_b\0"), -EEXIST);
+    assert_eq!(link("ferrno_missing\0", "ferrno_c\0"), -ENOENT);
+    assert_eq!(unlink("ferrno_missing\0"), -ENOENT);
+
+    let st = Stat::new();
+    assert_eq!(fstat(fd as usize, &st), -EBADF);
+    assert_eq!(fstat(1000, &st), -EBADF);
+
+    assert_eq!(unlink("ferrno_a\0"), 0);
+    assert_eq!(unlink("ferrno_b\0"), 0);
+    println!("Test errno OK!");
+    0
+}
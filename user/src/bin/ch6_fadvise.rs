@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fadvise, lseek, open, pipe, read, unlink, write, OpenFlags, EBADF, EINVAL, POSIX_FADV_NORMAL,
+    POSIX_FADV_RANDOM, POSIX_FADV_SEQUENTIAL, POSIX_FADV_WILLNEED, SEEK_SET,
+};
+
+/// 测试fadvise：各种建议下跳着读文件得到的内容都不变，输出　Test fadvise OK! 就算正确。
+
+const BLOCK: usize = 512;
+const BLOCKS: usize = 24;
+
+/// 隔一个块读一次每个块开头的16字节
+fn strided_read(fd: usize) {
+    let mut buf = [0u8; 16];
+    for block in (0..BLOCKS).step_by(2) {
+        assert_eq!(lseek(fd, (block * BLOCK) as isize, SEEK_SET), (block * BLOCK) as isize);
+        assert_eq!(read(fd, &mut buf), 16);
+        assert_eq!(buf, [block as u8; 16]);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("ffadvise\0", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    for block in 0..BLOCKS {
+        assert_eq!(write(fd as usize, &[block as u8; BLOCK]), BLOCK as isize);
+    }
+    close(fd as usize);
+
+    let fd = open("ffadvise\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_RANDOM), 0);
+    strided_read(fd);
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_SEQUENTIAL), 0);
+    strided_read(fd);
+    assert_eq!(fadvise(fd, 4 * BLOCK, 4 * BLOCK, POSIX_FADV_WILLNEED), 0);
+    // 超出文件末尾的范围被忽略
+    assert_eq!(fadvise(fd, 100 * BLOCK, 0, POSIX_FADV_WILLNEED), 0);
+    strided_read(fd);
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_NORMAL), 0);
+    strided_read(fd);
+    // 未知的建议、无效的fd、不是磁盘文件
+    assert_eq!(fadvise(fd, 0, 0, 100), -EINVAL);
+    close(fd);
+    assert_eq!(fadvise(fd, 0, 0, POSIX_FADV_RANDOM), -EBADF);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(fadvise(pipe_fd[0], 0, 0, POSIX_FADV_SEQUENTIAL), -EINVAL);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    unlink("ffadvise\0");
+    println!("Test fadvise OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fallocate, lseek, open, read, unlink, write, OpenFlags, EBADF, EINVAL, FALLOC_FL_KEEP_SIZE,
+    FALLOC_FL_PUNCH_HOLE, SEEK_END, SEEK_SET,
+};
+
+/// 测试 fallocate 打洞：5个块的文件打掉中间两个块，输出　Test fallocate OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fhole\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let block = [b'h'; 512];
+    for _ in 0..5 {
+        assert_eq!(write(fd, &block), 512);
+    }
+    let punch = FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE;
+    assert_eq!(fallocate(fd, punch, 512, 1024), 0);
+    // 大小不变，空洞读出来都是0，前后的数据不变
+    assert_eq!(lseek(fd, 0, SEEK_END), 5 * 512);
+    lseek(fd, 0, SEEK_SET);
+    let mut buffer = [0u8; 512];
+    for i in 0..5 {
+        assert_eq!(read(fd, &mut buffer), 512);
+        let expected = if i == 1 || i == 2 { 0 } else { b'h' };
+        assert!(buffer.iter().all(|&b| b == expected));
+    }
+    // 打洞必须同时保持文件大小
+    assert_eq!(fallocate(fd, FALLOC_FL_PUNCH_HOLE, 0, 512), -EINVAL);
+    // mode为0时预分配并扩展文件
+    assert_eq!(fallocate(fd, 0, 0, 8 * 512), 0);
+    assert_eq!(lseek(fd, 0, SEEK_END), 8 * 512);
+    close(fd);
+    let ro = open("fhole\0", OpenFlags::RDONLY);
+    assert!(ro > 0);
+    assert_eq!(fallocate(ro as usize, punch, 0, 512), -EBADF);
+    close(ro as usize);
+    unlink("fhole\0");
+    println!("Test fallocate OK!");
+    0
+}
@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, dup, exit, flock, fork, open, pread, pwrite, unlink, waitpid, yield_, OpenFlags, EBADF, EWOULDBLOCK, LOCK_EX,
+    LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+/// 测试flock：两个进程在排它锁保护下交替累加文件中的计数器不会丢失更新，共享锁可以同时持有，
+/// 输出　Test flock OK! 就算正确。
+
+const FNAME: &str = "fflock\0";
+const ROUNDS: usize = 20;
+
+fn read_counter(fd: usize) -> usize {
+    let mut buf = [0u8; 8];
+    assert_eq!(pread(fd, &mut buf, 0), 8);
+    usize::from_le_bytes(buf)
+}
+
+/// 在排它锁内读出计数器、让出CPU、再写回加一后的值
+fn increment(rounds: usize) {
+    let fd = open(FNAME, OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    for _ in 0..rounds {
+        assert_eq!(flock(fd, LOCK_EX), 0);
+        let counter = read_counter(fd);
+        yield_();
+        assert_eq!(pwrite(fd, &(counter + 1).to_le_bytes(), 0), 8);
+        assert_eq!(flock(fd, LOCK_UN), 0);
+        yield_();
+    }
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FNAME, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    assert_eq!(pwrite(fd as usize, &0usize.to_le_bytes(), 0), 8);
+    close(fd as usize);
+
+    // 同一进程中两次打开是两个持有者，同一次打开dup出的fd是同一个持有者
+    let a = open(FNAME, OpenFlags::RDONLY) as usize;
+    let b = open(FNAME, OpenFlags::RDONLY) as usize;
+    assert_eq!(flock(a, LOCK_EX), 0);
+    assert_eq!(flock(b, LOCK_EX | LOCK_NB), -EWOULDBLOCK);
+    assert_eq!(flock(b, LOCK_SH | LOCK_NB), -EWOULDBLOCK);
+    assert_eq!(flock(a, LOCK_UN), 0);
+    assert_eq!(flock(a, LOCK_SH), 0);
+    assert_eq!(flock(b, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(b, LOCK_EX | LOCK_NB), -EWOULDBLOCK);
+    let a2 = dup(a) as usize;
+    assert_eq!(flock(a2, LOCK_SH | LOCK_NB), 0);
+    // 另一个进程也可以同时持有共享锁，但拿不到排它锁
+    let pid = fork();
+    if pid == 0 {
+        let c = open(FNAME, OpenFlags::RDONLY) as usize;
+        if flock(c, LOCK_SH | LOCK_NB) != 0 || flock(c, LOCK_EX | LOCK_NB) != -EWOULDBLOCK {
+            exit(1);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // 关闭持有者的所有fd时释放它的锁
+    close(a);
+    assert_eq!(flock(b, LOCK_EX | LOCK_NB), -EWOULDBLOCK);
+    close(a2);
+    assert_eq!(flock(b, LOCK_EX | LOCK_NB), 0);
+    close(b);
+    assert_eq!(flock(b, LOCK_UN), -EBADF);
+
+    // 两个进程争用排它锁，计数器不会丢失更新
+    let pid = fork();
+    if pid == 0 {
+        increment(ROUNDS);
+        exit(0);
+    }
+    increment(ROUNDS);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    let fd = open(FNAME, OpenFlags::RDONLY) as usize;
+    assert_eq!(read_counter(fd), 2 * ROUNDS);
+    close(fd);
+    unlink(FNAME);
+    println!("Test flock OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, lseek, open, read, unlink, waitpid, write, OpenFlags, SEEK_CUR, SEEK_SET};
+
+/// 测试fork之后父子进程共享fd的读写位置，输出　Test fork offset OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "ffork_offset\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"0123456789"), 10);
+    close(fd as usize);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 2];
+    assert_eq!(read(fd, &mut buffer), 2);
+    let pid = fork();
+    if pid == 0 {
+        // 子进程继承了父进程的位置，seek之后父进程也能看到
+        assert_eq!(lseek(fd, 0, SEEK_CUR), 2);
+        assert_eq!(lseek(fd, 6, SEEK_SET), 6);
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(read(fd, &mut buffer), 2);
+    assert_eq!(&buffer, b"67");
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 8);
+    close(fd);
+    unlink(fname);
+    println!("Test fork offset OK!");
+    0
+}
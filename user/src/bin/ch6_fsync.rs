@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fsync, open, pipe, read, write, OpenFlags};
+
+/// 测试只同步一个文件，输出　Test fsync OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "ffsync\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let block = [0x37u8; 512];
+    for _ in 0..3 {
+        assert_eq!(write(fd as usize, &block), 512);
+    }
+    assert_eq!(fsync(fd as usize), 0);
+    close(fd as usize);
+    assert_eq!(fsync(fd as usize), -1);
+    assert_eq!(fsync(1000), -1);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 512];
+    let mut total = 0;
+    loop {
+        let len = read(fd as usize, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        assert!(buffer[..len as usize].iter().all(|byte| *byte == 0x37));
+        total += len as usize;
+    }
+    close(fd as usize);
+    assert_eq!(total, 3 * 512);
+
+    // 管道不是磁盘上的文件
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(fsync(pipe_fd[0]), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    println!("Test fsync OK!");
+    0
+}
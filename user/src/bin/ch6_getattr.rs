@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chmod, close, fallocate, fstat, getattr, link, open, unlink, write, OpenFlags, Stat, StatMode, XAttr, ENOENT,
+    FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE,
+};
+
+/// 测试 getattr 一次取得inode的全部元数据，输出　Test getattr OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fgetattr\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // 4个块的文件打掉第2个块，再建立一个硬链接、修改权限
+    for _ in 0..4 {
+        assert_eq!(write(fd, &[b'x'; 512]), 512);
+    }
+    assert_eq!(fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE, 512, 512), 0);
+    let st = Stat::new();
+    assert_eq!(fstat(fd, &st), 0);
+    close(fd);
+    assert_eq!(link("fgetattr\0", "fgetattr_link\0"), 0);
+    assert_eq!(chmod("fgetattr\0", 0o600), 0);
+
+    let mut attr = XAttr::new();
+    assert_eq!(getattr("fgetattr_link\0", &mut attr), 0);
+    assert_eq!(attr.ino, st.ino);
+    assert_eq!(attr.mode, StatMode::FILE | StatMode::from_bits_truncate(0o600));
+    assert_eq!(attr.nlink, 2);
+    assert_eq!(attr.size, 4 * 512);
+    assert_eq!(attr.blocks, 3);
+    assert_eq!(attr.perm, 0o600);
+    // easy-fs不记录时间戳
+    assert_eq!((attr.atime, attr.mtime, attr.ctime), (0, 0, 0));
+
+    let mut attr = XAttr::new();
+    assert_eq!(getattr("/\0", &mut attr), 0);
+    assert_eq!(attr.ino, 0);
+    assert!(attr.mode.contains(StatMode::DIR));
+    assert_eq!(attr.perm, 0o755);
+    assert_eq!(getattr("fgetattr_missing\0", &mut attr), -ENOENT);
+
+    unlink("fgetattr_link\0");
+    unlink("fgetattr\0");
+    println!("Test getattr OK!");
+    0
+}
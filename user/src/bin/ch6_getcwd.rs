@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, getcwd, mkdir};
+
+/// 测试切换工作目录后 getcwd 返回绝对路径，输出　Test getcwd OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buffer = [0u8; 32];
+    assert_eq!(getcwd(&mut buffer), 2);
+    assert_eq!(&buffer[..2], b"/\0");
+
+    assert_eq!(mkdir("a\0"), 0);
+    assert_eq!(mkdir("a/b\0"), 0);
+    assert_eq!(chdir("/a/b\0"), 0);
+    assert_eq!(getcwd(&mut buffer), 5);
+    assert_eq!(&buffer[..5], b"/a/b\0");
+    // 缓冲区放不下路径和结尾的'\0'
+    assert_eq!(getcwd(&mut buffer[..4]), -1);
+
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(getcwd(&mut buffer), 3);
+    assert_eq!(&buffer[..3], b"/a\0");
+    assert_eq!(chdir("/\0"), 0);
+    println!("Test getcwd OK!");
+    0
+}
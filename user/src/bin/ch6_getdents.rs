@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getdents, open, rewinddir, OpenFlags};
+
+/// 测试 getdents 列出根目录，输出　Test getdents OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("/\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 1024];
+    let len = getdents(fd, &mut buffer);
+    assert!(len > 0);
+    // 太小的缓冲区连一项都放不下
+    assert_eq!(rewinddir(fd), 0);
+    assert_eq!(getdents(fd, &mut buffer[..4]), -1);
+    close(fd);
+
+    // name_len(u8) | name | inode_id(u32) | type(u8)
+    let mut pos = 0usize;
+    let mut count = 0usize;
+    while pos < len as usize {
+        let name_len = buffer[pos] as usize;
+        let name = core::str::from_utf8(&buffer[pos + 1..pos + 1 + name_len]).unwrap();
+        let mut ino = [0u8; 4];
+        ino.copy_from_slice(&buffer[pos + 1 + name_len..pos + 5 + name_len]);
+        let is_dir = buffer[pos + 5 + name_len] != 0;
+        println!("{} ino={} dir={}", name, u32::from_le_bytes(ino), is_dir);
+        pos += name_len + 6;
+        count += 1;
+    }
+    assert!(count > 0);
+    println!("Test getdents OK!");
+    0
+}
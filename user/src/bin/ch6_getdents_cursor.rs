@@ -0,0 +1,79 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getdents, mkdir, open, rewinddir, seekdir, unlink, OpenFlags, ENOTDIR};
+
+/// 测试 getdents 分多次读取目录时既不遗漏也不重复，输出　Test getdents cursor OK! 就算正确。
+
+const NAMES: [&str; 8] = ["f0\0", "f1\0", "f2\0", "f3\0", "f4\0", "f5\0", "f6\0", "f7\0"];
+
+/// 把getdents的结果中的名字追加到names中，返回追加的个数
+/// name_len(u8) | name | inode_id(u32) | type(u8)
+fn collect<'a>(buffer: &'a [u8], names: &mut [&'a [u8]; 16], count: &mut usize) -> usize {
+    let mut pos = 0usize;
+    let mut added = 0usize;
+    while pos < buffer.len() {
+        let name_len = buffer[pos] as usize;
+        names[*count] = &buffer[pos + 1..pos + 1 + name_len];
+        *count += 1;
+        added += 1;
+        pos += name_len + 6;
+    }
+    added
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    mkdir("/dcursor\0");
+    let mut path = [0u8; 16];
+    for name in NAMES.iter() {
+        let len = "/dcursor/".len();
+        path[..len].copy_from_slice(b"/dcursor/");
+        path[len..len + name.len()].copy_from_slice(name.as_bytes());
+        let path = core::str::from_utf8(&path[..len + name.len()]).unwrap();
+        let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+        assert!(fd > 0);
+        close(fd as usize);
+    }
+    // 删除的项留下空槽，位置按槽位计算，跳过空槽不会打乱位置
+    assert_eq!(unlink("/dcursor/f2\0"), 0);
+    assert_eq!(unlink("/dcursor/f5\0"), 0);
+
+    let fd = open("/dcursor\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // "."、".."以及6个文件，每次最多放下4项
+    let mut first = [0u8; 32];
+    let mut second = [0u8; 32];
+    let len1 = getdents(fd, &mut first);
+    let len2 = getdents(fd, &mut second);
+    assert!(len1 > 0 && len2 > 0);
+    let mut names: [&[u8]; 16] = [&[]; 16];
+    let mut count = 0usize;
+    assert_eq!(collect(&first[..len1 as usize], &mut names, &mut count), 4);
+    assert_eq!(collect(&second[..len2 as usize], &mut names, &mut count), 4);
+    let mut empty = [0u8; 32];
+    assert_eq!(getdents(fd, &mut empty), 0);
+    let expected: [&[u8]; 8] = [b".", b"..", b"f0", b"f1", b"f3", b"f4", b"f6", b"f7"];
+    for name in expected.iter() {
+        assert_eq!(names[..count].iter().filter(|n| *n == name).count(), 1);
+    }
+
+    // 回到开头之后重新读到第一批
+    assert_eq!(rewinddir(fd), 0);
+    let mut again = [0u8; 32];
+    assert_eq!(getdents(fd, &mut again), len1);
+    assert_eq!(again, first);
+    assert_eq!(seekdir(fd, 0), 0);
+    assert_eq!(getdents(fd, &mut again), len1);
+    close(fd);
+    let file = open("/dcursor/f0\0", OpenFlags::RDONLY);
+    assert!(file > 0);
+    assert_eq!(seekdir(file as usize, 0), -ENOTDIR);
+    close(file as usize);
+    println!("Test getdents cursor OK!");
+    0
+}
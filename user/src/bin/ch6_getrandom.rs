@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::getrandom;
+
+/// 测试getrandom：每次都填满整个缓冲区，连续两次得到的64字节不同，输出　Test getrandom OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut first = [0u8; 64];
+    let mut second = [0u8; 64];
+    assert_eq!(getrandom(&mut first), 64);
+    assert_eq!(getrandom(&mut second), 64);
+    assert_ne!(first, second);
+    assert!(first.iter().any(|&b| b != 0));
+    // 长度不是8的倍数时也要填满
+    let mut odd = [0u8; 13];
+    assert_eq!(getrandom(&mut odd), 13);
+    assert_eq!(getrandom(&mut []), 0);
+    println!("Test getrandom OK!");
+    0
+}
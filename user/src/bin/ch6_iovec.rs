@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, readv, writev, IoVec, OpenFlags};
+
+/// 测试用三段缓冲区写入文件，再用两段长度不同的缓冲区读回，输出　Test iovec OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fiovec\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let parts: [&[u8]; 3] = [b"Hello, ", b"scatter-", b"gather!"];
+    let iov = [IoVec::new(parts[0]), IoVec::new(parts[1]), IoVec::new(parts[2])];
+    assert_eq!(writev(fd as usize, &iov), 22);
+    close(fd as usize);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut first = [0u8; 5];
+    let mut second = [0u8; 32];
+    let iov = [IoVec::new_mut(&mut first), IoVec::new_mut(&mut second)];
+    assert_eq!(readv(fd as usize, &iov), 22);
+    close(fd as usize);
+    assert_eq!(&first, b"Hello");
+    assert_eq!(&second[..17], b", scatter-gather!");
+    println!("Test iovec OK!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, link, mkdir, open, stat, unlink, OpenFlags, Stat, ENOENT, ENOTDIR};
+
+/// 测试在子目录中创建和删除硬链接，输出　Test link subdir OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/lsub\0"), 0);
+    assert_eq!(mkdir("/lsub/inner\0"), 0);
+    let fd = open("/lsub/inner/a\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let st = Stat::new();
+    assert_eq!(stat("/lsub/inner/a\0", &st), 0);
+    let ino = st.ino;
+    assert_eq!(st.nlink, 1);
+
+    // 绝对路径，跨目录
+    assert_eq!(link("/lsub/inner/a\0", "/lsub/b\0"), 0);
+    assert_eq!(stat("/lsub/b\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    // 相对于当前工作目录
+    assert_eq!(chdir("/lsub\0"), 0);
+    assert_eq!(link("inner/a\0", "inner/c\0"), 0);
+    assert_eq!(stat("/lsub/inner/c\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(st.nlink, 3);
+
+    assert_eq!(unlink("b\0"), 0);
+    assert_eq!(stat("/lsub/b\0", &st), -1);
+    assert_eq!(unlink("/lsub/b\0"), -ENOENT);
+    assert_eq!(stat("/lsub/inner/a\0", &st), 0);
+    assert_eq!(st.nlink, 2);
+    assert_eq!(unlink("/lsub/inner/c\0"), 0);
+    assert_eq!(unlink("/nodir/x\0"), -ENOENT);
+    assert_eq!(unlink("/lsub/inner/a/x\0"), -ENOTDIR);
+    assert_eq!(stat("/lsub/inner/a\0", &st), 0);
+    assert_eq!(st.nlink, 1);
+    assert_eq!(chdir("/\0"), 0);
+    println!("Test link subdir OK!");
+    0
+}
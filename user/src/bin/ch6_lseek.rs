@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, write, OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET};
+
+/// 测试 lseek 的三种 whence 以及越过文件末尾写入，输出　Test lseek OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "flseek\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    let mut buffer = [0u8; 16];
+
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    assert_eq!(read(fd, &mut buffer[..3]), 3);
+    assert_eq!(&buffer[..3], b"234");
+
+    // 向后移动再读取
+    assert_eq!(lseek(fd, -2, SEEK_CUR), 3);
+    assert_eq!(read(fd, &mut buffer[..2]), 2);
+    assert_eq!(&buffer[..2], b"34");
+
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buffer), 3);
+    assert_eq!(&buffer[..3], b"789");
+
+    assert_eq!(lseek(fd, -1, SEEK_SET), -1);
+
+    // 越过文件末尾写入会留下空洞
+    assert_eq!(lseek(fd, 2, SEEK_END), 12);
+    assert_eq!(write(fd, b"x"), 1);
+    assert_eq!(lseek(fd, 10, SEEK_SET), 10);
+    assert_eq!(read(fd, &mut buffer), 3);
+    assert_eq!(&buffer[..3], b"\0\0x");
+    close(fd);
+    println!("Test lseek OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup2, open, unlink, OpenFlags, EMFILE, MAX_FD};
+
+/// 测试每个进程最多持有 MAX_FD 个文件描述符，输出　Test max fd OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fmax_fd\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    // 0、1、2是标准输入输出，其余的位置依次被打开的文件占满
+    let mut last = 0;
+    for expected in 3..MAX_FD {
+        let fd = open("fmax_fd\0", OpenFlags::RDONLY);
+        assert_eq!(fd, expected as isize);
+        last = fd as usize;
+    }
+    assert_eq!(open("fmax_fd\0", OpenFlags::RDONLY), -EMFILE);
+    assert_eq!(dup(last), -EMFILE);
+    assert_eq!(dup2(last, MAX_FD), -1);
+    // 关闭一个之后又能打开，得到的正是刚关闭的fd
+    assert_eq!(close(10), 0);
+    assert_eq!(open("fmax_fd\0", OpenFlags::RDONLY), 10);
+    for fd in 3..MAX_FD {
+        assert_eq!(close(fd), 0);
+    }
+    unlink("fmax_fd\0");
+    println!("Test max fd OK!");
+    0
+}
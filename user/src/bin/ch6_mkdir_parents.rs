@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, open, read, stat, write, OpenFlags, Stat, StatMode, ENOENT, ENOTDIR};
+
+/// 测试带 MKDIR_PARENTS 标志打开一个多层的新路径时，沿途缺失的目录都会被创建，输出　Test mkdir parents OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "/mp_a/mp_b/mp_c/file\0";
+    // 没有 MKDIR_PARENTS 时父目录不存在就失败
+    assert_eq!(open(path, OpenFlags::CREATE | OpenFlags::WRONLY), -ENOENT);
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::MKDIR_PARENTS);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"nested"), 6);
+    close(fd as usize);
+
+    let st = Stat::new();
+    for dir in ["/mp_a\0", "/mp_a/mp_b\0", "/mp_a/mp_b/mp_c\0"] {
+        assert_eq!(stat(dir, &st), 0);
+        assert_eq!(st.mode - StatMode::PERM, StatMode::DIR);
+    }
+    assert_eq!(chdir("/mp_a/mp_b\0"), 0);
+    let fd = open("mp_c/file\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buffer), 6);
+    assert_eq!(&buffer[..6], b"nested");
+    close(fd as usize);
+
+    // 路径中间的分量是普通文件时失败
+    assert_eq!(
+        open("mp_c/file/sub/x\0", OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::MKDIR_PARENTS),
+        -ENOTDIR
+    );
+    assert_eq!(chdir("/\0"), 0);
+    println!("Test mkdir parents OK!");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap};
+
+/// 测试start为0的mmap：内核挑选地址并返回它，两次映射得到的区域互不重叠且都可以读写，
+/// 指定地址的mmap仍然返回0，输出　Test mmap anywhere OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let page: usize = 4096;
+    let len = 3 * page;
+    let a = mmap(0, len, 3);
+    let b = mmap(0, len, 3);
+    assert!(a > 0 && b > 0);
+    let (a, b) = (a as usize, b as usize);
+    assert_eq!(a % page, 0);
+    assert_eq!(b % page, 0);
+    assert!(a + len <= b || b + len <= a);
+    for i in 0..3 {
+        let pa = (a + i * page) as *mut usize;
+        let pb = (b + i * page) as *mut usize;
+        unsafe {
+            *pa = i;
+            *pb = i + 100;
+            assert_eq!(*pa, i);
+            assert_eq!(*pb, i + 100);
+        }
+    }
+    // 解除映射之后空出来的区域可以再被挑中
+    assert_eq!(munmap(a, len), 0);
+    assert_eq!(mmap(0, len, 3), a as isize);
+    assert_eq!(mmap(0x10000000, page, 3), 0);
+    assert_eq!(munmap(0x10000000, page), 0);
+    println!("Test mmap anywhere OK!");
+    0
+}
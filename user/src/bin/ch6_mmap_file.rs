@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mmap_file, munmap, open, read, unlink, write, OpenFlags};
+
+/// 测试把文件私有地映射到内存：通过映射读到文件内容，写映射不影响文件，
+/// 输出　Test mmap file OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fmmap\0";
+    let page: usize = 4096;
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut data = [0u8; 512];
+    for i in 0..10 {
+        for (j, byte) in data.iter_mut().enumerate() {
+            *byte = (i * 512 + j) as u8 ^ 0x5a;
+        }
+        assert_eq!(write(fd, &data), 512);
+    }
+    close(fd);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let start: usize = 0x10000000;
+    // 偏移量必须页对齐
+    assert_eq!(mmap_file(start, page * 2, 3, fd, 1), -1);
+    assert_eq!(mmap_file(start, page * 2, 3, fd, 0), 0);
+    let mapped = start as *mut u8;
+    unsafe {
+        assert_eq!(*mapped, 0x5a);
+        assert_eq!(*mapped.add(page + 1), ((page + 1) as u8) ^ 0x5a);
+        // 文件只有5120字节，第二页超出文件末尾的部分为0
+        assert_eq!(*mapped.add(5120), 0);
+        // 私有映射，写入只修改内存中的副本
+        *mapped = 0;
+        assert_eq!(*mapped, 0);
+    }
+    assert_eq!(munmap(start, page * 2), 0);
+
+    let mut buffer = [0u8; 1];
+    assert_eq!(read(fd, &mut buffer), 1);
+    assert_eq!(buffer[0], 0x5a);
+
+    // 映射持有文件：关闭fd、删除文件之后，尚未访问的页仍然读到原来的内容
+    assert_eq!(mmap_file(start, page * 2, 1, fd, 0), 0);
+    close(fd);
+    assert_eq!(unlink(fname), 0);
+    // 拆开后剩下的一半同样持有文件
+    assert_eq!(munmap(start, page), 0);
+    unsafe {
+        assert_eq!(*mapped.add(page + 1), ((page + 1) as u8) ^ 0x5a);
+    }
+    assert_eq!(munmap(start + page, page), 0);
+    println!("Test mmap file OK!");
+    0
+}
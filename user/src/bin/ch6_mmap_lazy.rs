@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mmap, munmap, open, read, write, OpenFlags};
+
+/// 测试mmap的区域按需分配物理页：映射比物理内存还大的区域也能成功，只有被访问的页才会分配，
+/// 内核写入尚未访问的页之前也会先分配，munmap可以同时解除已分配和未分配的页，输出　Test mmap lazy OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start: usize = 0x10000000;
+    let page: usize = 4096;
+    // 64MiB远大于内核可用的物理内存，预先分配物理页的话一定会失败
+    let huge = 16384 * page;
+    assert_eq!(mmap(start, huge, 3), 0);
+    for i in [0usize, 5000, 16383].iter() {
+        let addr = (start + i * page) as *mut usize;
+        unsafe {
+            assert_eq!(*addr, 0);
+            *addr = *i;
+            assert_eq!(*addr, *i);
+        }
+    }
+    assert_eq!(munmap(start, huge), 0);
+
+    let fname = "fmmap_lazy\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"lazy"), 4);
+    close(fd as usize);
+    assert_eq!(mmap(start, 100 * page, 3), 0);
+    // read直接写入第50页，这一页此前从未被访问过
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let buf = unsafe { core::slice::from_raw_parts_mut((start + 50 * page) as *mut u8, 4) };
+    assert_eq!(read(fd as usize, buf), 4);
+    assert_eq!(buf, b"lazy");
+    close(fd as usize);
+    assert_eq!(munmap(start, 100 * page), 0);
+    assert_eq!(munmap(start, page), -1);
+    println!("Test mmap lazy OK!");
+    0
+}
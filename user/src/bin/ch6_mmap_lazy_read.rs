@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mmap, mmap_file, munmap, open, read, unlink, write, OpenFlags};
+
+/// 测试内核读取还没有被进程访问过的mmap页：write的源缓冲区和open的路径都可以放在这样的页中，
+/// 未映射的缓冲区返回-EFAULT而不是让内核崩溃，输出　Test mmap lazy read OK! 就算正确。
+
+const EFAULT: isize = -14;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let page: usize = 4096;
+    let src = "lazysrc\0";
+    let dst = "lazydst\0";
+    let fd = open(src, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, src.as_bytes()), src.len() as isize);
+    close(fd as usize);
+
+    // 匿名映射的页还没有分配物理页，内核读取时应读到全0
+    let anon = mmap(0, page, 3);
+    assert!(anon > 0);
+    let anon = anon as usize;
+    let fd = open(dst, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let lazy = unsafe { core::slice::from_raw_parts(anon as *const u8, 10) };
+    assert_eq!(write(fd as usize, lazy), 10);
+    close(fd as usize);
+    let fd = open(dst, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0xffu8; 16];
+    assert_eq!(read(fd as usize, &mut buffer), 10);
+    assert!(buffer[..10].iter().all(|&b| b == 0));
+    close(fd as usize);
+
+    // 路径本身保存在文件映射中，内核要先从文件读入这一页
+    let fd = open(src, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mapped = mmap_file(0, page, 1, fd as usize, 0);
+    assert!(mapped > 0);
+    close(fd as usize);
+    let path = unsafe {
+        core::str::from_utf8_unchecked(core::slice::from_raw_parts(mapped as *const u8, src.len()))
+    };
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+
+    // 解除映射之后同一个地址就不能再作为缓冲区了
+    assert_eq!(munmap(anon, page), 0);
+    let fd = open(dst, OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, lazy), EFAULT);
+    close(fd as usize);
+
+    assert_eq!(munmap(mapped as usize, page), 0);
+    assert_eq!(unlink(src), 0);
+    assert_eq!(unlink(dst), 0);
+    println!("Test mmap lazy read OK!");
+    0
+}
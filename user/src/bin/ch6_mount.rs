@@ -0,0 +1,84 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    chdir, close, flock, getcwd, link, mkdir, mount, open, read, truncate, umount, unlink, write, OpenFlags, EBUSY, EINVAL,
+    EXDEV, LOCK_EX, LOCK_NB,
+};
+
+/// 测试把 mnt.img（打包时生成的easy-fs镜像）挂载到 /mnt，输出　Test mount OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 第一次运行时创建挂载点，之后它已经存在
+    mkdir("/mnt\0");
+    let image = open("mnt.img\0", OpenFlags::RDWR);
+    assert!(image > 0);
+    let image = image as usize;
+    // 只读打开的镜像不能挂载，不是easy-fs的文件也不能
+    let ro = open("mnt.img\0", OpenFlags::RDONLY);
+    assert!(ro > 0);
+    assert!(mount(ro as usize, "/mnt\0") < 0);
+    close(ro as usize);
+    let fd = open("fnot_an_image\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    write(fd as usize, &[0u8; 1024]);
+    assert_eq!(mount(fd as usize, "/mnt\0"), -EINVAL);
+    close(fd as usize);
+    // 挂载失败的文件不会一直被钉住
+    assert_eq!(unlink("fnot_an_image\0"), 0);
+
+    assert_eq!(mount(image, "/mnt\0"), 0);
+    let fd = open("/mnt/hello\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 64];
+    let len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(&buffer[..len], b"hello from mnt.img\n");
+    // 挂载期间镜像文件不能被截断、清空或删除
+    assert_eq!(truncate("mnt.img\0", 0), -EBUSY);
+    assert_eq!(open("mnt.img\0", OpenFlags::WRONLY | OpenFlags::TRUNC), -EBUSY);
+    assert_eq!(unlink("mnt.img\0"), -EBUSY);
+    // 镜像中的文件还打开着，不能卸载
+    assert_eq!(umount("/mnt\0"), -EBUSY);
+    close(fd as usize);
+    // 不能在两个文件系统之间建立硬链接
+    assert_eq!(link("/mnt/hello\0", "/fmount_link\0"), -EXDEV);
+    // 在镜像中写入新文件，路径也跨越挂载点
+    let fd = open("/mnt/written\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"kept"), 4);
+    close(fd as usize);
+    assert_eq!(chdir("/mnt\0"), 0);
+    let len = getcwd(&mut buffer);
+    assert_eq!(len, 5);
+    assert_eq!(&buffer[..5], b"/mnt\0");
+    assert_eq!(umount("/mnt\0"), -EBUSY);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(umount("/mnt\0"), 0);
+    assert!(open("/mnt/hello\0", OpenFlags::RDONLY) < 0);
+    assert_eq!(umount("/mnt\0"), -EINVAL);
+
+    // 卸载时修改已经写回镜像文件，再次挂载后还能读到
+    assert_eq!(mount(image, "/mnt\0"), 0);
+    let fd = open("/mnt/written\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(&buffer[..len], b"kept");
+    close(fd as usize);
+    // "/"和"/mnt"的inode编号都是0，但在不同的设备上，各自的flock互不影响
+    let root = open("/\0", OpenFlags::RDONLY);
+    let mnt = open("/mnt\0", OpenFlags::RDONLY);
+    assert!(root > 0 && mnt > 0);
+    assert_eq!(flock(root as usize, LOCK_EX | LOCK_NB), 0);
+    assert_eq!(flock(mnt as usize, LOCK_EX | LOCK_NB), 0);
+    close(root as usize);
+    close(mnt as usize);
+    unlink("/mnt/written\0");
+    assert_eq!(umount("/mnt\0"), 0);
+    close(image);
+    println!("Test mount OK!");
+    0
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getdents, mkdir, open, unlink, OpenFlags, EINVAL, EISDIR, ENOENT};
+
+/// 测试 O_DIRECTORY：只能打开目录；目录不能以可写方式打开，输出　Test open directory OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fodir_file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(mkdir("odir\0"), 0);
+    // 普通文件不能以 DIRECTORY 打开，也不能以此新建
+    assert_eq!(open("fodir_file\0", OpenFlags::DIRECTORY), -1);
+    assert_eq!(open("fodir_missing\0", OpenFlags::CREATE | OpenFlags::DIRECTORY), -EINVAL);
+    assert_eq!(open("fodir_missing\0", OpenFlags::RDONLY), -ENOENT);
+    // 目录可以以 DIRECTORY 只读打开并列出
+    let fd = open("odir\0", OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 256];
+    assert!(getdents(fd as usize, &mut buffer) > 0);
+    close(fd as usize);
+    let fd = open("/\0", OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    close(fd as usize);
+    // 以可写方式打开目录失败，带不带 DIRECTORY 都一样，也不会清空目录
+    assert_eq!(open("odir\0", OpenFlags::WRONLY), -EISDIR);
+    assert_eq!(open("odir\0", OpenFlags::RDWR | OpenFlags::DIRECTORY), -EISDIR);
+    assert_eq!(open("odir\0", OpenFlags::CREATE | OpenFlags::WRONLY), -EISDIR);
+    assert_eq!(open("/\0", OpenFlags::RDWR), -EISDIR);
+    let fd = open("odir\0", OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    assert!(getdents(fd as usize, &mut buffer) > 0);
+    close(fd as usize);
+    unlink("fodir_file\0");
+    println!("Test open directory OK!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, symlink, unlink, OpenFlags, EEXIST};
+
+/// 测试CREATE|EXCL在名字已存在时失败，输出　Test open excl OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fexcl\0";
+    let flags = OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY;
+    unlink(fname);
+    // 连续两次CREATE|EXCL，只有第一次成功
+    let fd = open(fname, flags);
+    assert!(fd > 0);
+    assert_eq!(open(fname, flags), -EEXIST);
+    close(fd as usize);
+    // 不带EXCL时照常打开已存在的文件
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    // 指向不存在文件的符号链接也算已存在
+    let lname = "fexcl_link\0";
+    unlink(lname);
+    assert_eq!(symlink("fexcl_missing\0", lname), 0);
+    assert_eq!(open(lname, flags), -EEXIST);
+    unlink(lname);
+    unlink(fname);
+    let fd = open(fname, flags);
+    assert!(fd > 0);
+    close(fd as usize);
+    unlink(fname);
+    println!("Test open excl OK!");
+    0
+}
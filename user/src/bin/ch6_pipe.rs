@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, pipe, read, waitpid, write};
+
+/// 测试父进程通过管道向子进程传输几KB数据，输出　Test pipe OK! 就算正确。
+
+const LENGTH: usize = 4000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let pid = fork();
+    if pid == 0 {
+        // 子进程只读，关闭自己的写端
+        close(pipe_fd[1]);
+        let mut buffer = [0u8; 128];
+        let mut total = 0usize;
+        loop {
+            let len = read(pipe_fd[0], &mut buffer) as usize;
+            if len == 0 {
+                break;
+            }
+            for (i, byte) in buffer[..len].iter().enumerate() {
+                assert_eq!(*byte, ((total + i) % 251) as u8);
+            }
+            total += len;
+        }
+        close(pipe_fd[0]);
+        assert_eq!(total, LENGTH);
+        exit(0);
+    }
+    // 父进程只写，关闭自己的读端
+    close(pipe_fd[0]);
+    let mut data = [0u8; LENGTH];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+    assert_eq!(write(pipe_fd[1], &data), LENGTH as isize);
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("Test pipe OK!");
+    0
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, pipe, poll, read, waitpid, write, PollEvents, PollFd};
+
+/// 测试poll报告管道的读写就绪状态，输出　Test poll OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let (read_fd, write_fd) = (pipe_fd[0], pipe_fd[1]);
+    // 空管道：读端未就绪，写端有空位
+    let mut fds = [
+        PollFd::new(read_fd, PollEvents::POLLIN),
+        PollFd::new(write_fd, PollEvents::POLLOUT),
+    ];
+    assert_eq!(poll(&mut fds[..1], 0), 0);
+    assert!(fds[0].revents.is_empty());
+    assert_eq!(poll(&mut fds, 0), 1);
+    assert_eq!(fds[1].revents, PollEvents::POLLOUT);
+    // 超时后返回0
+    assert_eq!(poll(&mut fds[..1], 10), 0);
+    // 写入之后读端就绪
+    assert_eq!(write(write_fd, b"x"), 1);
+    assert_eq!(poll(&mut fds[..1], 0), 1);
+    assert_eq!(fds[0].revents, PollEvents::POLLIN);
+    let mut buffer = [0u8; 1];
+    assert_eq!(read(read_fd, &mut buffer), 1);
+    assert_eq!(poll(&mut fds[..1], 0), 0);
+    // 另一个进程稍后写入，一直等待的poll被唤醒
+    let pid = fork();
+    if pid == 0 {
+        close(read_fd);
+        assert_eq!(write(write_fd, b"y"), 1);
+        close(write_fd);
+        return 0;
+    }
+    close(write_fd);
+    assert_eq!(poll(&mut fds[..1], -1), 1);
+    assert_eq!(read(read_fd, &mut buffer), 1);
+    assert_eq!(&buffer, b"y");
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    // 所有写端都关闭后读端一直就绪（read返回0）
+    assert_eq!(poll(&mut fds[..1], 0), 1);
+    assert_eq!(read(read_fd, &mut buffer), 0);
+    close(read_fd);
+    // 未打开的fd报告POLLNVAL
+    assert_eq!(poll(&mut fds[..1], 0), 1);
+    assert_eq!(fds[0].revents, PollEvents::POLLNVAL);
+    println!("Test poll OK!");
+    0
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, pipe, pread, pwrite, read, write, OpenFlags, SEEK_CUR};
+
+/// 测试pread/pwrite在指定的偏移处读写，且不影响fd自身的读写位置，输出　Test pread OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fpread\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789abcdef"), 16);
+    // pwrite不移动fd的位置
+    assert_eq!(pwrite(fd, b"XY", 4), 2);
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 16);
+    // 只写打开的fd不能pread
+    let mut buffer = [0u8; 4];
+    assert_eq!(pread(fd, &mut buffer, 0), -1);
+    close(fd);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // 两次pread互不影响，顺序无关
+    assert_eq!(pread(fd, &mut buffer, 10), 4);
+    assert_eq!(&buffer, b"abcd");
+    assert_eq!(pread(fd, &mut buffer, 2), 4);
+    assert_eq!(&buffer, b"23XY");
+    assert_eq!(pread(fd, &mut buffer, 10), 4);
+    assert_eq!(&buffer, b"abcd");
+    // 超过文件末尾只读到剩下的部分
+    assert_eq!(pread(fd, &mut buffer, 14), 2);
+    assert_eq!(&buffer[..2], b"ef");
+    assert_eq!(pread(fd, &mut buffer, 16), 0);
+    // fd自身的位置仍然从0开始
+    assert_eq!(read(fd, &mut buffer), 4);
+    assert_eq!(&buffer, b"0123");
+    assert_eq!(pwrite(fd, b"no", 0), -1);
+    close(fd);
+
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(pread(pipe_fd[0], &mut buffer, 0), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    assert_eq!(pread(fd, &mut buffer, 0), -1);
+    println!("Test pread OK!");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, setquota, unlink, write, OpenFlags, ENOENT, ENOSPC, ENOTDIR};
+
+/// 测试目录的块配额：/limited 最多占用10个块，输出　Test quota OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 第一次运行时创建目录，之后它已经存在
+    mkdir("/limited\0");
+    assert_eq!(setquota("/limited\0", 10), 0);
+    let fd = open("/limited/f\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    // 目录自己的数据块占了1个，文件还能写9个块，第11个块超出配额
+    let block = [b'q'; 512];
+    for _ in 0..9 {
+        assert_eq!(write(fd, &block), 512);
+    }
+    assert_eq!(write(fd, &block), -ENOSPC);
+    // 配额之外的地方仍然可以写
+    let other = open("fquota_other\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(other > 0);
+    assert_eq!(write(other as usize, &block), 512);
+    close(other as usize);
+    unlink("fquota_other\0");
+    assert_eq!(setquota("/limited/f\0", 1), -ENOTDIR);
+    assert_eq!(setquota("/no_such_dir\0", 1), -ENOENT);
+    // 取消配额之后可以继续写
+    assert_eq!(setquota("/limited\0", -1), 0);
+    assert_eq!(write(fd, &block), 512);
+    close(fd);
+    unlink("/limited/f\0");
+    println!("Test quota OK!");
+    0
+}
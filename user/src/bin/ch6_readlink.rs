@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, readlink, symlink, OpenFlags};
+
+/// 测试读取符号链接保存的路径，以及对普通文件调用readlink失败，输出　Test readlink OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "freadlink_target\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(symlink(fname, "freadlink\0"), 0);
+
+    let mut buffer = [0u8; 32];
+    assert_eq!(readlink("freadlink\0", &mut buffer), 16);
+    assert_eq!(&buffer[..16], b"freadlink_target");
+    // 缓冲区不够时只拷贝能放下的部分，返回值仍是完整长度
+    let mut short = [0u8; 4];
+    assert_eq!(readlink("freadlink\0", &mut short), 16);
+    assert_eq!(&short, b"frea");
+    // readlink不跟随链接，通过链接打开的仍是目标文件
+    let fd = open("freadlink\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buffer), 0);
+    close(fd as usize);
+
+    assert_eq!(readlink(fname, &mut buffer), -1);
+    assert_eq!(readlink("freadlink_missing\0", &mut buffer), -1);
+    println!("Test readlink OK!");
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, getdents, mkdir, open, read, rename, stat, write, OpenFlags, Stat, ENOENT};
+
+/// 测试重命名文件，输出　Test rename OK! 就算正确。
+
+/// 返回根目录下的目录项个数，以及其中是否有名为name的项
+fn list_root(name: &str) -> (usize, bool) {
+    let fd = open("/\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 2048];
+    let len = getdents(fd as usize, &mut buffer);
+    close(fd as usize);
+    assert!(len > 0);
+    // name_len(u8) | name | inode_id(u32) | type(u8)
+    let mut pos = 0usize;
+    let mut count = 0usize;
+    let mut found = false;
+    while pos < len as usize {
+        let name_len = buffer[pos] as usize;
+        if &buffer[pos + 1..pos + 1 + name_len] == name.as_bytes() {
+            found = true;
+        }
+        pos += name_len + 6;
+        count += 1;
+    }
+    (count, found)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let content = "rename me";
+    let fd = open("/a.txt\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, content.as_bytes());
+    close(fd as usize);
+    let st = Stat::new();
+    assert_eq!(stat("/a.txt\0", &st), 0);
+    let ino = st.ino;
+    let (count, _) = list_root("a.txt");
+
+    assert_eq!(rename("/a.txt\0", "/b.txt\0"), 0);
+    assert_eq!(list_root("a.txt"), (count, false));
+    assert_eq!(list_root("b.txt"), (count, true));
+    assert_eq!(open("/a.txt\0", OpenFlags::RDONLY), -ENOENT);
+    // 仍是同一个inode，没有新分配也没有释放
+    assert_eq!(stat("/b.txt\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(st.nlink, 1);
+    let fd = open("/b.txt\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 32];
+    let len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(core::str::from_utf8(&buffer[..len]).unwrap(), content);
+
+    // 源不存在、目标的父目录不存在
+    assert_eq!(rename("/a.txt\0", "/c.txt\0"), -1);
+    assert_eq!(rename("/b.txt\0", "/nodir/c.txt\0"), -1);
+    // 移动到目录下，以及不能把目录移动到它自己之下
+    assert_eq!(mkdir("drename\0"), 0);
+    assert_eq!(rename("b.txt\0", "drename/b.txt\0"), 0);
+    assert_eq!(stat("/drename/b.txt\0", &st), 0);
+    assert_eq!(st.ino, ino);
+    assert_eq!(rename("drename\0", "drename/inner\0"), -1);
+    println!("Test rename OK!");
+    0
+}
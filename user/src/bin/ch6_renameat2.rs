@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, open, read, renameat2, stat, unlink, write, OpenFlags, Stat, EEXIST, EINVAL, RENAME_EXCHANGE,
+    RENAME_NOREPLACE,
+};
+
+/// 测试renameat2的RENAME_NOREPLACE和RENAME_EXCHANGE，输出　Test renameat2 OK! 就算正确。
+
+fn write_file(path: &str, content: &[u8]) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, content), content.len() as isize);
+    close(fd as usize);
+}
+
+fn read_file<'a>(path: &str, buffer: &'a mut [u8]) -> &'a [u8] {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let len = read(fd as usize, buffer) as usize;
+    close(fd as usize);
+    &buffer[..len]
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    write_file("fren2_a\0", b"first");
+    write_file("fren2_b\0", b"second");
+    let st = Stat::new();
+    assert_eq!(stat("fren2_a\0", &st), 0);
+    let ino_a = st.ino;
+    assert_eq!(stat("fren2_b\0", &st), 0);
+    let ino_b = st.ino;
+    let mut buffer = [0u8; 32];
+
+    // 目标已存在时不覆盖
+    assert_eq!(renameat2("fren2_a\0", "fren2_b\0", RENAME_NOREPLACE), -EEXIST);
+    assert_eq!(read_file("fren2_b\0", &mut buffer), b"second");
+    assert_eq!(renameat2("fren2_a\0", "fren2_c\0", RENAME_NOREPLACE), 0);
+    assert!(open("fren2_a\0", OpenFlags::RDONLY) < 0);
+
+    // 交换后两个名字各自指向对方原来的内容（和inode）
+    assert_eq!(renameat2("fren2_c\0", "fren2_b\0", RENAME_EXCHANGE), 0);
+    assert_eq!(read_file("fren2_c\0", &mut buffer), b"second");
+    assert_eq!(read_file("fren2_b\0", &mut buffer), b"first");
+    assert_eq!(stat("fren2_b\0", &st), 0);
+    assert_eq!(st.ino, ino_a);
+    assert_eq!(stat("fren2_c\0", &st), 0);
+    assert_eq!(st.ino, ino_b);
+    // 交换的目标必须存在，两个flag不能同时使用
+    assert!(renameat2("fren2_c\0", "fren2_none\0", RENAME_EXCHANGE) < 0);
+    assert_eq!(renameat2("fren2_c\0", "fren2_b\0", RENAME_NOREPLACE | RENAME_EXCHANGE), -EINVAL);
+
+    unlink("fren2_b\0");
+    unlink("fren2_c\0");
+    println!("Test renameat2 OK!");
+    0
+}
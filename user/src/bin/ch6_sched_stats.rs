@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, getpid, sched_stats, set_priority, waitpid, SchedStat};
+
+/*
+三个优先级分别为2、4、8的子进程同时运行，每个进程被调度的次数应当基本正比于优先级，
+输出　Test sched stats OK! 就算正确。
+*/
+
+const MAX_TIME: isize = 2000;
+const PRIORITIES: [isize; 3] = [2, 4, 8];
+
+/// The scheduling state of current task
+fn my_stat() -> SchedStat {
+    let mut stats = [SchedStat::default(); 1];
+    assert!(sched_stats(&mut stats) >= 1);
+    assert_eq!(stats[0].pid, getpid() as usize);
+    stats[0]
+}
+
+fn run_during(prio: isize, start_time: isize) -> usize {
+    set_priority(prio);
+    let before = my_stat().run_count;
+    while get_time() - start_time <= MAX_TIME {}
+    let stat = my_stat();
+    assert_eq!(stat.prio, prio as usize);
+    stat.run_count - before
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 父进程使用最高优先级，尽快把三个子进程都创建出来
+    set_priority(64);
+    let start_time = get_time();
+    let mut pids = [0isize; 3];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            exit(run_during(*prio, start_time) as i32);
+        }
+        pids[i] = pid;
+    }
+    let parent = my_stat();
+    assert_eq!(parent.prio, 64);
+    assert!(parent.run_count > 0);
+    let mut runs = [0isize; 3];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let mut run_count: i32 = 0;
+        assert_eq!(waitpid(pids[i] as usize, &mut run_count), pids[i]);
+        runs[i] = run_count as isize;
+        println!("priority = {}, run_count = {}", prio, run_count);
+    }
+    // 调度次数之比接近 1:2:4，相邻两个的比值允许 25% 的误差
+    for i in 0..2 {
+        assert!(runs[i + 1] * 4 >= runs[i] * 6);
+        assert!(runs[i + 1] * 4 <= runs[i] * 10);
+    }
+    println!("Test sched stats OK!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::{close, lseek, open, pipe, read, sendfile, unlink, write, OpenFlags, SEEK_CUR};
+
+/// 测试sendfile在内核中复制跨越多个块的文件，输出　Test sendfile OK! 就算正确。
+
+const LEN: usize = 3 * 512 + 100;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let src = "fsendfile_src\0";
+    let dst = "fsendfile_dst\0";
+    let data: Vec<u8> = (0..LEN).map(|i| (i % 251) as u8).collect();
+    let fd = open(src, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &data), LEN as isize);
+    close(fd as usize);
+
+    let in_fd = open(src, OpenFlags::RDONLY) as usize;
+    let out_fd = open(dst, OpenFlags::CREATE | OpenFlags::WRONLY) as usize;
+    // 不给offset时从in_fd的当前位置读取，count超过文件长度时复制到文件末尾为止
+    assert_eq!(sendfile(out_fd, in_fd, None, LEN * 2), LEN as isize);
+    assert_eq!(lseek(in_fd, 0, SEEK_CUR), LEN as isize);
+    assert_eq!(lseek(out_fd, 0, SEEK_CUR), LEN as isize);
+    assert_eq!(sendfile(out_fd, in_fd, None, 10), 0);
+    // 给出offset时从该位置读取并更新它，in_fd自身的位置不变
+    let mut offset = 100usize;
+    assert_eq!(sendfile(out_fd, in_fd, Some(&mut offset), 50), 50);
+    assert_eq!(offset, 150);
+    assert_eq!(lseek(in_fd, 0, SEEK_CUR), LEN as isize);
+    close(in_fd);
+    close(out_fd);
+
+    let fd = open(dst, OpenFlags::RDONLY) as usize;
+    let mut buffer = vec![0u8; LEN + 100];
+    assert_eq!(read(fd, &mut buffer), (LEN + 50) as isize);
+    assert_eq!(&buffer[..LEN], &data[..]);
+    assert_eq!(&buffer[LEN..LEN + 50], &data[100..150]);
+    // 管道不是普通文件
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(sendfile(pipe_fd[1], fd, None, 10), -1);
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    close(fd);
+    unlink(src);
+    unlink(dst);
+    println!("Test sendfile OK!");
+    0
+}
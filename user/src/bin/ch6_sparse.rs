@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, write, OpenFlags, SEEK_SET};
+
+/// 测试越过文件末尾写入后中间的空洞读出来都是0，输出　Test sparse OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fsparse\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"head"), 4);
+    assert_eq!(lseek(fd, 8192, SEEK_SET), 8192);
+    assert_eq!(write(fd, b"x"), 1);
+
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buffer = [0xffu8; 512];
+    assert_eq!(read(fd, &mut buffer[..4]), 4);
+    assert_eq!(&buffer[..4], b"head");
+    let mut pos = 4;
+    while pos < 8192 {
+        let len = read(fd, &mut buffer[..(8192 - pos).min(512)]);
+        assert!(len > 0);
+        assert!(buffer[..len as usize].iter().all(|byte| *byte == 0));
+        pos += len as usize;
+    }
+    assert_eq!(read(fd, &mut buffer), 1);
+    assert_eq!(buffer[0], b'x');
+    close(fd);
+    println!("Test sparse OK!");
+    0
+}
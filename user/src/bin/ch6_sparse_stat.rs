@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fallocate, fstat, open, stat, unlink, write, OpenFlags, Stat, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE,
+};
+
+/// 测试 Stat 中的 size 与 blocks：逻辑上5个块的文件打洞后只占2个块，输出　Test sparse stat OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fsparse\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let block = [b's'; 512];
+    for _ in 0..5 {
+        assert_eq!(write(fd, &block), 512);
+    }
+    let st = Stat::new();
+    assert_eq!(fstat(fd, &st), 0);
+    assert_eq!(st.size, 5 * 512);
+    assert_eq!(st.blocks, 5);
+    // 打掉中间三个块，只剩首尾两个块
+    assert_eq!(fallocate(fd, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE, 512, 3 * 512), 0);
+    assert_eq!(fstat(fd, &st), 0);
+    assert_eq!(st.size, 5 * 512);
+    assert_eq!(st.blocks, 2);
+    close(fd);
+    // 按路径查询得到同样的结果
+    let st = Stat::new();
+    assert_eq!(stat("fsparse\0", &st), 0);
+    assert_eq!(st.size, 5 * 512);
+    assert_eq!(st.blocks, 2);
+    unlink("fsparse\0");
+    println!("Test sparse stat OK!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, stat, OpenFlags, Stat, StatMode};
+
+/// 测试按路径获取文件状态，输出　Test stat OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let root = Stat::new();
+    assert_eq!(stat("/\0", &root), 0);
+    assert_eq!(root.mode, StatMode::DIR | StatMode::from_bits_truncate(0o755));
+    assert_eq!(root.ino, 0);
+
+    let fname = "fstat_path\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let file = Stat::new();
+    assert_eq!(stat(fname, &file), 0);
+    assert_eq!(file.mode, StatMode::FILE | StatMode::from_bits_truncate(0o644));
+    assert_eq!(file.nlink, 1);
+
+    let missing = Stat::new();
+    assert_eq!(stat("not_exist\0", &missing), -1);
+    println!("Test stat OK!");
+    0
+}
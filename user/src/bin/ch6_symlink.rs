@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, symlink, write, OpenFlags, ENOENT};
+
+/// 测试通过符号链接打开文件，以及成环的符号链接无法打开，输出　Test symlink OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "fsymtarget\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"symlink"), 7);
+    close(fd as usize);
+
+    assert_eq!(symlink(fname, "fsymlink\0"), 0);
+    // 同名的链接不能重复创建
+    assert_eq!(symlink(fname, "fsymlink\0"), -1);
+    let fd = open("fsymlink\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buffer), 7);
+    assert_eq!(&buffer[..7], b"symlink");
+    close(fd as usize);
+
+    assert_eq!(symlink("fsymloop\0", "fsymloop\0"), 0);
+    assert_eq!(open("fsymloop\0", OpenFlags::RDONLY), -ENOENT);
+    println!("Test symlink OK!");
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, open, task_info, unlinkat, OpenFlags, Stat, TaskInfo, TaskStatus, AT_FDCWD,
+    SYSCALL_CLOSE, SYSCALL_FSTAT, SYSCALL_OPENAT, SYSCALL_TASK_INFO, SYSCALL_UNLINKAT,
+};
+
+/// 测试task_info统计的文件系统调用次数，输出　Test task info ch6 OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let before = TaskInfo::new();
+    assert_eq!(task_info(&before), 0);
+    let fname = "ftask_info\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let st = Stat::new();
+    assert_eq!(fstat(fd as usize, &st), 0);
+    close(fd as usize);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(unlinkat(AT_FDCWD, fname), 0);
+    let after = TaskInfo::new();
+    assert_eq!(task_info(&after), 0);
+    let delta = |id: usize| after.syscall_times[id] - before.syscall_times[id];
+    assert_eq!(delta(SYSCALL_OPENAT), 2);
+    assert_eq!(delta(SYSCALL_CLOSE), 2);
+    assert_eq!(delta(SYSCALL_FSTAT), 1);
+    assert_eq!(delta(SYSCALL_UNLINKAT), 1);
+    // 本次task_info调用自身也计入
+    assert_eq!(delta(SYSCALL_TASK_INFO), 1);
+    assert!(after.status == TaskStatus::Running);
+    assert!(after.time >= before.time);
+    println!("Test task info ch6 OK!");
+    0
+}
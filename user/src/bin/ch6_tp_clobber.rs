@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::{getpid, yield_};
+
+/// 测试用户程序修改tp不会影响内核：tp被设成任意值后系统调用和切换进程都正常，返回用户态时tp保持不变，
+/// 输出　Test tp clobber OK! 就算正确。
+
+fn set_tp(value: usize) {
+    unsafe {
+        asm!("mv tp, {}", in(reg) value);
+    }
+}
+
+fn get_tp() -> usize {
+    let value: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) value);
+    }
+    value
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid();
+    for value in [usize::MAX, 0x1234_5678, 3].iter() {
+        set_tp(*value);
+        assert_eq!(getpid(), pid);
+        yield_();
+        assert_eq!(get_tp(), *value);
+    }
+    set_tp(0);
+    println!("Test tp clobber OK!");
+    0
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, open, read, write, OpenFlags};
+
+/// 测试以TRUNC方式重新打开文件会清空原有内容，输出　Test trunc OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "ftrunc\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, &[b'x'; 100]), 100);
+    close(fd as usize);
+
+    // 只读打开时不允许TRUNC
+    assert_eq!(open(fname, OpenFlags::RDONLY | OpenFlags::TRUNC), -1);
+
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"0123456789"), 10);
+    close(fd as usize);
+
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 128];
+    assert_eq!(read(fd as usize, &mut buffer), 10);
+    assert_eq!(&buffer[..10], b"0123456789");
+    close(fd as usize);
+    println!("Test trunc OK!");
+    0
+}
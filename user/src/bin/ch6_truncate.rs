@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, read, truncate, write, OpenFlags};
+
+/// 测试不打开文件直接按路径截断文件，输出　Test truncate OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "ftruncate\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let block = [0x42u8; 512];
+    for _ in 0..5 {
+        assert_eq!(write(fd as usize, &block), 512);
+    }
+    close(fd as usize);
+
+    assert_eq!(truncate(fname, 2 * 512), 0);
+    let fd = open(fname, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 512];
+    let mut total = 0;
+    loop {
+        let len = read(fd as usize, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        assert!(buffer[..len as usize].iter().all(|byte| *byte == 0x42));
+        total += len as usize;
+    }
+    close(fd as usize);
+    assert_eq!(total, 2 * 512);
+
+    assert_eq!(truncate("ftruncate_missing\0", 0), -1);
+    assert_eq!(mkdir("dtruncate\0"), 0);
+    assert_eq!(truncate("dtruncate\0", 0), -1);
+    println!("Test truncate OK!");
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, unlink, OpenFlags, ENOENT, ENOTEMPTY};
+
+/// 测试删除目录：非空目录删除失败，空目录删除后不能再访问，输出　Test unlink dir OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("/ud_dir\0"), 0);
+    let fd = open("/ud_dir/file\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    // 目录里还有文件时不能删除
+    assert_eq!(unlink("/ud_dir\0"), -ENOTEMPTY);
+    assert_eq!(unlink("/ud_dir/file\0"), 0);
+    // 清空之后可以删除，目录的空间被释放
+    assert_eq!(unlink("/ud_dir\0"), 0);
+    assert_eq!(open("/ud_dir/file\0", OpenFlags::CREATE | OpenFlags::WRONLY), -ENOENT);
+    // 同名目录可以重新创建
+    assert_eq!(mkdir("/ud_dir\0"), 0);
+    assert_eq!(unlink("/ud_dir\0"), 0);
+    println!("Test unlink dir OK!");
+    0
+}
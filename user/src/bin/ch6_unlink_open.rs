@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, lseek, open, read, unlink, write, OpenFlags, ENOENT, SEEK_SET};
+
+/// 测试删除仍然打开的文件：删除之后通过fd继续读写都正常，输出　Test unlink open file OK! 就算正确。
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fname = "funlink_open\0";
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let first = [0x5au8; 600];
+    assert_eq!(write(fd, &first), first.len() as isize);
+    assert_eq!(unlink(fname), 0);
+    // 名字已经不存在，但打开的fd仍然可以读写
+    assert_eq!(open(fname, OpenFlags::RDONLY), -ENOENT);
+    let second = [0xa5u8; 1000];
+    assert_eq!(write(fd, &second), second.len() as isize);
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    let mut buf = [0u8; 1600];
+    assert_eq!(read(fd, &mut buf), buf.len() as isize);
+    assert!(buf[..600].iter().all(|&byte| byte == 0x5a));
+    assert!(buf[600..].iter().all(|&byte| byte == 0xa5));
+    close(fd);
+    // 同名的新文件与被删除的文件无关
+    let fd = open(fname, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    assert_eq!(read(fd as usize, &mut buf), 0);
+    close(fd as usize);
+    assert_eq!(unlink(fname), 0);
+    println!("Test unlink open file OK!");
+    0
+}
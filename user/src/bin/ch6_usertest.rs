@@ -20,13 +20,68 @@ static TESTS: &[&str] = &[
     "ch4_mmap3\0",
     "ch4_unmap\0",
     "ch4_unmap2\0",
+    "ch4_mmap_checks\0",
+    "ch4_unmap_partial\0",
     "ch5b_forktest2\0",
     "ch5_spawn0\0",
     "ch5_spawn1\0",
+    "ch5_cow_fork\0",
     "ch6_file0\0",
     "ch6_file1\0",
     "ch6_file2\0",
     "ch6_file3\0",
+    "ch6_getdents\0",
+    "ch6_append\0",
+    "ch6_lseek\0",
+    "ch6_dup\0",
+    "ch6_pipe\0",
+    "ch6_stat\0",
+    "ch6_chdir\0",
+    "ch6_mmap_file\0",
+    "ch6_trunc\0",
+    "ch6_symlink\0",
+    "ch6_access\0",
+    "ch6_iovec\0",
+    "ch6_truncate\0",
+    "ch6_getcwd\0",
+    "ch6_mkdir_parents\0",
+    "ch6_errno\0",
+    "ch6_fsync\0",
+    "ch6_rename\0",
+    "ch6_sparse\0",
+    "ch6_dirfd\0",
+    "ch6_link_subdir\0",
+    "ch6_readlink\0",
+    "ch6_mmap_lazy\0",
+    "ch6_pread\0",
+    "ch6_dup3\0",
+    "ch6_poll\0",
+    "ch6_open_excl\0",
+    "ch6_chmod\0",
+    "ch6_sendfile\0",
+    "ch6_task_info\0",
+    "ch6_close_flush\0",
+    "ch6_fork_offset\0",
+    "ch6_mount\0",
+    "ch6_getdents_cursor\0",
+    "ch6_quota\0",
+    "ch6_fallocate\0",
+    "ch6_sched_stats\0",
+    "ch6_yield_boost\0",
+    "ch6_sparse_stat\0",
+    "ch6_append_only\0",
+    "ch6_getattr\0",
+    "ch6_max_fd\0",
+    "ch6_renameat2\0",
+    "ch6_fadvise\0",
+    "ch6_open_directory\0",
+    "ch6_unlink_open\0",
+    "ch6_flock\0",
+    "ch6_mmap_anywhere\0",
+    "ch6_getrandom\0",
+    "ch6_tp_clobber\0",
+    "ch6_mmap_lazy_read\0",
+    "ch6_unlink_dir\0",
 ];
 
 use user_lib::{spawn, waitpid};
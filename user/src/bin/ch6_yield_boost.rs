@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::{exit, fork, get_time, getpid, sched_stats, waitpid, yield_, SchedStat};
+
+/*
+一个不断yield的进程（模拟等待I/O）与三个一直占用CPU的进程同时运行，优先级都相同。
+yield之后应当很快被重新调度，而不是排在所有计算进程之后，
+输出　Test yield boost OK! 就算正确。
+*/
+
+const MAX_TIME: isize = 1000;
+const HOGS: usize = 3;
+
+/// The scheduling state of current task
+fn my_run_count() -> usize {
+    let mut stats = [SchedStat::default(); 1];
+    assert!(sched_stats(&mut stats) >= 1);
+    assert_eq!(stats[0].pid, getpid() as usize);
+    stats[0].run_count
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start_time = get_time();
+    let mut pids = [0isize; HOGS];
+    for pid in pids.iter_mut() {
+        *pid = fork();
+        if *pid == 0 {
+            let before = my_run_count();
+            while get_time() - start_time <= MAX_TIME {}
+            exit((my_run_count() - before) as i32);
+        }
+    }
+    let before = my_run_count();
+    let mut yields = 0;
+    let mut waited = 0;
+    while get_time() - start_time <= MAX_TIME {
+        let t = get_time();
+        yield_();
+        waited += get_time() - t;
+        yields += 1;
+    }
+    let runs = my_run_count() - before;
+    println!("yields = {}, waited = {}ms, run_count = {}", yields, waited, runs);
+    for pid in pids {
+        let mut run_count: i32 = 0;
+        assert_eq!(waitpid(pid as usize, &mut run_count), pid);
+        println!("hog run_count = {}", run_count);
+        // 让出CPU的进程被调度的次数多于一直计算的进程
+        assert!(runs > run_count as usize);
+    }
+    // 没有加速时每次yield都要等三个计算进程各用完一个时间片（约30ms）
+    assert!(yields > 0);
+    assert!(waited < yields * 20);
+    println!("Test yield boost OK!");
+    0
+}
@@ -82,6 +82,15 @@ bitflags! {
         const RDWR = 1 << 1;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        /// together with CREATE, create missing parent directories like `mkdir -p`
+        const MKDIR_PARENTS = 1 << 12;
+        /// together with CREATE, fail with -EEXIST if the name already exists
+        const EXCL = 1 << 7;
+        /// fail with -1 unless the path is a directory
+        const DIRECTORY = 1 << 16;
+        /// close the fd on exec
+        const CLOEXEC = 1 << 19;
     }
 }
 
@@ -114,6 +123,7 @@ pub struct SyscallInfo {
 
 const MAX_SYSCALL_NUM: usize = 500;
 
+#[repr(C)]
 #[derive(Debug)]
 pub struct TaskInfo {
     pub status: TaskStatus,
@@ -131,6 +141,17 @@ impl TaskInfo {
     }
 }
 
+/// Scheduling state of one task, filled by sched_stats
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SchedStat {
+    pub pid: usize,
+    pub prio: usize,
+    pub stride: usize,
+    pub pass: usize,
+    pub run_count: usize,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct Stat {
@@ -142,8 +163,12 @@ pub struct Stat {
     pub mode: StatMode,
     /// number of hard links
     pub nlink: u32,
+    /// logical size in bytes
+    pub size: u64,
+    /// number of data blocks actually allocated
+    pub blocks: u64,
     /// unused pad
-    pad: [u64; 7],
+    pad: [u64; 5],
 }
 
 impl Stat {
@@ -153,17 +178,83 @@ impl Stat {
             ino: 0,
             mode: StatMode::NULL,
             nlink: 0,
-            pad: [0; 7],
+            size: 0,
+            blocks: 0,
+            pad: [0; 5],
+        }
+    }
+}
+
+/// Everything known about an inode, filled by getattr
+/// easy-fs不记录时间戳，atime/mtime/ctime总是0
+#[repr(C)]
+#[derive(Debug)]
+pub struct XAttr {
+    pub ino: u64,
+    pub mode: StatMode,
+    pub nlink: u32,
+    pub size: u64,
+    pub blocks: u64,
+    pub atime: u64,
+    pub mtime: u64,
+    pub ctime: u64,
+    pub perm: u32,
+    pad: u32,
+}
+
+impl XAttr {
+    pub fn new() -> Self {
+        XAttr {
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 0,
+            size: 0,
+            blocks: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            perm: 0,
+            pad: 0,
         }
     }
 }
 
+impl Default for XAttr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Default for Stat {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// A segment of a buffer used by readv/writev
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IoVec {
+    pub base: *const u8,
+    pub len: usize,
+}
+
+impl IoVec {
+    pub fn new(buf: &[u8]) -> Self {
+        Self {
+            base: buf.as_ptr(),
+            len: buf.len(),
+        }
+    }
+    /// A segment that the kernel may write into, used by readv
+    pub fn new_mut(buf: &mut [u8]) -> Self {
+        Self {
+            base: buf.as_mut_ptr(),
+            len: buf.len(),
+        }
+    }
+}
+
 bitflags! {
     pub struct StatMode: u32 {
         const NULL  = 0;
@@ -171,15 +262,112 @@ bitflags! {
         const DIR   = 0o040000;
         /// ordinary regular file
         const FILE  = 0o100000;
+        /// mask of the rwxrwxrwx permission bits
+        const PERM  = 0o777;
+    }
+}
+
+/// An entry of the fd array passed to poll
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PollFd {
+    pub fd: i32,
+    pub events: PollEvents,
+    /// filled in by the kernel
+    pub revents: PollEvents,
+}
+
+impl PollFd {
+    pub fn new(fd: usize, events: PollEvents) -> Self {
+        Self {
+            fd: fd as i32,
+            events,
+            revents: PollEvents::empty(),
+        }
+    }
+}
+
+bitflags! {
+    pub struct PollEvents: i16 {
+        const POLLIN   = 1;
+        const POLLOUT  = 4;
+        /// the fd is not open
+        const POLLNVAL = 0x20;
+    }
+}
+
+bitflags! {
+    pub struct AccessMode: u32 {
+        /// only check that the path exists
+        const F_OK = 0;
+        const X_OK = 1 << 0;
+        const W_OK = 1 << 1;
+        const R_OK = 1 << 2;
     }
 }
 
-const AT_FDCWD: isize = -100;
+/// Max number of file descriptors a process may hold, the same as the kernel's MAX_FD
+pub const MAX_FD: usize = 128;
+
+/// Pass as a dirfd to resolve relative paths from the current working directory
+pub const AT_FDCWD: isize = -100;
+
+/// errno values, filesystem syscalls return them negated
+pub const ENOENT: isize = 2;
+pub const EBADF: isize = 9;
+pub const EWOULDBLOCK: isize = 11;
+pub const EACCES: isize = 13;
+pub const EBUSY: isize = 16;
+pub const EEXIST: isize = 17;
+pub const EXDEV: isize = 18;
+pub const ENOTDIR: isize = 20;
+pub const EISDIR: isize = 21;
+pub const EINVAL: isize = 22;
+pub const EMFILE: isize = 24;
+pub const ENOSPC: isize = 28;
+pub const ENOTEMPTY: isize = 39;
+
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
+pub const FALLOC_FL_KEEP_SIZE: usize = 0x1;
+pub const FALLOC_FL_PUNCH_HOLE: usize = 0x2;
+
+pub const POSIX_FADV_NORMAL: usize = 0;
+pub const POSIX_FADV_RANDOM: usize = 1;
+pub const POSIX_FADV_SEQUENTIAL: usize = 2;
+pub const POSIX_FADV_WILLNEED: usize = 3;
+
+pub const LOCK_SH: usize = 1;
+pub const LOCK_EX: usize = 2;
+pub const LOCK_NB: usize = 4;
+pub const LOCK_UN: usize = 8;
+
+pub const RENAME_NOREPLACE: u32 = 0x1;
+pub const RENAME_EXCHANGE: u32 = 0x2;
 
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_openat(AT_FDCWD as usize, path, flags.bits, OpenFlags::RDWR.bits)
 }
 
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD as usize, path, 0)
+}
+
+pub fn mkdirat(dirfd: isize, path: &str) -> isize {
+    sys_mkdirat(dirfd as usize, path, 0)
+}
+
+/// 把当前工作目录的绝对路径（以'\0'结尾）写入buf，返回写入的字节数，buf太小时返回-1
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    sys_getcwd(buf)
+}
+
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
 pub fn close(fd: usize) -> isize {
     if fd == STDOUT {
         console::flush();
@@ -187,6 +375,24 @@ pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
 
+/// 每次调用从上一次结束的位置继续，读完之后返回0
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents(fd, buf)
+}
+
+pub fn rewinddir(fd: usize) -> isize {
+    sys_rewinddir(fd)
+}
+
+/// pos是目录项槽位的下标（包括已删除的空槽），0表示开头
+pub fn seekdir(fd: usize, pos: usize) -> isize {
+    sys_seekdir(fd, pos)
+}
+
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
@@ -195,18 +401,138 @@ pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
 
+/// 把多段缓冲区依次写入fd，返回写入的总字节数
+pub fn writev(fd: usize, iov: &[IoVec]) -> isize {
+    sys_writev(fd, iov)
+}
+
+/// 等待fds中的任意一个就绪，返回就绪的个数，超过timeout毫秒仍无就绪时返回0，timeout为负数时一直等待
+pub fn poll(fds: &mut [PollFd], timeout: isize) -> isize {
+    sys_poll(fds, timeout)
+}
+
+/// 在内核中把in_fd的最多count字节复制到out_fd，offset为Some时从该位置读取并更新它，in_fd自身的位置不变
+pub fn sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut usize>, count: usize) -> isize {
+    sys_sendfile(out_fd, in_fd, offset, count)
+}
+
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread(fd, buf, offset)
+}
+
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite(fd, buf, offset)
+}
+
+/// 从fd依次读入多段缓冲区，iov中的每一段都应由IoVec::new_mut构造
+pub fn readv(fd: usize, iov: &[IoVec]) -> isize {
+    sys_readv(fd, iov)
+}
+
 pub fn link(old_path: &str, new_path: &str) -> isize {
-    sys_linkat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, 0)
+    sys_link(old_path, new_path)
+}
+
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_renameat(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path)
+}
+
+pub fn renameat2(old_path: &str, new_path: &str, flags: u32) -> isize {
+    sys_renameat2(AT_FDCWD as usize, old_path, AT_FDCWD as usize, new_path, flags)
 }
 
 pub fn unlink(path: &str) -> isize {
-    sys_unlinkat(AT_FDCWD as usize, path, 0)
+    sys_unlink(path)
+}
+
+pub fn linkat(old_dirfd: isize, old_path: &str, new_dirfd: isize, new_path: &str) -> isize {
+    sys_linkat(old_dirfd as usize, old_path, new_dirfd as usize, new_path, 0)
+}
+
+pub fn unlinkat(dirfd: isize, path: &str) -> isize {
+    sys_unlinkat(dirfd as usize, path, 0)
+}
+
+pub fn symlink(target: &str, linkpath: &str) -> isize {
+    sys_symlinkat(target, AT_FDCWD as usize, linkpath)
+}
+
+pub fn readlink(path: &str, buf: &mut [u8]) -> isize {
+    sys_readlinkat(AT_FDCWD as usize, path, buf)
+}
+
+pub fn truncate(path: &str, len: usize) -> isize {
+    sys_truncate(path, len)
+}
+
+/// mode为0时预分配空间，为 FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE 时在文件中打洞
+pub fn fallocate(fd: usize, mode: usize, offset: usize, len: usize) -> isize {
+    sys_fallocate(fd, mode, offset, len)
+}
+
+/// advice为POSIX_FADV_*，调整这个打开的文件的预读窗口，或者（WILLNEED）立即把一段内容读进块缓存
+pub fn fadvise(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    sys_fadvise(fd, offset, len, advice)
+}
+
+/// op为LOCK_SH/LOCK_EX/LOCK_UN，可以与LOCK_NB组合；锁在打开这个文件的最后一个fd关闭时自动释放
+pub fn flock(fd: usize, op: usize) -> isize {
+    sys_flock(fd, op)
+}
+
+/// 用内核的伪随机数填满buf，返回写入的字节数；不是密码学安全的随机数
+pub fn getrandom(buf: &mut [u8]) -> isize {
+    sys_getrandom(buf)
+}
+
+pub fn access(path: &str, mode: AccessMode) -> isize {
+    sys_faccessat(AT_FDCWD as usize, path, mode.bits as usize)
+}
+
+/// 修改文件的rwxrwxrwx权限位，如0o444表示只读
+pub fn chmod(path: &str, perm: u32) -> isize {
+    sys_fchmodat(AT_FDCWD as usize, path, perm)
+}
+
+/// 把可读写打开的镜像文件image_fd中的easy-fs挂载到目录mountpoint上
+pub fn mount(image_fd: usize, mountpoint: &str) -> isize {
+    sys_mount(image_fd, mountpoint)
+}
+
+pub fn umount(mountpoint: &str) -> isize {
+    sys_umount2(mountpoint, 0)
+}
+
+/// 限制目录下的整个子树最多占用blocks个块，blocks为负数时取消限制
+pub fn setquota(path: &str, blocks: isize) -> isize {
+    sys_setquota(path, blocks)
+}
+
+/// 把文件设为追加专用或取消这一限制，追加专用的文件只能在末尾追加
+pub fn chattr(path: &str, append_only: bool) -> isize {
+    sys_chattr(path, append_only)
+}
+
+pub fn stat(path: &str, st: &Stat) -> isize {
+    sys_stat(path, st)
+}
+
+/// 一次取得path处inode的全部元数据
+pub fn getattr(path: &str, attr: &mut XAttr) -> isize {
+    sys_getattr(path, attr)
 }
 
 pub fn fstat(fd: usize, st: &Stat) -> isize {
     sys_fstat(fd, st)
 }
 
+pub fn sync() -> isize {
+    sys_sync()
+}
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+
 pub fn mail_read(buf: &mut [u8]) -> isize {
     sys_mail_read(buf)
 }
@@ -285,7 +611,11 @@ pub fn sleep(period_ms: usize) {
     }
 }
 pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
-    sys_mmap(start, len, prot)
+    sys_mmap(start, len, prot, usize::MAX, 0)
+}
+
+pub fn mmap_file(start: usize, len: usize, prot: usize, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot, fd, offset)
 }
 
 pub fn munmap(start: usize, len: usize) -> isize {
@@ -299,6 +629,12 @@ pub fn spawn(path: &str) -> isize {
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    sys_dup2(old_fd, new_fd)
+}
+pub fn dup3(old_fd: usize, new_fd: usize, flags: OpenFlags) -> isize {
+    sys_dup3(old_fd, new_fd, flags.bits)
+}
 pub fn pipe(pipe_fd: &mut [usize]) -> isize {
     sys_pipe(pipe_fd)
 }
@@ -307,6 +643,13 @@ pub fn task_info(info: &TaskInfo) -> isize {
     sys_task_info(info)
 }
 
+/// 第一项是当前进程，之后是就绪队列中的进程；返回进程总数，可能多于stats的长度
+pub fn sched_stats(stats: &mut [SchedStat]) -> isize {
+    sys_sched_stats(stats)
+}
+pub fn get_cpu_time() -> isize {
+    sys_get_cpu_time()
+}
 pub fn thread_create(entry: usize, arg: usize) -> isize {
     sys_thread_create(entry, arg)
 }
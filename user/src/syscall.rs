@@ -1,14 +1,37 @@
-use crate::TaskInfo;
-
-use super::{Stat, TimeVal};
-
+use crate::{SchedStat, TaskInfo};
+
+use super::{IoVec, PollFd, Stat, TimeVal, XAttr};
+
+pub const SYSCALL_GETCWD: usize = 17;
+pub const SYSCALL_MKDIRAT: usize = 34;
+pub const SYSCALL_UMOUNT2: usize = 39;
+pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_TRUNCATE: usize = 45;
+pub const SYSCALL_FALLOCATE: usize = 47;
+pub const SYSCALL_ACCESS: usize = 48;
+pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_FCHMODAT: usize = 53;
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_GETDENTS: usize = 61;
+pub const SYSCALL_LSEEK: usize = 62;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_READV: usize = 65;
+pub const SYSCALL_WRITEV: usize = 66;
+pub const SYSCALL_PREAD: usize = 67;
+pub const SYSCALL_PWRITE: usize = 68;
 pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_SYMLINKAT: usize = 36;
 pub const SYSCALL_LINKAT: usize = 37;
+pub const SYSCALL_RENAMEAT: usize = 38;
+pub const SYSCALL_SENDFILE: usize = 71;
+pub const SYSCALL_PPOLL: usize = 73;
+pub const SYSCALL_READLINKAT: usize = 78;
+pub const SYSCALL_STAT: usize = 79;
 pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
+pub const SYSCALL_FSYNC: usize = 82;
 pub const SYSCALL_EXIT: usize = 93;
 pub const SYSCALL_SLEEP: usize = 101;
 pub const SYSCALL_YIELD: usize = 124;
@@ -18,15 +41,30 @@ pub const SYSCALL_GETTID: usize = 178;
 pub const SYSCALL_FORK: usize = 220;
 pub const SYSCALL_EXEC: usize = 221;
 pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_RENAMEAT2: usize = 276;
 pub const SYSCALL_SET_PRIORITY: usize = 140;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_FADVISE: usize = 223;
+pub const SYSCALL_FLOCK: usize = 32;
+pub const SYSCALL_GETRANDOM: usize = 278;
 pub const SYSCALL_SPAWN: usize = 400;
 pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 pub const SYSCALL_DUP: usize = 24;
+pub const SYSCALL_DUP2: usize = 25;
+pub const SYSCALL_DUP3: usize = 26;
 pub const SYSCALL_PIPE: usize = 59;
 pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_GET_CPU_TIME: usize = 411;
+pub const SYSCALL_REWINDDIR: usize = 412;
+pub const SYSCALL_SEEKDIR: usize = 413;
+pub const SYSCALL_SETQUOTA: usize = 414;
+pub const SYSCALL_SCHED_STATS: usize = 415;
+pub const SYSCALL_CHATTR: usize = 416;
+pub const SYSCALL_GETATTR: usize = 417;
+pub const SYSCALL_LINK: usize = 1025;
+pub const SYSCALL_UNLINK: usize = 1026;
 pub const SYSCALL_THREAD_CREATE: usize = 460;
 pub const SYSCALL_WAITTID: usize = 462;
 pub const SYSCALL_MUTEX_CREATE: usize = 463;
@@ -84,10 +122,92 @@ pub fn sys_openat(dirfd: usize, path: &str, flags: u32, mode: u32) -> isize {
     )
 }
 
+pub fn sys_mkdirat(dirfd: usize, path: &str, mode: u32) -> isize {
+    syscall(SYSCALL_MKDIRAT, [dirfd, path.as_ptr() as usize, mode as usize])
+}
+
+pub fn sys_symlinkat(target: &str, dirfd: usize, linkpath: &str) -> isize {
+    syscall(
+        SYSCALL_SYMLINKAT,
+        [target.as_ptr() as usize, dirfd, linkpath.as_ptr() as usize],
+    )
+}
+
+pub fn sys_truncate(path: &str, len: usize) -> isize {
+    syscall(SYSCALL_TRUNCATE, [path.as_ptr() as usize, len, 0])
+}
+
+pub fn sys_fallocate(fd: usize, mode: usize, offset: usize, len: usize) -> isize {
+    syscall6(SYSCALL_FALLOCATE, [fd, mode, offset, len, 0, 0])
+}
+
+pub fn sys_fadvise(fd: usize, offset: usize, len: usize, advice: usize) -> isize {
+    syscall6(SYSCALL_FADVISE, [fd, offset, len, advice, 0, 0])
+}
+
+pub fn sys_flock(fd: usize, op: usize) -> isize {
+    syscall(SYSCALL_FLOCK, [fd, op, 0])
+}
+
+pub fn sys_getrandom(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETRANDOM, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+pub fn sys_faccessat(dirfd: usize, path: &str, mode: usize) -> isize {
+    syscall(SYSCALL_ACCESS, [dirfd, path.as_ptr() as usize, mode])
+}
+
+pub fn sys_fchmodat(dirfd: usize, path: &str, perm: u32) -> isize {
+    syscall(SYSCALL_FCHMODAT, [dirfd, path.as_ptr() as usize, perm as usize])
+}
+
+pub fn sys_getcwd(buffer: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buffer.as_mut_ptr() as usize, buffer.len(), 0])
+}
+
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_mount(image_fd: usize, mountpoint: &str) -> isize {
+    syscall(SYSCALL_MOUNT, [image_fd, mountpoint.as_ptr() as usize, 0])
+}
+
+pub fn sys_umount2(mountpoint: &str, flags: u32) -> isize {
+    syscall(SYSCALL_UMOUNT2, [mountpoint.as_ptr() as usize, flags as usize, 0])
+}
+
 pub fn sys_close(fd: usize) -> isize {
     syscall(SYSCALL_CLOSE, [fd, 0, 0])
 }
 
+pub fn sys_getdents(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+    )
+}
+
+pub fn sys_rewinddir(fd: usize) -> isize {
+    syscall(SYSCALL_REWINDDIR, [fd, 0, 0])
+}
+
+pub fn sys_seekdir(fd: usize, pos: usize) -> isize {
+    syscall(SYSCALL_SEEKDIR, [fd, pos, 0])
+}
+
+pub fn sys_setquota(path: &str, blocks: isize) -> isize {
+    syscall(SYSCALL_SETQUOTA, [path.as_ptr() as usize, blocks as usize, 0])
+}
+
+pub fn sys_chattr(path: &str, append_only: bool) -> isize {
+    syscall(SYSCALL_CHATTR, [path.as_ptr() as usize, append_only as usize, 0])
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+}
+
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_READ,
@@ -99,6 +219,86 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
+pub fn sys_poll(fds: &mut [PollFd], timeout: isize) -> isize {
+    syscall(SYSCALL_PPOLL, [fds.as_mut_ptr() as usize, fds.len(), timeout as usize])
+}
+
+pub fn sys_readv(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_READV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_writev(fd: usize, iov: &[IoVec]) -> isize {
+    syscall(SYSCALL_WRITEV, [fd, iov.as_ptr() as usize, iov.len()])
+}
+
+pub fn sys_sendfile(out_fd: usize, in_fd: usize, offset: Option<&mut usize>, count: usize) -> isize {
+    let offset = match offset {
+        Some(offset) => offset as *mut usize as usize,
+        None => 0,
+    };
+    syscall6(SYSCALL_SENDFILE, [out_fd, in_fd, offset, count, 0, 0])
+}
+
+pub fn sys_pread(fd: usize, buffer: &mut [u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PREAD,
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_pwrite(fd: usize, buffer: &[u8], offset: usize) -> isize {
+    syscall6(
+        SYSCALL_PWRITE,
+        [fd, buffer.as_ptr() as usize, buffer.len(), offset, 0, 0],
+    )
+}
+
+pub fn sys_renameat(
+    old_dirfd: usize,
+    old_path: &str,
+    new_dirfd: usize,
+    new_path: &str,
+) -> isize {
+    syscall6(
+        SYSCALL_RENAMEAT,
+        [
+            old_dirfd,
+            old_path.as_ptr() as usize,
+            new_dirfd,
+            new_path.as_ptr() as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_renameat2(
+    old_dirfd: usize,
+    old_path: &str,
+    new_dirfd: usize,
+    new_path: &str,
+    flags: u32,
+) -> isize {
+    syscall6(
+        SYSCALL_RENAMEAT2,
+        [
+            old_dirfd,
+            old_path.as_ptr() as usize,
+            new_dirfd,
+            new_path.as_ptr() as usize,
+            flags as usize,
+            0,
+        ],
+    )
+}
+
+pub fn sys_readlinkat(dirfd: usize, path: &str, buf: &mut [u8]) -> isize {
+    syscall6(
+        SYSCALL_READLINKAT,
+        [dirfd, path.as_ptr() as usize, buf.as_mut_ptr() as usize, buf.len(), 0, 0],
+    )
+}
+
 pub fn sys_linkat(
     old_dirfd: usize,
     old_path: &str,
@@ -123,10 +323,34 @@ pub fn sys_unlinkat(dirfd: usize, path: &str, flags: usize) -> isize {
     syscall(SYSCALL_UNLINKAT, [dirfd, path.as_ptr() as usize, flags])
 }
 
+pub fn sys_link(old_path: &str, new_path: &str) -> isize {
+    syscall(SYSCALL_LINK, [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0])
+}
+
+pub fn sys_unlink(path: &str) -> isize {
+    syscall(SYSCALL_UNLINK, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_stat(path: &str, st: &Stat) -> isize {
+    syscall(SYSCALL_STAT, [path.as_ptr() as usize, st as *const _ as usize, 0])
+}
+
+pub fn sys_getattr(path: &str, attr: &mut XAttr) -> isize {
+    syscall(SYSCALL_GETATTR, [path.as_ptr() as usize, attr as *mut _ as usize, 0])
+}
+
 pub fn sys_fstat(fd: usize, st: &Stat) -> isize {
     syscall(SYSCALL_FSTAT, [fd, st as *const _ as usize, 0])
 }
 
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
 pub fn sys_mail_read(buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_MAIL_READ,
@@ -181,8 +405,8 @@ pub fn sys_set_priority(prio: isize) -> isize {
     syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
 }
 
-pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
-    syscall(SYSCALL_MMAP, [start, len, prot])
+pub fn sys_mmap(start: usize, len: usize, prot: usize, fd: usize, offset: usize) -> isize {
+    syscall6(SYSCALL_MMAP, [start, len, prot, fd, offset, 0])
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
@@ -197,6 +421,14 @@ pub fn sys_dup(fd: usize) -> isize {
     syscall(SYSCALL_DUP, [fd, 0, 0])
 }
 
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    syscall(SYSCALL_DUP2, [old_fd, new_fd, 0])
+}
+
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [old_fd, new_fd, flags as usize])
+}
+
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
     syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
 }
@@ -205,6 +437,14 @@ pub fn sys_task_info(info: &TaskInfo) -> isize {
     syscall(SYSCALL_TASK_INFO, [info as *const _ as usize, 0, 0])
 }
 
+pub fn sys_sched_stats(stats: &mut [SchedStat]) -> isize {
+    syscall(SYSCALL_SCHED_STATS, [stats.as_mut_ptr() as usize, stats.len(), 0])
+}
+
+pub fn sys_get_cpu_time() -> isize {
+    syscall(SYSCALL_GET_CPU_TIME, [0, 0, 0])
+}
+
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
 }